@@ -1,12 +1,17 @@
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
 use std::process::exit;
 use std::rc::Rc;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
+use jmespath::diagnostics::Diagnostic;
+use jmespath::regression::run_directory;
 use jmespath::Rcvar;
-use jmespath::{compile, Variable};
+use jmespath::{compile, default_runtime, Variable};
+
+mod repl;
 
 macro_rules! die(
     ($msg:expr) => (
@@ -21,6 +26,36 @@ fn main() {
     let matches = App::new("jp")
         .version(env!("CARGO_PKG_VERSION"))
         .about("JMESPath command line interface")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Interactively evaluate expressions against a single document")
+                .arg(
+                    Arg::with_name("filename")
+                        .help("Read input JSON from a file instead of stdin.")
+                        .short("f")
+                        .takes_value(true)
+                        .long("filename")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Run a directory of JSON fixtures with an expression, input and expected result")
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Directory of .json fixture files.")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the report as JSON.")
+                        .long("json")
+                        .multiple(false),
+                ),
+        )
         .arg(
             Arg::with_name("filename")
                 .help("Read input JSON from a file instead of stdin.")
@@ -33,6 +68,29 @@ fn main() {
                 .help("If the final result is a string, it will be printed without quotes.")
                 .short("u")
                 .long("unquoted")
+                .visible_alias("raw-string")
+                .multiple(false),
+        )
+        .arg(
+            Arg::with_name("compact")
+                .help("Print the result as compact JSON on a single line.")
+                .short("c")
+                .long("compact")
+                .conflicts_with("pretty")
+                .multiple(false),
+        )
+        .arg(
+            Arg::with_name("pretty")
+                .help("Print the result as indented JSON (the default).")
+                .short("p")
+                .long("pretty")
+                .conflicts_with("compact")
+                .multiple(false),
+        )
+        .arg(
+            Arg::with_name("null-as-empty")
+                .help("If the final result is null, print nothing instead of null.")
+                .long("null-as-empty")
                 .multiple(false),
         )
         .arg(
@@ -62,6 +120,28 @@ fn main() {
         )
         .get_matches();
 
+    if let Some(repl_matches) = matches.subcommand_matches("repl") {
+        let json = Rc::new(get_json(repl_matches.value_of("filename")));
+        let stdin = io::stdin();
+        repl::run(&json, stdin.lock(), io::stdout())
+            .map_err(|e| die!(format!("Error running repl: {}", e)))
+            .ok();
+        exit(0);
+    }
+
+    if let Some(test_matches) = matches.subcommand_matches("test") {
+        let directory = Path::new(test_matches.value_of("directory").unwrap());
+        let report = run_directory(directory, default_runtime())
+            .map_err(|e| die!(format!("Error reading fixtures: {}", e)))
+            .unwrap();
+        if test_matches.is_present("json") {
+            println!("{}", report.to_json());
+        } else {
+            println!("{}", report);
+        }
+        exit(if report.is_success() { 0 } else { 1 });
+    }
+
     let file_expression = matches
         .value_of("expr-file")
         .map(|f| read_file("expression", f));
@@ -71,7 +151,7 @@ fn main() {
     } else {
         compile(matches.value_of("expression").unwrap())
     }
-    .map_err(|e| die!(e.to_string()))
+    .map_err(|e| die!(Diagnostic::from(e)))
     .unwrap();
 
     if matches.is_present("ast") {
@@ -81,19 +161,43 @@ fn main() {
 
     let json = Rc::new(get_json(matches.value_of("filename")));
 
+    let options = OutputOptions {
+        unquoted: matches.is_present("unquoted"),
+        compact: matches.is_present("compact"),
+        null_as_empty: matches.is_present("null-as-empty"),
+    };
+
     match expr.search(&json) {
-        Err(e) => die!(e.to_string()),
-        Ok(result) => show_result(result, matches.is_present("unquoted")),
+        Err(e) => die!(Diagnostic::from(e)),
+        Ok(result) => show_result(result, &options),
     }
 }
 
-fn show_result(result: Rcvar, unquoted: bool) {
-    if unquoted && result.is_string() {
+/// Controls how the final result is written to stdout.
+struct OutputOptions {
+    /// Print top-level string results without quotes.
+    unquoted: bool,
+    /// Print JSON on a single line rather than indented.
+    compact: bool,
+    /// Print nothing when the result is null.
+    null_as_empty: bool,
+}
+
+fn show_result(result: Rcvar, options: &OutputOptions) {
+    if options.null_as_empty && result.is_null() {
+        return;
+    }
+    if options.unquoted && result.is_string() {
         println!("{}", result.as_string().unwrap());
     } else {
         let mut out = io::stdout();
-        serde_json::to_writer_pretty(&mut out, &result)
-            .map(|_| out.write(&[b'\n']))
+        let written = if options.compact {
+            serde_json::to_writer(&mut out, &result)
+        } else {
+            serde_json::to_writer_pretty(&mut out, &result)
+        };
+        written
+            .map(|_| out.write(b"\n"))
             .map_err(|e| die!(format!("Error converting result to string: {}", e)))
             .ok();
     }
@@ -0,0 +1,108 @@
+//! Interactive read-eval-print loop used by `jp repl`.
+
+use std::io::{self, BufRead, Write};
+
+use jmespath::diagnostics::Diagnostic;
+use jmespath::{compile, Rcvar};
+
+const PROMPT: &str = "jp> ";
+
+const HELP: &str = "\
+Enter a JMESPath expression to evaluate it against the loaded document.
+Commands:
+  :history  List previously evaluated expressions
+  :N        Re-evaluate expression number N from the history
+  :help     Show this message
+  :quit     Exit the repl (end of input also exits)";
+
+/// Reads expressions from `input` until end of input or `:quit`, writing
+/// each result (or error) to `output`.
+pub fn run<R: BufRead, W: Write>(data: &Rcvar, input: R, mut output: W) -> io::Result<()> {
+    let mut history: Vec<String> = vec![];
+    write!(output, "{}", PROMPT)?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        match line {
+            "" => {}
+            ":quit" | ":q" => break,
+            ":help" => writeln!(output, "{}", HELP)?,
+            ":history" => {
+                for (i, expr) in history.iter().enumerate() {
+                    writeln!(output, "{:>4}  {}", i + 1, expr)?;
+                }
+            }
+            _ if line.starts_with(':') => match line[1..].parse::<usize>() {
+                Ok(n) if n >= 1 && n <= history.len() => {
+                    let expr = history[n - 1].clone();
+                    writeln!(output, "{}", expr)?;
+                    evaluate(data, &expr, &mut output)?;
+                    history.push(expr);
+                }
+                _ => writeln!(output, "Unknown command: {} (try :help)", line)?,
+            },
+            _ => {
+                evaluate(data, line, &mut output)?;
+                history.push(line.to_owned());
+            }
+        }
+        write!(output, "{}", PROMPT)?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+/// Compiles and evaluates a single expression, writing the outcome.
+fn evaluate<W: Write>(data: &Rcvar, expression: &str, output: &mut W) -> io::Result<()> {
+    let result = compile(expression).and_then(|expr| expr.search(data));
+    match result {
+        Ok(value) => {
+            serde_json::to_writer_pretty(&mut *output, &value)?;
+            writeln!(output)
+        }
+        Err(e) => write!(output, "{}", Diagnostic::from(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jmespath::Variable;
+
+    fn run_lines(input: &str) -> String {
+        let data = Rcvar::new(Variable::from_json("{\"foo\": [1, 2]}").unwrap());
+        let mut output = vec![];
+        run(&data, input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn evaluates_expressions_until_end_of_input() {
+        assert_eq!(
+            "jp> [\n  1,\n  2\n]\njp> 2\njp> \n",
+            run_lines("foo\nfoo[1]\n")
+        );
+    }
+
+    #[test]
+    fn shows_parse_errors_with_caret() {
+        let output = run_lines("foo{\n");
+        assert!(output.contains("error[unexpected_token]"));
+        assert!(output.contains("1 | foo{\n  |    ^ unexpected token\n"));
+    }
+
+    #[test]
+    fn lists_and_replays_history() {
+        let output = run_lines("foo[0]\nfoo[1]\n:history\n:1\n:quit\nfoo\n");
+        assert_eq!(
+            "jp> 1\njp> 2\njp>    1  foo[0]\n   2  foo[1]\njp> foo[0]\n1\njp> \n",
+            output
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        assert!(run_lines(":9\n").contains("Unknown command: :9"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::process::{Command, Stdio};
 
-const JPBIN: &str = "../target/debug/jp";
+const JPBIN: &str = env!("CARGO_BIN_EXE_jp");
 
 fn get_output(args: Vec<&str>) -> Result<String, String> {
     let mut cmd = Command::new(JPBIN);
@@ -25,8 +25,8 @@ fn prints_ast() {
 fn shows_parse_error_information_with_non_zero_rc() {
     let output = get_output(vec!["--ast", "foo{"]).unwrap_err();
     assert_eq!(
-        "Parse error: Unexpected led token -- found Lbrace (line 0, column 3)\nfoo{\
-               \n   ^\n\n",
+        "error[unexpected_token]: Unexpected led token -- found Lbrace\n \
+         --> 1:4\n  |\n1 | foo{\n  |    ^ unexpected token\n\n",
         output
     );
 }
@@ -68,6 +68,57 @@ fn unquoted_does_nothing_for_non_strings() {
     assert_eq!("[\n  \"foo\"\n]\n", output);
 }
 
+#[test]
+fn raw_string_is_an_alias_for_unquoted() {
+    let output = get_output(vec![
+        "-e",
+        "tests/fixtures/valid-expression",
+        "-f",
+        "tests/fixtures/valid-json",
+        "--raw-string",
+    ])
+    .unwrap();
+    assert_eq!("bar\n", output);
+}
+
+#[test]
+fn prints_compact_json() {
+    let output = get_output(vec![
+        "-f",
+        "tests/fixtures/valid-json",
+        "--compact",
+        "`{\"a\": [1, 2]}`",
+    ])
+    .unwrap();
+    assert_eq!("{\"a\":[1,2]}\n", output);
+}
+
+#[test]
+fn prints_pretty_json() {
+    let output = get_output(vec!["-f", "tests/fixtures/valid-json", "--pretty", "`[1]`"]).unwrap();
+    assert_eq!("[\n  1\n]\n", output);
+}
+
+#[test]
+fn compact_conflicts_with_pretty() {
+    let output = get_output(vec!["-f", "tests/fixtures/valid-json", "-c", "-p", "foo"]);
+    assert!(output.is_err());
+}
+
+#[test]
+fn prints_nothing_for_null_when_null_as_empty() {
+    let output = get_output(vec![
+        "-f",
+        "tests/fixtures/valid-json",
+        "--null-as-empty",
+        "missing",
+    ])
+    .unwrap();
+    assert_eq!("", output);
+    let output = get_output(vec!["-f", "tests/fixtures/valid-json", "missing"]).unwrap();
+    assert_eq!("null\n", output);
+}
+
 #[test]
 fn validates_json_file_exists() {
     let output = get_output(vec![
@@ -121,7 +172,7 @@ fn validates_expression_file_is_valid_expression() {
         "tests/fixtures/valid-json",
     ])
     .unwrap_err();
-    assert!(output.contains("Parse error"));
+    assert!(output.contains("error[unexpected_token]"));
 }
 
 #[test]
@@ -137,9 +188,51 @@ fn can_read_json_from_stdin() {
         .stdin
         .as_mut()
         .unwrap()
-        .write("{\"foo\":\"bar\"}".as_bytes())
+        .write_all("{\"foo\":\"bar\"}".as_bytes())
         .ok();
     let output = child.wait_with_output().unwrap();
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert_eq!("\"bar\"\n", stdout);
 }
+
+#[test]
+fn repl_evaluates_expressions_from_stdin() {
+    use std::io::prelude::*;
+    let mut child = Command::new(JPBIN)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .args(["repl", "-f", "tests/fixtures/valid-json"])
+        .spawn()
+        .expect("Failed to spawn process");
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all("foo.baz\n:history\n".as_bytes())
+        .ok();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!("jp> \"bar\"\njp>    1  foo.baz\njp> \n", stdout);
+}
+
+#[test]
+fn runs_regression_fixtures() {
+    let output = Command::new(JPBIN)
+        .args(["test", "tests/fixtures/regression"])
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "FAILED tests/fixtures/regression/names.json#1: length(people)\n  \
+         expected: 3\n  actual:   2\n2 passed, 1 failed\n",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    let output = Command::new(JPBIN)
+        .args(["test", "--json", "tests/fixtures/regression"])
+        .output()
+        .unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(2, report["passed"]);
+    assert_eq!("first name", report["results"][0]["name"]);
+    assert_eq!("failed", report["results"][1]["status"]);
+}
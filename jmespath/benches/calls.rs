@@ -0,0 +1,83 @@
+//! Benchmarks searching with function calls and multi-selects, and
+//! reports the number of allocations needed to evaluate each of them.
+//!
+//! The arguments of calls with up to three arguments are kept on the
+//! stack, so only the values the expressions build allocate.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bencher::*;
+use jmespath::{compile, Variable};
+
+/// Counts the allocations made by the benchmarks.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const CALLS: &str = "starts_with(name, 'a') && contains(tags, 'x')";
+const NESTED_CALLS: &str = "max([length(name), length(tags)])";
+const MULTI_LIST: &str = "[name, tags[0], length(tags)]";
+
+fn data() -> Variable {
+    Variable::from_json(r#"{"name": "abc", "tags": ["x", "y", "z"]}"#).unwrap()
+}
+
+fn allocations(expr: &str) -> usize {
+    let expr = compile(expr).unwrap();
+    let data = jmespath::Rcvar::new(data());
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    expr.search_shared(&data).unwrap();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn search(b: &mut Bencher, expr: &str) {
+    let expr = compile(expr).unwrap();
+    let data = jmespath::Rcvar::new(data());
+    b.iter(|| expr.search_shared(&data));
+}
+
+fn search_calls(b: &mut Bencher) {
+    search(b, CALLS);
+}
+
+fn search_nested_calls(b: &mut Bencher) {
+    search(b, NESTED_CALLS);
+}
+
+fn search_multi_list(b: &mut Bencher) {
+    search(b, MULTI_LIST);
+}
+
+benchmark_group!(
+    benches,
+    search_calls,
+    search_nested_calls,
+    search_multi_list
+);
+
+fn main() {
+    allocations(CALLS);
+    for expr in &[CALLS, NESTED_CALLS, MULTI_LIST] {
+        println!("{} allocations to search {}", allocations(expr), expr);
+    }
+    let mut test_opts = TestOpts::default();
+    if let Some(arg) = std::env::args().skip(1).find(|arg| *arg != "--bench") {
+        test_opts.filter = Some(arg);
+    }
+    run_tests_console(&test_opts, benches()).unwrap();
+}
@@ -0,0 +1,72 @@
+//! Benchmarks parsing expressions with short and long identifiers, and
+//! reports the number of allocations needed to parse each of them.
+//!
+//! Identifiers of up to 22 bytes are stored inline, so only the long
+//! identifiers allocate.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bencher::*;
+use jmespath::parse;
+
+/// Counts the allocations made by the benchmarks.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const SHORT: &str = "foo.bar.baz[0].qux";
+const LONG: &str =
+    "first_long_field_name_xx.second_long_field_name_x.third_long_field_name_x[0].fourth_long_field_name_x";
+const MULTI_HASH: &str = "people[*].{name: name, age: age, email: contact.email}";
+
+fn allocations(expr: &str) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    parse(expr).unwrap();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn parse_short_identifiers(b: &mut Bencher) {
+    b.iter(|| parse(SHORT));
+}
+
+fn parse_long_identifiers(b: &mut Bencher) {
+    b.iter(|| parse(LONG));
+}
+
+fn parse_multi_hash(b: &mut Bencher) {
+    b.iter(|| parse(MULTI_HASH));
+}
+
+benchmark_group!(
+    benches,
+    parse_short_identifiers,
+    parse_long_identifiers,
+    parse_multi_hash
+);
+
+fn main() {
+    allocations(SHORT);
+    for expr in &[SHORT, LONG, MULTI_HASH] {
+        println!("{} allocations to parse {}", allocations(expr), expr);
+    }
+    let mut test_opts = TestOpts::default();
+    if let Some(arg) = std::env::args().skip(1).find(|arg| *arg != "--bench") {
+        test_opts.filter = Some(arg);
+    }
+    run_tests_console(&test_opts, benches()).unwrap();
+}
@@ -18,6 +18,8 @@
 use std::fmt;
 
 use crate::lexer::Token;
+use crate::parser::PROJECTION_STOP;
+pub use crate::small_string::SmallString;
 use crate::Rcvar;
 
 /// A JMESPath expression abstract syntax tree.
@@ -68,7 +70,7 @@ pub enum Ast {
         /// Approximate absolute position in the parsed expression.
         offset: usize,
         /// Function name to invoke.
-        name: String,
+        name: SmallString,
         /// Function arguments.
         args: Vec<Ast>,
     },
@@ -77,7 +79,7 @@ pub enum Ast {
         /// Approximate absolute position in the parsed expression.
         offset: usize,
         /// Field name to extract.
-        name: String,
+        name: SmallString,
     },
     /// Extracts an index from a Vec.
     Index {
@@ -130,6 +132,24 @@ pub enum Ast {
         /// Node to extract object values from.
         node: Box<Ast>,
     },
+    /// Resolves to the value bound to a named parameter, written `:name`.
+    Parameter {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Name of the parameter, without the colon.
+        name: SmallString,
+    },
+    /// Evaluates the node and returns the values of a key found at any
+    /// depth in the result, in document order. Parsed from `a..name` when
+    /// `ParserOptions::recursive_descent` is enabled.
+    RecursiveDescent {
+        /// Approximate absolute position in the parsed expression.
+        offset: usize,
+        /// Node to search the values of.
+        node: Box<Ast>,
+        /// Key to search for.
+        name: SmallString,
+    },
     /// Evaluates LHS. If not truthy returns. Otherwise evaluates RHS.
     And {
         /// Approximate absolute position in the parsed expression.
@@ -170,9 +190,902 @@ pub enum Ast {
     },
 }
 
+impl Ast {
+    /// Returns a JMESPath expression that parses to an AST equivalent to
+    /// this AST, producing the same result for any input.
+    ///
+    /// The returned text is normalized: identifiers are only quoted when
+    /// necessary, literals are written as JSON, and parens are only added
+    /// when needed. Parsing the returned expression and converting it back
+    /// into a string yields the same text. An empty `MultiHash`, which the
+    /// parser never creates, has no syntax and is rendered as `{}`.
+    ///
+    /// ```
+    /// let ast = jmespath::parse("foo . \"bar\"[0] | [ *  ]").unwrap();
+    /// assert_eq!("foo.bar[0] | [*]", ast.to_expression_string());
+    /// ```
+    pub fn to_expression_string(&self) -> String {
+        render(self).text
+    }
+
+    /// Rebuilds the AST bottom up, replacing every node with the result
+    /// of `f`. See `jmespath::visitor::map_ast`.
+    pub fn map_ast<F: FnMut(Ast) -> Ast>(self, f: F) -> Ast {
+        crate::visitor::map_ast(self, f)
+    }
+
+    /// Rewrites the AST into a normal form, so that ASTs of expressions
+    /// that always produce the same result are more likely to be equal.
+    ///
+    /// The rewrites include removing sub-expressions of `@`, nesting chains
+    /// of sub-expressions, `||`, and `&&` the same way, writing `>` and `>=`
+    /// as `<` and `<=`, and ordering the operands of `==` and `!=`. In filter
+    /// predicates and `!`, where only truthiness matters, the operands of
+    /// `||` and `&&` are also sorted and deduplicated. Offsets of rewritten
+    /// nodes are not meaningful.
+    ///
+    /// ```
+    /// let a = jmespath::parse("foo[?b || a].bar | @").unwrap();
+    /// let b = jmespath::parse("foo[?a || b].bar").unwrap();
+    /// assert_eq!(a.normalize().to_expression_string(), b.normalize().to_expression_string());
+    /// ```
+    pub fn normalize(self) -> Ast {
+        self.map_ast(normalize_node)
+    }
+
+    /// Returns the kind of the node.
+    ///
+    /// ```
+    /// use jmespath::ast::NodeKind;
+    ///
+    /// assert_eq!(NodeKind::Subexpr, jmespath::parse("a.b").unwrap().node_kind());
+    /// ```
+    pub fn node_kind(&self) -> NodeKind {
+        match *self {
+            Ast::Comparison { .. } => NodeKind::Comparison,
+            Ast::Condition { .. } => NodeKind::Condition,
+            Ast::Identity { .. } => NodeKind::Identity,
+            Ast::Expref { .. } => NodeKind::Expref,
+            Ast::Flatten { .. } => NodeKind::Flatten,
+            Ast::Function { .. } => NodeKind::Function,
+            Ast::Field { .. } => NodeKind::Field,
+            Ast::Index { .. } => NodeKind::Index,
+            Ast::Literal { .. } => NodeKind::Literal,
+            Ast::MultiList { .. } => NodeKind::MultiList,
+            Ast::MultiHash { .. } => NodeKind::MultiHash,
+            Ast::Not { .. } => NodeKind::Not,
+            Ast::Projection { .. } => NodeKind::Projection,
+            Ast::ObjectValues { .. } => NodeKind::ObjectValues,
+            Ast::Parameter { .. } => NodeKind::Parameter,
+            Ast::RecursiveDescent { .. } => NodeKind::RecursiveDescent,
+            Ast::And { .. } => NodeKind::And,
+            Ast::Or { .. } => NodeKind::Or,
+            Ast::Slice { .. } => NodeKind::Slice,
+            Ast::Subexpr { .. } => NodeKind::Subexpr,
+        }
+    }
+
+    /// Returns the approximate absolute position of the node in the parsed
+    /// expression.
+    pub fn offset(&self) -> usize {
+        match *self {
+            Ast::Comparison { offset, .. }
+            | Ast::Condition { offset, .. }
+            | Ast::Identity { offset }
+            | Ast::Expref { offset, .. }
+            | Ast::Flatten { offset, .. }
+            | Ast::Function { offset, .. }
+            | Ast::Field { offset, .. }
+            | Ast::Index { offset, .. }
+            | Ast::Literal { offset, .. }
+            | Ast::MultiList { offset, .. }
+            | Ast::MultiHash { offset, .. }
+            | Ast::Not { offset, .. }
+            | Ast::Projection { offset, .. }
+            | Ast::ObjectValues { offset, .. }
+            | Ast::Parameter { offset, .. }
+            | Ast::RecursiveDescent { offset, .. }
+            | Ast::And { offset, .. }
+            | Ast::Or { offset, .. }
+            | Ast::Slice { offset, .. }
+            | Ast::Subexpr { offset, .. } => offset,
+        }
+    }
+
+    /// Returns the direct children of the node, in the order they appear
+    /// in the expression. The children of a `MultiHash` are the values of
+    /// its key value pairs.
+    ///
+    /// ```
+    /// let ast = jmespath::parse("a.b").unwrap();
+    /// let names: Vec<_> = ast.children().iter().filter_map(|c| c.field_name()).collect();
+    /// assert_eq!(vec!["a", "b"], names);
+    /// ```
+    pub fn children(&self) -> Vec<&Ast> {
+        match *self {
+            Ast::Comparison {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Projection {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::And {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Or {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Condition {
+                predicate: ref lhs,
+                then: ref rhs,
+                ..
+            } => vec![lhs, rhs],
+            Ast::Expref { ast: ref node, .. }
+            | Ast::Flatten { ref node, .. }
+            | Ast::Not { ref node, .. }
+            | Ast::ObjectValues { ref node, .. }
+            | Ast::RecursiveDescent { ref node, .. } => vec![node],
+            Ast::Function {
+                args: ref nodes, ..
+            }
+            | Ast::MultiList {
+                elements: ref nodes,
+                ..
+            } => nodes.iter().collect(),
+            Ast::MultiHash { ref elements, .. } => elements.iter().map(|kvp| &kvp.value).collect(),
+            Ast::Identity { .. }
+            | Ast::Field { .. }
+            | Ast::Index { .. }
+            | Ast::Literal { .. }
+            | Ast::Parameter { .. }
+            | Ast::Slice { .. } => vec![],
+        }
+    }
+
+    /// Returns the name of a `Field` node, or None for other nodes.
+    pub fn field_name(&self) -> Option<&str> {
+        match *self {
+            Ast::Field { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of a `Function` node, or None for other nodes.
+    pub fn function_name(&self) -> Option<&str> {
+        match *self {
+            Ast::Function { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns a graph of the AST in the Graphviz DOT language.
+    ///
+    /// Each node is labeled like a line of the `Display` tree and each
+    /// edge is labeled with the role of the child, e.g. `lhs` or `rhs`.
+    ///
+    /// ```
+    /// let ast = jmespath::parse("a.b").unwrap();
+    /// assert!(ast.to_dot().contains("n0 -> n1 [label=\"lhs\"];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph ast {\n    node [shape=box];\n");
+        self.write_dot(&mut out, &mut 0);
+        out.push_str("}\n");
+        out
+    }
+
+    /// Writes the node and its children as DOT statements, returning the
+    /// id assigned to the node.
+    fn write_dot(&self, out: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        out.push_str(&format!(
+            "    n{} [label={}];\n",
+            id,
+            dot_string(&self.label())
+        ));
+        for (edge, child) in self.labeled_children() {
+            let child_id = child.write_dot(out, next_id);
+            match edge {
+                Some(edge) => out.push_str(&format!(
+                    "    n{} -> n{} [label={}];\n",
+                    id,
+                    child_id,
+                    dot_string(&edge)
+                )),
+                None => out.push_str(&format!("    n{} -> n{};\n", id, child_id)),
+            }
+        }
+        id
+    }
+
+    /// Returns a one line description of the node, excluding its children.
+    fn label(&self) -> String {
+        match *self {
+            Ast::Comparison {
+                offset,
+                ref comparator,
+                ..
+            } => format!("Comparison {:?} @{}", comparator, offset),
+            Ast::Condition { offset, .. } => format!("Condition @{}", offset),
+            Ast::Identity { offset } => format!("Identity @{}", offset),
+            Ast::Expref { offset, .. } => format!("Expref @{}", offset),
+            Ast::Flatten { offset, .. } => format!("Flatten @{}", offset),
+            Ast::Function {
+                offset, ref name, ..
+            } => format!("Function {} @{}", name, offset),
+            Ast::Field { offset, ref name } => format!("Field {:?} @{}", name, offset),
+            Ast::Index { offset, idx } => format!("Index {} @{}", idx, offset),
+            Ast::Literal { offset, ref value } => format!("Literal `{}` @{}", value, offset),
+            Ast::MultiList { offset, .. } => format!("MultiList @{}", offset),
+            Ast::MultiHash { offset, .. } => format!("MultiHash @{}", offset),
+            Ast::Not { offset, .. } => format!("Not @{}", offset),
+            Ast::Projection { offset, .. } => format!("Projection @{}", offset),
+            Ast::ObjectValues { offset, .. } => format!("ObjectValues @{}", offset),
+            Ast::Parameter { offset, ref name } => format!("Parameter :{} @{}", name, offset),
+            Ast::RecursiveDescent {
+                offset, ref name, ..
+            } => format!("RecursiveDescent {:?} @{}", name, offset),
+            Ast::And { offset, .. } => format!("And @{}", offset),
+            Ast::Or { offset, .. } => format!("Or @{}", offset),
+            Ast::Slice {
+                offset,
+                start,
+                stop,
+                step,
+            } => {
+                let bound = |b: Option<i32>| b.map(|b| b.to_string()).unwrap_or_default();
+                format!(
+                    "Slice [{}:{}:{}] @{}",
+                    bound(start),
+                    bound(stop),
+                    step,
+                    offset
+                )
+            }
+            Ast::Subexpr { offset, .. } => format!("Subexpr @{}", offset),
+        }
+    }
+
+    /// Returns the children of the node, each with an optional name for
+    /// the role it plays in the parent.
+    fn labeled_children(&self) -> Vec<(Option<String>, &Ast)> {
+        fn named<'a>(
+            a: &str,
+            lhs: &'a Ast,
+            b: &str,
+            rhs: &'a Ast,
+        ) -> Vec<(Option<String>, &'a Ast)> {
+            vec![(Some(a.to_owned()), lhs), (Some(b.to_owned()), rhs)]
+        }
+        match *self {
+            Ast::Comparison {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Projection {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::And {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Or {
+                ref lhs, ref rhs, ..
+            }
+            | Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => named("lhs", lhs, "rhs", rhs),
+            Ast::Condition {
+                ref predicate,
+                ref then,
+                ..
+            } => named("predicate", predicate, "then", then),
+            Ast::Expref { ref ast, .. } => vec![(None, ast)],
+            Ast::Flatten { ref node, .. }
+            | Ast::Not { ref node, .. }
+            | Ast::ObjectValues { ref node, .. }
+            | Ast::RecursiveDescent { ref node, .. } => vec![(None, node)],
+            Ast::Function {
+                args: ref nodes, ..
+            }
+            | Ast::MultiList {
+                elements: ref nodes,
+                ..
+            } => nodes.iter().map(|node| (None, node)).collect(),
+            Ast::MultiHash { ref elements, .. } => elements
+                .iter()
+                .map(|kvp| (Some(format!("{:?}", kvp.key)), &kvp.value))
+                .collect(),
+            Ast::Identity { .. }
+            | Ast::Field { .. }
+            | Ast::Index { .. }
+            | Ast::Literal { .. }
+            | Ast::Parameter { .. }
+            | Ast::Slice { .. } => vec![],
+        }
+    }
+
+    /// Writes the node on a single line followed by each of its children,
+    /// indented one level deeper. `edge` names the role of the node in its
+    /// parent.
+    fn write_tree(
+        &self,
+        fmt: &mut fmt::Formatter<'_>,
+        depth: usize,
+        edge: Option<&str>,
+    ) -> Result<(), fmt::Error> {
+        if depth > 0 {
+            writeln!(fmt)?;
+        }
+        write!(fmt, "{:indent$}", "", indent = depth * 2)?;
+        if let Some(edge) = edge {
+            write!(fmt, "{}: ", edge)?;
+        }
+        write!(fmt, "{}", self.label())?;
+        for (edge, child) in self.labeled_children() {
+            child.write_tree(fmt, depth + 1, edge.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a string for use as a DOT identifier.
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Renders the AST as an expression, like `to_expression_string`.
+///
+/// The alternate form, `{:#}`, renders the AST as an indented tree with one
+/// node per line. Each line shows the node type, its most relevant data,
+/// and its offset in the original expression, e.g. `Field "foo" @0`.
+///
+/// ```
+/// let ast = jmespath::parse("foo[?bar > `1`]").unwrap();
+/// assert_eq!("foo[?bar > `1`]", ast.to_string());
+/// assert!(format!("{:#}", ast).starts_with("Projection @"));
+/// ```
 impl fmt::Display for Ast {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(fmt, "{:#?}", self)
+        if fmt.alternate() {
+            self.write_tree(fmt, 0, None)
+        } else {
+            fmt.write_str(&render(self).text)
+        }
+    }
+}
+
+/// The first token of rendered expression text.
+#[derive(Clone, Copy, PartialEq)]
+enum Start {
+    /// An identifier or quoted identifier.
+    Name,
+    Lbrace,
+    Lbracket,
+    Filter,
+    Ampersand,
+    Star,
+    /// The `..` of a recursive descent.
+    Descent,
+    Other,
+}
+
+/// Expression text along with how it binds to the surrounding tokens.
+struct Rendered {
+    text: String,
+    start: Start,
+    /// The lowest binding power of the infix tokens at the top level of
+    /// the text. The text is only parsed as a single operand by a parent
+    /// that binds less tightly than this.
+    leading: usize,
+    /// A token following the text with a binding power greater than this
+    /// is absorbed into the text rather than applied to it.
+    trailing: usize,
+    /// Like `trailing`, but ignores absorption into the right hand side
+    /// of a sub-expression, which is harmless for postfix tokens like
+    /// `.foo`, `[0]`, or `[*]` since sub-expressions are associative.
+    postfix: usize,
+}
+
+impl Rendered {
+    fn atom(text: String, start: Start) -> Rendered {
+        Rendered {
+            text,
+            start,
+            leading: usize::MAX,
+            trailing: usize::MAX,
+            postfix: usize::MAX,
+        }
+    }
+
+    /// Wraps the text in parens if it would not bind as a single operand
+    /// of a parent with the given right binding power.
+    fn operand(self, rbp: usize) -> Rendered {
+        if self.leading > rbp {
+            self
+        } else {
+            self.parenthesized()
+        }
+    }
+
+    /// Wraps the text in parens if a following token with the given
+    /// binding power would be absorbed into it.
+    fn left_of(self, lbp: usize) -> Rendered {
+        if self.trailing >= lbp {
+            self
+        } else {
+            self.parenthesized()
+        }
+    }
+
+    /// Like `left_of`, but for postfix tokens.
+    fn postfix_of(self, lbp: usize) -> Rendered {
+        if self.postfix >= lbp {
+            self
+        } else {
+            self.parenthesized()
+        }
+    }
+
+    fn parenthesized(self) -> Rendered {
+        Rendered::atom(format!("({})", self.text), Start::Other)
+    }
+}
+
+/// Returns true if the name can be written without quotes.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Writes a name as an identifier, quoting it only if necessary.
+pub(crate) fn render_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_owned()
+    } else {
+        serde_json::to_string(name).unwrap_or_else(|_| format!("{:?}", name))
+    }
+}
+
+fn render_slice(start: Option<i32>, stop: Option<i32>, step: i32) -> String {
+    let bound = |b: Option<i32>| b.map(|b| b.to_string()).unwrap_or_default();
+    if step == 1 {
+        format!("[{}:{}]", bound(start), bound(stop))
+    } else {
+        format!("[{}:{}:{}]", bound(start), bound(stop), step)
+    }
+}
+
+fn render_list(nodes: &[Ast]) -> String {
+    nodes
+        .iter()
+        .map(|node| render(node).text)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn render_infix(lhs: &Ast, op: &str, token: Token, rhs: &Ast) -> Rendered {
+    let bp = token.lbp();
+    let lhs = render(lhs).left_of(bp);
+    let rhs = render(rhs).operand(bp);
+    let trailing = bp.min(rhs.trailing);
+    Rendered {
+        text: format!("{} {} {}", lhs.text, op, rhs.text),
+        start: lhs.start,
+        leading: bp,
+        trailing,
+        postfix: trailing,
+    }
+}
+
+/// Renders the part of a projection that is applied to each element.
+///
+/// Returns the text along with its trailing binding power.
+fn render_projection_rhs(rhs: &Ast, bp: usize) -> (String, usize) {
+    if let Ast::Identity { .. } = *rhs {
+        // Tokens that can't continue a projection stop it.
+        return (String::new(), PROJECTION_STOP - 1);
+    }
+    let rendered = render(rhs);
+    let trailing = bp.min(rendered.trailing);
+    if rendered.leading > bp {
+        match rendered.start {
+            Start::Lbracket | Start::Filter | Start::Descent => return (rendered.text, trailing),
+            Start::Name | Start::Lbrace | Start::Ampersand | Start::Star => {
+                return (format!(".{}", rendered.text), trailing)
+            }
+            Start::Other => (),
+        }
+    }
+    // Any expression can be projected by selecting it into a list.
+    (format!("[({})][0]", rendered.text), bp)
+}
+
+/// Renders a projection whose lhs is either created by a nud (when the
+/// lhs is `@`) or by a postfix token applied to the lhs.
+fn render_projection(
+    lhs: &Ast,
+    nud: &str,
+    led: &str,
+    token: Token,
+    bp: usize,
+    rhs: &Ast,
+) -> Rendered {
+    let (head, start, leading) = match *lhs {
+        Ast::Identity { .. } => (nud.to_owned(), start_of(nud), usize::MAX),
+        _ => {
+            let lhs = render(lhs).postfix_of(token.lbp());
+            (format!("{}{}", lhs.text, led), lhs.start, token.lbp())
+        }
+    };
+    let (suffix, trailing) = render_projection_rhs(rhs, bp);
+    Rendered {
+        text: head + &suffix,
+        start,
+        leading,
+        trailing,
+        postfix: trailing,
+    }
+}
+
+fn start_of(text: &str) -> Start {
+    if text.starts_with("[?") {
+        Start::Filter
+    } else if text.starts_with("[]") {
+        Start::Other
+    } else if text.starts_with('[') {
+        Start::Lbracket
+    } else if text.starts_with('*') {
+        Start::Star
+    } else if text.starts_with("..") {
+        Start::Descent
+    } else {
+        Start::Other
+    }
+}
+
+/// Collects the nodes of a chain of sub-expressions in evaluation order.
+fn collect_subexprs<'a>(node: &'a Ast, segments: &mut Vec<&'a Ast>) {
+    match *node {
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            collect_subexprs(lhs, segments);
+            collect_subexprs(rhs, segments);
+        }
+        _ => segments.push(node),
+    }
+}
+
+/// Renders a chain of sub-expressions, joining each segment with `.`, a
+/// bracket, or a pipe, whichever reparses to the same evaluation.
+fn render_subexpr(node: &Ast) -> Rendered {
+    let mut segments = vec![];
+    collect_subexprs(node, &mut segments);
+    let mut segments = segments.into_iter();
+    let first = render(segments.next().expect("sub-expressions have a lhs"));
+    let mut text = first.text;
+    let mut start = first.start;
+    let mut leading = usize::MAX;
+    let mut trailing = first.trailing;
+    let mut last_trailing = first.trailing;
+    for segment in segments {
+        let is_index = match *segment {
+            Ast::Index { .. } => true,
+            Ast::Projection { ref lhs, .. } => matches!(**lhs, Ast::Slice { .. }),
+            _ => false,
+        };
+        let is_multi_list = matches!(*segment, Ast::MultiList { .. });
+        let rendered = render(segment);
+        let (dot, pipe) = (Token::Dot.lbp(), Token::Pipe.lbp());
+        if is_index && last_trailing >= Token::Lbracket.lbp() {
+            text.push_str(&rendered.text);
+            leading = leading.min(Token::Lbracket.lbp());
+        } else if last_trailing >= dot
+            && (is_multi_list
+                || (rendered.leading > dot
+                    && matches!(
+                        rendered.start,
+                        Start::Name | Start::Lbrace | Start::Ampersand
+                    )))
+        {
+            text.push('.');
+            text.push_str(&rendered.text);
+            leading = leading.min(dot);
+            trailing = trailing.min(dot);
+        } else {
+            if trailing < pipe {
+                text = format!("({})", text);
+                start = Start::Other;
+            }
+            let rendered = rendered.operand(pipe);
+            text.push_str(" | ");
+            text.push_str(&rendered.text);
+            leading = pipe;
+            trailing = pipe;
+            last_trailing = rendered.trailing;
+            continue;
+        }
+        last_trailing = rendered.trailing;
+    }
+    Rendered {
+        text,
+        start,
+        leading,
+        trailing: trailing.min(last_trailing),
+        postfix: last_trailing,
+    }
+}
+
+fn render(node: &Ast) -> Rendered {
+    match *node {
+        Ast::Identity { .. } => Rendered::atom("@".to_owned(), Start::Other),
+        Ast::Field { ref name, .. } => Rendered::atom(render_name(name), Start::Name),
+        Ast::Literal { ref value, .. } => Rendered::atom(
+            format!("`{}`", value.to_string().replace('`', "\\`")),
+            Start::Other,
+        ),
+        Ast::Index { idx, .. } => Rendered::atom(format!("[{}]", idx), Start::Lbracket),
+        Ast::Parameter { ref name, .. } => Rendered::atom(format!(":{}", name), Start::Other),
+        Ast::MultiList { ref elements, .. } => {
+            let text = match elements.as_slice() {
+                // "[]" is a flatten and "[*]" is a wildcard.
+                [] => "[ ]".to_owned(),
+                [element] if render(element).text == "*" => "[(*)]".to_owned(),
+                _ => format!("[{}]", render_list(elements)),
+            };
+            Rendered::atom(text, Start::Lbracket)
+        }
+        Ast::MultiHash { ref elements, .. } => {
+            let pairs = elements
+                .iter()
+                .map(|kvp| format!("{}: {}", render_name(&kvp.key), render(&kvp.value).text))
+                .collect::<Vec<_>>();
+            Rendered::atom(format!("{{{}}}", pairs.join(", ")), Start::Lbrace)
+        }
+        Ast::Function {
+            ref name, ref args, ..
+        } => Rendered {
+            leading: Token::Lparen.lbp(),
+            ..Rendered::atom(format!("{}({})", name, render_list(args)), Start::Name)
+        },
+        Ast::Expref { ref ast, .. } => Rendered {
+            text: format!("&{}", render(ast).text),
+            start: Start::Ampersand,
+            leading: usize::MAX,
+            trailing: 0,
+            postfix: 0,
+        },
+        Ast::Not { ref node, .. } => {
+            let bp = Token::Not.lbp();
+            let node = render(node).operand(bp);
+            let trailing = bp.min(node.trailing);
+            Rendered {
+                text: format!("!{}", node.text),
+                start: Start::Other,
+                leading: usize::MAX,
+                trailing,
+                postfix: trailing,
+            }
+        }
+        Ast::Comparison {
+            ref comparator,
+            ref lhs,
+            ref rhs,
+            ..
+        } => {
+            // Every comparator binds like `==`.
+            render_infix(lhs, comparator.as_str(), Token::Eq, rhs)
+        }
+        Ast::And {
+            ref lhs, ref rhs, ..
+        } => render_infix(lhs, "&&", Token::And, rhs),
+        Ast::Or {
+            ref lhs, ref rhs, ..
+        } => render_infix(lhs, "||", Token::Or, rhs),
+        Ast::Subexpr { .. } => render_subexpr(node),
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => {
+            if let Ast::Condition {
+                ref predicate,
+                ref then,
+                ..
+            } = **rhs
+            {
+                let filter = format!("[?{}]", render(predicate).text);
+                let bp = Token::Filter.lbp();
+                return render_projection(lhs, &filter, &filter, Token::Filter, bp, then);
+            }
+            match **lhs {
+                Ast::Flatten { ref node, .. } => {
+                    let bp = Token::Flatten.lbp();
+                    render_projection(node, "[]", "[]", Token::Flatten, bp, rhs)
+                }
+                Ast::ObjectValues { ref node, .. } => {
+                    let bp = Token::Star.lbp();
+                    render_projection(node, "*", ".*", Token::Dot, bp, rhs)
+                }
+                Ast::RecursiveDescent {
+                    ref node, ref name, ..
+                } => {
+                    let descent = format!("..{}", render_name(name));
+                    let bp = Token::Star.lbp();
+                    render_projection(node, &descent, &descent, Token::Dot, bp, rhs)
+                }
+                Ast::Slice {
+                    start, stop, step, ..
+                } => {
+                    let slice = render_slice(start, stop, step);
+                    let (suffix, trailing) = render_projection_rhs(rhs, Token::Star.lbp());
+                    Rendered {
+                        text: slice + &suffix,
+                        start: Start::Lbracket,
+                        leading: usize::MAX,
+                        trailing,
+                        postfix: trailing,
+                    }
+                }
+                _ => {
+                    let bp = Token::Star.lbp();
+                    render_projection(lhs, "[*]", "[*]", Token::Lbracket, bp, rhs)
+                }
+            }
+        }
+        // The following nodes only have a syntax as part of a projection,
+        // so they are rendered as the projection the parser would create.
+        Ast::Condition { offset, .. } => render(&Ast::Projection {
+            offset,
+            lhs: Box::new(Ast::Identity { offset }),
+            rhs: Box::new(node.clone()),
+        }),
+        Ast::Flatten { offset, .. }
+        | Ast::ObjectValues { offset, .. }
+        | Ast::RecursiveDescent { offset, .. }
+        | Ast::Slice { offset, .. } => render(&Ast::Projection {
+            offset,
+            lhs: Box::new(node.clone()),
+            rhs: Box::new(Ast::Identity { offset }),
+        }),
+    }
+}
+
+/// Normalizes a node whose children are already normalized.
+fn normalize_node(node: Ast) -> Ast {
+    match node {
+        Ast::Subexpr { offset, lhs, rhs } => subexpr(offset, *lhs, *rhs),
+        Ast::Or { offset, lhs, rhs } => chain(offset, Token::Or, *lhs, *rhs),
+        Ast::And { offset, lhs, rhs } => chain(offset, Token::And, *lhs, *rhs),
+        Ast::Comparison {
+            offset,
+            comparator,
+            lhs,
+            rhs,
+        } => {
+            let (comparator, lhs, rhs) = match comparator {
+                Comparator::GreaterThan => (Comparator::LessThan, rhs, lhs),
+                Comparator::GreaterThanEqual => (Comparator::LessThanEqual, rhs, lhs),
+                Comparator::Equal | Comparator::NotEqual
+                    if rhs.to_expression_string() < lhs.to_expression_string() =>
+                {
+                    (comparator, rhs, lhs)
+                }
+                comparator => (comparator, lhs, rhs),
+            };
+            Ast::Comparison {
+                offset,
+                comparator,
+                lhs,
+                rhs,
+            }
+        }
+        Ast::Condition {
+            offset,
+            predicate,
+            then,
+        } => Ast::Condition {
+            offset,
+            predicate: Box::new(truthiness(*predicate)),
+            then,
+        },
+        Ast::Not { offset, node } => Ast::Not {
+            offset,
+            node: Box::new(truthiness(*node)),
+        },
+        node => node,
+    }
+}
+
+/// Creates a normalized sub-expression of normalized nodes.
+fn subexpr(offset: usize, lhs: Ast, rhs: Ast) -> Ast {
+    match (lhs, rhs) {
+        (Ast::Identity { .. }, node) | (node, Ast::Identity { .. }) => node,
+        // Sub-expressions are associative, so always nest them on the left.
+        (lhs, Ast::Subexpr { lhs: a, rhs: b, .. }) => {
+            let lhs = subexpr(offset, lhs, *a);
+            subexpr(offset, lhs, *b)
+        }
+        (lhs, rhs) => Ast::Subexpr {
+            offset,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+    }
+}
+
+/// Creates a normalized `||` or `&&` of normalized nodes.
+fn chain(offset: usize, op: Token, lhs: Ast, rhs: Ast) -> Ast {
+    let mut operands = vec![];
+    collect_chain(&op, lhs, &mut operands);
+    collect_chain(&op, rhs, &mut operands);
+    build_chain(offset, &op, operands)
+}
+
+/// Collects the operands of a chain of `||` or `&&`, in order.
+fn collect_chain(op: &Token, node: Ast, operands: &mut Vec<Ast>) {
+    match (op, node) {
+        (Token::Or, Ast::Or { lhs, rhs, .. }) | (Token::And, Ast::And { lhs, rhs, .. }) => {
+            collect_chain(op, *lhs, operands);
+            collect_chain(op, *rhs, operands);
+        }
+        (_, node) => operands.push(node),
+    }
+}
+
+/// Nests the operands of a chain of `||` or `&&` on the left.
+fn build_chain(offset: usize, op: &Token, operands: Vec<Ast>) -> Ast {
+    let mut operands = operands.into_iter();
+    let first = operands.next().expect("chains have operands");
+    operands.fold(first, |lhs, rhs| {
+        let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+        match *op {
+            Token::Or => Ast::Or { offset, lhs, rhs },
+            _ => Ast::And { offset, lhs, rhs },
+        }
+    })
+}
+
+/// Normalizes a node whose result is only used for its truthiness, in
+/// which case operands of `||` and `&&` can be reordered and `!!` removed.
+fn truthiness(node: Ast) -> Ast {
+    match node {
+        Ast::Or { offset, .. } | Ast::And { offset, .. } => {
+            let op = match node {
+                Ast::Or { .. } => Token::Or,
+                _ => Token::And,
+            };
+            let mut operands = vec![];
+            collect_chain(&op, node, &mut operands);
+            let mut operands: Vec<_> = operands
+                .into_iter()
+                .map(|node| {
+                    let node = truthiness(node);
+                    (node.to_expression_string(), node)
+                })
+                .collect();
+            operands.sort_by(|a, b| a.0.cmp(&b.0));
+            operands.dedup_by(|a, b| a.0 == b.0);
+            build_chain(
+                offset,
+                &op,
+                operands.into_iter().map(|(_, node)| node).collect(),
+            )
+        }
+        Ast::Not { offset, node } => match *node {
+            Ast::Not { node, .. } => truthiness(*node),
+            node => Ast::Not {
+                offset,
+                node: Box::new(node),
+            },
+        },
+        node => node,
     }
 }
 
@@ -180,7 +1093,7 @@ impl fmt::Display for Ast {
 #[derive(Clone, PartialEq, Debug)]
 pub struct KeyValuePair {
     /// Key name.
-    pub key: String,
+    pub key: SmallString,
     /// Value expression used to determine the value.
     pub value: Ast,
 }
@@ -196,6 +1109,75 @@ pub enum Comparator {
     GreaterThanEqual,
 }
 
+impl KeyValuePair {
+    /// Creates a key value pair.
+    pub fn new<K: Into<SmallString>>(key: K, value: Ast) -> KeyValuePair {
+        KeyValuePair {
+            key: key.into(),
+            value,
+        }
+    }
+
+    /// Returns the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the value expression.
+    pub fn value(&self) -> &Ast {
+        &self.value
+    }
+}
+
+/// The kind of an `Ast` node, without its fields.
+///
+/// Matching on the kind instead of the node keeps working when fields are
+/// added to a variant.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NodeKind {
+    Comparison,
+    Condition,
+    Identity,
+    Expref,
+    Flatten,
+    Function,
+    Field,
+    Index,
+    Literal,
+    MultiList,
+    MultiHash,
+    Not,
+    Projection,
+    ObjectValues,
+    Parameter,
+    RecursiveDescent,
+    And,
+    Or,
+    Slice,
+    Subexpr,
+}
+
+impl Comparator {
+    /// Returns the operator of the comparator, like `==`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Comparator::Equal => "==",
+            Comparator::NotEqual => "!=",
+            Comparator::LessThan => "<",
+            Comparator::LessThanEqual => "<=",
+            Comparator::GreaterThan => ">",
+            Comparator::GreaterThanEqual => ">=",
+        }
+    }
+}
+
+/// Writes the operator of the comparator.
+impl fmt::Display for Comparator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.write_str(self.as_str())
+    }
+}
+
 /// Creates a Comparator from a Token.
 ///
 /// Note: panics if the Token is invalid.
@@ -220,12 +1202,176 @@ mod test {
     #[test]
     fn displays_pretty_printed_ast_node() {
         let node = Ast::Field {
-            name: "abc".to_string(),
+            name: "abc".into(),
             offset: 4,
         };
+        assert_eq!("Field \"abc\" @4", format!("{:#}", node));
+        assert_eq!("abc", format!("{}", node));
+    }
+
+    #[test]
+    fn displays_ast_as_indented_tree() {
+        let ast = crate::parse("foo[?a == `1`].{b: b, c: [0:2]} || length(@)").unwrap();
+        assert_eq!(
+            "Or @32
+  lhs: Projection @30
+    lhs: Field \"foo\" @0
+    rhs: Condition @30
+      predicate: Comparison Equal @10
+        lhs: Field \"a\" @5
+        rhs: Literal `1` @10
+      then: MultiHash @15
+        \"b\": Field \"b\" @19
+        \"c\": Projection @29
+          lhs: Slice [0:2:1] @29
+          rhs: Identity @29
+  rhs: Function length @41
+    Identity @42",
+            format!("{:#}", ast)
+        );
         assert_eq!(
-            "Field {\n    offset: 4,\n    name: \"abc\",\n}",
-            format!("{}", node)
+            "foo[?a == `1`].{b: b, c: [0:2]} || length(@)",
+            ast.to_string()
+        );
+        assert_eq!(
+            vec!["==", "!=", "<", "<=", ">", ">="],
+            [
+                Comparator::Equal,
+                Comparator::NotEqual,
+                Comparator::LessThan,
+                Comparator::LessThanEqual,
+                Comparator::GreaterThan,
+                Comparator::GreaterThanEqual,
+            ]
+            .iter()
+            .map(Comparator::to_string)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    fn field(name: &str) -> Box<Ast> {
+        Box::new(Ast::Field {
+            name: name.into(),
+            offset: 0,
+        })
+    }
+
+    #[test]
+    fn converts_parsed_ast_to_normalized_expression_string() {
+        let cases = [
+            ("foo . \"bar\"  [ 0 ]", "foo.bar[0]"),
+            ("\"a b\"[?c == 'd'].e[]", "\"a b\"[?c == `\"d\"`].e[]"),
+            ("(a[*]).b", "a[*] | b"),
+            ("(a || b) && !(c.d)", "(a || b) && !(c.d)"),
+            ("sort_by(@, &foo)[::-1].*", "sort_by(@, &foo)[::-1].*"),
+            ("{a: [a, b], \"c\": `[1]`}", "{a: [a, b], c: `[1]`}"),
+            ("a[ -1 ].b[-10] | [-3]", "a[-1].b[-10][-3]"),
+        ];
+        for (expr, expected) in cases.iter() {
+            let text = crate::parse(expr).unwrap().to_expression_string();
+            assert_eq!(*expected, text);
+            assert_eq!(text, crate::parse(&text).unwrap().to_expression_string());
+        }
+    }
+
+    #[test]
+    fn converts_constructed_ast_to_equivalent_expression_string() {
+        let data = crate::Variable::from_json(r#"{"a": {"b": [{"c": 1, "d": 2}, {"d": 3}]}}"#)
+            .map(crate::Rcvar::new)
+            .unwrap();
+        let cases = vec![
+            (
+                Ast::Projection {
+                    offset: 0,
+                    lhs: Box::new(Ast::Subexpr {
+                        offset: 0,
+                        lhs: field("a"),
+                        rhs: field("b"),
+                    }),
+                    rhs: Box::new(Ast::Or {
+                        offset: 0,
+                        lhs: field("c"),
+                        rhs: field("d"),
+                    }),
+                },
+                "a.b[*][(c || d)][0]",
+                "[1, 3]",
+            ),
+            (
+                Ast::Subexpr {
+                    offset: 0,
+                    lhs: Box::new(Ast::Subexpr {
+                        offset: 0,
+                        lhs: field("a"),
+                        rhs: field("b"),
+                    }),
+                    rhs: Box::new(Ast::Index { offset: 0, idx: -1 }),
+                },
+                "a.b[-1]",
+                r#"{"d": 3}"#,
+            ),
+            (
+                Ast::Not {
+                    offset: 0,
+                    node: Box::new(Ast::Flatten {
+                        offset: 0,
+                        node: field("x y"),
+                    }),
+                },
+                "!(\"x y\"[])",
+                "true",
+            ),
+        ];
+        for (ast, expected, result) in cases {
+            let text = ast.to_expression_string();
+            assert_eq!(expected, text);
+            let expr = crate::compile(&text).unwrap();
+            assert_eq!(
+                crate::Variable::from_json(result).unwrap(),
+                *expr.search(&data).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn inspects_nodes() {
+        let ast = crate::parse("{a: b, c: length(d)}").unwrap();
+        assert_eq!(NodeKind::MultiHash, ast.node_kind());
+        assert_eq!(0, ast.offset());
+        let children = ast.children();
+        assert_eq!(Some("b"), children[0].field_name());
+        assert_eq!(Some("length"), children[1].function_name());
+        assert_eq!(NodeKind::Field, children[1].children()[0].node_kind());
+        if let Ast::MultiHash { ref elements, .. } = ast {
+            assert_eq!("c", elements[1].key());
+            assert_eq!(children[1], elements[1].value());
+        }
+        assert_eq!(2, crate::parse("a[?b]").unwrap().children().len());
+        assert!(crate::parse("`1`").unwrap().children().is_empty());
+    }
+
+    #[test]
+    fn converts_ast_to_dot() {
+        let ast = crate::parse("a[?\"b\" == `\"c\"`]").unwrap();
+        assert_eq!(
+            r#"digraph ast {
+    node [shape=box];
+    n0 [label="Projection @15"];
+    n1 [label="Field \"a\" @0"];
+    n0 -> n1 [label="lhs"];
+    n2 [label="Condition @15"];
+    n3 [label="Comparison Equal @10"];
+    n4 [label="Field \"b\" @3"];
+    n3 -> n4 [label="lhs"];
+    n5 [label="Literal `\"c\"` @10"];
+    n3 -> n5 [label="rhs"];
+    n2 -> n3 [label="predicate"];
+    n6 [label="Identity @15"];
+    n2 -> n6 [label="then"];
+    n0 -> n2 [label="rhs"];
+}
+"#,
+            ast.to_dot()
         );
     }
 }
@@ -0,0 +1,257 @@
+//! Caching of compiled expressions.
+//!
+//! Applications that receive expressions as strings, such as one per
+//! request, can use an `ExpressionCache` to only parse each distinct
+//! expression once.
+//!
+//! ```
+//! use jmespath::cache::ExpressionCache;
+//!
+//! let mut cache = ExpressionCache::new(100);
+//! let data = jmespath::Variable::from_json(r#"{"foo": 1}"#).unwrap();
+//! for _ in 0..3 {
+//!     let expr = cache.compile("foo").unwrap();
+//!     assert_eq!(1.0, expr.search(&data).unwrap().as_number().unwrap());
+//! }
+//! assert_eq!(1, cache.len());
+//! ```
+//!
+//! With the `sync-cache` feature, `compile_cached` compiles expressions
+//! through a process-wide cache that can be used from any thread. The
+//! cache is split into shards with a lock each, so threads compiling
+//! different expressions rarely wait on each other.
+
+use std::collections::HashMap;
+
+use crate::{Expression, JmespathError, Runtime, DEFAULT_RUNTIME};
+
+/// A least recently used cache of compiled expressions, keyed by the
+/// expression text.
+///
+/// Expressions that fail to compile are not cached.
+pub struct ExpressionCache<'a> {
+    runtime: &'a Runtime,
+    capacity: usize,
+    /// Compiled expressions with the tick of their last use.
+    entries: HashMap<String, (u64, Expression<'a>)>,
+    tick: u64,
+}
+
+impl ExpressionCache<'static> {
+    /// Creates a cache that holds up to `capacity` expressions compiled
+    /// with the default Runtime.
+    pub fn new(capacity: usize) -> ExpressionCache<'static> {
+        ExpressionCache::with_runtime(&DEFAULT_RUNTIME, capacity)
+    }
+}
+
+impl<'a> ExpressionCache<'a> {
+    /// Creates a cache that holds up to `capacity` expressions compiled
+    /// with the given Runtime.
+    pub fn with_runtime(runtime: &'a Runtime, capacity: usize) -> ExpressionCache<'a> {
+        ExpressionCache {
+            runtime,
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns the compiled expression, compiling it if it isn't cached.
+    ///
+    /// When the cache is full, the least recently used expression is
+    /// evicted to make room. A cache with a capacity of 0 compiles and
+    /// keeps only the last expression.
+    pub fn compile(&mut self, expression: &str) -> Result<&Expression<'a>, JmespathError> {
+        self.tick += 1;
+        let tick = self.tick;
+        let cached = self.entries.contains_key(expression);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.runtime.metrics() {
+            if cached {
+                metrics.on_cache_hit(expression);
+            } else {
+                metrics.on_cache_miss(expression);
+            }
+        }
+        if !cached {
+            let compiled = self.runtime.compile(expression)?;
+            if self.entries.len() >= self.capacity.max(1) {
+                self.evict();
+            }
+            self.entries.insert(expression.to_owned(), (tick, compiled));
+        }
+        let entry = self.entries.get_mut(expression).unwrap();
+        entry.0 = tick;
+        Ok(&entry.1)
+    }
+
+    /// Returns true if the expression is cached, without marking it as used.
+    pub fn contains(&self, expression: &str) -> bool {
+        self.entries.contains_key(expression)
+    }
+
+    /// Returns the number of cached expressions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no expressions are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the maximum number of cached expressions.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes every cached expression.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, &(tick, _))| tick)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(feature = "sync-cache")]
+pub use self::global::{clear_global_cache, compile_cached, global_stats, CacheStats};
+
+#[cfg(feature = "sync-cache")]
+mod global {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    use lazy_static::lazy_static;
+
+    use super::ExpressionCache;
+    use crate::{Expression, JmespathError};
+
+    /// Number of shards of the global cache.
+    const SHARDS: usize = 16;
+
+    /// Number of expressions each shard of the global cache holds.
+    const SHARD_CAPACITY: usize = 64;
+
+    lazy_static! {
+        static ref GLOBAL_CACHE: Vec<Mutex<ExpressionCache<'static>>> = (0..SHARDS)
+            .map(|_| Mutex::new(ExpressionCache::new(SHARD_CAPACITY)))
+            .collect();
+    }
+
+    static HITS: AtomicU64 = AtomicU64::new(0);
+    static MISSES: AtomicU64 = AtomicU64::new(0);
+
+    /// Hit and miss counts of the global cache.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+    pub struct CacheStats {
+        /// Number of compilations that were answered from the cache.
+        pub hits: u64,
+        /// Number of compilations that had to parse the expression, including
+        /// the ones that failed.
+        pub misses: u64,
+    }
+
+    /// Compiles an expression with the default Runtime through the global
+    /// cache, which holds up to 1024 expressions.
+    ///
+    /// The returned expression is a clone of the cached one, which shares
+    /// its AST.
+    ///
+    /// ```
+    /// let expr = jmespath::cache::compile_cached("foo.bar").unwrap();
+    /// assert_eq!("foo.bar", expr.as_str());
+    /// assert!(jmespath::cache::global_stats().misses > 0);
+    /// ```
+    pub fn compile_cached(expression: &str) -> Result<Expression<'static>, JmespathError> {
+        let mut hasher = DefaultHasher::new();
+        expression.hash(&mut hasher);
+        let shard = &GLOBAL_CACHE[hasher.finish() as usize % SHARDS];
+        // A panic while holding the lock can't leave the cache inconsistent,
+        // so a poisoned lock is still used.
+        let mut cache = shard.lock().unwrap_or_else(|e| e.into_inner());
+        let counter = if cache.contains(expression) {
+            &HITS
+        } else {
+            &MISSES
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cache.compile(expression).cloned()
+    }
+
+    /// Returns the hit and miss counts of the global cache since the process
+    /// started.
+    pub fn global_stats() -> CacheStats {
+        CacheStats {
+            hits: HITS.load(Ordering::Relaxed),
+            misses: MISSES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Removes every expression from the global cache. The counts returned by
+    /// `global_stats` are not reset.
+    pub fn clear_global_cache() {
+        for shard in GLOBAL_CACHE.iter() {
+            shard.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_expressions() {
+        let mut cache = ExpressionCache::new(2);
+        cache.compile("a").unwrap();
+        cache.compile("b").unwrap();
+        cache.compile("a").unwrap();
+        cache.compile("c").unwrap();
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert_eq!(2, cache.len());
+        assert_eq!("c", cache.compile("c").unwrap().as_str());
+    }
+
+    #[test]
+    fn does_not_cache_errors() {
+        let mut cache = ExpressionCache::new(2);
+        assert!(cache.compile("a[").is_err());
+        assert!(cache.is_empty());
+        let mut cache = ExpressionCache::new(0);
+        cache.compile("a").unwrap();
+        cache.compile("b").unwrap();
+        assert_eq!(1, cache.len());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "sync-cache")]
+    #[test]
+    fn shares_global_cache_between_threads() {
+        let before = global_stats();
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| compile_cached("global_cache_test").unwrap()))
+            .collect();
+        for thread in threads {
+            assert_eq!("global_cache_test", thread.join().unwrap().as_str());
+        }
+        let after = global_stats();
+        // Other tests may use the cache at the same time.
+        assert!(after.misses > before.misses);
+        assert!(after.hits >= before.hits + 3);
+    }
+}
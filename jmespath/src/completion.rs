@@ -0,0 +1,274 @@
+//! Completion of partial JMESPath expressions.
+//!
+//! Given a partial expression, a cursor position, and a sample document,
+//! `complete` suggests the object keys, function names, and operators that
+//! can be inserted at the cursor. Candidates are found by asking the parser
+//! which tokens are valid at the cursor, and keys are taken from the values
+//! of the sample document that an identifier at the cursor would be
+//! evaluated against.
+//!
+//! ```
+//! use jmespath::completion::{complete, CompletionKind};
+//! use jmespath::{Rcvar, Variable};
+//!
+//! let data = Rcvar::new(Variable::from_json(r#"{"people": [{"name": "a", "age": 1}]}"#).unwrap());
+//! let completions = complete("people[?a", 9, &data);
+//! assert_eq!("age", completions[0].text);
+//! assert_eq!(CompletionKind::Key, completions[0].kind);
+//! assert_eq!(8..9, completions[0].span);
+//! ```
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::ast::{render_name, Ast};
+use crate::interpreter::interpret;
+use crate::lexer::{tokenize, Token};
+use crate::{parse, Context, Rcvar, Runtime, Variable, DEFAULT_RUNTIME};
+
+/// Identifier inserted at the cursor to find out what the parser expects.
+const PLACEHOLDER: &str = "__jmespath_completion__";
+
+/// Operators that are suggested when the cursor follows a complete
+/// expression, each with the text used to check if it is valid.
+const OPERATORS: &[(&str, &str)] = &[
+    (".", ".a"),
+    ("[", "[0]"),
+    ("[*]", "[*]"),
+    ("[]", "[]"),
+    ("[?", "[?@]"),
+    ("|", "| @"),
+    ("||", "|| @"),
+    ("&&", "&& @"),
+    ("==", "== @"),
+    ("!=", "!= @"),
+    ("<", "< @"),
+    ("<=", "<= @"),
+    (">", "> @"),
+    (">=", ">= @"),
+];
+
+/// The kind of completion candidate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompletionKind {
+    /// A key of an object in the sample document.
+    Key,
+    /// The name of a function registered with the runtime.
+    Function,
+    /// An operator that can follow the expression before the cursor.
+    Operator,
+}
+
+/// A completion candidate.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Completion {
+    /// Text to insert, quoted if necessary.
+    pub text: String,
+    /// What the text refers to.
+    pub kind: CompletionKind,
+    /// Byte range of the expression replaced by `text`, which covers the
+    /// partially typed identifier before the cursor, if any.
+    pub span: Range<usize>,
+}
+
+/// Returns completions for the expression at the cursor using the
+/// functions of the default runtime.
+///
+/// `cursor` is a byte offset into `expr`. Only the text before the cursor is
+/// considered. Keys are returned first, then functions, then operators.
+pub fn complete(expr: &str, cursor: usize, sample: &Rcvar) -> Vec<Completion> {
+    complete_with_runtime(&DEFAULT_RUNTIME, expr, cursor, sample)
+}
+
+/// Returns completions for the expression at the cursor using the
+/// functions of the given runtime.
+pub fn complete_with_runtime(
+    runtime: &Runtime,
+    expr: &str,
+    cursor: usize,
+    sample: &Rcvar,
+) -> Vec<Completion> {
+    let mut cursor = cursor.min(expr.len());
+    while !expr.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    let prefix = &expr[..cursor];
+    let word_len = prefix
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .count();
+    let (before, word) = prefix.split_at(cursor - word_len);
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        // A number is being typed.
+        return vec![];
+    }
+    let closers = match closers(before) {
+        Some(closers) => closers,
+        None => return vec![],
+    };
+    let span = before.len()..cursor;
+    let candidate = |text: String, kind: CompletionKind| Completion {
+        text,
+        kind,
+        span: span.clone(),
+    };
+    let mut completions = vec![];
+    if let Ok(ast) = parse(&format!("{}{}{}", before, PLACEHOLDER, closers)) {
+        completions.extend(
+            keys_at_placeholder(runtime, ast, sample)
+                .into_iter()
+                .map(|key| render_name(&key))
+                .filter(|key| key.starts_with(word))
+                .map(|key| candidate(key, CompletionKind::Key)),
+        );
+        let mut functions: Vec<_> = runtime
+            .function_names()
+            .filter(|name| name.starts_with(word))
+            .map(str::to_owned)
+            .collect();
+        functions.sort();
+        completions.extend(
+            functions
+                .into_iter()
+                .map(|name| candidate(name, CompletionKind::Function)),
+        );
+    }
+    // Operators can only follow a complete expression.
+    if word.is_empty() && parse(&format!("{}{}", before, closers)).is_ok() {
+        completions.extend(
+            OPERATORS
+                .iter()
+                .filter(|(_, probe)| parse(&format!("{} {}{}", before, probe, closers)).is_ok())
+                .map(|(op, _)| candidate((*op).to_owned(), CompletionKind::Operator)),
+        );
+    }
+    completions
+}
+
+/// Returns the tokens that close the brackets, braces, and parens left open
+/// by the expression, or None if the expression can't be tokenized.
+fn closers(expr: &str) -> Option<String> {
+    let mut open = vec![];
+    for (_, token) in tokenize(expr).ok()? {
+        match token {
+            Token::Lbracket | Token::Filter => open.push(']'),
+            Token::Lbrace => open.push('}'),
+            Token::Lparen => open.push(')'),
+            Token::Rbracket | Token::Rbrace | Token::Rparen => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+    Some(open.into_iter().rev().collect())
+}
+
+/// Evaluates the AST against the sample, returning the keys of every object
+/// that the placeholder field was evaluated against.
+fn keys_at_placeholder(runtime: &Runtime, ast: Ast, sample: &Rcvar) -> BTreeSet<String> {
+    let keys = Arc::new(Mutex::new(BTreeSet::new()));
+    // Replace the placeholder field with a call to a function that records
+    // the keys of its argument.
+    let ast = ast.map_ast(|node| match node {
+        Ast::Field { offset, ref name } if name == PLACEHOLDER => Ast::Function {
+            offset,
+            name: PLACEHOLDER.into(),
+            args: vec![Ast::Identity { offset }],
+        },
+        node => node,
+    });
+    let mut runtime = runtime.clone();
+    let recorded = keys.clone();
+    runtime.register_function(
+        PLACEHOLDER,
+        Arc::new(move |args: &[Rcvar], _: &mut Context<'_>| {
+            if let Some(object) = args[0].as_object() {
+                recorded.lock().unwrap().extend(object.keys().cloned());
+            }
+            Ok(Rcvar::new(Variable::Null))
+        }),
+    );
+    let mut ctx = Context::new(PLACEHOLDER, &runtime);
+    // Errors are expected since the placeholder evaluates to null, but any
+    // keys recorded before the error are still useful.
+    let _ = interpret(sample, &ast, &mut ctx);
+    let keys = keys.lock().unwrap().clone();
+    keys
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> Rcvar {
+        Rcvar::new(
+            Variable::from_json(
+                r#"{
+                    "foo": {"bar": 1, "baz": [{"qux": 1}, {"quux": 2}], "a b": 0},
+                    "fizz": true
+                }"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn texts(expr: &str, kind: CompletionKind) -> Vec<String> {
+        complete(expr, expr.len(), &sample())
+            .into_iter()
+            .filter(|c| c.kind == kind)
+            .map(|c| c.text)
+            .collect()
+    }
+
+    #[test]
+    fn completes_keys_in_context() {
+        assert_eq!(vec!["fizz", "foo"], texts("", CompletionKind::Key));
+        assert_eq!(vec!["foo"], texts("fo", CompletionKind::Key));
+        assert_eq!(
+            vec!["\"a b\"", "bar", "baz"],
+            texts("foo.", CompletionKind::Key)
+        );
+        assert_eq!(vec!["bar", "baz"], texts("foo.ba", CompletionKind::Key));
+        assert_eq!(
+            vec!["quux", "qux"],
+            texts("foo.baz[*].", CompletionKind::Key)
+        );
+        assert_eq!(
+            vec!["quux", "qux"],
+            texts("foo.baz[?q", CompletionKind::Key)
+        );
+        assert_eq!(
+            vec!["bar", "baz"],
+            texts("foo | {x: b", CompletionKind::Key)
+        );
+        assert_eq!(vec!["fizz", "foo"], texts("length(f", CompletionKind::Key));
+        assert!(texts("foo[", CompletionKind::Key).is_empty());
+        assert!(texts("foo.bar.", CompletionKind::Key).is_empty());
+    }
+
+    #[test]
+    fn completes_functions_and_operators() {
+        assert_eq!(vec!["length"], texts("foo | len", CompletionKind::Function));
+        assert!(texts("foo.", CompletionKind::Operator).is_empty());
+        assert_eq!(
+            vec![".", "[", "[*]", "[]", "[?", "|", "||", "&&", "==", "!=", "<", "<=", ">", ">="],
+            texts("foo ", CompletionKind::Operator)
+        );
+        assert_eq!(
+            texts("foo ", CompletionKind::Operator),
+            texts("[?foo ", CompletionKind::Operator)
+        );
+        // Operators aren't suggested while an identifier is being typed.
+        assert!(texts("[?foo", CompletionKind::Operator).is_empty());
+    }
+
+    #[test]
+    fn reports_replaced_span() {
+        let completions = complete("foo.ba || x", 6, &sample());
+        assert_eq!(4..6, completions[0].span);
+        assert_eq!("bar", completions[0].text);
+        assert!(complete("\"fo", 3, &sample()).is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+//! JMESPath compliance test harness.
+//!
+//! Loads the JSON compliance files published with the JMESPath
+//! specification and runs each case through the parser and interpreter.
+//! The crate's own compliance tests are generated from this module by
+//! `build.rs`, and the same API can be used to measure conformance
+//! against any directory of compliance files.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use jmespath::compliance::run_directory;
+//!
+//! let report = run_directory(Path::new("tests/compliance")).unwrap();
+//! println!("{}", report);
+//! assert!(report.is_success());
+//! ```
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::{compile, Expression, Rcvar, RuntimeError, Variable};
+
+/// Avaliable benchmark types.
+pub enum BenchType {
+    /// The benchmark must only parse an expression.
+    Parse,
+    /// The benchmark must benchmark only the interpreter
+    Interpret,
+    /// The benchmark must benchmark both the parser and interpreter.
+    /// JMESPath.rs will benchmark an entire execute, parsing, interpreting separately.
+    Full,
+}
+
+impl BenchType {
+    /// Try to create a benchmark assertion from a JSON value.
+    fn from_json(bench_type: &Value) -> Result<Self, TestCaseError> {
+        bench_type
+            .as_str()
+            .ok_or(TestCaseError::BenchIsNotString)
+            .and_then(|b| match b {
+                "parse" => Ok(BenchType::Parse),
+                "interpret" => Ok(BenchType::Interpret),
+                "full" => Ok(BenchType::Full),
+                s => Err(TestCaseError::UnknownBenchType(s.to_string())),
+            })
+    }
+}
+
+impl fmt::Display for BenchType {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            BenchType::Parse => write!(fmt, "parse"),
+            BenchType::Interpret => write!(fmt, "interpret"),
+            BenchType::Full => write!(fmt, "full"),
+        }
+    }
+}
+
+/// Avaliable error types.
+pub enum ErrorType {
+    /// Ensures that the expression fails due to an invalid-arity error.
+    InvalidArity,
+    /// Ensures that the expression fails due to an invalid-type error.
+    InvalidType,
+    /// Ensures that the expression fails due to an invalid-value error.
+    InvalidSlice,
+    /// Ensures that the expression fails due to an unknown-function error.
+    UnknownFunction,
+    /// Ensures that an expression cannot be parsed due to a syntax error.
+    SyntaxError,
+}
+
+impl ErrorType {
+    /// Try to create an error assertion from a JSON value.
+    fn from_json(error_type: &Value) -> Result<Self, TestCaseError> {
+        error_type
+            .as_str()
+            .ok_or(TestCaseError::ErrorIsNotString)
+            .and_then(|b| match b {
+                "syntax" => Ok(ErrorType::SyntaxError),
+                "invalid-type" => Ok(ErrorType::InvalidType),
+                "invalid-value" => Ok(ErrorType::InvalidSlice),
+                "invalid-arity" => Ok(ErrorType::InvalidArity),
+                "unknown-function" => Ok(ErrorType::UnknownFunction),
+                e => Err(TestCaseError::UnknownErrorType(e.to_string())),
+            })
+    }
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        use self::ErrorType::*;
+        match *self {
+            InvalidArity => write!(fmt, "invalid-arity"),
+            InvalidType => write!(fmt, "invalid-type"),
+            InvalidSlice => write!(fmt, "invalid-value"),
+            UnknownFunction => write!(fmt, "unknown-function"),
+            SyntaxError => write!(fmt, "syntax"),
+        }
+    }
+}
+
+/// Test case assertions.
+pub enum Assertion {
+    /// Ensures that a test fails with a particular error type.
+    Error(ErrorType),
+    /// Ignores the result and marks the test as a benchmark
+    Bench(BenchType),
+    /// Ensures that the expression is parsed and returns an expected result.
+    ValidResult(Rcvar),
+}
+
+impl Assertion {
+    /// Runs the assertion of a test case
+    pub fn assert(&self, suite: &str, case: &TestCase, given: Rcvar) -> Result<(), String> {
+        match self {
+            &Assertion::Bench(_) => Ok(()),
+            Assertion::ValidResult(expected_result) => {
+                let expr = self.try_parse(suite, case)?;
+                match expr.search(given) {
+                    Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                    Ok(r) => {
+                        if *r == **expected_result {
+                            Ok(())
+                        } else {
+                            Err(self.err_message(
+                                suite,
+                                case,
+                                format!("{:?}, {}", r, expr.as_ast()),
+                            ))
+                        }
+                    }
+                }
+            }
+            Assertion::Error(error_type) => {
+                use crate::ErrorReason::*;
+                let result = self.try_parse(suite, case);
+                match *error_type {
+                    ErrorType::InvalidArity => match result?.search(given).map_err(|e| e.reason) {
+                        Err(Runtime(RuntimeError::NotEnoughArguments { .. })) => Ok(()),
+                        Err(Runtime(RuntimeError::TooManyArguments { .. })) => Ok(()),
+                        Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                        Ok(r) => Err(self.err_message(suite, case, r.to_string())),
+                    },
+                    ErrorType::InvalidType => match result?.search(given).map_err(|e| e.reason) {
+                        Err(Runtime(RuntimeError::InvalidType { .. })) => Ok(()),
+                        Err(Runtime(RuntimeError::InvalidReturnType { .. })) => Ok(()),
+                        Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                        Ok(r) => Err(self.err_message(suite, case, r.to_string())),
+                    },
+                    ErrorType::InvalidSlice => match result?.search(given).map_err(|e| e.reason) {
+                        Err(Runtime(RuntimeError::InvalidSlice)) => Ok(()),
+                        Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                        Ok(r) => Err(self.err_message(suite, case, r.to_string())),
+                    },
+                    ErrorType::UnknownFunction => {
+                        match result?.search(given).map_err(|e| e.reason) {
+                            Err(Runtime(RuntimeError::UnknownFunction(_))) => Ok(()),
+                            Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+                            Ok(r) => Err(self.err_message(suite, case, r.to_string())),
+                        }
+                    }
+                    ErrorType::SyntaxError => match result {
+                        Err(_) => Ok(()),
+                        Ok(expr) => {
+                            Err(self.err_message(suite, case, format!("Parsed {:?}", expr)))
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Attempts to parse an expression for a case, returning the expression or an error string.
+    fn try_parse(&self, suite: &str, case: &TestCase) -> Result<Expression<'_>, String> {
+        match compile(&case.expression) {
+            Err(e) => Err(self.err_message(suite, case, format!("{}", e))),
+            Ok(expr) => Ok(expr),
+        }
+    }
+
+    /// Formats an error message for a test case failure.
+    fn err_message(&self, suite: &str, case: &TestCase, message: String) -> String {
+        format!(
+            "Test suite: {}\nExpression: {}\nAssertion: {}\nResult: {}\n==============",
+            suite, case.expression, self, message
+        )
+        .to_string()
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Assertion::Error(e) => write!(fmt, "expects error({})", e),
+            Assertion::Bench(b) => write!(fmt, "expects bench({})", b),
+            Assertion::ValidResult(r) => write!(fmt, "expects result({:?})", r),
+        }
+    }
+}
+
+/// The test suite holds a collection of test cases and has a given value.
+pub struct TestSuite {
+    /// Filename of the test suite
+    filename: String,
+    /// Given data of the test suite
+    given: Rcvar,
+    /// Collection of test cases to perform
+    cases: Vec<TestCase>,
+}
+
+impl TestSuite {
+    /// Creates a test suite from JSON string.
+    pub fn from_str(filename: &str, suite: &str) -> Result<TestSuite, String> {
+        serde_json::from_str::<Value>(suite)
+            .map_err(|e| e.to_string())
+            .and_then(|j| TestSuite::from_json(filename, &j))
+    }
+
+    /// Loads every test suite from a compliance file.
+    ///
+    /// Compliance files contain a JSON array of suites, each of which has
+    /// a `given` value and a list of `cases`.
+    pub fn from_file(path: &Path) -> Result<Vec<TestSuite>, String> {
+        let filename = path.to_string_lossy().into_owned();
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", filename, e))?;
+        let json =
+            serde_json::from_str::<Value>(&contents).map_err(|e| format!("{}: {}", filename, e))?;
+        json.as_array()
+            .ok_or_else(|| format!("{}: compliance file is not an array", filename))?
+            .iter()
+            .map(|suite| TestSuite::from_json(&filename, suite))
+            .collect()
+    }
+
+    /// Creates a test suite from parsed JSON data.
+    fn from_json(filename: &str, suite: &Value) -> Result<TestSuite, String> {
+        let suite = suite.as_object().ok_or("test suite is not an object")?;
+        let test_case = suite.get("cases").ok_or("No cases value")?;
+        let case_array = test_case.as_array().ok_or("cases is not an array")?;
+        let mut cases = vec![];
+        for case in case_array {
+            cases.push(TestCase::from_json(case).map_err(|e| e.to_string())?);
+        }
+        let value = suite.get("given").ok_or("No given value")?.clone();
+        let given = serde_json::from_value::<Variable>(value).map_err(|e| format!("{}", e))?;
+        Ok(TestSuite {
+            filename: filename.to_owned(),
+            given: Rcvar::new(given),
+            cases,
+        })
+    }
+
+    /// Returns the filename from which the suite was loaded.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Returns the value that every case in the suite is evaluated against.
+    pub fn given(&self) -> &Rcvar {
+        &self.given
+    }
+
+    /// Returns the test cases of the suite.
+    pub fn cases(&self) -> &[TestCase] {
+        &self.cases
+    }
+
+    /// Runs every case of the suite, recording the outcome in `report`
+    /// under the given category.
+    pub fn run(&self, category: &str, report: &mut Report) {
+        let results = report.categories.entry(category.to_owned()).or_default();
+        for case in &self.cases {
+            if let Assertion::Bench(_) = case.assertion {
+                results.skipped += 1;
+                continue;
+            }
+            match case.assert(&self.filename, self.given.clone()) {
+                Ok(()) => results.passed += 1,
+                Err(message) => results.failures.push(message),
+            }
+        }
+    }
+}
+
+/// Errors that can occur when creating a TestCase
+pub enum TestCaseError {
+    InvalidJSON(String),
+    NoCaseType,
+    NoResult,
+    ResultCannotToString,
+    NoExpression,
+    ExpressionIsNotString,
+    ErrorIsNotString,
+    UnknownErrorType(String),
+    UnknownBenchType(String),
+    BenchIsNotString,
+}
+
+impl fmt::Display for TestCaseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        use self::TestCaseError::*;
+        match self {
+            InvalidJSON(msg) => write!(fmt, "invalid test case JSON: {}", msg),
+            &NoCaseType => write!(fmt, "case has no result, error, or bench"),
+            &NoResult => write!(fmt, "test case has no result key"),
+            &ResultCannotToString => write!(fmt, "result could not be cast to string"),
+            &NoExpression => write!(fmt, "test case has no expression key"),
+            &ExpressionIsNotString => write!(fmt, "test case expression is not a string"),
+            &ErrorIsNotString => write!(fmt, "test case error value is not a string"),
+            UnknownErrorType(t) => write!(fmt, "unknown error type: {}", t),
+            &BenchIsNotString => write!(fmt, "bench value is not a string"),
+            UnknownBenchType(bench) => write!(
+                fmt,
+                "unknown bench value: {}, expected one of of parse|full",
+                bench
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for TestCaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Represents a test case that contains an expression and assertion.
+pub struct TestCase {
+    /// The expression being evaluated.
+    pub expression: String,
+    /// The assertion to perform for the test case.
+    pub assertion: Assertion,
+}
+
+impl TestCase {
+    /// Creates a test case from a JSON encoded string.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(case: &str) -> Result<TestCase, TestCaseError> {
+        serde_json::from_str::<Value>(case)
+            .map_err(|e| TestCaseError::InvalidJSON(e.to_string()))
+            .and_then(|json| TestCase::from_json(&json))
+    }
+
+    /// Creates a test case from parsed JSON data.
+    fn from_json(case: &Value) -> Result<TestCase, TestCaseError> {
+        use self::TestCaseError::*;
+        let case = case
+            .as_object()
+            .ok_or(InvalidJSON("not an object".to_string()))?;
+        Ok(TestCase {
+            expression: case
+                .get("expression")
+                .ok_or(NoExpression)
+                .and_then(|expression| {
+                    expression
+                        .as_str()
+                        .ok_or(ExpressionIsNotString)
+                        .map(|expression_str| expression_str.to_string())
+                })?,
+            assertion: match case.get("error") {
+                Some(err) => Assertion::Error(ErrorType::from_json(err)?),
+                None if case.contains_key("result") => {
+                    let value = case.get("result").unwrap();
+                    let var = serde_json::from_value::<Variable>(value.clone()).unwrap();
+                    Assertion::ValidResult(Rcvar::new(var))
+                }
+                None if case.contains_key("bench") => {
+                    Assertion::Bench(BenchType::from_json(case.get("bench").unwrap())?)
+                }
+                _ => return Err(NoCaseType),
+            },
+        })
+    }
+
+    /// Perform the test case assertion against a given value.
+    pub fn assert(&self, suite_filename: &str, given: Rcvar) -> Result<(), String> {
+        self.assertion.assert(suite_filename, self, given)
+    }
+}
+
+/// Pass/fail counts for a single category of compliance tests.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CategoryReport {
+    /// Number of cases that passed.
+    pub passed: usize,
+    /// Number of benchmark-only cases that were not asserted.
+    pub skipped: usize,
+    /// Failure message of each case that failed.
+    pub failures: Vec<String>,
+}
+
+impl CategoryReport {
+    /// Returns the number of cases that failed.
+    pub fn failed(&self) -> usize {
+        self.failures.len()
+    }
+}
+
+/// Results of running compliance suites, grouped by category.
+///
+/// A category is the file stem of the compliance file the suite came
+/// from (e.g., `slice` for `slice.json`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Results keyed by category name.
+    pub categories: BTreeMap<String, CategoryReport>,
+}
+
+impl Report {
+    /// Creates an empty report.
+    pub fn new() -> Report {
+        Default::default()
+    }
+
+    /// Returns the total number of passed cases across all categories.
+    pub fn passed(&self) -> usize {
+        self.categories.values().map(|c| c.passed).sum()
+    }
+
+    /// Returns the total number of failed cases across all categories.
+    pub fn failed(&self) -> usize {
+        self.categories.values().map(CategoryReport::failed).sum()
+    }
+
+    /// Returns true if no case failed.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        for (name, category) in &self.categories {
+            writeln!(
+                fmt,
+                "{}: {} passed, {} failed, {} skipped",
+                name,
+                category.passed,
+                category.failed(),
+                category.skipped
+            )?;
+        }
+        write!(
+            fmt,
+            "total: {} passed, {} failed",
+            self.passed(),
+            self.failed()
+        )
+    }
+}
+
+/// Runs every suite of a single compliance file, adding the results to
+/// `report` under the file's stem.
+pub fn run_file(path: &Path, report: &mut Report) -> Result<(), String> {
+    let category = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for suite in TestSuite::from_file(path)? {
+        suite.run(&category, report);
+    }
+    Ok(())
+}
+
+/// Runs every `.json` compliance file found in a directory.
+pub fn run_directory(dir: &Path) -> Result<Report, String> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut report = Report::new();
+    for path in paths {
+        run_file(&path, &mut report)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SUITE: &str = r#"{
+        "given": {"foo": [1, 2]},
+        "cases": [
+            {"expression": "foo[0]", "result": 1},
+            {"expression": "foo[1]", "result": 1},
+            {"expression": "foo[", "error": "syntax"},
+            {"expression": "foo", "bench": "full"}
+        ]
+    }"#;
+
+    #[test]
+    fn runs_suite_into_category_report() {
+        let suite = TestSuite::from_str("test.json", SUITE).unwrap();
+        assert_eq!(4, suite.cases().len());
+        let mut report = Report::new();
+        suite.run("test", &mut report);
+        let category = &report.categories["test"];
+        assert_eq!(2, category.passed);
+        assert_eq!(1, category.failed());
+        assert_eq!(1, category.skipped);
+        assert!(category.failures[0].contains("Expression: foo[1]"));
+        assert!(!report.is_success());
+        assert_eq!(
+            "test: 2 passed, 1 failed, 1 skipped\ntotal: 2 passed, 1 failed",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_error_types() {
+        let err = TestCase::from_str(r#"{"expression": "a", "error": "nope"}"#)
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!("unknown error type: nope", err.to_string());
+    }
+}
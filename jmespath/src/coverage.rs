@@ -0,0 +1,190 @@
+//! Coverage of expressions across a corpus of documents.
+//!
+//! A `Coverage` searches documents with an expression and records how many
+//! times each of its AST nodes was evaluated. Nodes that were never
+//! evaluated point at logic the corpus never exercises, like the right
+//! hand side of an `||` whose left hand side is always truthy.
+//!
+//! ```
+//! use jmespath::coverage::Coverage;
+//! use jmespath::Variable;
+//!
+//! let expr = jmespath::compile("nickname || name").unwrap();
+//! let mut coverage = Coverage::new(&expr);
+//! for json in &[r#"{"nickname": "a"}"#, r#"{"nickname": "b", "name": "c"}"#] {
+//!     coverage.search(Variable::from_json(json).unwrap()).unwrap();
+//! }
+//! let uncovered: Vec<_> = coverage.uncovered().map(|node| node.node.as_str()).collect();
+//! assert_eq!(vec!["name"], uncovered);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{Ast, NodeKind};
+use crate::debugger::{DebugAction, Debugger, Frame};
+use crate::interpreter::SearchResult;
+use crate::{Expression, ToJmespath};
+
+/// How often one AST node was evaluated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeCoverage {
+    /// Offset of the node in the expression.
+    pub offset: usize,
+    /// The node, formatted as an expression.
+    pub node: String,
+    /// Number of times the node was evaluated.
+    pub hits: u64,
+}
+
+/// Records which nodes of an expression are evaluated across searches.
+///
+/// Nodes are identified by their offset and kind, so the nodes of an
+/// expression reference are covered when a function evaluates it.
+#[derive(Clone)]
+pub struct Coverage<'a> {
+    expression: Expression<'a>,
+    documents: u64,
+    counter: Counter,
+}
+
+#[derive(Clone)]
+struct Counter {
+    /// The nodes of the expression, in the order they are written.
+    nodes: Vec<NodeCoverage>,
+    positions: HashMap<(usize, NodeKind), usize>,
+}
+
+impl<'a> Coverage<'a> {
+    /// Creates a coverage of the nodes of an expression.
+    pub fn new(expression: &Expression<'a>) -> Coverage<'a> {
+        let mut nodes = vec![];
+        collect(expression.as_ast(), &mut nodes);
+        nodes.sort_by_key(|(_, node)| node.offset);
+        let mut positions = HashMap::new();
+        for (i, (kind, node)) in nodes.iter().enumerate() {
+            positions.entry((node.offset, *kind)).or_insert(i);
+        }
+        Coverage {
+            expression: expression.clone(),
+            documents: 0,
+            counter: Counter {
+                nodes: nodes.into_iter().map(|(_, node)| node).collect(),
+                positions,
+            },
+        }
+    }
+
+    /// Searches a document with the expression, recording the nodes that
+    /// are evaluated.
+    pub fn search<T: ToJmespath>(&mut self, data: T) -> SearchResult {
+        self.documents += 1;
+        self.expression.debug(data, &mut self.counter)
+    }
+
+    /// Returns the number of documents searched.
+    pub fn documents(&self) -> u64 {
+        self.documents
+    }
+
+    /// Returns every node of the expression, ordered by offset.
+    pub fn nodes(&self) -> &[NodeCoverage] {
+        &self.counter.nodes
+    }
+
+    /// Returns the nodes that were never evaluated.
+    pub fn uncovered(&self) -> impl Iterator<Item = &NodeCoverage> {
+        self.counter.nodes.iter().filter(|node| node.hits == 0)
+    }
+
+    /// Returns the fraction of the nodes that were evaluated, from 0 to 1.
+    pub fn ratio(&self) -> f64 {
+        let covered = self.nodes().iter().filter(|node| node.hits > 0).count();
+        covered as f64 / self.nodes().len() as f64
+    }
+}
+
+/// Writes a summary followed by the nodes that were never evaluated.
+impl<'a> fmt::Display for Coverage<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            fmt,
+            "{:.1}% of {} nodes covered by {} documents",
+            self.ratio() * 100.0,
+            self.nodes().len(),
+            self.documents
+        )?;
+        for node in self.uncovered() {
+            writeln!(fmt, "never evaluated at {}: {}", node.offset, node.node)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds a node and its descendants to the coverage entries.
+fn collect(node: &Ast, nodes: &mut Vec<(NodeKind, NodeCoverage)>) {
+    nodes.push((
+        node.node_kind(),
+        NodeCoverage {
+            offset: node.offset(),
+            node: node.to_string(),
+            hits: 0,
+        },
+    ));
+    for child in node.children() {
+        collect(child, nodes);
+    }
+}
+
+impl Debugger for Counter {
+    fn on_node(&mut self, _: &Frame<'_>) -> DebugAction {
+        DebugAction::Step
+    }
+
+    fn on_node_end(&mut self, node: &Ast, _: &SearchResult) {
+        if let Some(&i) = self.positions.get(&(node.offset(), node.node_kind())) {
+            self.nodes[i].hits += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Variable;
+
+    #[test]
+    fn reports_nodes_that_are_never_evaluated() {
+        let expr = crate::compile("a && sort_by(b, &c) || d").unwrap();
+        let mut coverage = Coverage::new(&expr);
+        assert_eq!(0.0, coverage.ratio());
+        coverage
+            .search(Variable::from_json(r#"{"a": true, "b": [{"c": 1}, {"c": 2}]}"#).unwrap())
+            .unwrap();
+        let hits: Vec<_> = coverage
+            .nodes()
+            .iter()
+            .map(|node| (node.node.as_str(), node.hits))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a", 1),
+                ("a && sort_by(b, &c)", 1),
+                ("sort_by(b, &c)", 1),
+                ("b", 1),
+                ("&c", 1),
+                ("c", 2),
+                ("a && sort_by(b, &c) || d", 1),
+                ("d", 0),
+            ],
+            hits
+        );
+        coverage.search(Variable::Null).unwrap();
+        assert_eq!(2, coverage.documents());
+        assert_eq!(1.0, coverage.ratio());
+        assert_eq!(
+            "100.0% of 8 nodes covered by 2 documents\n",
+            coverage.to_string()
+        );
+    }
+}
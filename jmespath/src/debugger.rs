@@ -0,0 +1,192 @@
+//! Step-through evaluation of expressions.
+//!
+//! `Expression::debug` evaluates an expression like `search`, but hands
+//! control to a `Debugger` before each AST node is evaluated. The debugger
+//! sees the node, the value it is evaluated against and the values of the
+//! enclosing nodes, and decides whether to pause at the next node, run to
+//! the next breakpoint, or abort the evaluation.
+//!
+//! ```
+//! use jmespath::debugger::{DebugAction, Frame};
+//! use jmespath::Variable;
+//!
+//! let expr = jmespath::compile("a.b").unwrap();
+//! let data = Variable::from_json(r#"{"a": {"b": 1}}"#).unwrap();
+//! let mut visited = vec![];
+//! let result = expr
+//!     .debug(data, &mut |frame: &Frame<'_>| {
+//!         visited.push(format!("{} on {}", frame.node, frame.value));
+//!         DebugAction::Step
+//!     })
+//!     .unwrap();
+//! assert_eq!("1", result.to_string());
+//! assert_eq!(
+//!     vec![
+//!         r#"a.b on {"a":{"b":1}}"#,
+//!         r#"a on {"a":{"b":1}}"#,
+//!         r#"b on {"b":1}"#,
+//!     ],
+//!     visited
+//! );
+//! ```
+
+use crate::ast::Ast;
+use crate::interpreter::SearchResult;
+use crate::Rcvar;
+
+/// What a `Debugger` wants the evaluation to do next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DebugAction {
+    /// Evaluates the node and pauses at the next one.
+    Step,
+    /// Evaluates without pausing until a node is a breakpoint.
+    Continue,
+    /// Stops the evaluation, which fails with a `RuntimeError::Aborted`
+    /// error at the offset of the node.
+    Abort,
+}
+
+/// A paused evaluation, just before a node is evaluated.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame<'f> {
+    /// Node that is evaluated next.
+    pub node: &'f Ast,
+    /// Value the node is evaluated against.
+    pub value: &'f Rcvar,
+    /// Values the enclosing nodes are evaluated against, outermost first.
+    /// The root node has an empty scope.
+    pub scope: &'f [Rcvar],
+}
+
+/// Controls a step-through evaluation.
+///
+/// Closures that take a `&Frame` and return a `DebugAction` are debuggers
+/// without breakpoints.
+pub trait Debugger {
+    /// Called when the evaluation pauses at a node.
+    fn on_node(&mut self, frame: &Frame<'_>) -> DebugAction;
+
+    /// Returns true if the evaluation should pause at the node after
+    /// `DebugAction::Continue`. There are no breakpoints by default.
+    fn is_breakpoint(&mut self, node: &Ast) -> bool {
+        let _ = node;
+        false
+    }
+
+    /// Called after each node is evaluated, whether or not the evaluation
+    /// paused at it, with the result of the node.
+    fn on_node_end(&mut self, node: &Ast, result: &SearchResult) {
+        let _ = (node, result);
+    }
+}
+
+impl<F: FnMut(&Frame<'_>) -> DebugAction> Debugger for F {
+    fn on_node(&mut self, frame: &Frame<'_>) -> DebugAction {
+        self(frame)
+    }
+}
+
+/// State of a step-through evaluation, kept in its `Context`.
+pub(crate) struct Session<'a> {
+    pub debugger: &'a mut dyn Debugger,
+    pub scope: Vec<Rcvar>,
+    /// True if the evaluation pauses at the next node.
+    pub stepping: bool,
+}
+
+impl<'a> Session<'a> {
+    /// Creates a session that pauses at the first node.
+    pub fn new(debugger: &'a mut dyn Debugger) -> Session<'a> {
+        Session {
+            debugger,
+            scope: vec![],
+            stepping: true,
+        }
+    }
+
+    /// Returns the action of the debugger if the evaluation pauses at the
+    /// node.
+    pub fn pause(&mut self, node: &Ast, value: &Rcvar) -> Option<DebugAction> {
+        if !self.stepping && !self.debugger.is_breakpoint(node) {
+            return None;
+        }
+        let frame = Frame {
+            node,
+            value,
+            scope: &self.scope,
+        };
+        let action = self.debugger.on_node(&frame);
+        self.stepping = action == DebugAction::Step;
+        Some(action)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ErrorKind, Variable};
+
+    struct Breakpoints {
+        fields: Vec<&'static str>,
+        paused: Vec<(String, usize)>,
+    }
+
+    impl Debugger for Breakpoints {
+        fn on_node(&mut self, frame: &Frame<'_>) -> DebugAction {
+            self.paused
+                .push((frame.node.to_string(), frame.scope.len()));
+            DebugAction::Continue
+        }
+
+        fn is_breakpoint(&mut self, node: &Ast) -> bool {
+            node.field_name()
+                .is_some_and(|name| self.fields.contains(&name))
+        }
+    }
+
+    #[test]
+    fn continues_to_breakpoints() {
+        let expr = crate::compile("a[*].b | length(@)").unwrap();
+        let data = Variable::from_json(r#"{"a": [{"b": "x"}, {"b": "yz"}]}"#).unwrap();
+        let mut debugger = Breakpoints {
+            fields: vec!["b"],
+            paused: vec![],
+        };
+        assert_eq!(
+            Some(2.0),
+            expr.debug(data, &mut debugger).unwrap().as_number()
+        );
+        // The root node is always paused at, and the projection evaluates
+        // `b` once per element.
+        assert_eq!(
+            vec![
+                ("a[*].b | length(@)".to_owned(), 0),
+                ("b".to_owned(), 2),
+                ("b".to_owned(), 2),
+            ],
+            debugger.paused
+        );
+    }
+
+    #[test]
+    fn aborts_at_a_node() {
+        let expr = crate::compile("foo.bar").unwrap();
+        let mut steps = 0;
+        let error = expr
+            .debug(Variable::Null, &mut |frame: &Frame<'_>| {
+                steps += 1;
+                match frame.node.field_name() {
+                    Some("foo") => DebugAction::Abort,
+                    _ => DebugAction::Step,
+                }
+            })
+            .unwrap_err();
+        assert_eq!(2, steps);
+        assert_eq!(ErrorKind::Aborted, error.kind);
+        assert_eq!(0, error.offset);
+        assert!(crate::compile("a")
+            .unwrap()
+            .debug(Variable::Null, &mut |_: &Frame<'_>| DebugAction::Abort)
+            .is_err());
+    }
+}
@@ -0,0 +1,417 @@
+//! Exact decimal arithmetic on numbers, used with the `bignum` feature.
+//!
+//! With `bignum`, a `serde_json::Number` keeps the digits it was written
+//! with, and numbers are compared, summed, averaged and rounded as the
+//! decimals they spell instead of as `f64`s. A result that would need more
+//! than `MAX_DIGITS` digits, like the sum of `1e-1000` and `1e1000`, is
+//! computed with `f64` arithmetic instead.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Number;
+
+use crate::{Rcvar, Variable};
+
+/// Name of the struct that serde_json serializes its numbers as, to keep
+/// their digits.
+pub(crate) const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Maximum number of digits of an exact result.
+const MAX_DIGITS: usize = 1000;
+
+/// Number of significant digits of a quotient, which is rounded toward
+/// zero.
+const QUOTIENT_DIGITS: usize = 40;
+
+/// A decimal number, `digits * 10^exponent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    negative: bool,
+    /// Digits from the most significant, without leading or trailing
+    /// zeros. Zero has no digits, and is never negative.
+    digits: Vec<u8>,
+    exponent: i64,
+}
+
+impl Decimal {
+    fn new(negative: bool, mut digits: Vec<u8>, mut exponent: i64) -> Decimal {
+        let leading = digits.iter().take_while(|&&d| d == 0).count();
+        digits.drain(..leading);
+        while digits.last() == Some(&0) {
+            digits.pop();
+            exponent += 1;
+        }
+        if digits.is_empty() {
+            exponent = 0;
+        }
+        Decimal {
+            negative: negative && !digits.is_empty(),
+            digits,
+            exponent,
+        }
+    }
+
+    /// Returns the decimal a number is written as.
+    pub fn zero() -> Decimal {
+        Decimal::new(false, vec![], 0)
+    }
+
+    pub fn from_number(number: &Number) -> Option<Decimal> {
+        number.to_string().parse().ok()
+    }
+
+    /// Returns the decimal as a number.
+    pub fn to_number(&self) -> Number {
+        Number::from_str(&self.to_string()).expect("decimals are valid JSON numbers")
+    }
+
+    /// Exponent of the position after the most significant digit.
+    fn magnitude(&self) -> i64 {
+        self.exponent + self.digits.len() as i64
+    }
+
+    fn cmp_magnitude(&self, other: &Decimal) -> Ordering {
+        match (self.digits.is_empty(), other.digits.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            _ => {}
+        }
+        // Without leading and trailing zeros, the digits compare like
+        // strings once the magnitudes are equal.
+        self.magnitude()
+            .cmp(&other.magnitude())
+            .then_with(|| self.digits.cmp(&other.digits))
+    }
+
+    /// Returns the digits of the magnitude as an integer multiple of
+    /// `10^exponent`, least significant first, or None if that needs more
+    /// than `MAX_DIGITS` digits.
+    fn aligned(&self, exponent: i64, magnitude: i64) -> Option<Vec<u8>> {
+        let len = usize::try_from(magnitude - exponent).ok()?;
+        if len > MAX_DIGITS {
+            return None;
+        }
+        let mut aligned = vec![0; (self.exponent - exponent) as usize];
+        aligned.extend(self.digits.iter().rev());
+        aligned.resize(len, 0);
+        Some(aligned)
+    }
+
+    /// Returns the sum, or None if it needs more than `MAX_DIGITS` digits.
+    pub fn add(&self, other: &Decimal) -> Option<Decimal> {
+        if other.digits.is_empty() {
+            return Some(self.clone());
+        }
+        if self.digits.is_empty() {
+            return Some(other.clone());
+        }
+        let exponent = self.exponent.min(other.exponent);
+        let magnitude = self.magnitude().max(other.magnitude()) + 1;
+        let a = self.aligned(exponent, magnitude)?;
+        let b = other.aligned(exponent, magnitude)?;
+        let (larger, smaller, negative) = if self.cmp_magnitude(other) == Ordering::Less {
+            (b, a, other.negative)
+        } else {
+            (a, b, self.negative)
+        };
+        let mut digits = Vec::with_capacity(larger.len());
+        let mut carry = 0;
+        for (x, y) in larger.iter().zip(&smaller) {
+            let digit = if self.negative == other.negative {
+                let sum = x + y + carry;
+                carry = sum / 10;
+                sum % 10
+            } else {
+                let difference = 10 + x - y - carry;
+                carry = 1 - difference / 10;
+                difference % 10
+            };
+            digits.push(digit);
+        }
+        digits.reverse();
+        Some(Decimal::new(negative, digits, exponent))
+    }
+
+    pub fn abs(&self) -> Decimal {
+        Decimal {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the largest integer that is not greater than the decimal.
+    pub fn floor(&self) -> Option<Decimal> {
+        self.round(self.negative)
+    }
+
+    /// Returns the smallest integer that is not less than the decimal.
+    pub fn ceil(&self) -> Option<Decimal> {
+        self.round(!self.negative)
+    }
+
+    /// Drops the fractional digits, and adds one to the magnitude if
+    /// `away` and there were any.
+    fn round(&self, away: bool) -> Option<Decimal> {
+        if self.exponent >= 0 {
+            return Some(self.clone());
+        }
+        let integral = usize::try_from(self.magnitude()).unwrap_or(0);
+        let truncated = Decimal::new(self.negative, self.digits[..integral].to_vec(), 0);
+        if !away {
+            return Some(truncated);
+        }
+        let one = Decimal::new(self.negative, vec![1], 0);
+        truncated.add(&one)
+    }
+
+    /// Returns the quotient of a division by a positive integer, rounded
+    /// toward zero to `QUOTIENT_DIGITS` significant digits.
+    pub fn div(&self, divisor: u64) -> Decimal {
+        let divisor = u128::from(divisor);
+        let mut quotient = vec![];
+        let mut remainder = 0;
+        // Exponent of the last digit of the quotient.
+        let mut exponent = self.magnitude();
+        let mut digits = self.digits.iter();
+        while quotient.iter().skip_while(|&&d| d == 0).count() < QUOTIENT_DIGITS {
+            let digit = match digits.next() {
+                Some(&digit) => digit,
+                None if remainder == 0 => break,
+                None => 0,
+            };
+            remainder = remainder * 10 + u128::from(digit);
+            quotient.push((remainder / divisor) as u8);
+            remainder %= divisor;
+            exponent -= 1;
+        }
+        Decimal::new(self.negative, quotient, exponent)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Parses a number in the JSON syntax.
+impl FromStr for Decimal {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Decimal, ()> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().map_err(|_| ())?),
+            None => (s, 0),
+        };
+        let (integral, fraction) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+        if integral.is_empty() {
+            return Err(());
+        }
+        let mut digits = Vec::with_capacity(integral.len() + fraction.len());
+        for c in integral.chars().chain(fraction.chars()) {
+            digits.push(c.to_digit(10).ok_or(())? as u8);
+        }
+        // Keeps the exponents far from overflowing.
+        if exponent.unsigned_abs() > 1 << 48 || digits.len() > 1 << 32 {
+            return Err(());
+        }
+        Ok(Decimal::new(
+            negative,
+            digits,
+            exponent - fraction.len() as i64,
+        ))
+    }
+}
+
+/// Writes the decimal without an exponent, unless that takes more than 20
+/// zeros, like `1e300` or `1.5e-30`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return fmt.write_str("0");
+        }
+        if self.negative {
+            fmt.write_str("-")?;
+        }
+        let digits: String = self.digits.iter().map(|d| char::from(b'0' + d)).collect();
+        let magnitude = self.magnitude();
+        if (0..=20).contains(&self.exponent) {
+            write!(fmt, "{}{}", digits, "0".repeat(self.exponent as usize))
+        } else if self.exponent < 0 && magnitude > 0 {
+            let (integral, fraction) = digits.split_at(magnitude as usize);
+            write!(fmt, "{}.{}", integral, fraction)
+        } else if self.exponent < 0 && magnitude >= -20 {
+            write!(fmt, "0.{}{}", "0".repeat(-magnitude as usize), digits)
+        } else {
+            let (first, rest) = digits.split_at(1);
+            let point = if rest.is_empty() { "" } else { "." };
+            write!(fmt, "{}{}{}e{}", first, point, rest, magnitude - 1)
+        }
+    }
+}
+
+/// Compares two numbers exactly, or returns None if one of them is not a
+/// valid decimal.
+pub(crate) fn compare(a: &Number, b: &Number) -> Option<Ordering> {
+    Some(Decimal::from_number(a)?.cmp(&Decimal::from_number(b)?))
+}
+
+/// Returns a variable as a decimal, or None if it is not a number.
+pub(crate) fn from_variable(value: &Variable) -> Option<Decimal> {
+    match *value {
+        Variable::Number(ref n) => Decimal::from_number(n),
+        _ => None,
+    }
+}
+
+/// Returns the exact sum of numbers, or None if one of them is not a
+/// number or the sum needs more than `MAX_DIGITS` digits.
+pub(crate) fn sum<'a, I>(numbers: I) -> Option<Decimal>
+where
+    I: IntoIterator<Item = &'a Rcvar>,
+{
+    let mut sum = Decimal::zero();
+    for number in numbers {
+        sum = sum.add(&from_variable(number)?)?;
+    }
+    Some(sum)
+}
+
+/// Compares values like `Variable::cmp`, with the decimal of every number
+/// parsed once rather than in each comparison.
+fn compare_values<'a>(values: &'a [&'a Variable]) -> impl Fn(usize, usize) -> Ordering + 'a {
+    let decimals: Vec<_> = values.iter().map(|value| from_variable(value)).collect();
+    move |a, b| match (&decimals[a], &decimals[b]) {
+        (Some(x), Some(y)) => x.cmp(y),
+        _ => values[a].cmp(values[b]),
+    }
+}
+
+/// Returns items sorted by a value of each, like a stable `sort_by` on the
+/// values.
+pub(crate) fn sort_by_value<T: Clone>(items: &[T], value: impl Fn(&T) -> &Variable) -> Vec<T> {
+    let values: Vec<_> = items.iter().map(value).collect();
+    let compare = compare_values(&values);
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| compare(a, b));
+    order.into_iter().map(|i| items[i].clone()).collect()
+}
+
+/// Returns the last of the largest values, like `Iterator::max`, or None if
+/// there are no values.
+pub(crate) fn max(values: &[Rcvar]) -> Option<&Rcvar> {
+    extreme(values, |ordering| ordering != Ordering::Less)
+}
+
+/// Returns the first of the smallest values, like `Iterator::min`, or None
+/// if there are no values.
+pub(crate) fn min(values: &[Rcvar]) -> Option<&Rcvar> {
+    extreme(values, |ordering| ordering == Ordering::Less)
+}
+
+/// Returns the value that replaces every candidate it compares to with
+/// `replaces`.
+fn extreme(values: &[Rcvar], replaces: fn(Ordering) -> bool) -> Option<&Rcvar> {
+    let refs: Vec<&Variable> = values.iter().map(|value| &**value).collect();
+    let compare = compare_values(&refs);
+    let mut best = 0;
+    for i in 1..values.len() {
+        if replaces(compare(i, best)) {
+            best = i;
+        }
+    }
+    values.get(best)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_formats_decimals() {
+        let format = |s: &str| decimal(s).to_string();
+        assert_eq!("0", format("-0.000"));
+        assert_eq!("1", format("1.0"));
+        assert_eq!("-12.5", format("-125e-1"));
+        assert_eq!("0.001", format("1E-3"));
+        assert_eq!(
+            "340282366920938463463374607431768211457",
+            format("340282366920938463463374607431768211457")
+        );
+        assert_eq!("100000000000000000000", format("1e20"));
+        assert_eq!("1e300", format("1e300"));
+        assert_eq!("1.5e-30", format("15e-31"));
+        for invalid in &["", "-", ".5", "1.2.3", "1e", "a"] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn computes_exactly() {
+        let sum = |a: &str, b: &str| decimal(a).add(&decimal(b)).unwrap().to_string();
+        assert_eq!("0.3", sum("0.1", "0.2"));
+        assert_eq!("-0.1", sum("0.1", "-0.2"));
+        assert_eq!("0", sum("-0.5", "0.5"));
+        assert_eq!(
+            "170141183460469231731687303715884105728",
+            sum("170141183460469231731687303715884105727", "1")
+        );
+        assert_eq!("99.99", sum("100", "-0.01"));
+        assert!(decimal("1e-1000").add(&decimal("1e1000")).is_none());
+        assert_eq!("-3", decimal("-2.5").floor().unwrap().to_string());
+        assert_eq!("-2", decimal("-2.5").ceil().unwrap().to_string());
+        assert_eq!("1", decimal("0.1").ceil().unwrap().to_string());
+        assert_eq!("0", decimal("0.9").floor().unwrap().to_string());
+        assert_eq!("12", decimal("12").ceil().unwrap().to_string());
+        assert_eq!("2.5", decimal("-2.5").abs().to_string());
+        assert_eq!("0.5", decimal("1.5").div(3).to_string());
+        assert_eq!(
+            "0.3333333333333333333333333333333333333333",
+            decimal("1").div(3).to_string()
+        );
+        assert_eq!("25000000000000000000", decimal("1e20").div(4).to_string());
+        assert!(decimal("0.71") < decimal("0.7100000000000002"));
+        assert!(decimal("-1e20") < decimal("-99"));
+        assert_eq!(Ordering::Equal, decimal("1.50").cmp(&decimal("15e-1")));
+    }
+
+    #[test]
+    fn sorts_numbers_parsed_once() {
+        let values: Vec<Rcvar> = ["0.7100000000000002", "2", "0.71", "1"]
+            .iter()
+            .map(|n| Rcvar::new(Variable::from_json(n).unwrap()))
+            .collect();
+        let sorted = sort_by_value(&values, |value| value);
+        let sorted: Vec<_> = sorted.iter().map(|value| value.to_string()).collect();
+        assert_eq!(vec!["0.71", "0.7100000000000002", "1", "2"], sorted);
+        assert_eq!("2", max(&values).unwrap().to_string());
+        assert_eq!("0.71", min(&values).unwrap().to_string());
+        assert_eq!(None, max(&[]));
+    }
+}
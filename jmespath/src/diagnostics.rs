@@ -0,0 +1,301 @@
+//! Rich diagnostics for parse and runtime errors.
+//!
+//! This module is only available with the `diagnostics` feature. A
+//! `Diagnostic` describes an error with a stable code, labeled spans of the
+//! expression, notes, and an optional suggested fix, and renders it in the
+//! style used by the Rust compiler:
+//!
+//! ```text
+//! error[unknown_function]: Call to undefined function lenght
+//!  --> 1:7
+//!   |
+//! 1 | foo | lenght(@)
+//!   |       ^^^^^^ unknown function
+//!   |
+//!   = help: did you mean `length`?
+//! ```
+//!
+//! ```
+//! use jmespath::diagnostics::Diagnostic;
+//!
+//! let err = jmespath::compile("foo[").unwrap_err();
+//! let diagnostic = Diagnostic::from(&err);
+//! assert_eq!("unexpected_end", diagnostic.code);
+//! println!("{}", diagnostic);
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::errors::coordinates;
+use crate::{ErrorKind, ErrorReason, JmespathError, RuntimeError, DEFAULT_RUNTIME};
+
+/// A span of the expression annotated with a message.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Label {
+    /// Byte range of the expression that the label points at.
+    pub span: Range<usize>,
+    /// Short description of the span.
+    pub message: String,
+}
+
+/// An error with everything needed to explain it to a user.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    /// Stable identifier of the error, as returned by `ErrorKind::code`.
+    pub code: &'static str,
+    /// Description of the error.
+    pub message: String,
+    /// The expression that caused the error.
+    pub expression: String,
+    /// Labeled spans of the expression, the primary span first.
+    pub labels: Vec<Label>,
+    /// Additional context about the error.
+    pub notes: Vec<String>,
+    /// A suggested fix, if one is known.
+    pub help: Option<String>,
+}
+
+impl<'a> From<&'a JmespathError> for Diagnostic {
+    fn from(err: &'a JmespathError) -> Diagnostic {
+        let mut span = err.span();
+        if span.is_empty() {
+            span.end = next_boundary(&err.expression, span.start);
+        }
+        let mut diagnostic = Diagnostic {
+            code: err.kind.code(),
+            message: err.message(),
+            expression: err.expression.clone(),
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        };
+        let label = match err.reason {
+            ErrorReason::Runtime(RuntimeError::UnknownFunction(ref name)) => {
+                // Runtime errors point at the `(` of the call, so label the
+                // name of the function instead.
+                let start = err.offset.saturating_sub(name.len());
+                if err.expression.get(start..err.offset) == Some(name.as_str()) {
+                    span = start..err.offset;
+                }
+                diagnostic.help = suggest_function(name)
+                    .map(|suggestion| format!("did you mean `{}`?", suggestion));
+                "unknown function"
+            }
+            ErrorReason::Runtime(ref e) => {
+                diagnostic
+                    .notes
+                    .push(format!("the JMESPath error type is `{}`", e.error_type()));
+                match *e {
+                    RuntimeError::InvalidSlice => {
+                        diagnostic.help = Some("use a non-zero step, e.g. `1` or `-1`".to_owned());
+                        "slice step cannot be 0"
+                    }
+                    RuntimeError::TooManyArguments { .. }
+                    | RuntimeError::NotEnoughArguments { .. } => "called here",
+                    RuntimeError::InvalidType { position, .. }
+                    | RuntimeError::InvalidReturnType { position, .. } => {
+                        diagnostic.notes.push(format!(
+                            "the invalid value was passed as argument {}",
+                            position
+                        ));
+                        "called here"
+                    }
+                    _ => "evaluated here",
+                }
+            }
+            ErrorReason::Internal(_) => {
+                diagnostic
+                    .notes
+                    .push("the expression caused a panic, which is a bug".to_owned());
+                "while handling this expression"
+            }
+            ErrorReason::Parse(_) => match err.kind {
+                ErrorKind::InvalidCharacter => "invalid character",
+                ErrorKind::UnclosedDelimiter => {
+                    let wrapper = err.expression[err.offset..].chars().next();
+                    diagnostic.help = wrapper.map(|c| format!("add a closing `{}`", c));
+                    "unclosed delimiter"
+                }
+                ErrorKind::InvalidLiteral => "invalid literal",
+                ErrorKind::UnexpectedEnd => {
+                    diagnostic.help = Some("complete the expression".to_owned());
+                    "expression ends here"
+                }
+                ErrorKind::NestingTooDeep => {
+                    diagnostic.notes.push(
+                        "deeply nested expressions and long chains of operators are rejected \
+                         to bound recursion"
+                            .to_owned(),
+                    );
+                    "nested too deeply"
+                }
+                _ => "unexpected token",
+            },
+        };
+        diagnostic.labels.push(Label {
+            span,
+            message: label.to_owned(),
+        });
+        diagnostic
+    }
+}
+
+impl From<JmespathError> for Diagnostic {
+    fn from(err: JmespathError) -> Diagnostic {
+        Diagnostic::from(&err)
+    }
+}
+
+/// Renders the diagnostic with each label drawn under its line of the
+/// expression.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(fmt, "error[{}]: {}", self.code, self.message)?;
+        let primary = self.labels.first().map_or(0, |label| label.span.start);
+        let (line, column) = coordinates(&self.expression, primary);
+        let gutter = self
+            .labels
+            .iter()
+            .map(|label| coordinates(&self.expression, label.span.start).0)
+            .max()
+            .unwrap_or(line)
+            .to_string()
+            .len();
+        let pad = " ".repeat(gutter);
+        writeln!(fmt, "{}--> {}:{}", pad, line, column)?;
+        writeln!(fmt, "{} |", pad)?;
+        for label in &self.labels {
+            let (line, column) = coordinates(&self.expression, label.span.start);
+            let text = self.expression.lines().nth(line - 1).unwrap_or("");
+            // Carets stop at the end of the line for spans over several lines.
+            let width = self.expression[label.span.clone()]
+                .chars()
+                .take_while(|c| *c != '\n')
+                .count()
+                .max(1);
+            writeln!(fmt, "{:>gutter$} | {}", line, text, gutter = gutter)?;
+            writeln!(
+                fmt,
+                "{} | {}{} {}",
+                pad,
+                " ".repeat(column - 1),
+                "^".repeat(width),
+                label.message
+            )?;
+        }
+        if !self.notes.is_empty() || self.help.is_some() {
+            writeln!(fmt, "{} |", pad)?;
+        }
+        for note in &self.notes {
+            writeln!(fmt, "{} = note: {}", pad, note)?;
+        }
+        if let Some(ref help) = self.help {
+            writeln!(fmt, "{} = help: {}", pad, help)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the offset of the character after `offset`, or `offset` if the
+/// expression ends there.
+fn next_boundary(expr: &str, offset: usize) -> usize {
+    expr.get(offset..)
+        .and_then(|rest| rest.chars().next())
+        .map_or(offset, |c| offset + c.len_utf8())
+}
+
+/// Returns the name of the closest function of the default runtime, if one
+/// is close enough to be a likely typo.
+fn suggest_function(name: &str) -> Option<&'static str> {
+    DEFAULT_RUNTIME
+        .function_names()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Rcvar, Variable};
+
+    fn diagnose(expr: &str) -> Diagnostic {
+        let err = match crate::compile(expr) {
+            Ok(expr) => expr.search(Rcvar::new(Variable::Null)).unwrap_err(),
+            Err(err) => err,
+        };
+        Diagnostic::from(err)
+    }
+
+    #[test]
+    fn renders_parse_errors() {
+        assert_eq!(
+            "error[unclosed_delimiter]: Unclosed ' delimiter: 'bar\n \
+             --> 1:7\n  \
+             |\n\
+             1 | foo | 'bar\n  \
+             |       ^^^^ unclosed delimiter\n  \
+             |\n  \
+             = help: add a closing `'`\n",
+            diagnose("foo | 'bar").to_string()
+        );
+        let diagnostic = diagnose("foo |\n  bar[");
+        assert_eq!("unexpected_end", diagnostic.code);
+        assert!(diagnostic
+            .to_string()
+            .contains("--> 2:7\n  |\n2 |   bar[\n  |       ^ expression ends here\n"));
+    }
+
+    #[test]
+    fn renders_runtime_errors() {
+        assert_eq!(
+            "error[unknown_function]: Call to undefined function lenght\n \
+             --> 1:7\n  \
+             |\n\
+             1 | foo | lenght(@)\n  \
+             |       ^^^^^^ unknown function\n  \
+             |\n  \
+             = help: did you mean `length`?\n",
+            diagnose("foo | lenght(@)").to_string()
+        );
+        let diagnostic = diagnose("abs('a')");
+        assert_eq!("invalid_type", diagnostic.code);
+        assert_eq!(
+            vec![
+                "the JMESPath error type is `invalid-type`",
+                "the invalid value was passed as argument 0"
+            ],
+            diagnostic.notes
+        );
+        assert_eq!(3..4, diagnostic.labels[0].span);
+        assert_eq!(None, diagnose("zzzzzz(@)").help);
+    }
+
+    #[test]
+    fn computes_edit_distance() {
+        assert_eq!(0, edit_distance("abs", "abs"));
+        assert_eq!(2, edit_distance("lenght", "length"));
+        assert_eq!(3, edit_distance("", "abc"));
+    }
+}
@@ -2,58 +2,273 @@
 
 use std::error::Error;
 use std::fmt;
+use std::ops::Range;
 
+use serde::ser::{self, SerializeStruct};
+
+use crate::parser::Expected;
 use crate::Context;
 
 /// JMESPath error.
 #[derive(Clone, Debug, PartialEq)]
 pub struct JmespathError {
-    /// Absolute character position.
+    /// Absolute byte offset in the expression.
     pub offset: usize,
-    /// Line number of the coordinate.
+    /// Line number of the offset, starting at 1.
     pub line: usize,
-    /// Column of the line number.
+    /// Column of the offset in characters, starting at 1.
     pub column: usize,
+    /// Length in bytes of the text at the offset that caused the error,
+    /// which is 0 when no token is to blame. Stored as a `u32` to keep
+    /// `Result`s of errors small.
+    length: u32,
+    /// Kinds of tokens the parser expected, which is empty for other
+    /// errors.
+    expected: Expected,
     /// Expression being evaluated.
     pub expression: String,
     /// Error reason information.
     pub reason: ErrorReason,
+    /// Category of the error.
+    pub kind: ErrorKind,
 }
 
 impl JmespathError {
     /// Create a new JMESPath Error.
     pub fn new(expr: &str, offset: usize, reason: ErrorReason) -> JmespathError {
-        // Find each new line so we can create a formatted error message.
-        let mut line: usize = 0;
-        let mut column: usize = 0;
-        for c in expr.chars().take(offset) {
-            match c {
-                '\n' => {
-                    line += 1;
-                    column = 0;
-                }
-                _ => column += 1,
-            }
-        }
+        let (line, column) = coordinates(expr, offset);
+        let kind = match reason {
+            ErrorReason::Parse(_) => ErrorKind::Parse,
+            ErrorReason::Runtime(ref e) => e.kind(),
+            ErrorReason::Internal(_) => ErrorKind::Internal,
+        };
         JmespathError {
             expression: expr.to_owned(),
             offset,
             line,
             column,
+            length: 0,
+            expected: Expected::NONE,
             reason,
+            kind,
+        }
+    }
+
+    /// Creates an error that is safe to log when expressions embed
+    /// sensitive literals, as a Runtime with `ParserOptions::redact` and a
+    /// search with `EvalOptions::redact` do.
+    ///
+    /// The contents of raw string and JSON literals in the expression are
+    /// replaced with one `*` per byte, so the offset and span still point at
+    /// the same tokens, and the message no longer quotes literal values.
+    ///
+    /// ```
+    /// use jmespath::{ParserOptions, Runtime};
+    ///
+    /// let runtime = Runtime::builder()
+    ///     .parser_options(ParserOptions {
+    ///         redact: true,
+    ///         ..ParserOptions::default()
+    ///     })
+    ///     .build();
+    /// let err = runtime.compile("password == 'hunter2' 'x'").unwrap_err();
+    /// assert_eq!("password == '*******' '*'", err.expression);
+    /// assert_eq!(
+    ///     "Parse error: Did not parse the complete expression -- found Literal (line 1, column 23)",
+    ///     err.to_string()
+    /// );
+    /// ```
+    pub fn new_redacted(expr: &str, offset: usize, reason: ErrorReason) -> JmespathError {
+        let reason = match reason {
+            ErrorReason::Parse(message) => ErrorReason::Parse(redact_message(&message)),
+            ErrorReason::Runtime(e) => ErrorReason::Runtime(e),
+            // Panic messages can quote anything, including literal values.
+            ErrorReason::Internal(_) => ErrorReason::Internal("redacted".to_owned()),
+        };
+        JmespathError::new(&redact_literals(expr), offset, reason)
+    }
+
+    /// Recreates the error with `new_redacted` if `redact` is set, keeping
+    /// its kind and span.
+    pub(crate) fn redact_if(self, redact: bool) -> JmespathError {
+        if !redact {
+            return self;
         }
+        let (kind, length, expected) = (self.kind, self.length as usize, self.expected);
+        JmespathError::new_redacted(&self.expression, self.offset, self.reason)
+            .with_kind(kind)
+            .with_length(length)
+            .with_expected(expected)
+    }
+
+    /// Moves the error into `expr`, which contains the expression that
+    /// failed at `start`, keeping its kind, span, and expected tokens.
+    #[cfg(feature = "template")]
+    pub(crate) fn relocate(self, expr: &str, start: usize) -> JmespathError {
+        let (kind, length, expected) = (self.kind, self.length as usize, self.expected);
+        JmespathError::new(expr, start + self.offset, self.reason)
+            .with_kind(kind)
+            .with_length(length)
+            .with_expected(expected)
+    }
+
+    /// Sets the kinds of tokens the parser expected.
+    pub(crate) fn with_expected(mut self, expected: Expected) -> JmespathError {
+        self.expected = expected;
+        self
     }
 
-    /// Create a new JMESPath Error from a Context struct.
+    /// Sets the length in bytes of the text that caused the error.
+    pub fn with_length(mut self, length: usize) -> JmespathError {
+        self.length = length as u32;
+        self
+    }
+
+    /// Returns the byte range of the text that caused the error.
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.length as usize
+    }
+
+    /// Returns the range of the text that caused the error in characters
+    /// rather than bytes, for editors and APIs that index text by character.
+    ///
+    /// ```
+    /// let err = jmespath::parse("'\u{e9}t\u{e9}' |").unwrap_err();
+    /// assert_eq!(9..9, err.span());
+    /// assert_eq!(7..7, err.char_span());
+    /// assert_eq!(8, err.column);
+    /// ```
+    pub fn char_span(&self) -> Range<usize> {
+        let span = self.span();
+        let chars_before = |offset: usize| {
+            self.expression
+                .char_indices()
+                .take_while(|&(i, _)| i < offset)
+                .count()
+        };
+        chars_before(span.start)..chars_before(span.end)
+    }
+
+    /// Sets the category of the error.
+    pub fn with_kind(mut self, kind: ErrorKind) -> JmespathError {
+        self.kind = kind;
+        self
+    }
+
+    /// Create a new JMESPath Error from a Context struct, redacted if the
+    /// evaluation has `EvalOptions::redact` set.
     pub fn from_ctx(ctx: &Context<'_>, reason: ErrorReason) -> JmespathError {
-        JmespathError::new(ctx.expression, ctx.offset, reason)
+        if ctx.redact {
+            JmespathError::new_redacted(ctx.expression, ctx.offset, reason)
+        } else {
+            JmespathError::new(ctx.expression, ctx.offset, reason)
+        }
+    }
+
+    /// Renders the error as a multi-line message that quotes the line of
+    /// `expr` containing the error with a caret under the error position.
+    ///
+    /// `expr` is normally the expression the error was created from, but it
+    /// can be provided for errors that were created without one, such as
+    /// errors returned by functions.
+    pub fn render(&self, expr: &str) -> String {
+        let (line, column) = coordinates(expr, self.offset);
+        let quoted = expr.split('\n').nth(line - 1).unwrap_or("");
+        format!(
+            "{} (line {}, column {}), for expression:\n\"{}\"\n{}^",
+            self.reason,
+            line,
+            column,
+            quoted,
+            // The opening quote takes the place of the first column.
+            " ".repeat(column)
+        )
+    }
+
+    /// Returns the description of the error without the "Parse error"
+    /// prefix or the position.
+    pub fn message(&self) -> String {
+        match self.reason {
+            ErrorReason::Parse(ref message) | ErrorReason::Internal(ref message) => message.clone(),
+            ErrorReason::Runtime(ref e) => e.to_string(),
+        }
+    }
+
+    /// Returns the tokens the parser expected when it found an unexpected
+    /// token, e.g. `[":", "]"]` for `foo[0`.
+    ///
+    /// The tokens are recorded by the parser, so the list is empty for
+    /// errors that don't come from an unexpected token.
+    pub fn expected(&self) -> Vec<String> {
+        self.expected
+            .names()
+            .map(|name| name.trim_matches('\'').to_owned())
+            .collect()
+    }
+
+    /// Serializes the error to a JSON object with the kind, message,
+    /// position, span, and expected tokens of the error.
+    ///
+    /// ```
+    /// let err = jmespath::compile("foo[0").unwrap_err();
+    /// assert_eq!(
+    ///     r#"{"kind":"unexpected_end","message":"Expected ':' or ']' -- found Eof","line":1,"column":6,"offset":5,"span":{"start":5,"end":5},"expected":[":","]"],"expression":"foo[0"}"#,
+    ///     err.to_json()
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("errors can always be serialized")
     }
 }
 
+impl ser::Serialize for JmespathError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let span = self.span();
+        let mut state = serializer.serialize_struct("JmespathError", 8)?;
+        state.serialize_field("kind", self.kind.code())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("line", &self.line)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field("span", &span)?;
+        state.serialize_field("expected", &self.expected())?;
+        state.serialize_field("expression", &self.expression)?;
+        state.end()
+    }
+}
+
+/// Finds the 1-based line and column of a byte offset in an expression.
+pub(crate) fn coordinates(expr: &str, offset: usize) -> (usize, usize) {
+    let mut line: usize = 1;
+    let mut column: usize = 1;
+    for (_, c) in expr.char_indices().take_while(|&(i, _)| i < offset) {
+        match c {
+            '\n' => {
+                line += 1;
+                column = 1;
+            }
+            _ => column += 1,
+        }
+    }
+    (line, column)
+}
+
+/// Runtime errors are exposed as the `source` of the error so that callers
+/// handling `Box<dyn Error>` can still find out why evaluation failed.
 impl Error for JmespathError {
     fn description(&self) -> &str {
         "error evaluating JMESPath expression"
     }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.reason {
+            ErrorReason::Runtime(ref e) => Some(e),
+            ErrorReason::Parse(_) | ErrorReason::Internal(_) => None,
+        }
+    }
 }
 
 impl From<serde_json::Error> for JmespathError {
@@ -66,13 +281,69 @@ impl From<serde_json::Error> for JmespathError {
     }
 }
 
+/// Masks the contents of raw string and JSON literals, keeping newlines so
+/// that line numbers are unchanged.
+fn redact_literals(expr: &str) -> String {
+    let mut redacted = String::with_capacity(expr.len());
+    let mut chars = expr.chars();
+    while let Some(c) = chars.next() {
+        redacted.push(c);
+        if c != '\'' && c != '`' && c != '"' {
+            continue;
+        }
+        let mut escaped = false;
+        for inner in &mut chars {
+            if inner == c && !escaped {
+                redacted.push(c);
+                break;
+            }
+            escaped = inner == '\\' && !escaped;
+            // Quoted identifiers are skipped so that quotes inside of them
+            // aren't mistaken for the start of a literal.
+            if c == '"' || inner == '\n' {
+                redacted.push(inner);
+            } else {
+                redacted.extend(std::iter::repeat_n('*', inner.len_utf8()));
+            }
+        }
+    }
+    redacted
+}
+
+/// Removes literal values that the lexer and parser quote in messages.
+fn redact_message(message: &str) -> String {
+    const PREFIXES: &[&str] = &[
+        " -- found Literal",
+        "Unclosed ' delimiter",
+        "Unclosed ` delimiter",
+        "Unable to parse literal JSON",
+    ];
+    for prefix in PREFIXES {
+        if let Some(i) = message.find(prefix) {
+            return message[..i + prefix.len()].to_owned();
+        }
+    }
+    message.to_owned()
+}
+
 fn inject_carat(column: usize, buff: &mut String) {
-    buff.push_str(&(0..column).map(|_| ' ').collect::<String>());
+    buff.push_str(&(1..column).map(|_| ' ').collect::<String>());
     buff.push_str("^\n");
 }
 
+/// Writes the reason and coordinates of the error on a single line.
+///
+/// The alternate form (`{:#}`) also writes the expression with a caret
+/// under the line and column of the error.
 impl fmt::Display for JmespathError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if !fmt.alternate() {
+            return write!(
+                fmt,
+                "{} (line {}, column {})",
+                self.reason, self.line, self.column
+            );
+        }
         let mut error_location = String::new();
         let mut matched = false;
         let mut current_line = 0;
@@ -80,7 +351,7 @@ impl fmt::Display for JmespathError {
             error_location.push(c);
             if c == '\n' {
                 current_line += 1;
-                if current_line == self.line + 1 {
+                if current_line == self.line {
                     matched = true;
                     inject_carat(self.column, &mut error_location);
                 }
@@ -99,6 +370,93 @@ impl fmt::Display for JmespathError {
     }
 }
 
+/// Category of a JMESPath error.
+///
+/// Unlike error messages, kinds and their codes are stable, so they can be
+/// used to handle specific errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The expression contains a character that can't start a token.
+    InvalidCharacter,
+    /// A quoted identifier, raw string, or literal is not closed.
+    UnclosedDelimiter,
+    /// A number, quoted identifier, raw string, or literal is invalid.
+    InvalidLiteral,
+    /// A token is not valid where it appears in the expression.
+    UnexpectedToken,
+    /// The expression ended before it was complete.
+    UnexpectedEnd,
+    /// The expression is nested too deeply, or its chains of operators are
+    /// too long, to parse and evaluate without overflowing the stack.
+    NestingTooDeep,
+    /// Any other error while parsing an expression.
+    Parse,
+    /// A slice expression uses a step of 0.
+    InvalidSlice,
+    /// Too many arguments were provided to a function.
+    TooManyArguments,
+    /// Too few arguments were provided to a function.
+    NotEnoughArguments,
+    /// An unknown function was called.
+    UnknownFunction,
+    /// A value of the wrong type was provided to a function.
+    InvalidType,
+    /// An expression reference returned a value of the wrong type.
+    InvalidReturnType,
+    /// Evaluation exceeded a resource limit.
+    LimitExceeded,
+    /// A debugger aborted the evaluation.
+    Aborted,
+    /// A parameter of the expression was not given a value.
+    UnboundParameter,
+    /// A function computed NaN or an infinity.
+    NotFinite,
+    /// Parsing or evaluation panicked.
+    Internal,
+}
+
+impl ErrorKind {
+    /// Returns a stable identifier of the kind, e.g. `unexpected_token`.
+    pub fn code(self) -> &'static str {
+        use self::ErrorKind::*;
+        match self {
+            InvalidCharacter => "invalid_character",
+            UnclosedDelimiter => "unclosed_delimiter",
+            InvalidLiteral => "invalid_literal",
+            UnexpectedToken => "unexpected_token",
+            UnexpectedEnd => "unexpected_end",
+            NestingTooDeep => "nesting_too_deep",
+            Parse => "parse",
+            InvalidSlice => "invalid_slice",
+            TooManyArguments => "too_many_arguments",
+            NotEnoughArguments => "not_enough_arguments",
+            UnknownFunction => "unknown_function",
+            InvalidType => "invalid_type",
+            InvalidReturnType => "invalid_return_type",
+            LimitExceeded => "limit_exceeded",
+            Aborted => "aborted",
+            UnboundParameter => "unbound_parameter",
+            NotFinite => "not_finite",
+            Internal => "internal",
+        }
+    }
+
+    /// Returns true if the error was encountered while parsing.
+    pub fn is_parse(self) -> bool {
+        use self::ErrorKind::*;
+        matches!(
+            self,
+            InvalidCharacter
+                | UnclosedDelimiter
+                | InvalidLiteral
+                | UnexpectedToken
+                | UnexpectedEnd
+                | NestingTooDeep
+                | Parse
+        )
+    }
+}
+
 /// Error context to provide specific details about an error.
 #[derive(Clone, Debug, PartialEq)]
 pub enum ErrorReason {
@@ -106,6 +464,9 @@ pub enum ErrorReason {
     Parse(String),
     /// An error occurred while evaluating an expression.
     Runtime(RuntimeError),
+    /// Parsing or evaluating an expression panicked. Only returned by
+    /// `safe_search`, with the panic message.
+    Internal(String),
 }
 
 impl fmt::Display for ErrorReason {
@@ -113,6 +474,7 @@ impl fmt::Display for ErrorReason {
         match *self {
             ErrorReason::Parse(ref e) => write!(fmt, "Parse error: {}", e),
             ErrorReason::Runtime(ref e) => write!(fmt, "Runtime error: {}", e),
+            ErrorReason::Internal(ref e) => write!(fmt, "Internal error: {}", e),
         }
     }
 }
@@ -158,8 +520,64 @@ pub enum RuntimeError {
         /// Which invocation iteration of the expression reference failed.
         invocation: usize,
     },
+    /// Encountered when evaluating an expression exceeds a resource limit.
+    LimitExceeded {
+        /// Name of the limited resource, e.g. "output size".
+        resource: String,
+        /// The maximum that was exceeded.
+        limit: usize,
+    },
+    /// Encountered when a `Debugger` aborts a step-through evaluation.
+    Aborted,
+    /// Encountered when a parameter like `:name` has no value.
+    UnboundParameter(String),
+    /// Encountered when a function computes NaN or an infinity, with
+    /// `NonFinite::Error`. Holds `NaN`, `Infinity` or `-Infinity`.
+    NotFinite(String),
 }
 
+impl RuntimeError {
+    /// Returns the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            RuntimeError::InvalidSlice => ErrorKind::InvalidSlice,
+            RuntimeError::TooManyArguments { .. } => ErrorKind::TooManyArguments,
+            RuntimeError::NotEnoughArguments { .. } => ErrorKind::NotEnoughArguments,
+            RuntimeError::UnknownFunction(_) => ErrorKind::UnknownFunction,
+            RuntimeError::InvalidType { .. } => ErrorKind::InvalidType,
+            RuntimeError::InvalidReturnType { .. } => ErrorKind::InvalidReturnType,
+            RuntimeError::LimitExceeded { .. } => ErrorKind::LimitExceeded,
+            RuntimeError::Aborted => ErrorKind::Aborted,
+            RuntimeError::UnboundParameter(_) => ErrorKind::UnboundParameter,
+            RuntimeError::NotFinite(_) => ErrorKind::NotFinite,
+        }
+    }
+
+    /// Returns the name of the error type used by the JMESPath compliance
+    /// tests, e.g. `invalid-arity`.
+    ///
+    /// Resource limits and aborted evaluations are not part of the
+    /// specification and are reported as `limit-exceeded` and `aborted`.
+    pub fn error_type(&self) -> &'static str {
+        match *self {
+            RuntimeError::InvalidSlice => "invalid-value",
+            RuntimeError::TooManyArguments { .. } | RuntimeError::NotEnoughArguments { .. } => {
+                "invalid-arity"
+            }
+            RuntimeError::UnknownFunction(_) => "unknown-function",
+            RuntimeError::InvalidType { .. } | RuntimeError::InvalidReturnType { .. } => {
+                "invalid-type"
+            }
+            RuntimeError::LimitExceeded { .. } => "limit-exceeded",
+            RuntimeError::Aborted => "aborted",
+            RuntimeError::UnboundParameter(_) => "unbound-parameter",
+            RuntimeError::NotFinite(_) => "invalid-value",
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
 impl fmt::Display for RuntimeError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         use self::RuntimeError::*;
@@ -201,6 +619,13 @@ impl fmt::Display for RuntimeError {
                 "Argument {} must return {} but invocation {} returned {}",
                 position, expected, invocation, actual
             ),
+            LimitExceeded {
+                ref resource,
+                ref limit,
+            } => write!(fmt, "Exceeded the {} limit of {}", resource, limit),
+            Aborted => write!(fmt, "Evaluation aborted by the debugger"),
+            UnboundParameter(ref name) => write!(fmt, "No value is bound to parameter :{}", name),
+            NotFinite(ref value) => write!(fmt, "Computed {}, which is not a JSON number", value),
         }
     }
 }
@@ -212,40 +637,147 @@ mod test {
     #[test]
     fn coordinates_can_be_created_from_string_with_new_lines() {
         let expr = "foo\n..bar";
-        let err = JmespathError::new(&expr, 5, ErrorReason::Parse("Test".to_owned()));
-        assert_eq!(1, err.line);
-        assert_eq!(1, err.column);
+        let err = JmespathError::new(expr, 5, ErrorReason::Parse("Test".to_owned()));
+        assert_eq!(2, err.line);
+        assert_eq!(2, err.column);
         assert_eq!(5, err.offset);
         assert_eq!(
-            "Parse error: Test (line 1, column 1)\nfoo\n..bar\n ^\n",
-            err.to_string()
+            "Parse error: Test (line 2, column 2)\nfoo\n..bar\n ^\n",
+            format!("{:#}", err)
         );
     }
 
     #[test]
     fn coordinates_can_be_created_from_string_with_new_lines_pointing_to_non_last() {
         let expr = "foo\n..bar\nbaz";
-        let err = JmespathError::new(&expr, 5, ErrorReason::Parse("Test".to_owned()));
-        assert_eq!(1, err.line);
-        assert_eq!(1, err.column);
+        let err = JmespathError::new(expr, 5, ErrorReason::Parse("Test".to_owned()));
+        assert_eq!(2, err.line);
+        assert_eq!(2, err.column);
         assert_eq!(5, err.offset);
         assert_eq!(
-            "Parse error: Test (line 1, column 1)\nfoo\n..bar\n ^\nbaz",
-            err.to_string()
+            "Parse error: Test (line 2, column 2)\nfoo\n..bar\n ^\nbaz",
+            format!("{:#}", err)
         );
     }
 
     #[test]
     fn coordinates_can_be_created_from_string_with_no_new_lines() {
         let expr = "foo..bar";
-        let err = JmespathError::new(&expr, 4, ErrorReason::Parse("Test".to_owned()));
-        assert_eq!(0, err.line);
-        assert_eq!(4, err.column);
+        let err = JmespathError::new(expr, 4, ErrorReason::Parse("Test".to_owned()));
+        assert_eq!(1, err.line);
+        assert_eq!(5, err.column);
         assert_eq!(4, err.offset);
         assert_eq!(
-            "Parse error: Test (line 0, column 4)\nfoo..bar\n    ^\n",
+            "Parse error: Test (line 1, column 5)\nfoo..bar\n    ^\n",
+            format!("{:#}", err)
+        );
+        assert_eq!("Parse error: Test (line 1, column 5)", err.to_string());
+    }
+
+    #[test]
+    fn coordinates_count_characters_of_byte_offsets() {
+        let expr = "\"\u{e9}\u{e9}\" |\n \u{e9}";
+        let err = crate::parse(expr).unwrap_err();
+        assert_eq!(10, err.offset);
+        assert_eq!((2, 2), (err.line, err.column));
+        assert_eq!(10..12, err.span());
+    }
+
+    #[test]
+    fn reports_character_positions_of_multibyte_text() {
+        let cases = [
+            ("\"\u{e9}l\u{e8}ve\".[", 11..11, 9..9, 10),
+            ("`\"\u{65e5}\u{672c}\"` == {", 15..15, 11..11, 12),
+            ("\"\u{1f600}\" ~", 7..8, 4..5, 5),
+            ("'\u{e9}\u{e9}", 0..5, 0..3, 1),
+        ];
+        for (expr, span, char_span, column) in cases.iter() {
+            let err = crate::parse(expr).unwrap_err();
+            assert_eq!(*span, err.span(), "{}", expr);
+            assert_eq!(*char_span, err.char_span(), "{}", expr);
+            assert_eq!(*column, err.column, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn spans_cover_the_offending_token() {
+        let cases = [
+            ("foo{", 3..4),
+            ("foo || bar baz", 11..14),
+            ("foo[0", 5..5),
+            ("a.'b'", 2..5),
+            ("foo[?a == `{`]", 10..13),
+            ("'abc", 0..4),
+        ];
+        for (expr, span) in cases.iter() {
+            assert_eq!(*span, crate::parse(expr).unwrap_err().span(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn converts_into_boxed_error() {
+        fn parse(expr: &str) -> Result<crate::ast::Ast, Box<dyn Error + Send + Sync>> {
+            Ok(crate::parse(expr)?)
+        }
+        let err = parse("foo{").unwrap_err();
+        assert_eq!(
+            "Parse error: Unexpected led token -- found Lbrace (line 1, column 4)",
             err.to_string()
         );
+        let err = err.downcast::<JmespathError>().unwrap();
+        assert_eq!(3, err.offset);
+    }
+
+    #[test]
+    fn renders_line_with_caret() {
+        let err = crate::parse("foo[0").unwrap_err();
+        assert_eq!(
+            "Parse error: Expected ':' or ']' -- found Eof (line 1, column 6), for \
+             expression:\n\
+             \"foo[0\"\n      ^",
+            err.render("foo[0")
+        );
+        let expr = "foo\n| bar{";
+        let err = crate::parse(expr).unwrap_err();
+        assert_eq!(
+            "Parse error: Unexpected led token -- found Lbrace (line 2, column 6), for \
+             expression:\n\"| bar{\"\n      ^",
+            err.render(expr)
+        );
+        // Errors without an expression can be rendered against one.
+        let err = JmespathError::new("", 4, ErrorReason::Parse("Test".to_owned()));
+        assert_eq!(
+            "Parse error: Test (line 1, column 5), for expression:\n\"foo.bar\"\n     ^",
+            err.render("foo.bar")
+        );
+    }
+
+    #[test]
+    fn categorizes_errors_by_kind() {
+        let cases = [
+            ("foo#", ErrorKind::InvalidCharacter),
+            ("a = b", ErrorKind::InvalidCharacter),
+            ("\"foo", ErrorKind::UnclosedDelimiter),
+            ("`{`", ErrorKind::InvalidLiteral),
+            ("foo[-0]", ErrorKind::InvalidLiteral),
+            ("foo{", ErrorKind::UnexpectedToken),
+            ("foo bar", ErrorKind::UnexpectedToken),
+            ("foo[0", ErrorKind::UnexpectedEnd),
+            ("foo.", ErrorKind::UnexpectedEnd),
+        ];
+        for (expr, kind) in cases.iter() {
+            let err = crate::parse(expr).unwrap_err();
+            assert_eq!(*kind, err.kind, "{}", expr);
+            assert!(err.kind.is_parse());
+        }
+        let err = crate::parse(&"[".repeat(1000)).unwrap_err();
+        assert_eq!("nesting_too_deep", err.kind.code());
+        let err = crate::compile("length(`1`, `2`)")
+            .unwrap()
+            .search(crate::Variable::Null)
+            .unwrap_err();
+        assert_eq!(ErrorKind::TooManyArguments, err.kind);
+        assert!(!err.kind.is_parse());
     }
 
     #[test]
@@ -316,4 +848,141 @@ mod test {
             error.to_string()
         );
     }
+
+    #[test]
+    fn displays_limit_exceeded_error() {
+        let error = RuntimeError::LimitExceeded {
+            resource: "output size".to_owned(),
+            limit: 10,
+        };
+        assert_eq!("Exceeded the output size limit of 10", error.to_string());
+        assert_eq!(ErrorKind::LimitExceeded, error.kind());
+    }
+
+    #[test]
+    fn maps_runtime_errors_to_compliance_error_types() {
+        let cases = vec![
+            (RuntimeError::InvalidSlice, "invalid-value"),
+            (
+                RuntimeError::NotEnoughArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+                "invalid-arity",
+            ),
+            (
+                RuntimeError::UnknownFunction("a".to_owned()),
+                "unknown-function",
+            ),
+            (
+                RuntimeError::UnboundParameter("a".to_owned()),
+                "unbound-parameter",
+            ),
+            (RuntimeError::NotFinite("NaN".to_owned()), "invalid-value"),
+            (
+                RuntimeError::InvalidReturnType {
+                    expected: "string".to_owned(),
+                    actual: "null".to_owned(),
+                    position: 1,
+                    invocation: 0,
+                },
+                "invalid-type",
+            ),
+        ];
+        for (error, error_type) in cases {
+            assert_eq!(error_type, error.error_type());
+        }
+    }
+
+    #[test]
+    fn chains_runtime_errors_as_source() {
+        let data = crate::Rcvar::new(crate::Variable::Null);
+        let err = crate::compile("length(@, @)")
+            .unwrap()
+            .search(data)
+            .unwrap_err();
+        let source = err.source().unwrap();
+        assert_eq!(
+            Some(&RuntimeError::TooManyArguments {
+                expected: 1,
+                actual: 2
+            }),
+            source.downcast_ref::<RuntimeError>()
+        );
+        assert!(crate::parse("foo[").unwrap_err().source().is_none());
+    }
+
+    #[test]
+    fn lists_expected_tokens() {
+        let cases = vec![
+            ("foo[0", vec![":", "]"]),
+            ("foo.", vec!["identifier", "*", "{", "[", "&", "[?"]),
+            ("{a: b", vec!["}", ","]),
+            ("(@", vec![")"]),
+            ("{a b}", vec![":"]),
+            ("{1: b}", vec!["identifier"]),
+            ("foo{", vec![]),
+            ("'foo", vec![]),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(
+                expected,
+                crate::parse(expr).unwrap_err().expected(),
+                "{}",
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn serializes_runtime_errors_to_json() {
+        let err = JmespathError::new(
+            "foo()",
+            3,
+            ErrorReason::Runtime(RuntimeError::UnknownFunction("foo".to_owned())),
+        );
+        let value: serde_json::Value = serde_json::from_str(&err.to_json()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "kind": "unknown_function",
+                "message": "Call to undefined function foo",
+                "line": 1,
+                "column": 4,
+                "offset": 3,
+                "span": {"start": 3, "end": 3},
+                "expected": [],
+                "expression": "foo()"
+            }),
+            value
+        );
+    }
+
+    #[test]
+    fn redacts_literals() {
+        let cases = vec![
+            ("a == 'secret", "a == '******", "Unclosed ' delimiter"),
+            (
+                "`{\"key\": 1}` == `{bad`",
+                "`**********` == `****`",
+                "Unable to parse literal JSON",
+            ),
+            (
+                "\"it's\" == 'sécret'\n.b[",
+                "\"it's\" == '*******'\n.b[",
+                "Expected number, ':', or '*' -- found Eof",
+            ),
+        ];
+        let redact = crate::ParserOptions {
+            redact: true,
+            ..crate::ParserOptions::default()
+        };
+        for (expr, expression, message) in cases {
+            let err = crate::parse(expr).unwrap_err();
+            let redacted = crate::parse_with_options(expr, &redact).unwrap_err();
+            assert_eq!(expression, redacted.expression);
+            assert_eq!(message, redacted.message());
+            assert_eq!(err.span(), redacted.span());
+            assert_eq!(err.line, redacted.line);
+        }
+    }
 }
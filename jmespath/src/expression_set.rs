@@ -0,0 +1,245 @@
+//! Sets of named expressions that reference each other.
+//!
+//! An `ExpressionSet` holds named expressions that are compiled together.
+//! An expression references another one of the set by calling it like a
+//! function without arguments, so a large rule set can define a common
+//! expression once instead of repeating it. References are inlined when
+//! the set is compiled, and evaluate against the current node like any
+//! other expression.
+//!
+//! ```
+//! use jmespath::expression_set::ExpressionSet;
+//! use jmespath::Variable;
+//!
+//! let mut set = ExpressionSet::new();
+//! set.define("active_users", "users[?active]")
+//!     .define("active_names", "active_users()[*].name")
+//!     .define("active_count", "length(active_users())");
+//! let compiled = set.compile(jmespath::default_runtime()).unwrap();
+//! assert_eq!("length(users[?active])", compiled.get("active_count").unwrap().as_str());
+//!
+//! let data = Variable::from_json(
+//!     r#"{"users": [{"name": "a", "active": true}, {"name": "b", "active": false}]}"#,
+//! )
+//! .unwrap();
+//! let names = compiled.get("active_names").unwrap().search(data).unwrap();
+//! assert_eq!(r#"["a"]"#, names.to_string());
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::ast::Ast;
+use crate::visitor::AstVisitor;
+use crate::{parse_with_options, ErrorReason, Expression, JmespathError, Runtime};
+
+/// Named expressions to compile together.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpressionSet {
+    definitions: BTreeMap<String, String>,
+}
+
+/// The compiled expressions of an `ExpressionSet`, with every reference
+/// inlined.
+#[derive(Clone)]
+pub struct CompiledSet<'a> {
+    expressions: BTreeMap<String, Expression<'a>>,
+}
+
+impl ExpressionSet {
+    /// Creates an empty set.
+    pub fn new() -> ExpressionSet {
+        ExpressionSet::default()
+    }
+
+    /// Defines the expression of a name, replacing any previous definition.
+    ///
+    /// Expressions are parsed when the set is compiled.
+    pub fn define(&mut self, name: &str, expression: &str) -> &mut ExpressionSet {
+        self.definitions
+            .insert(name.to_owned(), expression.to_owned());
+        self
+    }
+
+    /// Returns the defined names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.definitions.keys().map(String::as_str)
+    }
+
+    /// Parses every expression, inlines its references, and compiles it
+    /// with the runtime.
+    ///
+    /// Fails if an expression doesn't parse, if a name is also a function
+    /// of the runtime, or if an expression references itself, directly or
+    /// through other expressions.
+    pub fn compile<'a>(&self, runtime: &'a Runtime) -> Result<CompiledSet<'a>, JmespathError> {
+        let mut resolver = Resolver {
+            set: self,
+            runtime,
+            resolved: BTreeMap::new(),
+            path: vec![],
+        };
+        let mut expressions = BTreeMap::new();
+        for (name, expression) in &self.definitions {
+            if runtime.get_function(name).is_some() {
+                let message = format!("`{}` is already a function of the runtime", name);
+                return Err(JmespathError::new(
+                    expression,
+                    0,
+                    ErrorReason::Parse(message),
+                ));
+            }
+            let ast = resolver.resolve(name)?;
+            let expression = runtime.compile(&ast.to_expression_string())?;
+            expressions.insert(name.clone(), expression);
+        }
+        Ok(CompiledSet { expressions })
+    }
+}
+
+impl<'a> CompiledSet<'a> {
+    /// Returns the compiled expression of a name.
+    pub fn get(&self, name: &str) -> Option<&Expression<'a>> {
+        self.expressions.get(name)
+    }
+
+    /// Returns the names and compiled expressions, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Expression<'a>)> {
+        self.expressions
+            .iter()
+            .map(|(name, expression)| (name.as_str(), expression))
+    }
+}
+
+/// Inlines the references of expressions, depth first.
+struct Resolver<'s> {
+    set: &'s ExpressionSet,
+    runtime: &'s Runtime,
+    resolved: BTreeMap<String, Ast>,
+    /// Names being resolved, outermost first, to detect cycles.
+    path: Vec<String>,
+}
+
+impl<'s> Resolver<'s> {
+    fn resolve(&mut self, name: &str) -> Result<Ast, JmespathError> {
+        if let Some(ast) = self.resolved.get(name) {
+            return Ok(ast.clone());
+        }
+        let expression = &self.set.definitions[name];
+        let (ast, _) = parse_with_options(expression, self.runtime.parser_options())?;
+        let mut references = References {
+            names: &self.set.definitions,
+            found: vec![],
+        };
+        references.visit(&ast);
+        self.path.push(name.to_owned());
+        for (reference, offset) in references.found {
+            if let Some(start) = self.path.iter().position(|n| *n == reference) {
+                let mut cycle = self.path[start..].to_vec();
+                cycle.push(reference);
+                let message = format!("Reference cycle: {}", cycle.join(" -> "));
+                return Err(JmespathError::new(
+                    expression,
+                    offset,
+                    ErrorReason::Parse(message),
+                ));
+            }
+            self.resolve(&reference)?;
+        }
+        self.path.pop();
+        let resolved = &self.resolved;
+        let names = &self.set.definitions;
+        let ast = ast.map_ast(|node| match node {
+            Ast::Function {
+                ref name, ref args, ..
+            } if args.is_empty() && names.contains_key(name.as_str()) => {
+                resolved[name.as_str()].clone()
+            }
+            node => node,
+        });
+        self.resolved.insert(name.to_owned(), ast.clone());
+        Ok(ast)
+    }
+}
+
+/// Collects the references to the expressions of a set, with their offsets.
+struct References<'s> {
+    names: &'s BTreeMap<String, String>,
+    found: Vec<(String, usize)>,
+}
+
+impl<'s> AstVisitor for References<'s> {
+    fn visit_function(&mut self, offset: usize, name: &str, args: &[Ast]) {
+        if args.is_empty() && self.names.contains_key(name) {
+            self.found.push((name.to_owned(), offset));
+        }
+        for arg in args {
+            self.visit(arg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Variable;
+    use std::collections::HashSet;
+
+    #[test]
+    fn inlines_references() {
+        let mut set = ExpressionSet::new();
+        set.define("adults", "people[?age >= `18`]")
+            .define("names", "adults()[*].name")
+            .define("first", "names() | [0]")
+            .define("count", "{adults: length(adults()), first: first()}");
+        let compiled = set.compile(crate::default_runtime()).unwrap();
+        assert_eq!(
+            vec!["adults", "count", "first", "names"],
+            compiled.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "(people[?age >= `18`])[*].name | [0]",
+            compiled.get("first").unwrap().as_str()
+        );
+        let data = Variable::from_json(
+            r#"{"people": [{"name": "a", "age": 10}, {"name": "b", "age": 20}]}"#,
+        )
+        .unwrap();
+        let count = compiled.get("count").unwrap().search(data).unwrap();
+        assert_eq!(r#"{"adults":1,"first":"b"}"#, count.to_string());
+        assert!(compiled.get("missing").is_none());
+    }
+
+    #[test]
+    fn rejects_cycles_and_shadowed_functions() {
+        let mut set = ExpressionSet::new();
+        set.define("a", "b() || c")
+            .define("b", "x.c()")
+            .define("c", "a()");
+        let error = set.compile(crate::default_runtime()).err().unwrap();
+        assert_eq!(
+            "Parse error: Reference cycle: a -> b -> c -> a",
+            error.reason.to_string()
+        );
+        assert_eq!(("a()", 1), (error.expression.as_str(), error.offset));
+
+        let mut set = ExpressionSet::new();
+        set.define("a", "a()");
+        assert!(set.compile(crate::default_runtime()).is_err());
+
+        let mut set = ExpressionSet::new();
+        set.define("length", "@");
+        assert!(set.compile(crate::default_runtime()).is_err());
+        let mut set = ExpressionSet::new();
+        set.define("a", "[");
+        assert!(set.compile(crate::default_runtime()).is_err());
+    }
+
+    #[test]
+    fn leaves_calls_with_arguments_alone() {
+        let mut set = ExpressionSet::new();
+        set.define("a", "`1`").define("b", "a(@)");
+        let compiled = set.compile(crate::default_runtime()).unwrap();
+        assert_eq!("a(@)", compiled.get("b").unwrap().as_str());
+        assert_eq!(HashSet::from(["a", "b"]), set.names().collect());
+    }
+}
@@ -0,0 +1,127 @@
+//! Formatting of JMESPath expressions into a canonical style.
+//!
+//! Unlike `Ast::to_expression_string`, the formatter keeps the structure of
+//! the expression as it was written (parens, pipes, etc.) and only
+//! normalizes the way each token is written:
+//!
+//! * Binary operators (`||`, `&&`, `|`, comparators) are surrounded by a
+//!   single space, commas and the colons of multi-select hashes are
+//!   followed by one, and no other whitespace is emitted.
+//! * Identifiers are only quoted when necessary.
+//! * String literals are written as raw strings (`'foo'`) when possible and
+//!   all other literals are written as compact JSON.
+//!
+//! ```
+//! let formatted = jmespath::formatter::format("foo[? \"bar\"==`\"baz\"` ]|{a:a,b : [0: 2]}").unwrap();
+//! assert_eq!("foo[?bar == 'baz'] | {a: a, b: [0:2]}", formatted);
+//! ```
+
+use crate::ast::render_name;
+use crate::lexer::{tokenize_spans, Token};
+use crate::{parse, JmespathError, Variable};
+
+/// Formats a JMESPath expression.
+///
+/// Returns an error if the expression is not valid.
+pub fn format(expr: &str) -> Result<String, JmespathError> {
+    // Only valid expressions are formatted so that the token context used
+    // below (e.g., whether a colon is part of a slice) is unambiguous.
+    parse(expr)?;
+    let mut out = String::with_capacity(expr.len());
+    // Tracks the open braces and brackets that enclose the current token.
+    let mut enclosing = vec![];
+    let mut previous = Token::Eof;
+    for (span, token) in tokenize_spans(expr)? {
+        let raw = &expr[span];
+        // Keep tokens apart that would otherwise be lexed as one token:
+        // "[ ]" is an empty list rather than a flatten and "& &" is not "&&".
+        match (&previous, &token) {
+            (Token::Lbracket, Token::Rbracket) | (Token::Ampersand, Token::Ampersand) => {
+                out.push(' ')
+            }
+            _ => (),
+        }
+        match token {
+            Token::QuotedIdentifier(ref name) => out.push_str(&render_name(name)),
+            Token::Literal(ref value) => out.push_str(&format_literal(value)),
+            Token::Number(n) => out.push_str(&n.to_string()),
+            Token::Or
+            | Token::And
+            | Token::Pipe
+            | Token::Eq
+            | Token::Ne
+            | Token::Lt
+            | Token::Lte
+            | Token::Gt
+            | Token::Gte => {
+                out.push(' ');
+                out.push_str(raw);
+                out.push(' ');
+            }
+            Token::Comma => out.push_str(", "),
+            Token::Colon if enclosing.last() == Some(&Token::Lbrace) => out.push_str(": "),
+            Token::Lbrace | Token::Lbracket | Token::Filter | Token::Lparen => {
+                out.push_str(raw);
+                enclosing.push(token.clone());
+            }
+            Token::Rbrace | Token::Rbracket | Token::Rparen => {
+                out.push_str(raw);
+                enclosing.pop();
+            }
+            Token::Eof => break,
+            _ => out.push_str(raw),
+        }
+        previous = token;
+    }
+    Ok(out)
+}
+
+/// Returns true if the expression is already formatted.
+pub fn is_formatted(expr: &str) -> Result<bool, JmespathError> {
+    format(expr).map(|formatted| formatted == expr)
+}
+
+fn format_literal(value: &Variable) -> String {
+    match *value {
+        // Raw strings can't contain a backslash that precedes a quote, so
+        // fall back to JSON for any string with backslashes.
+        Variable::String(ref s) if !s.contains('\\') => format!("'{}'", s.replace('\'', "\\'")),
+        _ => format!("`{}`", value.to_string().replace('`', "\\`")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalizes_whitespace_and_quoting() {
+        let cases = [
+            ("foo . bar", "foo.bar"),
+            ("a||b&&!c", "a || b && !c"),
+            ("( a | b )[ 0 ]", "(a | b)[0]"),
+            ("{ \"a\" :a , \"b c\":`[1, 2]`}", "{a: a, \"b c\": `[1,2]`}"),
+            ("foo[ -1 : : -1 ]", "foo[-1::-1]"),
+            ("sort_by( @ , & foo )", "sort_by(@, &foo)"),
+            ("[? a>=`1` ] . b []", "[?a >= `1`].b[]"),
+            ("[ ]", "[ ]"),
+            ("sort_by(@, & &a)", "sort_by(@, & &a)"),
+            ("`\"it's\"` ", "'it\\'s'"),
+            ("'a\\\\b'", "`\"a\\\\\\\\b\"`"),
+            ("{a: [0:1]}", "{a: [0:1]}"),
+        ];
+        for (expr, expected) in cases.iter() {
+            let formatted = format(expr).unwrap();
+            assert_eq!(*expected, formatted, "{}", expr);
+            assert!(is_formatted(&formatted).unwrap());
+        }
+    }
+
+    #[test]
+    fn does_not_format_invalid_expressions() {
+        assert!(format("foo[").is_err());
+        assert!(is_formatted("foo[").is_err());
+        assert!(is_formatted("foo | bar").unwrap());
+        assert!(!is_formatted("foo|bar").unwrap());
+    }
+}
@@ -1,18 +1,51 @@
 //! JMESPath functions.
 
 use std::cmp::{max, min};
-use std::collections::BTreeMap;
 use std::fmt;
 
+#[cfg(feature = "bignum")]
+use crate::decimal;
 use crate::interpreter::{interpret, SearchResult};
-use crate::variable::{JmespathType, Variable};
-use crate::{Context, ErrorReason, JmespathError, Rcvar, RuntimeError};
+use crate::variable::{integer, integer_number, JmespathType, Variable};
+use crate::{Context, ErrorReason, JmespathError, Map, Rcvar, RuntimeError};
 use serde_json::Number;
 
 /// Represents a JMESPath function.
 pub trait Function: Sync + Send {
     /// Evaluates the function against an in-memory variable.
     fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult;
+
+    /// Returns the signature of the function, if it has one.
+    ///
+    /// The signature is used by static analysis of expressions that call
+    /// the function, so functions still need to validate their arguments.
+    fn signature(&self) -> Option<&Signature> {
+        None
+    }
+
+    /// Returns what the function does besides computing its result from
+    /// its arguments, which a `Profile` can forbid.
+    ///
+    /// Functions have `Capability::Custom` by default, so a profile that
+    /// doesn't allow code of the embedding application rejects them unless
+    /// they declare what they do. The built-in functions override it.
+    fn capabilities(&self) -> &[Capability] {
+        &[Capability::Custom]
+    }
+}
+
+/// Something a function does besides computing its result from its
+/// arguments.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Capability {
+    /// Matches strings against a pattern taken from its arguments, like a
+    /// regular expression or a glob, so its cost depends on the pattern.
+    Pattern,
+    /// Reads the evaluation context or parameters of the search. A
+    /// profile without it also rejects expressions that use `:param`.
+    Context,
+    /// Runs code of the embedding application, whose behavior is unknown.
+    Custom,
 }
 
 /// Function argument types used when validating.
@@ -26,6 +59,12 @@ pub enum ArgumentType {
     Object,
     Array,
     Expref,
+    /// Binary data, with the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    Bytes,
+    /// A timestamp, with the `datetime` feature.
+    #[cfg(feature = "datetime")]
+    Timestamp,
     /// Each element of the array must matched the provided type.
     TypedArray(Box<ArgumentType>),
     /// Accepts one of a number of `ArgumentType`s
@@ -45,6 +84,10 @@ impl ArgumentType {
             Bool if value.is_boolean() => true,
             Expref if value.is_expref() => true,
             Array if value.is_array() => true,
+            #[cfg(feature = "bytes")]
+            Bytes if value.is_bytes() => true,
+            #[cfg(feature = "datetime")]
+            Timestamp if value.is_timestamp() => true,
             TypedArray(ref t) if value.is_array() => {
                 if let Some(array) = value.as_array() {
                     array.iter().all(|v| t.is_valid(v))
@@ -70,6 +113,10 @@ impl fmt::Display for ArgumentType {
             Object => write!(fmt, "object"),
             Null => write!(fmt, "null"),
             Expref => write!(fmt, "expref"),
+            #[cfg(feature = "bytes")]
+            Bytes => write!(fmt, "bytes"),
+            #[cfg(feature = "datetime")]
+            Timestamp => write!(fmt, "timestamp"),
             TypedArray(ref t) => write!(fmt, "array[{}]", t),
             Union(ref types) => {
                 let str_value = types
@@ -94,23 +141,25 @@ macro_rules! arg {
     (array_number) => (ArgumentType::TypedArray(Box::new(ArgumentType::Number)));
     (array_string) => (ArgumentType::TypedArray(Box::new(ArgumentType::String)));
     (array) => (ArgumentType::Array);
+    (bytes) => (ArgumentType::Bytes);
+    (timestamp) => (ArgumentType::Timestamp);
     ($($x:ident) | *) => (ArgumentType::Union(vec![$(arg!($x)), *]));
 }
 
+/// Boxed closure invoked by a `CustomFunction`.
+type CustomFunctionFn = Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync + Send>;
+
 /// Custom function that allows the creation of runtime functions with signature validation.
 pub struct CustomFunction {
     /// Signature used to validate the function.
     signature: Signature,
     /// Function to invoke after validating the signature.
-    f: Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync + Send>,
+    f: CustomFunctionFn,
 }
 
 impl CustomFunction {
     /// Creates a new custom function.
-    pub fn new(
-        fn_signature: Signature,
-        f: Box<dyn Fn(&[Rcvar], &mut Context<'_>) -> SearchResult + Sync + Send>,
-    ) -> CustomFunction {
+    pub fn new(fn_signature: Signature, f: CustomFunctionFn) -> CustomFunction {
         CustomFunction {
             signature: fn_signature,
             f,
@@ -123,6 +172,10 @@ impl Function for CustomFunction {
         self.signature.validate(args, ctx)?;
         (self.f)(args, ctx)
     }
+
+    fn signature(&self) -> Option<&Signature> {
+        Some(&self.signature)
+    }
 }
 
 /// Normal closures can be used as functions.
@@ -214,8 +267,14 @@ impl Signature {
 }
 
 /// Macro to more easily and quickly define a function and signature.
+///
+/// The `Function` impl it defines evaluates the function with its `call`
+/// method, and has the capabilities listed after the signature, if any.
 macro_rules! defn {
     ($name:ident, $args:expr, $variadic:expr) => {
+        defn!($name, $args, $variadic, []);
+    };
+    ($name:ident, $args:expr, $variadic:expr, [$($capability:expr),*]) => {
         pub struct $name {
             signature: Signature,
         }
@@ -233,6 +292,20 @@ macro_rules! defn {
                 }
             }
         }
+
+        impl Function for $name {
+            fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+                self.call(args, ctx)
+            }
+
+            fn signature(&self) -> Option<&Signature> {
+                Some(&self.signature)
+            }
+
+            fn capabilities(&self) -> &[Capability] {
+                &[$($capability),*]
+            }
+        }
     };
 }
 
@@ -304,6 +377,12 @@ macro_rules! min_and_max {
                 ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
             )
         })?;
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(result) = decimal::$operator(values) {
+                return Ok(result.clone());
+            }
+        }
         if values.is_empty() {
             Ok(Rcvar::new(Variable::Null))
         } else {
@@ -318,30 +397,32 @@ macro_rules! min_and_max {
 
 defn!(AbsFn, vec![arg!(number)], None);
 
-impl Function for AbsFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl AbsFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(n) = decimal::from_variable(&args[0]) {
+                return Ok(Rcvar::new(Variable::Number(n.abs().to_number())));
+            }
+        }
+        if let Variable::Number(n) = args[0].as_ref() {
+            if let Some(abs) = integer(n).and_then(|n| integer_number(n.abs())) {
+                return Ok(Rcvar::new(Variable::Number(abs)));
+            }
+        }
         match args[0].as_ref() {
-            Variable::Number(n) => Ok(Rcvar::new(Variable::Number(
-                Number::from_f64(
-                    n.as_f64()
-                        .ok_or_else(|| {
-                            JmespathError::new(
-                                "",
-                                0,
-                                ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                            )
-                        })?
-                        .abs(),
-                )
-                .ok_or_else(|| {
-                    JmespathError::new(
-                        "",
-                        0,
-                        ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                    )
-                })?,
-            ))),
+            Variable::Number(n) => ctx.number(
+                n.as_f64()
+                    .ok_or_else(|| {
+                        JmespathError::new(
+                            "",
+                            0,
+                            ErrorReason::Parse("Expected to be a valid f64".to_owned()),
+                        )
+                    })?
+                    .abs(),
+            ),
             _ => Ok(args[0].clone()),
         }
     }
@@ -349,8 +430,8 @@ impl Function for AbsFn {
 
 defn!(AvgFn, vec![arg!(array_number)], None);
 
-impl Function for AvgFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl AvgFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let values = args[0].as_array().ok_or_else(|| {
             JmespathError::new(
@@ -360,6 +441,16 @@ impl Function for AvgFn {
             )
         })?;
 
+        #[cfg(feature = "bignum")]
+        {
+            if !values.is_empty() {
+                if let Some(sum) = decimal::sum(values) {
+                    let average = sum.div(values.len() as u64);
+                    return Ok(Rcvar::new(Variable::Number(average.to_number())));
+                }
+            }
+        }
+
         let mut sum = 0.0;
 
         for value in values {
@@ -372,22 +463,14 @@ impl Function for AvgFn {
             })?;
         }
 
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(sum / (values.len() as f64)).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid f64".to_owned()),
-                )
-            })?,
-        )))
+        ctx.number(sum / (values.len() as f64))
     }
 }
 
 defn!(CeilFn, vec![arg!(number)], None);
 
-impl Function for CeilFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl CeilFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let n = args[0].as_number().ok_or_else(|| {
             JmespathError::new(
@@ -396,22 +479,23 @@ impl Function for CeilFn {
                 ErrorReason::Parse("Expected args[0] to be a number".to_owned()),
             )
         })?;
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(n.ceil()).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected n.ceil() to be a valid f64".to_owned()),
-                )
-            })?,
-        )))
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(ceil) = decimal::from_variable(&args[0]).and_then(|n| n.ceil()) {
+                return Ok(Rcvar::new(Variable::Number(ceil.to_number())));
+            }
+        }
+        if args[0].as_i64().is_some() || args[0].as_u64().is_some() {
+            return Ok(args[0].clone());
+        }
+        ctx.number(n.ceil())
     }
 }
 
 defn!(ContainsFn, vec![arg!(string | array), arg!(any)], None);
 
-impl Function for ContainsFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ContainsFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let haystack = &args[0];
         let needle = &args[1];
@@ -428,8 +512,8 @@ impl Function for ContainsFn {
 
 defn!(EndsWithFn, vec![arg!(string), arg!(string)], None);
 
-impl Function for EndsWithFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl EndsWithFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let subject = args[0].as_string().ok_or_else(|| {
             JmespathError::new(
@@ -451,8 +535,8 @@ impl Function for EndsWithFn {
 
 defn!(FloorFn, vec![arg!(number)], None);
 
-impl Function for FloorFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl FloorFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let n = args[0].as_number().ok_or_else(|| {
             JmespathError::new(
@@ -461,22 +545,23 @@ impl Function for FloorFn {
                 ErrorReason::Parse("Expected args[0] to be a valid number".to_owned()),
             )
         })?;
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(n.floor()).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid number".to_owned()),
-                )
-            })?,
-        )))
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(floor) = decimal::from_variable(&args[0]).and_then(|n| n.floor()) {
+                return Ok(Rcvar::new(Variable::Number(floor.to_number())));
+            }
+        }
+        if args[0].as_i64().is_some() || args[0].as_u64().is_some() {
+            return Ok(args[0].clone());
+        }
+        ctx.number(n.floor())
     }
 }
 
 defn!(JoinFn, vec![arg!(string), arg!(array_string)], None);
 
-impl Function for JoinFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl JoinFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let glue = args[0].as_string().ok_or_else(|| {
             JmespathError::new(
@@ -511,8 +596,8 @@ impl Function for JoinFn {
 
 defn!(KeysFn, vec![arg!(object)], None);
 
-impl Function for KeysFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl KeysFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let object = args[0].as_object().ok_or_else(|| {
             JmespathError::new(
@@ -521,18 +606,24 @@ impl Function for KeysFn {
                 ErrorReason::Parse("Expected args[0] to be a valid Object".to_owned()),
             )
         })?;
-        let keys = object
-            .keys()
-            .map(|k| Rcvar::new(Variable::String((*k).clone())))
+        let keys = ctx
+            .runtime
+            .key_order()
+            .entries(object)
+            .into_iter()
+            .map(|(k, _)| Rcvar::new(Variable::String(k.clone())))
             .collect::<Vec<Rcvar>>();
-        Ok(Rcvar::new(Variable::Array(keys)))
+        ctx.output(Variable::Array(keys))
     }
 }
 
+#[cfg(not(feature = "bytes"))]
 defn!(LengthFn, vec![arg!(array | object | string)], None);
+#[cfg(feature = "bytes")]
+defn!(LengthFn, vec![arg!(array | object | string | bytes)], None);
 
-impl Function for LengthFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl LengthFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         match args[0].as_ref() {
             Variable::Array(ref a) => Ok(Rcvar::new(Variable::Number(Number::from(a.len())))),
@@ -541,6 +632,8 @@ impl Function for LengthFn {
             Variable::String(ref s) => Ok(Rcvar::new(Variable::Number(Number::from(
                 s.chars().count(),
             )))),
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(ref b) => Ok(Rcvar::new(Variable::Number(Number::from(b.len())))),
             _ => unreachable!(),
         }
     }
@@ -548,8 +641,8 @@ impl Function for LengthFn {
 
 defn!(MapFn, vec![arg!(expref), arg!(array)], None);
 
-impl Function for MapFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MapFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let ast = args[0].as_expref().ok_or_else(|| {
             JmespathError::new(
@@ -569,14 +662,14 @@ impl Function for MapFn {
         for value in values {
             results.push(interpret(value, ast, ctx)?);
         }
-        Ok(Rcvar::new(Variable::Array(results)))
+        ctx.output(Variable::Array(results))
     }
 }
 
 defn!(MaxFn, vec![arg!(array_string | array_number)], None);
 
-impl Function for MaxFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MaxFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         min_and_max!(max, args)
     }
@@ -584,8 +677,8 @@ impl Function for MaxFn {
 
 defn!(MinFn, vec![arg!(array_string | array_number)], None);
 
-impl Function for MinFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MinFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         min_and_max!(min, args)
     }
@@ -593,8 +686,8 @@ impl Function for MinFn {
 
 defn!(MaxByFn, vec![arg!(array), arg!(expref)], None);
 
-impl Function for MaxByFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MaxByFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         min_and_max_by!(ctx, gt, args)
     }
@@ -602,8 +695,8 @@ impl Function for MaxByFn {
 
 defn!(MinByFn, vec![arg!(array), arg!(expref)], None);
 
-impl Function for MinByFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MinByFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         min_and_max_by!(ctx, lt, args)
     }
@@ -611,10 +704,10 @@ impl Function for MinByFn {
 
 defn!(MergeFn, vec![arg!(object)], Some(arg!(object)));
 
-impl Function for MergeFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl MergeFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let mut result = BTreeMap::new();
+        let mut result = Map::new();
         for arg in args {
             result.extend(
                 arg.as_object()
@@ -628,14 +721,15 @@ impl Function for MergeFn {
                     .clone(),
             );
         }
-        Ok(Rcvar::new(Variable::Object(result)))
+        let result = ctx.runtime.key_order().arrange(result);
+        ctx.output(Variable::Object(result))
     }
 }
 
 defn!(NotNullFn, vec![arg!(any)], Some(arg!(any)));
 
-impl Function for NotNullFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl NotNullFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         for arg in args {
             if !arg.is_null() {
@@ -648,8 +742,8 @@ impl Function for NotNullFn {
 
 defn!(ReverseFn, vec![arg!(array | string)], None);
 
-impl Function for ReverseFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ReverseFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         if args[0].is_array() {
             let mut values = args[0]
@@ -663,7 +757,7 @@ impl Function for ReverseFn {
                 })?
                 .clone();
             values.reverse();
-            Ok(Rcvar::new(Variable::Array(values)))
+            ctx.output(Variable::Array(values))
         } else {
             let word: String = args[0]
                 .as_string()
@@ -684,28 +778,33 @@ impl Function for ReverseFn {
 
 defn!(SortFn, vec![arg!(array_string | array_number)], None);
 
-impl Function for SortFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl SortFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let mut values = args[0]
-            .as_array()
-            .ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
-                )
-            })?
-            .clone();
-        values.sort();
-        Ok(Rcvar::new(Variable::Array(values)))
+        let values = args[0].as_array().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
+            )
+        })?;
+        // Decimals are parsed once instead of in every comparison.
+        #[cfg(feature = "bignum")]
+        let values = decimal::sort_by_value(values, |value| value);
+        #[cfg(not(feature = "bignum"))]
+        let values = {
+            let mut values = values.clone();
+            values.sort();
+            values
+        };
+        ctx.output(Variable::Array(values))
     }
 }
 
 defn!(SortByFn, vec![arg!(array), arg!(expref)], None);
 
-impl Function for SortByFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl SortByFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let vals = args[0]
             .as_array()
@@ -718,7 +817,7 @@ impl Function for SortByFn {
             })?
             .clone();
         if vals.is_empty() {
-            return Ok(Rcvar::new(Variable::Array(vals)));
+            return ctx.output(Variable::Array(vals));
         }
         let ast = args[1].as_expref().ok_or_else(|| {
             JmespathError::new(
@@ -755,16 +854,19 @@ impl Function for SortByFn {
             }
             mapped.push((v.clone(), mapped_value));
         }
+        #[cfg(feature = "bignum")]
+        let mapped = decimal::sort_by_value(&mapped, |tuple| &tuple.1);
+        #[cfg(not(feature = "bignum"))]
         mapped.sort_by(|a, b| a.1.cmp(&b.1));
         let result = mapped.iter().map(|tuple| tuple.0.clone()).collect();
-        Ok(Rcvar::new(Variable::Array(result)))
+        ctx.output(Variable::Array(result))
     }
 }
 
 defn!(StartsWithFn, vec![arg!(string), arg!(string)], None);
 
-impl Function for StartsWithFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl StartsWithFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let subject = args[0].as_string().ok_or_else(|| {
             JmespathError::new(
@@ -786,48 +888,64 @@ impl Function for StartsWithFn {
 
 defn!(SumFn, vec![arg!(array_number)], None);
 
-impl Function for SumFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl SumFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
-        let result = args[0]
-            .as_array()
-            .ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
-                )
-            })?
+        let values = args[0].as_array().ok_or_else(|| {
+            JmespathError::new(
+                "",
+                0,
+                ErrorReason::Parse("Expected args[0] to be an array".to_owned()),
+            )
+        })?;
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(sum) = decimal::sum(values) {
+                return Ok(Rcvar::new(Variable::Number(sum.to_number())));
+            }
+        }
+        if let Some(sum) = integer_sum(values) {
+            return Ok(Rcvar::new(Variable::Number(sum)));
+        }
+        let result = values
             .iter()
             .fold(0.0, |acc, item| acc + item.as_number().unwrap_or(0.0));
-        Ok(Rcvar::new(Variable::Number(
-            Number::from_f64(result).ok_or_else(|| {
-                JmespathError::new(
-                    "",
-                    0,
-                    ErrorReason::Parse("Expected to be a valid number".to_owned()),
-                )
-            })?,
-        )))
+        ctx.number(result)
     }
 }
 
+/// Returns the sum of numbers that are all stored as integers, or None if
+/// one of them is a float or if the sum fits in neither an i64 nor a u64.
+pub(crate) fn integer_sum<'a, I>(values: I) -> Option<Number>
+where
+    I: IntoIterator<Item = &'a Rcvar>,
+{
+    let mut sum: i128 = 0;
+    for value in values {
+        match **value {
+            Variable::Number(ref n) => sum = sum.checked_add(integer(n)?)?,
+            _ => return None,
+        }
+    }
+    integer_number(sum)
+}
+
 defn!(ToArrayFn, vec![arg!(any)], None);
 
-impl Function for ToArrayFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ToArrayFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         match *args[0] {
             Variable::Array(_) => Ok(args[0].clone()),
-            _ => Ok(Rcvar::new(Variable::Array(vec![args[0].clone()]))),
+            _ => ctx.output(Variable::Array(vec![args[0].clone()])),
         }
     }
 }
 
 defn!(ToNumberFn, vec![arg!(any)], None);
 
-impl Function for ToNumberFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ToNumberFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         match *args[0] {
             Variable::Number(_) => Ok(args[0].clone()),
@@ -840,26 +958,58 @@ impl Function for ToNumberFn {
     }
 }
 
+#[cfg(not(feature = "datetime"))]
 defn!(
     ToStringFn,
     vec![arg!(object | array | bool | number | string | null)],
     None
 );
+#[cfg(feature = "datetime")]
+defn!(
+    ToStringFn,
+    vec![arg!(
+        object | array | bool | number | string | null | timestamp
+    )],
+    None
+);
 
-impl Function for ToStringFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ToStringFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         match *args[0] {
             Variable::String(_) => Ok(args[0].clone()),
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(ref t) => Ok(Rcvar::new(Variable::String(t.to_string()))),
             _ => Ok(Rcvar::new(Variable::String(args[0].to_string()))),
         }
     }
 }
 
+#[cfg(feature = "datetime")]
+defn!(ToTimestampFn, vec![arg!(string | number | timestamp)], None);
+
+/// Converts an RFC 3339 string, or a number of seconds since the Unix
+/// epoch, to a timestamp. Returns null if the string is not a timestamp.
+#[cfg(feature = "datetime")]
+impl ToTimestampFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        use crate::timestamp::Timestamp;
+
+        self.signature.validate(args, ctx)?;
+        let timestamp = match *args[0] {
+            Variable::Timestamp(_) => return Ok(args[0].clone()),
+            Variable::String(ref s) => Timestamp::parse(s),
+            _ => args[0].as_number().and_then(Timestamp::from_unix),
+        };
+        let result = timestamp.map_or(Variable::Null, Variable::Timestamp);
+        Ok(Rcvar::new(result))
+    }
+}
+
 defn!(TypeFn, vec![arg!(any)], None);
 
-impl Function for TypeFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl TypeFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         Ok(Rcvar::new(Variable::String(args[0].get_type().to_string())))
     }
@@ -867,8 +1017,8 @@ impl Function for TypeFn {
 
 defn!(ValuesFn, vec![arg!(object)], None);
 
-impl Function for ValuesFn {
-    fn evaluate(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+impl ValuesFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
         self.signature.validate(args, ctx)?;
         let map = args[0].as_object().ok_or_else(|| {
             JmespathError::new(
@@ -877,8 +1027,12 @@ impl Function for ValuesFn {
                 ErrorReason::Parse("Expected args[1] to be an Object".to_owned()),
             )
         })?;
-        Ok(Rcvar::new(Variable::Array(
-            map.values().cloned().collect::<Vec<Rcvar>>(),
-        )))
+        let values = ctx.runtime.key_order().entries(map).into_iter();
+        ctx.output(Variable::Array(
+            values.map(|(_, v)| v.clone()).collect::<Vec<Rcvar>>(),
+        ))
     }
 }
+
+#[cfg(feature = "extensions")]
+pub mod extensions;
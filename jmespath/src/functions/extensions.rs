@@ -0,0 +1,606 @@
+//! Functions that are not part of the JMESPath specification.
+//!
+//! The extension functions are only available with the `extensions`
+//! feature, and only in runtimes they are registered with, so expressions
+//! that use them are not portable to other JMESPath implementations.
+//!
+//! ```
+//! use jmespath::Runtime;
+//!
+//! let runtime = Runtime::builder().extensions(true).build();
+//! let data = jmespath::Variable::from_json(r#"{"a": {"b": 1}, "c": {"b": 2}}"#).unwrap();
+//! let result = runtime.search("deep_merge(a, c)", data).unwrap();
+//! assert_eq!(r#"{"b":2}"#, result.to_string());
+//! ```
+//!
+//! | Function | Description |
+//! |----------|-------------|
+//! | `deep_merge(object, object...)` | Merges objects recursively. |
+//! | `unique(array)` | Removes repeated elements, keeping the first. |
+//! | `flatten(array, depth?)` | Flattens nested arrays, completely without a depth. |
+//! | `format_number(number, decimals, separator?)` | Formats a number as a string. |
+//! | `count_if(array, &predicate)` | Counts the elements the predicate is truthy for. |
+//! | `sum_by(array, &number)` | Sums a number taken from each element. |
+//! | `concat(string\|number, string\|number...)` | Concatenates strings and numbers. |
+//! | `wildcard_keys(object, pattern)` | Keeps the keys that match a glob pattern. |
+//! | `ctx()` | Returns the value of `EvalOptions::context`, or null. |
+//! | `base64_decode(string)` | Decodes base64 into bytes, with the `bytes` feature. |
+
+use std::sync::Arc;
+
+use super::*;
+use crate::{KeyOrder, Runtime};
+
+/// Registers every extension function with the runtime.
+pub fn register_extensions(runtime: &mut Runtime) {
+    runtime.register_function("deep_merge", Arc::new(DeepMergeFn::new()));
+    runtime.register_function("unique", Arc::new(UniqueFn::new()));
+    runtime.register_function("flatten", Arc::new(FlattenFn::new()));
+    runtime.register_function("format_number", Arc::new(FormatNumberFn::new()));
+    runtime.register_function("count_if", Arc::new(CountIfFn::new()));
+    runtime.register_function("sum_by", Arc::new(SumByFn::new()));
+    runtime.register_function("concat", Arc::new(ConcatFn::new()));
+    runtime.register_function("wildcard_keys", Arc::new(WildcardKeysFn::new()));
+    runtime.register_function("ctx", Arc::new(CtxFn::new()));
+    #[cfg(feature = "bytes")]
+    runtime.register_function("base64_decode", Arc::new(Base64DecodeFn::new()));
+}
+
+defn!(DeepMergeFn, vec![arg!(object)], Some(arg!(object)));
+
+/// Merges objects like `merge`, except that the values of a key that are
+/// objects in both objects are merged too. Other values, including arrays,
+/// are replaced by the value of the later object.
+impl DeepMergeFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let mut result = Map::new();
+        for arg in args {
+            if let Some(map) = arg.as_object() {
+                deep_merge(&mut result, map, ctx.runtime.key_order());
+            }
+        }
+        ctx.output(Variable::Object(result))
+    }
+}
+
+fn deep_merge(target: &mut Map, source: &Map, key_order: KeyOrder) {
+    for (key, value) in source {
+        let merged = match (target.get(key.as_str()), value.as_object()) {
+            (Some(existing), Some(source)) => existing.as_object().map(|existing| {
+                let mut merged = existing.clone();
+                deep_merge(&mut merged, source, key_order);
+                Rcvar::new(Variable::Object(merged))
+            }),
+            _ => None,
+        };
+        target.insert(key.clone(), merged.unwrap_or_else(|| value.clone()));
+    }
+    *target = key_order.arrange(std::mem::take(target));
+}
+
+defn!(UniqueFn, vec![arg!(array)], None);
+
+/// Removes the elements that are equal to an earlier element, using the
+/// same equality as `==`, so `1` and `1.0` are duplicates.
+impl UniqueFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let values = match args[0].as_array() {
+            Some(values) => values,
+            None => return Ok(args[0].clone()),
+        };
+        let mut unique: Vec<Rcvar> = Vec::with_capacity(values.len());
+        for value in values {
+            if !unique.contains(value) {
+                unique.push(value.clone());
+            }
+        }
+        if unique.len() == values.len() {
+            return Ok(args[0].clone());
+        }
+        ctx.output(Variable::Array(unique))
+    }
+}
+
+// The depth is optional, which signatures can only express as variadic.
+defn!(FlattenFn, vec![arg!(array)], Some(arg!(number)));
+
+/// Flattens arrays nested up to `depth` levels into the array, or every
+/// nested array without a depth. A depth of 1 flattens like `[]`, except
+/// that null elements are kept.
+impl FlattenFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        validate_max_arity(args, 2, ctx)?;
+        let depth = match args.get(1) {
+            None => usize::MAX,
+            Some(depth) => non_negative_integer(depth, 1, ctx)?,
+        };
+        let values = match args[0].as_array() {
+            Some(values) if depth > 0 && values.iter().any(|v| v.is_array()) => values,
+            _ => return Ok(args[0].clone()),
+        };
+        let mut flattened = vec![];
+        flatten(values, depth, &mut flattened);
+        ctx.output(Variable::Array(flattened))
+    }
+}
+
+/// Returns an error if more than `max` arguments are given, for functions
+/// with optional arguments.
+fn validate_max_arity(args: &[Rcvar], max: usize, ctx: &Context<'_>) -> Result<(), JmespathError> {
+    if args.len() > max {
+        let reason = ErrorReason::Runtime(RuntimeError::TooManyArguments {
+            expected: max,
+            actual: args.len(),
+        });
+        return Err(JmespathError::from_ctx(ctx, reason));
+    }
+    Ok(())
+}
+
+/// Returns a number argument that has to be a non-negative integer.
+fn non_negative_integer(
+    value: &Rcvar,
+    position: usize,
+    ctx: &Context<'_>,
+) -> Result<usize, JmespathError> {
+    match value.as_number() {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => {
+            let reason = ErrorReason::Runtime(RuntimeError::InvalidType {
+                expected: "non-negative integer".to_owned(),
+                actual: value.get_type().to_string(),
+                position,
+            });
+            Err(JmespathError::from_ctx(ctx, reason))
+        }
+    }
+}
+
+fn flatten(values: &[Rcvar], depth: usize, flattened: &mut Vec<Rcvar>) {
+    for value in values {
+        match value.as_array() {
+            Some(nested) if depth > 0 => flatten(nested, depth - 1, flattened),
+            _ => flattened.push(value.clone()),
+        }
+    }
+}
+
+// The separator is optional, which signatures can only express as variadic.
+defn!(
+    FormatNumberFn,
+    vec![arg!(number), arg!(number)],
+    Some(arg!(string))
+);
+
+/// Formats a number with a fixed number of decimals, rounding half away
+/// from zero, and optionally separates the thousands of the integer part.
+///
+/// The decimal point is always `.`; the output does not depend on the
+/// locale of the host.
+impl FormatNumberFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        validate_max_arity(args, 3, ctx)?;
+        let n = args[0].as_number().unwrap_or_default();
+        // f64 has at most 17 significant digits.
+        let decimals = non_negative_integer(&args[1], 1, ctx)?.min(17);
+        let separator = args.get(2).and_then(|s| s.as_str()).unwrap_or("");
+        let scale = 10f64.powi(decimals as i32);
+        // Numbers too large to scale have no fraction to round.
+        let rounded = match n * scale {
+            scaled if scaled.is_finite() => scaled.round() / scale,
+            _ => n,
+        };
+        let text = format!("{:.*}", decimals, rounded.abs());
+        let (integer, fraction) = match text.find('.') {
+            Some(point) => text.split_at(point),
+            None => (text.as_str(), ""),
+        };
+        let mut formatted = String::with_capacity(text.len() * 2);
+        if rounded < 0.0 {
+            formatted.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                formatted.push_str(separator);
+            }
+            formatted.push(digit);
+        }
+        formatted.push_str(fraction);
+        Ok(Rcvar::new(Variable::String(formatted)))
+    }
+}
+
+defn!(CountIfFn, vec![arg!(array), arg!(expref)], None);
+
+/// Counts the elements for which the expression is truthy, like
+/// `length(array[?predicate])` without collecting the elements.
+impl CountIfFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let mut count: u64 = 0;
+        if let (Some(values), Some(ast)) = (args[0].as_array(), args[1].as_expref()) {
+            for value in values {
+                if interpret(value, ast, ctx)?.is_truthy() {
+                    count += 1;
+                }
+            }
+        }
+        Ok(Rcvar::new(Variable::Number(Number::from(count))))
+    }
+}
+
+defn!(SumByFn, vec![arg!(array), arg!(expref)], None);
+
+/// Sums the numbers the expression returns for each element, like
+/// `sum(array[*].expression)` without collecting the numbers. The
+/// expression has to return a number for every element.
+impl SumByFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let mut sum = 0.0;
+        let mut numbers = vec![];
+        if let (Some(values), Some(ast)) = (args[0].as_array(), args[1].as_expref()) {
+            for (invocation, value) in values.iter().enumerate() {
+                let mapped = interpret(value, ast, ctx)?;
+                match mapped.as_number() {
+                    Some(n) => {
+                        sum += n;
+                        numbers.push(mapped);
+                    }
+                    None => {
+                        let reason = ErrorReason::Runtime(RuntimeError::InvalidReturnType {
+                            expected: "expression->number".to_owned(),
+                            actual: mapped.get_type().to_string(),
+                            position: 1,
+                            invocation,
+                        });
+                        return Err(JmespathError::from_ctx(ctx, reason));
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "bignum")]
+        {
+            if let Some(exact) = crate::decimal::sum(&numbers) {
+                return Ok(Rcvar::new(Variable::Number(exact.to_number())));
+            }
+        }
+        if let Some(exact) = integer_sum(&numbers) {
+            return Ok(Rcvar::new(Variable::Number(exact)));
+        }
+        ctx.number(sum)
+    }
+}
+
+defn!(
+    ConcatFn,
+    vec![arg!(string | number)],
+    Some(arg!(string | number))
+);
+
+/// Concatenates its arguments into a string, so composite keys can be
+/// built with `concat(region, '-', id)`. Numbers are written like in JSON.
+impl ConcatFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let mut result = String::new();
+        for arg in args {
+            match arg.as_string() {
+                Some(s) => result.push_str(s),
+                None => result.push_str(&arg.to_string()),
+            }
+        }
+        Ok(Rcvar::new(Variable::String(result)))
+    }
+}
+
+defn!(
+    WildcardKeysFn,
+    vec![arg!(object), arg!(string)],
+    None,
+    [Capability::Pattern]
+);
+
+/// Returns the entries of an object whose keys match a glob pattern, for
+/// documents whose keys encode dimensions, like `cpu_user` and `cpu_idle`.
+/// In the pattern, `*` matches any characters, `?` matches one character,
+/// and `\` escapes the next character. Project the values of the result
+/// with `.*`, as in `wildcard_keys(metrics, 'cpu_*').*.avg`.
+impl WildcardKeysFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let map = args[0].as_object().unwrap();
+        let pattern = args[1].as_string().unwrap().chars().collect::<Vec<_>>();
+        let matched = map
+            .iter()
+            .filter(|(key, _)| glob_matches(&pattern, key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let matched = ctx.runtime.key_order().arrange(matched);
+        ctx.output(Variable::Object(matched))
+    }
+}
+
+/// Returns true if the text matches the glob pattern.
+///
+/// On a mismatch after a `*`, the match restarts one character later in
+/// the text, which takes linear time per `*` rather than exponential time.
+fn glob_matches(pattern: &[char], text: &str) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, t));
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some('\\') if pattern.get(p + 1) == Some(&text[t]) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(&c) if c != '\\' && c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((after, matched)) => {
+                p = after;
+                t = matched + 1;
+                star = Some((after, t));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+defn!(CtxFn, vec![], None, [Capability::Context]);
+
+/// Returns the context the expression is searched with, as set by
+/// `EvalOptions::context`, or null when there is none.
+impl CtxFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        Ok(ctx
+            .context
+            .cloned()
+            .unwrap_or_else(|| Rcvar::new(Variable::Null)))
+    }
+}
+
+#[cfg(feature = "bytes")]
+defn!(Base64DecodeFn, vec![arg!(string)], None);
+
+/// Decodes a string of standard base64, with or without padding, into
+/// bytes. Returns null if the string is not valid base64.
+#[cfg(feature = "bytes")]
+impl Base64DecodeFn {
+    fn call(&self, args: &[Rcvar], ctx: &mut Context<'_>) -> SearchResult {
+        self.signature.validate(args, ctx)?;
+        let decoded = args[0].as_string().and_then(|s| decode_base64(s));
+        Ok(Rcvar::new(decoded.map_or(Variable::Null, Variable::Bytes)))
+    }
+}
+
+#[cfg(feature = "bytes")]
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let digits = text.trim_end_matches('=');
+    let padding = text.len() - digits.len();
+    if padding > 2 || (padding > 0 && !text.len().is_multiple_of(4)) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    // The last 12 bits read, of which `bits` are not decoded yet.
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in digits.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6 | u32::from(value)) & 0xfff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    // A single leftover digit, or leftover bits that are set, can't come
+    // from encoding bytes.
+    if bits >= 6 || buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Runtime, RuntimeBuilder, Variable};
+
+    fn search(expr: &str, data: &str) -> String {
+        let runtime: Runtime = RuntimeBuilder::new().extensions(true).build();
+        match runtime.search(expr, Variable::from_json(data).unwrap()) {
+            Ok(result) => result.to_string(),
+            Err(e) => e.message(),
+        }
+    }
+
+    #[test]
+    fn merges_objects_recursively() {
+        let data = r#"{
+            "base": {"a": {"b": 1, "c": [1, 2]}, "d": 1},
+            "local": {"a": {"c": [3], "e": {"f": true}}, "d": {"g": 1}},
+            "extra": {"a": {"b": null}}
+        }"#;
+        assert_eq!(
+            r#"{"a":{"b":null,"c":[3],"e":{"f":true}},"d":{"g":1}}"#,
+            search("deep_merge(base, local, extra)", data)
+        );
+        assert_eq!(r#"{"b":1,"c":[1,2]}"#, search("deep_merge(base.a)", data));
+        assert_eq!(
+            "Argument 1 expects type object, given array",
+            search("deep_merge(base, base.a.c)", data)
+        );
+    }
+
+    #[test]
+    fn removes_repeated_elements() {
+        let data = r#"[3, 1, 3.0, "3", {"a": [1]}, {"a": [1.0]}, null, 1, null]"#;
+        assert_eq!(r#"[3,1,"3",{"a":[1]},null]"#, search("unique(@)", data));
+        assert_eq!("[]", search("unique(@)", "[]"));
+        assert_eq!(
+            r#"["a","b"]"#,
+            search(
+                "unique([*].id)",
+                r#"[{"id": "a"}, {"id": "b"}, {"id": "a"}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn flattens_to_a_depth() {
+        let data = r#"[1, [2, [3, [4]]], null, []]"#;
+        assert_eq!("[1,2,3,4,null]", search("flatten(@)", data));
+        assert_eq!("[1,[2,[3,[4]]],null,[]]", search("flatten(@, `0`)", data));
+        assert_eq!("[1,2,[3,[4]],null]", search("flatten(@, `1`)", data));
+        assert_eq!("[1,2,3,[4],null]", search("flatten(@, `2`)", data));
+        assert_eq!(
+            "Argument 1 expects type non-negative integer, given number",
+            search("flatten(@, `-1`)", data)
+        );
+        assert_eq!(
+            "Too many arguments: expected 2, found 3",
+            search("flatten(@, `1`, `1`)", data)
+        );
+    }
+
+    #[test]
+    fn formats_numbers() {
+        let format = |args: &str| search(&format!("format_number({})", args), "null");
+        assert_eq!(r#""1234.57""#, format("`1234.567`, `2`"));
+        assert_eq!(r#""1,234,567.0""#, format("`1234567`, `1`, ','"));
+        assert_eq!(r#""-1 000""#, format("`-999.5`, `0`, ' '"));
+        assert_eq!(r#""0.13""#, format("`0.125`, `2`"));
+        assert_eq!(r#""100""#, format("`100`, `0`, ','"));
+        assert_eq!(r#""0""#, format("`-0.1`, `0`"));
+        let large = format("`1e300`, `17`, ','");
+        assert!(large.starts_with(r#""1,000,000"#) && large.ends_with(".00000000000000000\""));
+        assert_eq!(
+            "Argument 1 expects type non-negative integer, given number",
+            format("`1`, `1.5`")
+        );
+    }
+
+    #[test]
+    fn aggregates_with_expressions() {
+        let data = r#"[{"n": 1, "ok": true}, {"n": 2.5, "ok": false}, {"n": 3, "ok": "yes"}]"#;
+        assert_eq!("2", search("count_if(@, &ok)", data));
+        assert_eq!("2", search("count_if(@, &n > `2`)", data));
+        assert_eq!("0", search("count_if(`[]`, &ok)", data));
+        assert_eq!("6.5", search("sum_by(@, &n)", data));
+        assert_eq!("0", search("sum_by(`[]`, &n)", data));
+        assert!(search("sum_by(@, &ok)", data).contains("expression->number"));
+    }
+
+    #[test]
+    fn concatenates_strings_and_numbers() {
+        let data = r#"{"region": "eu", "id": 42, "ratio": 0.5}"#;
+        assert_eq!(r#""eu-42""#, search("concat(region, '-', id)", data));
+        assert_eq!(r#""0.5%""#, search("concat(ratio, '%')", data));
+        assert_eq!(r#""eu""#, search("concat(region)", data));
+        assert_eq!(
+            "Argument 1 expects type string|number, given null",
+            search("concat(region, missing)", data)
+        );
+    }
+
+    #[test]
+    fn selects_keys_matching_globs() {
+        let data = r#"{"cpu_user": {"avg": 1}, "cpu_idle": {"avg": 2}, "mem_used": {"avg": 3}}"#;
+        assert_eq!(
+            "[1,2]",
+            search("wildcard_keys(@, 'cpu_*').*.avg | sort(@)", data)
+        );
+        assert_eq!(
+            r#"["mem_used"]"#,
+            search("keys(wildcard_keys(@, '*_us?d'))", data)
+        );
+        assert_eq!("{}", search("wildcard_keys(@, 'cpu')", data));
+        assert_eq!("3", search("length(wildcard_keys(@, '*'))", data));
+        let data = r#"{"a*b": 1, "axb": 2, "a?": 3}"#;
+        assert_eq!(r#"{"a*b":1}"#, search(r"wildcard_keys(@, 'a\*b')", data));
+        assert_eq!(r#"{"a?":3}"#, search(r"wildcard_keys(@, 'a\?')", data));
+        assert_eq!(
+            "Argument 0 expects type object, given array",
+            search("wildcard_keys(`[]`, '*')", data)
+        );
+        let data = Variable::from_json(r#"{"cpu_b": 1, "mem": 2, "cpu_a": 3}"#).unwrap();
+        let runtime = RuntimeBuilder::new()
+            .extensions(true)
+            .key_order(crate::KeyOrder::Lexicographic)
+            .build();
+        let result = runtime.search("wildcard_keys(@, 'cpu_*')", data).unwrap();
+        assert_eq!(r#"{"cpu_a":3,"cpu_b":1}"#, result.to_string());
+    }
+
+    #[test]
+    fn reads_the_evaluation_context() {
+        use crate::{EvalOptions, Rcvar};
+
+        let runtime: Runtime = RuntimeBuilder::new().extensions(true).build();
+        let expr = runtime.compile("[ctx().region, a]").unwrap();
+        let data = Variable::from_json(r#"{"a": 1}"#).unwrap();
+        let context = Variable::from_json(r#"{"region": "eu"}"#).unwrap();
+        let options = EvalOptions::new().context(Rcvar::new(context));
+        assert_eq!(
+            r#"["eu",1]"#,
+            expr.search_with_options(&data, &options)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!("[null,1]", expr.search(&data).unwrap().to_string());
+        assert_eq!(
+            "Too many arguments: expected 0, found 1",
+            search("ctx(@)", "null")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn decodes_base64() {
+        assert_eq!("[104,105,33]", search("base64_decode('aGkh')", "null"));
+        assert_eq!("[104,105]", search("base64_decode('aGk=')", "null"));
+        assert_eq!("[104,105]", search("base64_decode('aGk')", "null"));
+        assert_eq!("[]", search("base64_decode('')", "null"));
+        assert_eq!(
+            r#"["bytes",5,[101,108]]"#,
+            search(
+                "base64_decode(@) | [type(@), length(@), [1:3]]",
+                r#""aGVsbG8=""#
+            )
+        );
+        for invalid in &["'a'", "'aGk=='", "'aG=k'", "'aGl='", "'a-b_'"] {
+            let expr = format!("base64_decode({})", invalid);
+            assert_eq!("null", search(&expr, "null"), "{}", invalid);
+        }
+    }
+}
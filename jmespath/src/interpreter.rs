@@ -1,32 +1,202 @@
 //! Interprets JMESPath expressions.
 
-use std::collections::BTreeMap;
-
-use super::ast::Ast;
-use super::variable::Variable;
+use super::ast::{Ast, KeyValuePair, SmallString};
+#[cfg(feature = "debugger")]
+use super::debugger::DebugAction;
+use super::variable::{is_identity_slice, Variable};
 use super::Context;
-use super::{ErrorReason, JmespathError, Rcvar, RuntimeError};
+use super::{ErrorReason, JmespathError, KeyOrder, Map, Rcvar, RuntimeError};
 
 /// Result of searching data using a JMESPath Expression.
 pub type SearchResult = Result<Rcvar, JmespathError>;
 
 /// Interprets the given data using an AST node.
 pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    #[cfg(feature = "debugger")]
+    {
+        if ctx.debug.is_some() {
+            return debug(data, node, ctx);
+        }
+    }
+    evaluate(data, node, ctx)
+}
+
+/// Returns true if the result of a node is truthy, evaluating only as much
+/// of it as that takes: a projection stops at its first non-null result,
+/// and `&&`, `||` and `!` are answered from the truthiness of their
+/// operands.
+pub(crate) fn interpret_truthy(
+    data: &Rcvar,
+    node: &Ast,
+    ctx: &mut Context<'_>,
+) -> Result<bool, JmespathError> {
+    match *node {
+        Ast::Projection { .. } => {
+            ctx.page = Some(Page {
+                offset: 0,
+                limit: Some(1),
+            });
+            Ok(interpret(data, node, ctx)?.is_truthy())
+        }
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            let left = interpret(data, lhs, ctx)?;
+            if left.is_null() && selects_from_null(rhs) {
+                return Ok(false);
+            }
+            interpret_truthy(&left, rhs, ctx)
+        }
+        Ast::Or {
+            ref lhs, ref rhs, ..
+        } => Ok(interpret_truthy(data, lhs, ctx)? || interpret_truthy(data, rhs, ctx)?),
+        Ast::And {
+            ref lhs, ref rhs, ..
+        } => Ok(interpret_truthy(data, lhs, ctx)? && interpret_truthy(data, rhs, ctx)?),
+        Ast::Not { ref node, .. } => Ok(!interpret_truthy(data, node, ctx)?),
+        _ => Ok(interpret(data, node, ctx)?.is_truthy()),
+    }
+}
+
+/// Evaluates a node with the page of `EvalOptions::limit` and
+/// `EvalOptions::offset`.
+///
+/// The page is given to the projection that produces the result, found by
+/// following the right hand sides of sub-expressions and pipes, like
+/// `a[*].b` in `x | a[*].b | @`, so that projection stops once the page is
+/// full. The result of any other node is paged once it is evaluated, if it
+/// is an array.
+pub(crate) fn interpret_page(
+    data: &Rcvar,
+    node: &Ast,
+    page: Page,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    match *node {
+        Ast::Projection { .. } => {
+            ctx.page = Some(page);
+            interpret(data, node, ctx)
+        }
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } if matches!(**rhs, Ast::Identity { .. }) => interpret_page(data, lhs, page, ctx),
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            let left = interpret(data, lhs, ctx)?;
+            if left.is_null() && selects_from_null(rhs) {
+                return Ok(left);
+            }
+            interpret_page(&left, rhs, page, ctx)
+        }
+        _ => {
+            let result = interpret(data, node, ctx)?;
+            let limit = page.limit.unwrap_or(usize::MAX);
+            match result.as_array() {
+                Some(array) if page.offset > 0 || array.len() > limit => {
+                    let paged = array.iter().skip(page.offset).take(limit).cloned();
+                    output(Variable::Array(paged.collect()), node, ctx)
+                }
+                _ => Ok(result),
+            }
+        }
+    }
+}
+
+/// Returns the number of elements in the result of a node.
+///
+/// The results of the projection that produces the result, found like the
+/// one `interpret_page` pages, are counted without collecting them in an
+/// array.
+pub(crate) fn interpret_count(
+    data: &Rcvar,
+    node: &Ast,
+    ctx: &mut Context<'_>,
+) -> Result<usize, JmespathError> {
+    match *node {
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => {
+            let subject = interpret(data, lhs, ctx)?;
+            let mut count = 0;
+            for element in subject.as_array().map_or(&[][..], Vec::as_slice) {
+                if !interpret(element, rhs, ctx)?.is_null() {
+                    count += 1;
+                }
+            }
+            Ok(count)
+        }
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } if matches!(**rhs, Ast::Identity { .. }) => interpret_count(data, lhs, ctx),
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            let left = interpret(data, lhs, ctx)?;
+            if left.is_null() && selects_from_null(rhs) {
+                return Ok(0);
+            }
+            interpret_count(&left, rhs, ctx)
+        }
+        _ => Ok(interpret(data, node, ctx)?.as_array().map_or(0, Vec::len)),
+    }
+}
+
+/// Interprets a node of a step-through evaluation, pausing before it if
+/// the debugger asks to.
+#[cfg(feature = "debugger")]
+fn debug(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    if let Some(session) = ctx.debug.as_mut() {
+        if session.pause(node, data) == Some(DebugAction::Abort) {
+            ctx.offset = node.offset();
+            let reason = ErrorReason::Runtime(RuntimeError::Aborted);
+            return Err(JmespathError::from_ctx(ctx, reason));
+        }
+        session.scope.push(data.clone());
+    }
+    let result = evaluate(data, node, ctx);
+    if let Some(session) = ctx.debug.as_mut() {
+        session.scope.pop();
+        session.debugger.on_node_end(node, &result);
+    }
+    result
+}
+
+/// Evaluates a node.
+///
+/// The nodes that build values are evaluated by separate functions, which
+/// keeps the stack frame of `evaluate` small: long chains like
+/// `a || b || c` go through it once for every operand.
+fn evaluate(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
     match *node {
         Ast::Field { ref name, .. } => Ok(data.get_field(name)),
         Ast::Subexpr {
             ref lhs, ref rhs, ..
         } => {
             let left_result = interpret(data, lhs, ctx)?;
+            if left_result.is_null() && selects_from_null(rhs) {
+                return Ok(left_result);
+            }
             interpret(&left_result, rhs, ctx)
         }
         Ast::Identity { .. } => Ok(data.clone()),
         Ast::Literal { ref value, .. } => Ok(value.clone()),
+        Ast::Parameter { ref name, offset } => {
+            match ctx.params.and_then(|params| params.get(name.as_str())) {
+                Some(value) => Ok(value.clone()),
+                None => {
+                    ctx.offset = offset;
+                    let reason =
+                        ErrorReason::Runtime(RuntimeError::UnboundParameter(name.to_string()));
+                    Err(JmespathError::from_ctx(ctx, reason))
+                }
+            }
+        }
         Ast::Index { idx, .. } => {
             if idx >= 0 {
                 Ok(data.get_index(idx as usize))
             } else {
-                Ok(data.get_negative_index((-idx) as usize))
+                Ok(data.get_negative_index(idx.unsigned_abs() as usize))
             }
         }
         Ast::Or {
@@ -75,7 +245,7 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
             let left = interpret(data, lhs, ctx)?;
             let right = interpret(data, rhs, ctx)?;
             Ok(left
-                .compare(comparator, &*right)
+                .compare(comparator, &right)
                 .map_or(Rcvar::new(Variable::Null), |result| {
                     Rcvar::new(Variable::Bool(result))
                 }))
@@ -84,102 +254,616 @@ pub fn interpret(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResul
         Ast::ObjectValues { ref node, .. } => {
             let subject = interpret(data, node, ctx)?;
             match *subject {
-                Variable::Object(ref v) => Ok(Rcvar::new(Variable::Array(
-                    v.values().cloned().collect::<Vec<Rcvar>>(),
-                ))),
+                Variable::Object(ref v) => {
+                    let values = object_values(v, ctx.runtime.key_order());
+                    output(Variable::Array(values), node, ctx)
+                }
                 _ => Ok(Rcvar::new(Variable::Null)),
             }
         }
-        // Passes the results of lhs into rhs if lhs yields an array and
-        // each node of lhs that passes through rhs yields a non-null value.
-        Ast::Projection {
-            ref lhs, ref rhs, ..
-        } => match interpret(data, lhs, ctx)?.as_array() {
-            None => Ok(Rcvar::new(Variable::Null)),
-            Some(left) => {
-                let mut collected = vec![];
-                for element in left {
-                    let current = interpret(element, rhs, ctx)?;
-                    if !current.is_null() {
-                        collected.push(current);
-                    }
-                }
-                Ok(Rcvar::new(Variable::Array(collected)))
-            }
-        },
-        Ast::Flatten { ref node, .. } => match interpret(data, node, ctx)?.as_array() {
-            None => Ok(Rcvar::new(Variable::Null)),
-            Some(a) => {
-                let mut collected: Vec<Rcvar> = vec![];
-                for element in a {
-                    match element.as_array() {
-                        Some(array) => collected.extend(array.iter().cloned()),
-                        _ => collected.push(element.clone()),
+        // Collects the values of a key at any depth of an object or array.
+        Ast::RecursiveDescent {
+            ref node, ref name, ..
+        } => {
+            let subject = interpret(data, node, ctx)?;
+            match *subject {
+                Variable::Object(_) | Variable::Array(_) => {
+                    if ctx.output.is_some() {
+                        ctx.offset = node.offset();
                     }
+                    let found = descendants(&subject, name, ctx)?;
+                    Ok(Rcvar::new(Variable::Array(found)))
                 }
-                Ok(Rcvar::new(Variable::Array(collected)))
-            }
-        },
-        Ast::MultiList { ref elements, .. } => {
-            if data.is_null() {
-                Ok(Rcvar::new(Variable::Null))
-            } else {
-                let mut collected = vec![];
-                for node in elements {
-                    collected.push(interpret(data, node, ctx)?);
-                }
-                Ok(Rcvar::new(Variable::Array(collected)))
-            }
-        }
-        Ast::MultiHash { ref elements, .. } => {
-            if data.is_null() {
-                Ok(Rcvar::new(Variable::Null))
-            } else {
-                let mut collected = BTreeMap::new();
-                for kvp in elements {
-                    let value = interpret(data, &kvp.value, ctx)?;
-                    collected.insert(kvp.key.clone(), value);
-                }
-                Ok(Rcvar::new(Variable::Object(collected)))
+                _ => Ok(Rcvar::new(Variable::Null)),
             }
         }
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => project(data, node, lhs, rhs, ctx),
+        Ast::Flatten { ref node, .. } => flatten(data, node, ctx),
+        Ast::MultiList { ref elements, .. } => multi_list(data, node, elements, ctx),
+        Ast::MultiHash { ref elements, .. } => multi_hash(data, node, elements, ctx),
         Ast::Function {
             ref name,
             ref args,
             offset,
-        } => {
-            let mut fn_args: Vec<Rcvar> = vec![];
-            for arg in args {
-                fn_args.push(interpret(data, arg, ctx)?);
-            }
-            // Reset the offset so that it points to the function being evaluated.
-            ctx.offset = offset;
-            match ctx.runtime.get_function(name) {
-                Some(f) => f.evaluate(&fn_args, ctx),
-                None => {
-                    let reason =
-                        ErrorReason::Runtime(RuntimeError::UnknownFunction(name.to_owned()));
-                    Err(JmespathError::from_ctx(ctx, reason))
-                }
-            }
-        }
+        } => call(data, name, args, offset, ctx),
         Ast::Expref { ref ast, .. } => Ok(Rcvar::new(Variable::Expref(*ast.clone()))),
         Ast::Slice {
             start,
             stop,
             step,
             offset,
-        } => {
-            if step == 0 {
-                ctx.offset = offset;
-                let reason = ErrorReason::Runtime(RuntimeError::InvalidSlice);
-                Err(JmespathError::from_ctx(ctx, reason))
-            } else {
-                match data.slice(start, stop, step) {
-                    Some(array) => Ok(Rcvar::new(Variable::Array(array))),
-                    None => Ok(Rcvar::new(Variable::Null)),
+        } => slice(data, node, (start, stop, step), offset, ctx),
+    }
+}
+
+/// Passes the results of lhs into rhs if lhs yields an array and
+/// each node of lhs that passes through rhs yields a non-null value.
+///
+/// The array of lhs is reused as the result while rhs returns each
+/// element unchanged, and only copied once an element changes.
+#[inline(never)]
+fn project(data: &Rcvar, node: &Ast, lhs: &Ast, rhs: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    let page = ctx.page.take();
+    let subject = interpret(data, lhs, ctx)?;
+    // A slice of binary data, like `[1:3]`, is a projection of the
+    // bytes it selects. Other projections, like `[*]`, only project arrays.
+    #[cfg(feature = "bytes")]
+    {
+        let slice = matches!(*lhs, Ast::Slice { .. });
+        if slice && subject.is_bytes() && matches!(*rhs, Ast::Identity { .. }) {
+            return Ok(subject);
+        }
+    }
+    let left = match subject.as_array() {
+        None => return Ok(Rcvar::new(Variable::Null)),
+        Some(left) => left,
+    };
+    if let Some(page) = page {
+        let collected = project_page(left, rhs, page, ctx)?;
+        return output(Variable::Array(collected), node, ctx);
+    }
+    let mut collected: Option<Vec<Rcvar>> = None;
+    for (i, element) in left.iter().enumerate() {
+        let current = interpret(element, rhs, ctx)?;
+        if collected.is_none() && !Rcvar::ptr_eq(element, &current) {
+            collected = Some(left[..i].iter().filter(|e| !e.is_null()).cloned().collect());
+        }
+        if let Some(ref mut collected) = collected {
+            if !current.is_null() {
+                collected.push(current);
+            }
+        }
+    }
+    match collected {
+        Some(collected) => output(Variable::Array(collected), node, ctx),
+        None if left.iter().any(|element| element.is_null()) => output(
+            Variable::Array(left.iter().filter(|e| !e.is_null()).cloned().collect()),
+            node,
+            ctx,
+        ),
+        None => Ok(subject),
+    }
+}
+
+/// Flattens the nested arrays of an array by one level.
+#[inline(never)]
+fn flatten(data: &Rcvar, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    let subject = interpret(data, node, ctx)?;
+    match subject.as_array() {
+        None => Ok(Rcvar::new(Variable::Null)),
+        // There is nothing to flatten, so the array is the result.
+        Some(a) if !a.iter().any(|element| element.is_array()) => Ok(subject.clone()),
+        Some(a) => {
+            let mut collected: Vec<Rcvar> = vec![];
+            for element in a {
+                match element.as_array() {
+                    Some(array) => collected.extend(array.iter().cloned()),
+                    _ => collected.push(element.clone()),
+                }
+            }
+            output(Variable::Array(collected), node, ctx)
+        }
+    }
+}
+
+/// Collects the results of the elements of a multi-select list.
+#[inline(never)]
+fn multi_list(data: &Rcvar, node: &Ast, elements: &[Ast], ctx: &mut Context<'_>) -> SearchResult {
+    if data.is_null() {
+        return Ok(Rcvar::new(Variable::Null));
+    }
+    let mut collected = Vec::with_capacity(elements.len());
+    for element in elements {
+        collected.push(interpret(data, element, ctx)?);
+    }
+    output(Variable::Array(collected), node, ctx)
+}
+
+/// Collects the results of the values of a multi-select hash.
+///
+/// The value of a repeated key is the value of its last occurrence,
+/// since later insertions replace earlier ones.
+#[inline(never)]
+fn multi_hash(
+    data: &Rcvar,
+    node: &Ast,
+    elements: &[KeyValuePair],
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    if data.is_null() {
+        return Ok(Rcvar::new(Variable::Null));
+    }
+    let mut collected = Map::new();
+    for kvp in elements {
+        let value = interpret(data, &kvp.value, ctx)?;
+        collected.insert(kvp.key.to_string(), value);
+    }
+    let collected = ctx.runtime.key_order().arrange(collected);
+    output(Variable::Object(collected), node, ctx)
+}
+
+/// Calls a function of the runtime with the results of its arguments.
+///
+/// Most calls have up to three arguments, whose results are held in an
+/// array on the stack rather than in a vector, so they don't allocate.
+#[inline(never)]
+fn call(
+    data: &Rcvar,
+    name: &str,
+    args: &[Ast],
+    offset: usize,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    match *args {
+        [] => apply(name, &[], offset, ctx),
+        [ref a] => {
+            let fn_args = [interpret(data, a, ctx)?];
+            apply(name, &fn_args, offset, ctx)
+        }
+        [ref a, ref b] => {
+            let fn_args = [interpret(data, a, ctx)?, interpret(data, b, ctx)?];
+            apply(name, &fn_args, offset, ctx)
+        }
+        [ref a, ref b, ref c] => {
+            let fn_args = [
+                interpret(data, a, ctx)?,
+                interpret(data, b, ctx)?,
+                interpret(data, c, ctx)?,
+            ];
+            apply(name, &fn_args, offset, ctx)
+        }
+        _ => {
+            let mut fn_args: Vec<Rcvar> = Vec::with_capacity(args.len());
+            for arg in args {
+                fn_args.push(interpret(data, arg, ctx)?);
+            }
+            apply(name, &fn_args, offset, ctx)
+        }
+    }
+}
+
+/// Evaluates a function of the runtime with the results of its arguments.
+fn apply(name: &str, fn_args: &[Rcvar], offset: usize, ctx: &mut Context<'_>) -> SearchResult {
+    // Reset the offset so that it points to the function being evaluated.
+    ctx.offset = offset;
+    match ctx.runtime.get_function(name) {
+        Some(f) => f.evaluate(fn_args, ctx),
+        None => {
+            let reason = ErrorReason::Runtime(RuntimeError::UnknownFunction(name.to_string()));
+            Err(JmespathError::from_ctx(ctx, reason))
+        }
+    }
+}
+
+/// Slices an array, or binary data with the `bytes` feature.
+#[inline(never)]
+fn slice(
+    data: &Rcvar,
+    node: &Ast,
+    (start, stop, step): (Option<i32>, Option<i32>, i32),
+    offset: usize,
+    ctx: &mut Context<'_>,
+) -> SearchResult {
+    if step == 0 {
+        ctx.offset = offset;
+        let reason = ErrorReason::Runtime(RuntimeError::InvalidSlice);
+        return Err(JmespathError::from_ctx(ctx, reason));
+    }
+    if data
+        .as_array()
+        .is_some_and(|array| is_identity_slice(array.len(), start, stop, step))
+    {
+        return Ok(data.clone());
+    }
+    #[cfg(feature = "bytes")]
+    {
+        if let Some(bytes) = data.slice_bytes(start, stop, step) {
+            return Ok(Rcvar::new(Variable::Bytes(bytes)));
+        }
+    }
+    match data.slice(start, stop, step) {
+        Some(array) => output(Variable::Array(array), node, ctx),
+        None => Ok(Rcvar::new(Variable::Null)),
+    }
+}
+
+/// Returns true if the node evaluates to null when the current node is
+/// null, without raising an error.
+///
+/// This is the case for nodes that select from the current node, like the
+/// right hand side of `a.b`, `a[0]`, `a.[b]`, or `a.*`. Once a chain of
+/// sub-expressions reaches null, the rest of the chain is skipped. Other
+/// nodes, which can follow a pipe, are still evaluated with null: in
+/// `a | not_null(@, 'x')` the function returns `'x'` and in `a | [::0]`
+/// the slice is an error.
+fn selects_from_null(node: &Ast) -> bool {
+    match *node {
+        Ast::Field { .. } | Ast::Index { .. } | Ast::MultiList { .. } | Ast::MultiHash { .. } => {
+            true
+        }
+        Ast::Slice { step, .. } => step != 0,
+        Ast::Subexpr { ref lhs, .. } | Ast::Projection { ref lhs, .. } => selects_from_null(lhs),
+        Ast::Flatten { ref node, .. }
+        | Ast::ObjectValues { ref node, .. }
+        | Ast::RecursiveDescent { ref node, .. } => selects_from_null(node),
+        _ => false,
+    }
+}
+
+/// Returns the values of an object, in the key order of the runtime.
+fn object_values(map: &Map, key_order: KeyOrder) -> Vec<Rcvar> {
+    key_order
+        .entries(map)
+        .into_iter()
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+/// Returns the values of the key `name` found at any depth in `value`, in
+/// document order: a match comes before the matches nested inside it, and
+/// the values of an object are visited in the key order of the runtime.
+///
+/// The traversal uses an explicit stack rather than recursion, so deeply
+/// nested documents can't overflow the stack. Every match is counted
+/// against the output budget as it is found.
+fn descendants(
+    value: &Rcvar,
+    name: &str,
+    ctx: &mut Context<'_>,
+) -> Result<Vec<Rcvar>, JmespathError> {
+    let key_order = ctx.runtime.key_order();
+    let mut found = vec![];
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match **value {
+            Variable::Object(ref map) => {
+                if let Some(matched) = map.get(name) {
+                    ctx.charge_output(1)?;
+                    found.push(matched.clone());
                 }
+                let entries = key_order.entries(map).into_iter().rev();
+                stack.extend(entries.map(|(_, value)| value));
             }
+            Variable::Array(ref array) => stack.extend(array.iter().rev()),
+            _ => {}
+        }
+    }
+    Ok(found)
+}
+
+/// The results of a projection to keep, set with `EvalOptions::limit`
+/// and `EvalOptions::offset`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Page {
+    /// Number of non-null results to skip.
+    pub offset: usize,
+    /// Maximum number of results to keep.
+    pub limit: Option<usize>,
+}
+
+/// Projects the elements of an array through `rhs`, until the page is
+/// full. The remaining elements are not evaluated.
+fn project_page(
+    left: &[Rcvar],
+    rhs: &Ast,
+    page: Page,
+    ctx: &mut Context<'_>,
+) -> Result<Vec<Rcvar>, JmespathError> {
+    let limit = page.limit.unwrap_or(usize::MAX);
+    let mut skipped = 0;
+    let mut collected = vec![];
+    for element in left {
+        if collected.len() >= limit {
+            break;
+        }
+        let current = interpret(element, rhs, ctx)?;
+        if current.is_null() {
+            continue;
+        }
+        if skipped < page.offset {
+            skipped += 1;
+        } else {
+            collected.push(current);
+        }
+    }
+    Ok(collected)
+}
+
+/// The number of elements the arrays and objects built by an evaluation
+/// may have in total, set with `EvalOptions::max_output_elements`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct OutputBudget {
+    pub limit: usize,
+    pub used: usize,
+}
+
+/// Returns an array or object built by a node, after counting its
+/// elements against the output budget of the evaluation.
+fn output(value: Variable, node: &Ast, ctx: &mut Context<'_>) -> SearchResult {
+    if ctx.output.is_some() {
+        ctx.offset = node.offset();
+    }
+    ctx.output(value)
+}
+
+/// A step of a simple path expression, like `a.b[0]`.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum PathStep {
+    Field(SmallString),
+    Index(i32),
+}
+
+/// Returns the steps of an expression that only uses fields, indices, and
+/// the current node, chained by sub-expressions or pipes. Returns None for
+/// any other expression.
+pub(crate) fn path_steps(node: &Ast) -> Option<Vec<PathStep>> {
+    fn collect(node: &Ast, steps: &mut Vec<PathStep>) -> bool {
+        match *node {
+            Ast::Identity { .. } => true,
+            Ast::Field { ref name, .. } => {
+                steps.push(PathStep::Field(name.clone()));
+                true
+            }
+            Ast::Index { idx, .. } => {
+                steps.push(PathStep::Index(idx));
+                true
+            }
+            Ast::Subexpr {
+                ref lhs, ref rhs, ..
+            } => collect(lhs, steps) && collect(rhs, steps),
+            _ => false,
+        }
+    }
+    let mut steps = vec![];
+    if collect(node, &mut steps) {
+        Some(steps)
+    } else {
+        None
+    }
+}
+
+/// Returns the value selected by one step, or None if it selects null.
+#[inline]
+pub(crate) fn follow_step<'d>(data: &'d Variable, step: &PathStep) -> Option<&'d Rcvar> {
+    match (step, data) {
+        (PathStep::Field(name), Variable::Object(map)) => map.get(name.as_str()),
+        (&PathStep::Index(idx), Variable::Array(array)) if idx >= 0 => array.get(idx as usize),
+        (&PathStep::Index(idx), Variable::Array(array)) => {
+            // Like `get_negative_index`, -0 is the last element.
+            let from_end = (idx.unsigned_abs() as usize).max(1);
+            array.len().checked_sub(from_end).map(|i| &array[i])
+        }
+        _ => None,
+    }
+}
+
+/// Evaluates the steps of a simple path, which gives the same result as
+/// interpreting the expression they were taken from. Only the reference
+/// count of the selected value is changed.
+pub(crate) fn follow_path(data: &Rcvar, steps: &[PathStep]) -> Rcvar {
+    let mut current = data;
+    for step in steps {
+        match follow_step(current, step) {
+            Some(next) => current = next,
+            None => return Rcvar::new(Variable::Null),
+        }
+    }
+    current.clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn follows_simple_paths() {
+        let data = Rcvar::new(Variable::from_json(r#"{"a": {"b": [1, {"c": true}]}}"#).unwrap());
+        let steps = |expr: &str| path_steps(&crate::parse(expr).unwrap());
+        let c = follow_path(&data, &steps("a.b[-1].c").unwrap());
+        assert_eq!(Variable::Bool(true), *c);
+        let b = follow_path(&data, &steps("@.a | b").unwrap());
+        assert!(Rcvar::ptr_eq(&data.get_field("a").get_field("b"), &b));
+        assert!(Rcvar::ptr_eq(&data, &follow_path(&data, &[])));
+        for missing in &["a.b[5]", "a.b[-3]", "a.x", "a.b.c", "a[0]"] {
+            assert!(follow_path(&data, &steps(missing).unwrap()).is_null());
+        }
+        assert_eq!(None, steps("a[*]"));
+        assert_eq!(None, steps("a || b"));
+    }
+
+    fn search(expr: &str, data: &str) -> SearchResult {
+        crate::compile(expr)?.search(Variable::from_json(data).unwrap())
+    }
+
+    #[test]
+    fn subexpressions_of_null_are_null() {
+        let data = r#"{"a": null, "b": {"c": null}}"#;
+        for expr in &[
+            "a.b",
+            "a.b.c.d",
+            "a[0].b",
+            "a.b[*].c",
+            "a.*.b",
+            "a.b[].c",
+            "a.[b, c]",
+            "a.{b: b}",
+            "a[1:].b",
+            "b.c.d | e",
+            "x.y[?z].w",
+        ] {
+            assert_eq!(Variable::Null, *search(expr, data).unwrap(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn pipes_evaluate_functions_with_null() {
+        let data = r#"{"a": null}"#;
+        let result = search("a | not_null(@, 'x')", data).unwrap();
+        assert_eq!(Some("x"), result.as_str());
+        assert_eq!(
+            Some("null"),
+            search("a.to_string(@)", data).unwrap().as_str()
+        );
+        assert!(search("a.length(@)", data).is_err());
+        assert!(search("a[::0]", data).is_err());
+        assert!(search("a | [::0]", data).is_err());
+    }
+
+    #[test]
+    fn projections_of_mismatched_types_are_null() {
+        let data = r#"{"o": {"a": 1}, "l": [1, 2], "s": "abc", "n": 1, "t": true}"#;
+        for expr in &[
+            "o[*]", "o[]", "o[0]", "o[:1]", "o[?a]", "s[*]", "s[]", "s[0]", "s[::-1]", "n[*].a",
+            "t[]", "l.*", "s.*", "n.*.a", "l.a", "o[*][*]",
+        ] {
+            assert_eq!(Variable::Null, *search(expr, data).unwrap(), "{}", expr);
+        }
+        assert_eq!("[1]", search("o.*", data).unwrap().to_string());
+        assert_eq!("[1,2]", search("l[]", data).unwrap().to_string());
+    }
+
+    #[test]
+    fn repeated_keys_use_the_last_value() {
+        let result = search("{a: x, b: y, a: z}", r#"{"x": 1, "y": 2, "z": 3}"#).unwrap();
+        assert_eq!(Variable::from_json(r#"{"a": 3, "b": 2}"#).unwrap(), *result);
+        #[cfg(feature = "preserve-order")]
+        assert_eq!(r#"{"a":3,"b":2}"#, result.to_string());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_null() {
+        let data = r#"{"a": [{"b": 1}, {"b": 2}, {"b": 3}], "o": {"0": 1}, "s": "abc"}"#;
+        for expr in &[
+            "a[5]",
+            "a[3]",
+            "a[5].b",
+            "a[5].b.c",
+            "a[5][0]",
+            "o[0]",
+            "s[0]",
+            "a[0].b[0]",
+        ] {
+            assert_eq!(Variable::Null, *search(expr, data).unwrap(), "{}", expr);
+        }
+        assert_eq!(Some(3.0), search("a[2].b", data).unwrap().as_number());
+        assert_eq!("[]", search("a[*][5]", data).unwrap().to_string());
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_end() {
+        let data = r#"{"a": [1, 2, 3]}"#;
+        let index = |expr: &str| search(expr, data).unwrap().as_number();
+        assert_eq!(Some(3.0), index("a[-1]"));
+        assert_eq!(Some(1.0), index("a[-3]"));
+        assert_eq!(None, index("a[-4]"));
+        assert_eq!(None, index("a[-2147483648]"));
+        assert_eq!(Some(2.0), index("a | [-2]"));
+        let mut ctx = crate::Context::new("a[-1]", &crate::DEFAULT_RUNTIME);
+        let ast = crate::parse("a[-1]").unwrap();
+        let data = Rcvar::new(Variable::from_json(data).unwrap());
+        assert_eq!(
+            Some(3.0),
+            interpret(&data, &ast, &mut ctx).unwrap().as_number()
+        );
+    }
+
+    #[test]
+    fn flattens_one_level_and_projects() {
+        let data = r#"{"a": [[1, [2]], 3, [[4]], null, {"b": 5}], "c": [[{"d": 1}], [{"d": 2}, {"e": 3}]]}"#;
+        let flatten = |expr: &str| search(expr, data).unwrap().to_string();
+        assert_eq!(r#"[1,[2],3,[4],{"b":5}]"#, flatten("a[]"));
+        assert_eq!(r#"[1,2,3,4,{"b":5}]"#, flatten("a[][]"));
+        assert_eq!("[5]", flatten("a[].b"));
+        assert_eq!("[1,2]", flatten("c[].d"));
+        assert_eq!("[[1],[2]]", flatten("c[*][*].d"));
+        assert_eq!("[]", flatten("c[][0].d"));
+        assert_eq!("[1,2]", flatten("c[*][0].d"));
+        assert_eq!("2", flatten("c[] | [1].d"));
+        let unchanged = Rcvar::new(Variable::from_json("[1, 2]").unwrap());
+        let ast = crate::parse("[]").unwrap();
+        let mut ctx = crate::Context::new("[]", &crate::DEFAULT_RUNTIME);
+        assert!(Rcvar::ptr_eq(
+            &unchanged,
+            &interpret(&unchanged, &ast, &mut ctx).unwrap()
+        ));
+    }
+
+    #[test]
+    fn searches_keys_at_any_depth() {
+        let runtime = crate::Runtime::builder()
+            .parser_options(crate::ParserOptions {
+                recursive_descent: true,
+                ..crate::ParserOptions::default()
+            })
+            .build();
+        let data = r#"{"a": {"b": 1, "c": [{"b": 2}, {"d": {"b": {"b": 3}}}]}, "b": 0}"#;
+        let data = Variable::from_json(data).unwrap();
+        let search = |expr: &str| runtime.search(expr, &data).unwrap().to_string();
+        assert_eq!(r#"[0,1,2,{"b":3},3]"#, search("..b"));
+        assert_eq!(r#"[1,2,{"b":3},3]"#, search("a..b"));
+        assert_eq!("[3]", search("a..b.b"));
+        assert_eq!(r#"[[2],[{"b":3},3]]"#, search("a.c[*]..b"));
+        assert_eq!("5", search("@..b | length(@)"));
+        assert_eq!("[]", search("a..x"));
+        assert_eq!("null", search("x..b"));
+        assert_eq!("null", search("a.b..b"));
+        let expression = runtime.compile("..b").unwrap();
+        let search = |limit: usize| {
+            let options = crate::EvalOptions::new().max_output_elements(limit);
+            expression.search_with_options(&data, &options)
+        };
+        assert!(search(5).is_ok());
+        let error = search(4).unwrap_err();
+        assert_eq!(crate::ErrorKind::LimitExceeded, error.kind);
+    }
+
+    #[test]
+    fn searches_keys_at_any_depth_in_key_order() {
+        use crate::KeyOrder;
+
+        let data = r#"{"c": {"b": 1}, "a": [{"b": 2}], "b": {"b": 3}}"#;
+        let data = Variable::from_json(data).unwrap();
+        let search = |key_order: KeyOrder| {
+            let runtime = crate::Runtime::builder()
+                .parser_options(crate::ParserOptions {
+                    recursive_descent: true,
+                    ..crate::ParserOptions::default()
+                })
+                .key_order(key_order)
+                .build();
+            runtime.search("..b", &data).unwrap().to_string()
+        };
+        let sorted = r#"[{"b":3},2,3,1]"#;
+        assert_eq!(sorted, search(KeyOrder::Lexicographic));
+        #[cfg(not(feature = "preserve-order"))]
+        assert_eq!(sorted, search(KeyOrder::Native));
+        #[cfg(feature = "preserve-order")]
+        {
+            let inserted = r#"[{"b":3},1,2,3]"#;
+            assert_eq!(inserted, search(KeyOrder::Native));
+            assert_eq!(inserted, search(KeyOrder::Insertion));
         }
     }
 }
@@ -0,0 +1,155 @@
+//! Iterator adapters that evaluate expressions on streams of values.
+//!
+//! `JmespathIteratorExt` is implemented for every iterator, so compiled
+//! expressions can be spliced into iterator chains:
+//!
+//! ```
+//! use jmespath::iter::JmespathIteratorExt;
+//! use jmespath::Variable;
+//!
+//! let events = vec![
+//!     Variable::from_json(r#"{"level": "error", "id": 1}"#).unwrap(),
+//!     Variable::from_json(r#"{"level": "info", "id": 2}"#).unwrap(),
+//! ];
+//! let is_error = jmespath::compile("level == 'error'").unwrap();
+//! let id = jmespath::compile("id").unwrap();
+//! let ids = events
+//!     .into_iter()
+//!     .jmes_filter(&is_error)
+//!     .map(|event| id.search(event?).map(|id| id.to_string()))
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! assert_eq!(vec!["1"], ids);
+//! ```
+
+use crate::interpreter::SearchResult;
+use crate::{Expression, JmespathError, ToJmespath};
+
+/// Adapters that evaluate a compiled expression on each item of an
+/// iterator.
+///
+/// An item that fails to evaluate is yielded as an error, and the adapters
+/// carry on with the next item.
+pub trait JmespathIteratorExt: Iterator + Sized {
+    /// Yields the items the expression evaluates to a truthy value on.
+    ///
+    /// The expression is evaluated on a reference to each item, so the
+    /// items are yielded unchanged.
+    fn jmes_filter<'e, 'r>(self, expression: &'e Expression<'r>) -> JmesFilter<'e, 'r, Self>
+    where
+        for<'a> &'a Self::Item: ToJmespath,
+    {
+        JmesFilter {
+            iter: self,
+            expression,
+        }
+    }
+
+    /// Yields the result of evaluating the expression on each item.
+    fn jmes_map<'e, 'r>(self, expression: &'e Expression<'r>) -> JmesMap<'e, 'r, Self>
+    where
+        Self::Item: ToJmespath,
+    {
+        JmesMap {
+            iter: self,
+            expression,
+        }
+    }
+}
+
+impl<I: Iterator> JmespathIteratorExt for I {}
+
+/// Iterator returned by `JmespathIteratorExt::jmes_filter`.
+pub struct JmesFilter<'e, 'r, I> {
+    iter: I,
+    expression: &'e Expression<'r>,
+}
+
+impl<'e, 'r, I> Iterator for JmesFilter<'e, 'r, I>
+where
+    I: Iterator,
+    for<'a> &'a I::Item: ToJmespath,
+{
+    type Item = Result<I::Item, JmespathError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for item in &mut self.iter {
+            match self.expression.search(&item) {
+                Ok(result) if result.is_truthy() => return Some(Ok(item)),
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Iterator returned by `JmespathIteratorExt::jmes_map`.
+pub struct JmesMap<'e, 'r, I> {
+    iter: I,
+    expression: &'e Expression<'r>,
+}
+
+impl<'e, 'r, I> Iterator for JmesMap<'e, 'r, I>
+where
+    I: Iterator,
+    I::Item: ToJmespath,
+{
+    type Item = SearchResult;
+
+    fn next(&mut self) -> Option<SearchResult> {
+        self.iter.next().map(|item| self.expression.search(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Variable;
+
+    fn values(json: &[&str]) -> Vec<Variable> {
+        json.iter()
+            .map(|j| Variable::from_json(j).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn filters_items() {
+        let expr = crate::compile("a > `1`").unwrap();
+        let kept: Vec<Variable> = values(&[r#"{"a": 1}"#, r#"{"a": 2}"#, r#"{"a": 3}"#])
+            .into_iter()
+            .jmes_filter(&expr)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values(&[r#"{"a": 2}"#, r#"{"a": 3}"#]), kept);
+        let expr = crate::compile("length(@) > `1`").unwrap();
+        let results: Vec<_> = values(&["\"abc\"", "1", "\"a\""])
+            .into_iter()
+            .jmes_filter(&expr)
+            .collect();
+        assert_eq!(2, results.len());
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn maps_items() {
+        let expr = crate::compile("a").unwrap();
+        let mapped: Vec<String> = vec![r#"{"a": 1}"#, "[]"]
+            .into_iter()
+            .map(|j| Variable::from_json(j).unwrap())
+            .jmes_map(&expr)
+            .map(|result| result.unwrap().to_string())
+            .collect();
+        assert_eq!(vec!["1", "null"], mapped);
+        assert_eq!((3, Some(3)), [1, 2, 3].iter().jmes_map(&expr).size_hint());
+    }
+}
@@ -8,17 +8,19 @@
 
 use std::collections::VecDeque;
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::CharIndices;
 
 use self::Token::*;
+use crate::ast::SmallString;
 use crate::variable::Variable;
-use crate::{ErrorReason, JmespathError, Rcvar};
+use crate::{ErrorKind, ErrorReason, JmespathError, Rcvar};
 
 /// Represents a lexical token of a JMESPath expression.
 #[derive(Clone, PartialEq, Debug)]
 pub enum Token {
-    Identifier(String),
-    QuotedIdentifier(String),
+    Identifier(SmallString),
+    QuotedIdentifier(SmallString),
     Number(i32),
     Literal(Rcvar),
     Dot,
@@ -48,7 +50,84 @@ pub enum Token {
     Eof,
 }
 
+/// Number of kinds of tokens, which is the length of the tables indexed by
+/// `Token::kind`.
+pub const TOKEN_KINDS: usize = 29;
+
+/// Left binding power of each kind of token, indexed by `Token::kind`.
+const BINDING_POWERS: [usize; TOKEN_KINDS] = [
+    0,  // Identifier
+    0,  // QuotedIdentifier
+    0,  // Number
+    0,  // Literal
+    40, // Dot
+    20, // Star
+    9,  // Flatten
+    3,  // And
+    2,  // Or
+    1,  // Pipe
+    21, // Filter
+    55, // Lbracket
+    0,  // Rbracket
+    0,  // Comma
+    0,  // Colon
+    45, // Not
+    5,  // Ne
+    5,  // Eq
+    5,  // Gt
+    5,  // Gte
+    5,  // Lt
+    5,  // Lte
+    0,  // At
+    0,  // Ampersand
+    60, // Lparen
+    0,  // Rparen
+    50, // Lbrace
+    0,  // Rbrace
+    0,  // Eof
+];
+
 impl Token {
+    /// Returns the kind of the token, which is its position in the
+    /// declaration of `Token`, regardless of its value.
+    ///
+    /// Tables with an entry for each kind of token are indexed by kind, so
+    /// adding a token means adding an entry to each table in this order.
+    #[inline]
+    pub fn kind(&self) -> usize {
+        match *self {
+            Identifier(_) => 0,
+            QuotedIdentifier(_) => 1,
+            Number(_) => 2,
+            Literal(_) => 3,
+            Dot => 4,
+            Star => 5,
+            Flatten => 6,
+            And => 7,
+            Or => 8,
+            Pipe => 9,
+            Filter => 10,
+            Lbracket => 11,
+            Rbracket => 12,
+            Comma => 13,
+            Colon => 14,
+            Not => 15,
+            Ne => 16,
+            Eq => 17,
+            Gt => 18,
+            Gte => 19,
+            Lt => 20,
+            Lte => 21,
+            At => 22,
+            Ampersand => 23,
+            Lparen => 24,
+            Rparen => 25,
+            Lbrace => 26,
+            Rbrace => 27,
+            Eof => 28,
+        }
+    }
+
     /// Provides the left binding power of the token.
     ///
     /// This is used in the parser to determine whether or not
@@ -56,26 +135,7 @@ impl Token {
     /// by consuming a token.
     #[inline]
     pub fn lbp(&self) -> usize {
-        match *self {
-            Pipe => 1,
-            Or => 2,
-            And => 3,
-            Eq => 5,
-            Gt => 5,
-            Lt => 5,
-            Gte => 5,
-            Lte => 5,
-            Ne => 5,
-            Flatten => 9,
-            Star => 20,
-            Filter => 21,
-            Dot => 40,
-            Not => 45,
-            Lbrace => 50,
-            Lbracket => 55,
-            Lparen => 60,
-            _ => 0,
-        }
+        BINDING_POWERS[self.kind()]
     }
 }
 
@@ -83,13 +143,28 @@ impl Token {
 pub type TokenTuple = (usize, Token);
 
 /// Tokenizes a JMESPath expression.
+#[cfg_attr(not(feature = "completion"), allow(dead_code))]
 pub fn tokenize(expr: &str) -> Result<VecDeque<TokenTuple>, JmespathError> {
     Lexer::new(expr).tokenize()
 }
 
+/// Tokenizes a JMESPath expression, returning each token along with the
+/// byte range of its raw text in the expression.
+pub fn tokenize_spans(expr: &str) -> Result<Vec<(Range<usize>, Token)>, JmespathError> {
+    let mut lexer = Lexer::new(expr);
+    let tokens = lexer.tokenize()?;
+    Ok(tokens
+        .into_iter()
+        .zip(lexer.ends)
+        .map(|((start, token), end)| (start..end, token))
+        .collect())
+}
+
 struct Lexer<'a> {
     iter: Peekable<CharIndices<'a>>,
     expr: &'a str,
+    /// The end position of each token that has been consumed.
+    ends: Vec<usize>,
 }
 
 impl<'a> Lexer<'a> {
@@ -97,6 +172,7 @@ impl<'a> Lexer<'a> {
         Lexer {
             iter: expr.char_indices().peekable(),
             expr,
+            ends: vec![],
         }
     }
 
@@ -108,7 +184,7 @@ impl<'a> Lexer<'a> {
                 Some((pos, ch)) => {
                     match ch {
                         'a'..='z' | 'A'..='Z' | '_' => {
-                            tokens.push_back((pos, self.consume_identifier(ch)))
+                            tokens.push_back((pos, self.consume_identifier(pos)))
                         }
                         '.' => tokens.push_back((pos, Dot)),
                         '[' => tokens.push_back((pos, self.consume_lbracket())),
@@ -127,11 +203,13 @@ impl<'a> Lexer<'a> {
                         '\'' => tokens.push_back((pos, self.consume_raw_string(pos)?)),
                         '`' => tokens.push_back((pos, self.consume_literal(pos)?)),
                         '=' => match self.iter.next() {
-                            Some((_, c)) if c == '=' => tokens.push_back((pos, Eq)),
+                            Some((_, '=')) => tokens.push_back((pos, Eq)),
                             _ => {
                                 let message = "'=' is not valid. Did you mean '=='?";
                                 let reason = ErrorReason::Parse(message.to_owned());
-                                return Err(JmespathError::new(self.expr, pos, reason));
+                                return Err(JmespathError::new(self.expr, pos, reason)
+                                    .with_kind(ErrorKind::InvalidCharacter)
+                                    .with_length(1));
                             }
                         },
                         '>' => tokens.push_back((pos, self.alt('=', Gte, Gt))),
@@ -143,18 +221,32 @@ impl<'a> Lexer<'a> {
                         ' ' | '\n' | '\t' | '\r' => {}
                         c => {
                             let reason = ErrorReason::Parse(format!("Invalid character: {}", c));
-                            return Err(JmespathError::new(self.expr, pos, reason));
+                            return Err(JmespathError::new(self.expr, pos, reason)
+                                .with_kind(ErrorKind::InvalidCharacter)
+                                .with_length(c.len_utf8()));
                         }
                     }
+                    if tokens.len() > self.ends.len() {
+                        let end = self.iter.peek().map_or(last_position, |&(end, _)| end);
+                        self.ends.push(end);
+                    }
                 }
                 None => {
                     tokens.push_back((last_position, Eof));
+                    self.ends.push(last_position);
                     return Ok(tokens);
                 }
             }
         }
     }
 
+    // Returns the offset of the next character, or the end of the expression.
+    #[inline]
+    fn position(&mut self) -> usize {
+        let len = self.expr.len();
+        self.iter.peek().map_or(len, |&(pos, _)| pos)
+    }
+
     // Consumes characters while the predicate function returns true.
     #[inline]
     fn consume_while<F>(&mut self, mut buffer: String, predicate: F) -> String
@@ -192,26 +284,42 @@ impl<'a> Lexer<'a> {
 
     // Consume identifiers: ( ALPHA / "_" ) *( DIGIT / ALPHA / "_" )
     #[inline]
-    fn consume_identifier(&mut self, first_char: char) -> Token {
-        Identifier(self.consume_while(
-            first_char.to_string(),
-            |c| matches!(c, 'a'..='z' | '_' | 'A'..='Z' | '0'..='9'),
-        ))
+    fn consume_identifier(&mut self, pos: usize) -> Token {
+        // Identifiers are ASCII, so the end is the offset of the first
+        // character that is not part of it.
+        let mut end = pos + 1;
+        while let Some(&(offset, c)) = self.iter.peek() {
+            if !matches!(c, 'a'..='z' | '_' | 'A'..='Z' | '0'..='9') {
+                break;
+            }
+            end = offset + 1;
+            self.iter.next();
+        }
+        Identifier(SmallString::from(&self.expr[pos..end]))
     }
 
     // Consumes numbers: *"-" "0" / ( %x31-39 *DIGIT )
     #[inline]
-    fn consume_number(&mut self, pos: usize, first_char: char, is_negative: bool) -> Result<Token, JmespathError> {
-        let lexeme = self.consume_while(first_char.to_string(), |c| c.is_digit(10));
+    fn consume_number(
+        &mut self,
+        pos: usize,
+        first_char: char,
+        is_negative: bool,
+    ) -> Result<Token, JmespathError> {
+        let first = if is_negative {
+            format!("-{}", first_char)
+        } else {
+            first_char.to_string()
+        };
+        // The sign is parsed with the digits, so that `-2147483648` fits.
+        let lexeme = self.consume_while(first, |c| c.is_ascii_digit());
         let numeric_value: i32 = lexeme.parse().map_err(|_| {
             let reason = ErrorReason::Parse("Expected valid number".to_owned());
             JmespathError::new(self.expr, pos, reason)
+                .with_kind(ErrorKind::InvalidLiteral)
+                .with_length(self.position() - pos)
         })?;
-        Ok(if is_negative {
-            Number(-numeric_value)
-        } else {
-            Number(numeric_value)
-        })
+        Ok(Number(numeric_value))
     }
 
     // Consumes a negative number
@@ -222,7 +330,9 @@ impl<'a> Lexer<'a> {
             Some((_, c)) if c.is_numeric() && c != '0' => Ok(self.consume_number(pos, c, true)?),
             _ => {
                 let reason = ErrorReason::Parse("'-' must be followed by numbers 1-9".to_owned());
-                Err(JmespathError::new(self.expr, pos, reason))
+                Err(JmespathError::new(self.expr, pos, reason)
+                    .with_kind(ErrorKind::InvalidLiteral)
+                    .with_length(self.position() - pos))
             }
         }
     }
@@ -242,8 +352,11 @@ impl<'a> Lexer<'a> {
         let mut buffer = String::new();
         while let Some((_, c)) = self.iter.next() {
             if c == wrapper {
-                return invoke(buffer)
-                    .map_err(|e| JmespathError::new(self.expr, pos, ErrorReason::Parse(e)));
+                return invoke(buffer).map_err(|e| {
+                    JmespathError::new(self.expr, pos, ErrorReason::Parse(e))
+                        .with_kind(ErrorKind::InvalidLiteral)
+                        .with_length(self.position() - pos)
+                });
             } else if c == '\\' {
                 buffer.push(c);
                 if let Some((_, c)) = self.iter.next() {
@@ -256,11 +369,11 @@ impl<'a> Lexer<'a> {
         // The token was not closed, so error with the string, including the
         // wrapper (e.g., '"foo').
         let message = format!("Unclosed {} delimiter: {}{}", wrapper, wrapper, buffer);
-        Err(JmespathError::new(
-            self.expr,
-            pos,
-            ErrorReason::Parse(message),
-        ))
+        Err(
+            JmespathError::new(self.expr, pos, ErrorReason::Parse(message))
+                .with_kind(ErrorKind::UnclosedDelimiter)
+                .with_length(self.expr.len() - pos),
+        )
     }
 
     // Consume and parse a quoted identifier token.
@@ -270,9 +383,11 @@ impl<'a> Lexer<'a> {
             // JSON decode the string to expand escapes
             match Variable::from_json(format!(r##""{}""##, s).as_ref()) {
                 // Convert the JSON value into a string literal.
-                Ok(j) => Ok(QuotedIdentifier(j.as_string().cloned().ok_or_else(
-                    || "consume_quoted_identifier expected a string".to_owned(),
-                )?)),
+                Ok(j) => Ok(QuotedIdentifier(SmallString::from(
+                    j.as_string()
+                        .ok_or_else(|| "consume_quoted_identifier expected a string".to_owned())?
+                        .as_str(),
+                ))),
                 Err(e) => Err(format!("Unable to parse quoted identifier {}: {}", s, e)),
             }
         })
@@ -407,15 +522,15 @@ mod tests {
     fn tokenize_identifier_test() {
         assert_eq!(
             tokenize_queue("foo_bar"),
-            vec![(0, Identifier("foo_bar".to_string())), (7, Eof)]
+            vec![(0, Identifier("foo_bar".into())), (7, Eof)]
         );
         assert_eq!(
             tokenize_queue("a"),
-            vec![(0, Identifier("a".to_string())), (1, Eof)]
+            vec![(0, Identifier("a".into())), (1, Eof)]
         );
         assert_eq!(
             tokenize_queue("_a"),
-            vec![(0, Identifier("_a".to_string())), (2, Eof)]
+            vec![(0, Identifier("_a".into())), (2, Eof)]
         );
     }
 
@@ -423,23 +538,23 @@ mod tests {
     fn tokenize_quoted_identifier_test() {
         assert_eq!(
             tokenize_queue("\"foo\""),
-            vec![(0, QuotedIdentifier("foo".to_string())), (5, Eof)]
+            vec![(0, QuotedIdentifier("foo".into())), (5, Eof)]
         );
         assert_eq!(
             tokenize_queue("\"\""),
-            vec![(0, QuotedIdentifier("".to_string())), (2, Eof)]
+            vec![(0, QuotedIdentifier("".into())), (2, Eof)]
         );
         assert_eq!(
             tokenize_queue("\"a_b\""),
-            vec![(0, QuotedIdentifier("a_b".to_string())), (5, Eof)]
+            vec![(0, QuotedIdentifier("a_b".into())), (5, Eof)]
         );
         assert_eq!(
             tokenize_queue("\"a\\nb\""),
-            vec![(0, QuotedIdentifier("a\nb".to_string())), (6, Eof)]
+            vec![(0, QuotedIdentifier("a\nb".into())), (6, Eof)]
         );
         assert_eq!(
             tokenize_queue("\"a\\\\nb\""),
-            vec![(0, QuotedIdentifier("a\\nb".to_string())), (7, Eof)]
+            vec![(0, QuotedIdentifier("a\\nb".into())), (7, Eof)]
         );
     }
 
@@ -515,9 +630,9 @@ mod tests {
     fn tokenize_successive_test() {
         let expr = "foo.bar || `\"a\"` | 10";
         let tokens = tokenize_queue(expr);
-        assert_eq!(tokens[0], (0, Identifier("foo".to_string())));
+        assert_eq!(tokens[0], (0, Identifier("foo".into())));
         assert_eq!(tokens[1], (3, Dot));
-        assert_eq!(tokens[2], (4, Identifier("bar".to_string())));
+        assert_eq!(tokens[2], (4, Identifier("bar".into())));
         assert_eq!(tokens[3], (8, Or));
         assert_eq!(
             tokens[4],
@@ -537,4 +652,70 @@ mod tests {
             format!("{:?}", tokens)
         );
     }
+
+    #[test]
+    fn tokenizes_with_spans() {
+        let spans: Vec<_> = tokenize_spans("foo[?\"b\" != `[1]`] || 'x'")
+            .unwrap()
+            .into_iter()
+            .map(|(span, _)| span)
+            .collect();
+        assert_eq!(
+            vec![
+                0..3,
+                3..5,
+                5..8,
+                9..11,
+                12..17,
+                17..18,
+                19..21,
+                22..25,
+                25..25
+            ],
+            spans
+        );
+    }
+
+    #[test]
+    fn numbers_token_kinds_in_declaration_order() {
+        let tokens = [
+            Identifier("a".into()),
+            QuotedIdentifier("a".into()),
+            Number(1),
+            Literal(Rcvar::new(Variable::Null)),
+            Dot,
+            Star,
+            Flatten,
+            And,
+            Or,
+            Pipe,
+            Filter,
+            Lbracket,
+            Rbracket,
+            Comma,
+            Colon,
+            Not,
+            Ne,
+            Eq,
+            Gt,
+            Gte,
+            Lt,
+            Lte,
+            At,
+            Ampersand,
+            Lparen,
+            Rparen,
+            Lbrace,
+            Rbrace,
+            Eof,
+        ];
+        assert_eq!(TOKEN_KINDS, tokens.len());
+        for (i, token) in tokens.iter().enumerate() {
+            assert_eq!(i, token.kind(), "{:?}", token);
+        }
+        assert_eq!(1, Pipe.lbp());
+        assert_eq!(5, Lte.lbp());
+        assert_eq!(60, Lparen.lbp());
+        assert_eq!(0, Identifier("a".into()).lbp());
+    }
 }
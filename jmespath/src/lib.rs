@@ -30,7 +30,7 @@
 //!
 //! let expr = jmespath::compile("foo").unwrap();
 //! assert_eq!("foo", expr.as_str());
-//! assert_eq!(&Ast::Field {name: "foo".to_string(), offset: 0}, expr.as_ast());
+//! assert_eq!(&Ast::Field {name: "foo".into(), offset: 0}, expr.as_ast());
 //! ```
 //!
 //! ## JMESPath variables
@@ -45,6 +45,56 @@
 //! the `sync` feature, you can utilize an `std::sync::Arc<Variable>` to
 //! share `Expression` structs across threads.
 //!
+//! Objects are stored in a `jmespath::Map`, which is a `BTreeMap` that
+//! iterates over keys in sorted order. With the `preserve-order` feature,
+//! it is a `jmespath::map::OrderedMap` instead, and objects keep the order
+//! of their keys: objects parsed from JSON keep the order of the document,
+//! and multi-hash expressions like `{b: foo, a: bar}` produce keys in the
+//! order they are written. Object equality never depends on key order.
+//! The keys of the objects an evaluation visits and builds, through
+//! projections, `keys()`, `values()`, `merge()` and multi-hashes, are in
+//! the order of the map, unless `RuntimeBuilder::key_order` selects
+//! another `KeyOrder`. With `preserve-order` and the default key order,
+//! results keep the order of the input from end to end.
+//!
+//! Numbers are stored like a `serde_json::Number`, as an i64, a u64 or an
+//! f64, so integers like IDs beyond 2^53 survive a search bit for bit.
+//! Comparisons of integers are exact, and `abs`, `ceil`, `floor`, `sum`
+//! and the `sum_by` extension keep integer results as integers while they
+//! fit in an i64 or a u64. `Variable::as_i64` and `Variable::as_u64` read
+//! them without going through `as_number`, which returns an f64.
+//!
+//! Numbers are serialized the same way on every platform, by `to_string()`
+//! and by JSON serializers. Floats without a fractional part that fit in a
+//! 64-bit integer are written as integers, so `1.0` is written `1`. Other
+//! floats are written as the shortest digits that parse back to the same
+//! float, like `0.1`, with an exponent below 1e-5, like `1.5e-7`, and for
+//! integral floats beyond the 64-bit range, like `1e+20`.
+//!
+//! With the `bignum` feature, numbers keep the digits they are written
+//! with instead of being read as floats, so an amount like
+//! `170141183460469231731687303715884105727` survives a search unchanged.
+//! Comparisons, and the `abs`, `avg`, `ceil`, `floor` and `sum` functions,
+//! then work on the exact decimals, and results are written with their
+//! digits, like `1.5` for ``abs(`-1.50`)``.
+//!
+//! With the `bytes` feature, `Variable::Bytes` holds the binary values that
+//! formats like CBOR or MessagePack deserialize with `visit_bytes`, and
+//! that types serialize with `serialize_bytes`. `length()` counts their
+//! bytes, slices like `[1:3]` select some of them, and `type()` returns
+//! `bytes`. Cargo enables the feature for every crate of a build that uses
+//! `jmespath` when one of them does, so code that matches on `Variable`
+//! needs a `_` arm to keep compiling when the variant appears. `Variable`
+//! will be `#[non_exhaustive]` in the next breaking release.
+//!
+//! With the `datetime` feature, `Variable::Timestamp` holds instants that
+//! `to_timestamp()` parses from RFC 3339 strings or Unix times, in runtimes
+//! built with `RuntimeBuilder::datetime`. Timestamps can be ordered with
+//! `<` and `>`, unlike strings, so
+//! `[?to_timestamp(created) > to_timestamp('2024-01-01')]` filters by date.
+//! See `jmespath::timestamp`. The variant is added like `Variable::Bytes`,
+//! for every crate of the build, so the same `_` arm covers it.
+//!
 //! Any type that implements `jmespath::ToJmespath` can be used in a JMESPath
 //! Expression. Various types have default `ToJmespath` implementations,
 //! including `serde::ser::Serialize`. Because `jmespath::Variable` implements
@@ -58,6 +108,14 @@
 //! can be used directly, or you can convert `Variable` to any serde value
 //! implementing `serde::de::Deserialize`.
 //!
+//! Results share the subtrees they copy from the input instead of cloning
+//! them: a projection or a multi-select like `{a: user, b: user}` holds the
+//! same `Rcvar` in every position it copies `user` to, so fan-out
+//! transformations don't multiply the memory of the input.
+//! `Expression::search_shared` searches an `Rcvar` without copying it, so
+//! results also share their subtrees with the input, and
+//! `Variable::deep_clone` copies a result that shares none of its values.
+//!
 //! # Custom Functions
 //!
 //! You can register custom functions with a JMESPath expression by using
@@ -68,6 +126,8 @@
 //! `Runtime` and compiling expressions directly from the `Runtime`.
 //!
 //! ```
+//! use std::sync::Arc;
+//!
 //! use jmespath::{Runtime, Context, Rcvar};
 //! use jmespath::functions::{CustomFunction, Signature, ArgumentType};
 //!
@@ -76,14 +136,14 @@
 //! runtime.register_builtin_functions();
 //!
 //! // Create an identity string function that returns string values as-is.
-//! runtime.register_function("str_identity", Box::new(CustomFunction::new(
+//! runtime.register_function("str_identity", Arc::new(CustomFunction::new(
 //!     Signature::new(vec![ArgumentType::String], None),
 //!     Box::new(|args: &[Rcvar], _: &mut Context| Ok(args[0].clone()))
 //! )));
 //!
 //! // You can also use normal closures as functions.
 //! runtime.register_function("identity",
-//!     Box::new(|args: &[Rcvar], _: &mut Context| Ok(args[0].clone())));
+//!     Arc::new(|args: &[Rcvar], _: &mut Context| Ok(args[0].clone())));
 //!
 //! let expr = runtime.compile("str_identity('foo')").unwrap();
 //! assert_eq!("foo", expr.search(()).unwrap().as_string().unwrap());
@@ -94,39 +154,120 @@
 
 #![cfg_attr(feature = "specialized", feature(specialization))]
 
-pub use crate::errors::{ErrorReason, JmespathError, RuntimeError};
-pub use crate::parser::{parse, ParseResult};
-pub use crate::runtime::Runtime;
-pub use crate::variable::Variable;
+pub use crate::errors::{ErrorKind, ErrorReason, JmespathError, RuntimeError};
+pub use crate::parser::{
+    parse, parse_with_options, parse_with_warnings, ParseResult, ParseWarning, WarningKind,
+};
+pub use crate::runtime::{KeyOrder, Limits, ParserOptions, Profile, Runtime, RuntimeBuilder};
+pub use crate::variable::{to_value, Variable};
 
 pub mod ast;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "completion")]
+pub mod completion;
+#[cfg(feature = "compliance")]
+pub mod compliance;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(feature = "expression-set")]
+pub mod expression_set;
+#[cfg(feature = "formatter")]
+pub mod formatter;
 pub mod functions;
+#[cfg(feature = "iter")]
+pub mod iter;
+#[cfg(feature = "lint")]
+pub mod lint;
+pub mod map;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod prelude;
+#[cfg(feature = "profiler")]
+pub mod profile;
+#[cfg(feature = "query")]
+pub mod query;
+#[cfg(feature = "compliance")]
+pub mod regression;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "template")]
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "datetime")]
+pub mod timestamp;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+#[cfg(feature = "visitor")]
+pub mod visitor;
 
 use serde::ser;
 #[cfg(feature = "specialized")]
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 #[cfg(feature = "specialized")]
 use std::convert::TryInto;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic;
+use std::str::FromStr;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
 use lazy_static::*;
 
 use crate::ast::Ast;
-use crate::interpreter::{interpret, SearchResult};
+#[cfg(feature = "debugger")]
+use crate::debugger::{Debugger, Session};
+use crate::interpreter::{
+    follow_path, follow_step, interpret, interpret_count, interpret_page, interpret_truthy,
+    path_steps, OutputBudget, Page, PathStep, SearchResult,
+};
 
+#[cfg(feature = "bignum")]
+mod decimal;
 mod errors;
 mod interpreter;
 mod lexer;
 mod parser;
 mod runtime;
+mod small_string;
+// The runtime checks the limits of expressions with the statistics of
+// their AST, which the visitor computes, so both modules are built without
+// their features, only not exported, and their examples are ignored.
+#[cfg(not(feature = "stats"))]
+#[allow(dead_code)]
+mod stats;
 mod variable;
+#[cfg(not(feature = "visitor"))]
+#[allow(dead_code)]
+mod visitor;
 
 lazy_static! {
-    pub static ref DEFAULT_RUNTIME: Runtime = {
-        let mut runtime = Runtime::new();
-        runtime.register_builtin_functions();
-        runtime
-    };
+    /// The Runtime used by `compile` and `search`, with the built-in
+    /// functions and no limits. It is created the first time it is used.
+    pub static ref DEFAULT_RUNTIME: Runtime = RuntimeBuilder::new().build();
+}
+
+/// Returns the default Runtime, creating it if it is not created yet.
+///
+/// ```
+/// let runtime = jmespath::default_runtime();
+/// assert!(runtime.get_function("sort_by").is_some());
+/// assert_eq!(&jmespath::Limits::default(), runtime.limits());
+/// ```
+pub fn default_runtime() -> &'static Runtime {
+    &DEFAULT_RUNTIME
 }
 
 /// `Rc` reference counted JMESPath `Variable`.
@@ -136,6 +277,17 @@ pub type Rcvar = std::rc::Rc<Variable>;
 #[cfg(feature = "sync")]
 pub type Rcvar = std::sync::Arc<Variable>;
 
+/// Map of an object `Variable`, which iterates in key order.
+#[cfg(not(feature = "preserve-order"))]
+pub type Map = std::collections::BTreeMap<String, Rcvar>;
+/// Map of an object `Variable`, which iterates in insertion order.
+#[cfg(feature = "preserve-order")]
+pub type Map = crate::map::OrderedMap;
+
+/// Result of a JMESPath operation, which fails with a `JmespathError`
+/// unless another error type is given.
+pub type Result<T, E = JmespathError> = std::result::Result<T, E>;
+
 /// Compiles a JMESPath expression using the default Runtime.
 ///
 /// The default Runtime is created lazily the first time it is dereferenced
@@ -148,6 +300,62 @@ pub fn compile(expression: &str) -> Result<Expression<'static>, JmespathError> {
     DEFAULT_RUNTIME.compile(expression)
 }
 
+/// Compiles and searches data with an expression using the default Runtime.
+///
+/// ```
+/// let data = jmespath::Variable::from_json(r#"{"foo": [1, 2]}"#).unwrap();
+/// let result = jmespath::search("length(foo)", data).unwrap();
+/// assert_eq!(2.0, result.as_number().unwrap());
+/// ```
+pub fn search<T: ToJmespath>(expression: &str, data: T) -> SearchResult {
+    compile(expression)?.search(data)
+}
+
+/// Compiles and searches data with an expression using the default Runtime,
+/// converting panics into errors.
+///
+/// Parsing and evaluation are not expected to panic, but servers that
+/// evaluate untrusted expressions can use this function so that a bug
+/// triggered by one expression does not unwind through the caller. A panic
+/// is returned as an `ErrorReason::Internal` error with the panic message.
+/// The panic hook still runs, so the panic is reported as usual.
+///
+/// ```
+/// let result = jmespath::safe_search("foo.bar", jmespath::Variable::from_json(r#"{"foo": {"bar": 1}}"#).unwrap());
+/// assert_eq!(1.0, result.unwrap().as_number().unwrap());
+/// ```
+pub fn safe_search<T: ToJmespath>(expression: &str, data: T) -> SearchResult {
+    safe_search_with_runtime(&DEFAULT_RUNTIME, expression, data)
+}
+
+/// Compiles and searches data with an expression using the given Runtime,
+/// converting panics, including panics of custom functions, into errors.
+pub fn safe_search_with_runtime<T: ToJmespath>(
+    runtime: &Runtime,
+    expression: &str,
+    data: T,
+) -> SearchResult {
+    // The runtime is only read and the data is consumed, so no state that
+    // was being modified can be observed after a panic.
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        runtime.compile(expression)?.search(data)
+    }));
+    result.unwrap_or_else(|payload| {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast_ref::<&str>() {
+                Some(message) => (*message).to_owned(),
+                None => "unknown panic".to_owned(),
+            },
+        };
+        Err(JmespathError::new(
+            expression,
+            0,
+            ErrorReason::Internal(message),
+        ))
+    })
+}
+
 /// Converts a value into a reference-counted JMESPath Variable.
 ///
 #[cfg_attr(
@@ -175,7 +383,7 @@ pub trait ToJmespath {
 }
 
 /// Create searchable values from Serde serializable values.
-impl<'a, T: ser::Serialize> ToJmespath for T {
+impl<T: ser::Serialize> ToJmespath for T {
     #[cfg(not(feature = "specialized"))]
     fn to_jmespath(self) -> Result<Rcvar, JmespathError> {
         Variable::from_serializable(self).map(Rcvar::new)
@@ -363,11 +571,30 @@ impl ToJmespath for bool {
 /// be shared between threads if JMESPath is compiled with the `sync`
 /// feature, which forces the use of an `Arc` instead of an `Rc` for
 /// runtime variables.
+///
+/// The AST and text of the expression are reference counted, so cloning an
+/// expression is cheap.
 #[derive(Clone)]
 pub struct Expression<'a> {
+    compiled: Shared<Compiled>,
+    runtime: &'a Runtime,
+}
+
+/// `Rc` or, with the `sync` feature, `Arc`.
+#[cfg(not(feature = "sync"))]
+type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+type Shared<T> = std::sync::Arc<T>;
+
+/// The parts of an `Expression` that are shared between its clones.
+#[derive(Clone)]
+struct Compiled {
     ast: Ast,
     expression: String,
-    runtime: &'a Runtime,
+    warnings: Vec<ParseWarning>,
+    /// Steps of the expression if it is a simple path, which is evaluated
+    /// without the interpreter.
+    path: Option<Vec<PathStep>>,
 }
 
 impl<'a> Expression<'a> {
@@ -381,21 +608,373 @@ impl<'a> Expression<'a> {
         S: Into<String>,
     {
         Expression {
-            expression: expression.into(),
-            ast,
+            compiled: Shared::new(Compiled {
+                expression: expression.into(),
+                path: path_steps(&ast),
+                ast,
+                warnings: vec![],
+            }),
             runtime,
         }
     }
 
+    /// Sets the warnings found while parsing the expression.
+    pub fn with_warnings(mut self, warnings: Vec<ParseWarning>) -> Expression<'a> {
+        Shared::make_mut(&mut self.compiled).warnings = warnings;
+        self
+    }
+
     /// Returns the result of searching data with the compiled expression.
     ///
     /// The SearchResult contains a JMESPath Rcvar, or a reference counted
     /// Variable. This value can be used directly like a JSON object.
     /// Alternatively, Variable does implement Serde serialzation and
     /// deserialization, so it can easily be marshalled to another type.
+    ///
+    /// If the Runtime has `Metrics` hooks, the evaluation is reported to them.
     pub fn search<T: ToJmespath>(&self, data: T) -> SearchResult {
-        let mut ctx = Context::new(&self.expression, self.runtime);
-        interpret(&data.to_jmespath()?, &self.ast, &mut ctx)
+        self.search_with_options(data, &EvalOptions::default())
+    }
+
+    /// Searches data with the compiled expression like `search`, with
+    /// options for this evaluation only.
+    ///
+    /// The context is read by functions, like the `ctx()` extension
+    /// function or this `user` function:
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use jmespath::{Context, EvalOptions, Rcvar, Runtime, Variable};
+    ///
+    /// let user = |_: &[Rcvar], ctx: &mut Context<'_>| {
+    ///     Ok(ctx.context.cloned().unwrap_or_else(|| Rcvar::new(Variable::Null)))
+    /// };
+    /// let runtime = Runtime::builder().function("user", Arc::new(user)).build();
+    /// let expr = runtime.compile("items[?owner == user().user_id].name").unwrap();
+    /// let data = Variable::from_json(r#"{"items": [{"owner": 1, "name": "a"}, {"owner": 2, "name": "b"}]}"#).unwrap();
+    /// let context = Variable::from_json(r#"{"user_id": 2}"#).unwrap();
+    /// let options = EvalOptions::new().context(Rcvar::new(context));
+    /// let result = expr.search_with_options(data, &options).unwrap();
+    /// assert_eq!(r#"["b"]"#, result.to_string());
+    /// ```
+    pub fn search_with_options<T: ToJmespath>(
+        &self,
+        data: T,
+        options: &EvalOptions,
+    ) -> SearchResult {
+        self.measure(|| self.evaluate(data, options), Rcvar::clone)
+    }
+
+    /// Searches shared data with the compiled expression like `search`,
+    /// without copying it first.
+    ///
+    /// Without the `specialized` feature, `search` converts its input to a
+    /// new `Variable` even if it is already an `Rcvar`. The result of
+    /// `search_shared` holds the subtrees of `data` itself instead, so it
+    /// takes no memory for the values it copies from the input.
+    ///
+    /// ```
+    /// use jmespath::{Rcvar, Variable};
+    ///
+    /// let data = Rcvar::new(Variable::from_json(r#"{"user": {"name": "a"}}"#).unwrap());
+    /// let expr = jmespath::compile("[user, user]").unwrap();
+    /// let result = expr.search_shared(&data).unwrap();
+    /// assert!(Rcvar::ptr_eq(&data.get_field("user"), &result.get_index(1)));
+    /// ```
+    pub fn search_shared(&self, data: &Rcvar) -> SearchResult {
+        let options = EvalOptions::default();
+        self.measure(|| self.evaluate_shared(data, &options), Rcvar::clone)
+    }
+
+    /// Runs an evaluation, reporting it to the `Metrics` of the Runtime
+    /// with its result converted by `value`.
+    #[cfg(feature = "metrics")]
+    fn measure<R, F>(&self, evaluate: F, value: fn(&R) -> Rcvar) -> Result<R, JmespathError>
+    where
+        F: FnOnce() -> Result<R, JmespathError>,
+    {
+        match self.runtime.metrics() {
+            None => evaluate(),
+            Some(metrics) => {
+                let start = Instant::now();
+                let result = evaluate();
+                let reported = result.as_ref().map(value);
+                let reported = reported.as_ref().map_err(|e| *e);
+                metrics.on_eval(self.as_str(), start.elapsed(), reported);
+                result
+            }
+        }
+    }
+
+    /// Runs an evaluation, which isn't reported without the `metrics`
+    /// feature.
+    #[cfg(not(feature = "metrics"))]
+    #[inline]
+    fn measure<R, F>(&self, evaluate: F, _: fn(&R) -> Rcvar) -> Result<R, JmespathError>
+    where
+        F: FnOnce() -> Result<R, JmespathError>,
+    {
+        evaluate()
+    }
+
+    /// Searches data with the compiled expression, binding the values of
+    /// its parameters, like `:min_age` in `people[?age > :min_age]`.
+    ///
+    /// Parameters keep values out of the expression text, so an expression
+    /// compiled once can be searched with values taken from user input
+    /// without quoting them into a literal. A parameter without a value is
+    /// a `RuntimeError::UnboundParameter` error when it is evaluated.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use jmespath::{Rcvar, Variable};
+    ///
+    /// let expr = jmespath::compile("people[?age > :min_age].name").unwrap();
+    /// let data = Variable::from_json(r#"{"people": [{"name": "a", "age": 50}, {"name": "b", "age": 30}]}"#).unwrap();
+    /// let mut params = BTreeMap::new();
+    /// params.insert("min_age".to_owned(), Rcvar::new(Variable::from(40)));
+    /// let result = expr.search_with_params(&data, &params).unwrap();
+    /// assert_eq!(r#"["a"]"#, result.to_string());
+    /// ```
+    pub fn search_with_params<T: ToJmespath>(
+        &self,
+        data: T,
+        params: &BTreeMap<String, Rcvar>,
+    ) -> SearchResult {
+        let options = EvalOptions {
+            params: params.clone(),
+            ..EvalOptions::default()
+        };
+        self.search_with_options(data, &options)
+    }
+
+    fn evaluate<T: ToJmespath>(&self, data: T, options: &EvalOptions) -> SearchResult {
+        self.evaluate_shared(&data.to_jmespath()?, options)
+    }
+
+    fn evaluate_shared(&self, data: &Rcvar, options: &EvalOptions) -> SearchResult {
+        let mut ctx = self.context(options);
+        match options.page {
+            Some(page) => interpret_page(data, &self.compiled.ast, page, &mut ctx),
+            None => match self.compiled.path {
+                Some(ref steps) => Ok(follow_path(data, steps)),
+                None => interpret(data, &self.compiled.ast, &mut ctx),
+            },
+        }
+    }
+
+    /// Creates the context of an evaluation with options, besides its page.
+    fn context<'b>(&'b self, options: &'b EvalOptions) -> Context<'b> {
+        let mut ctx = Context::new(&self.compiled.expression, self.runtime);
+        ctx.context = options.context.as_ref();
+        ctx.params = Some(&options.params);
+        ctx.output = options
+            .max_output_elements
+            .map(|limit| OutputBudget { limit, used: 0 });
+        ctx.non_finite = options.non_finite;
+        ctx.redact = options.redact;
+        ctx
+    }
+
+    /// Returns the first element of the result of searching data with the
+    /// compiled expression, or null if the result is not an array or is
+    /// empty.
+    ///
+    /// The outermost projection stops at its first non-null result, like
+    /// with `EvalOptions::limit(1)`, so looking up one record of a huge
+    /// array doesn't evaluate the records after it.
+    ///
+    /// ```
+    /// use jmespath::Variable;
+    ///
+    /// let expr = jmespath::compile("users[?id == `2`].name").unwrap();
+    /// let data = Variable::from_json(r#"{"users": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}]}"#).unwrap();
+    /// assert_eq!(r#""b""#, expr.search_first(&data).unwrap().to_string());
+    /// assert!(expr.search_first(Variable::Null).unwrap().is_null());
+    /// ```
+    pub fn search_first<T: ToJmespath>(&self, data: T) -> SearchResult {
+        let result = self.search_with_options(data, &EvalOptions::new().limit(1))?;
+        Ok(result
+            .as_array()
+            .and_then(|array| array.first().cloned())
+            .unwrap_or_else(|| Rcvar::new(Variable::Null)))
+    }
+
+    /// Returns the number of elements in the result of searching data with
+    /// the compiled expression, or 0 if the result is not an array.
+    ///
+    /// The results of the outermost projection are counted as they are
+    /// produced, so the result array is never built.
+    ///
+    /// ```
+    /// use jmespath::Variable;
+    ///
+    /// let expr = jmespath::compile("orders[?status == 'open']").unwrap();
+    /// let data = Variable::from_json(r#"{"orders": [{"status": "open"}, {"status": "closed"}]}"#).unwrap();
+    /// assert_eq!(1, expr.count(&data).unwrap());
+    /// ```
+    pub fn count<T: ToJmespath>(&self, data: T) -> Result<usize, JmespathError> {
+        self.count_with_options(data, &EvalOptions::default())
+    }
+
+    /// Counts the elements of the result like `count`, with options for
+    /// this evaluation only. The limit and offset of the options are not
+    /// used, since no results are kept.
+    ///
+    /// If the Runtime has `Metrics` hooks, the evaluation is reported to
+    /// them with the count as its result.
+    pub fn count_with_options<T: ToJmespath>(
+        &self,
+        data: T,
+        options: &EvalOptions,
+    ) -> Result<usize, JmespathError> {
+        let count = || {
+            let data = data.to_jmespath()?;
+            let mut ctx = self.context(options);
+            interpret_count(&data, &self.compiled.ast, &mut ctx)
+        };
+        self.measure(count, |&count| {
+            Rcvar::new(Variable::Number(serde_json::Number::from(count)))
+        })
+    }
+
+    /// Returns true if the result of searching data with the compiled
+    /// expression is truthy, without building more of the result than that
+    /// takes.
+    ///
+    /// Projections stop at their first non-null element, and `&&`, `||`
+    /// and `!` only evaluate the operands they need, so a filter that
+    /// matches early in a huge array is answered without projecting the
+    /// rest of it. Errors are the ones `search` would return when it
+    /// evaluates the same nodes.
+    ///
+    /// ```
+    /// use jmespath::Variable;
+    ///
+    /// let expr = jmespath::compile("items[?price > `100`]").unwrap();
+    /// let data = Variable::from_json(r#"{"items": [{"price": 150}, {"price": 50}]}"#).unwrap();
+    /// assert!(expr.matches(&data).unwrap());
+    /// assert!(!expr.matches(Variable::Null).unwrap());
+    /// ```
+    pub fn matches<T: ToJmespath>(&self, data: T) -> Result<bool, JmespathError> {
+        self.matches_with_options(data, &EvalOptions::default())
+    }
+
+    /// Tests the truthiness of the result like `matches`, with options for
+    /// this evaluation only. The limit and offset of the options are not
+    /// used, since projections already stop at their first result.
+    ///
+    /// If the Runtime has `Metrics` hooks, the evaluation is reported to
+    /// them with the boolean as its result.
+    ///
+    /// ```
+    /// use jmespath::{EvalOptions, Rcvar, Variable};
+    ///
+    /// let expr = jmespath::compile("items[?owner == :user]").unwrap();
+    /// let data = Variable::from_json(r#"{"items": [{"owner": "a"}]}"#).unwrap();
+    /// let options = EvalOptions::new().param("user", Rcvar::new(Variable::from("a")));
+    /// assert!(expr.matches_with_options(&data, &options).unwrap());
+    /// ```
+    pub fn matches_with_options<T: ToJmespath>(
+        &self,
+        data: T,
+        options: &EvalOptions,
+    ) -> Result<bool, JmespathError> {
+        let matches = || {
+            let data = data.to_jmespath()?;
+            if let Some(ref steps) = self.compiled.path {
+                return Ok(follow_path(&data, steps).is_truthy());
+            }
+            let mut ctx = self.context(options);
+            interpret_truthy(&data, &self.compiled.ast, &mut ctx)
+        };
+        self.measure(matches, |&matches| Rcvar::new(Variable::Bool(matches)))
+    }
+
+    /// Searches each document of a slice in parallel, and returns the
+    /// results in the order of the documents.
+    ///
+    /// The slice is split into one chunk per available CPU, and each chunk
+    /// is searched on its own scoped thread. Every document gets its own
+    /// result, so documents that fail to evaluate don't stop the others.
+    ///
+    /// ```
+    /// let expr = jmespath::compile("length(@)").unwrap();
+    /// let results = expr.search_batch_par(&["a", "bc", "def"]);
+    /// let lengths: Vec<_> = results.iter().map(|r| r.as_ref().unwrap().to_string()).collect();
+    /// assert_eq!(vec!["1", "2", "3"], lengths);
+    /// assert!(expr.search_batch_par(&[1])[0].is_err());
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn search_batch_par<T>(&self, documents: &[T]) -> Vec<SearchResult>
+    where
+        T: Sync,
+        for<'b> &'b T: ToJmespath,
+    {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads == 1 || documents.len() < 2 {
+            return documents
+                .iter()
+                .map(|document| self.search(document))
+                .collect();
+        }
+        let chunk_size = documents.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = documents
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|document| self.search(document))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+
+    /// Searches data with the compiled expression, handing control to the
+    /// debugger before each AST node is evaluated, starting with the root.
+    ///
+    /// See the `debugger` module for an example.
+    #[cfg(feature = "debugger")]
+    pub fn debug<T: ToJmespath>(&self, data: T, debugger: &mut dyn Debugger) -> SearchResult {
+        let data = data.to_jmespath()?;
+        let mut ctx = Context::new(&self.compiled.expression, self.runtime);
+        ctx.debug = Some(Session::new(debugger));
+        interpret(&data, &self.compiled.ast, &mut ctx)
+    }
+
+    /// Returns a reference to the value that a simple path expression, like
+    /// `a.b[0].c`, selects from `data`, without cloning or allocating.
+    ///
+    /// Returns None if the expression is not a simple path, as reported by
+    /// `ExpressionStats::simple_path`, in which case `search` has to be used.
+    /// A path that selects nothing returns `Some(&Variable::Null)`.
+    ///
+    /// ```
+    /// use jmespath::Variable;
+    ///
+    /// let data = Variable::from_json(r#"{"a": [{"b": 1}]}"#).unwrap();
+    /// let expr = jmespath::compile("a[0].b").unwrap();
+    /// assert_eq!(Some(1.0), expr.lookup(&data).and_then(Variable::as_number));
+    /// assert_eq!(None, jmespath::compile("a[*].b").unwrap().lookup(&data));
+    /// ```
+    pub fn lookup<'d>(&self, data: &'d Variable) -> Option<&'d Variable> {
+        let steps = self.compiled.path.as_ref()?;
+        let mut current = data;
+        for step in steps {
+            match follow_step(current, step) {
+                Some(next) => current = next,
+                None => return Some(&Variable::Null),
+            }
+        }
+        Some(current)
     }
 
     /// Returns the JMESPath expression from which the Expression was compiled.
@@ -403,14 +982,132 @@ impl<'a> Expression<'a> {
     /// Note that this is the same value that is returned by calling
     /// `to_string`.
     pub fn as_str(&self) -> &str {
-        &self.expression
+        &self.compiled.expression
     }
 
     /// Returns the AST of the parsed JMESPath expression.
     ///
     /// This can be useful for debugging purposes, caching, etc.
     pub fn as_ast(&self) -> &Ast {
-        &self.ast
+        &self.compiled.ast
+    }
+
+    /// Returns the warnings found while parsing the expression.
+    ///
+    /// ```
+    /// let expr = jmespath::compile("foo[?bar == `\"baz\"`]").unwrap();
+    /// assert_eq!(
+    ///     "string literals should be written as raw strings ('...') (at offset 12)",
+    ///     expr.warnings()[0].to_string()
+    /// );
+    /// ```
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.compiled.warnings
+    }
+
+    /// Returns the size and complexity statistics of the expression.
+    ///
+    /// ```
+    /// let stats = jmespath::compile("foo.bar[0]").unwrap().stats();
+    /// assert_eq!(5, stats.node_count);
+    /// assert!(stats.simple_path);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> stats::ExpressionStats {
+        stats::stats(self.as_ast())
+    }
+
+    /// Returns true if this expression and `other` have the same AST after
+    /// normalizing both with `Ast::normalize`.
+    ///
+    /// Unlike `==`, which compares the expression text, this ignores
+    /// differences in whitespace and quoting, and rewrites such as
+    /// reordering the operands of `||` in filters. Expressions that are not
+    /// equivalent according to this method may still always produce the
+    /// same result.
+    ///
+    /// ```
+    /// let a = jmespath::compile("foo[?a == `1` || b].\"bar\"").unwrap();
+    /// let b = jmespath::compile("@.foo[?b || `1` == a].bar").unwrap();
+    /// assert!(a.equivalent_to(&b));
+    /// ```
+    pub fn equivalent_to(&self, other: &Expression<'_>) -> bool {
+        let normalized =
+            |expr: &Expression<'_>| expr.as_ast().clone().normalize().to_expression_string();
+        normalized(self) == normalized(other)
+    }
+
+    /// Returns an expression that evaluates `other` against the result of
+    /// this expression (`a | b`).
+    ///
+    /// Combined expressions are built from the ASTs of their parts, and use
+    /// the runtime of `self`. Their text is rendered from the combined AST
+    /// and parsed again, so the offsets of errors refer to that text.
+    ///
+    /// ```
+    /// let people = jmespath::compile("people[?age > `40`]").unwrap();
+    /// let names = jmespath::compile("[*].name").unwrap();
+    /// assert_eq!("people[?age > `40`] | [*].name", people.then(&names).as_str());
+    /// ```
+    pub fn then(&self, other: &Expression<'_>) -> Expression<'a> {
+        self.combine(Ast::Subexpr {
+            offset: 0,
+            lhs: Box::new(self.as_ast().clone()),
+            rhs: Box::new(other.as_ast().clone()),
+        })
+    }
+
+    /// Returns an expression that evaluates to the result of this
+    /// expression if it is truthy, otherwise to the result of `other`
+    /// (`a || b`).
+    ///
+    /// ```
+    /// let nickname = jmespath::compile("nickname").unwrap();
+    /// let name = jmespath::compile("names[0]").unwrap();
+    /// assert_eq!("nickname || names[0]", nickname.or(&name).as_str());
+    /// ```
+    pub fn or(&self, other: &Expression<'_>) -> Expression<'a> {
+        self.combine(Ast::Or {
+            offset: 0,
+            lhs: Box::new(self.as_ast().clone()),
+            rhs: Box::new(other.as_ast().clone()),
+        })
+    }
+
+    /// Returns an expression that evaluates each of `elements` against the
+    /// result of this expression, and collects their results in a list
+    /// (`a | [b, c]`).
+    ///
+    /// ```
+    /// let user = jmespath::compile("user").unwrap();
+    /// let id = jmespath::compile("id").unwrap();
+    /// let name = jmespath::compile("profile.name").unwrap();
+    /// assert_eq!("user.[id, profile.name]", user.select(&[&id, &name]).as_str());
+    /// ```
+    pub fn select(&self, elements: &[&Expression<'_>]) -> Expression<'a> {
+        let elements = elements
+            .iter()
+            .map(|element| element.as_ast().clone())
+            .collect();
+        self.combine(Ast::Subexpr {
+            offset: 0,
+            lhs: Box::new(self.as_ast().clone()),
+            rhs: Box::new(Ast::MultiList {
+                offset: 0,
+                elements,
+            }),
+        })
+    }
+
+    fn combine(&self, ast: Ast) -> Expression<'a> {
+        let text = ast.to_expression_string();
+        match parse_with_options(&text, self.runtime.parser_options()) {
+            Ok((parsed, warnings)) => {
+                Expression::new(text, parsed, self.runtime).with_warnings(warnings)
+            }
+            // The spliced AST is kept if the text is too deep to parse.
+            Err(_) => Expression::new(text, ast, self.runtime),
+        }
     }
 }
 
@@ -433,6 +1130,182 @@ impl<'a> PartialEq for Expression<'a> {
     }
 }
 
+impl<'a> Eq for Expression<'a> {}
+
+/// Hashes the text of the expression, so expressions can be used as keys
+/// of a map that is keyed by the text.
+impl<'a> Hash for Expression<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl<'a> AsRef<str> for Expression<'a> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Compiles an expression with the default Runtime.
+///
+/// ```
+/// let expr: jmespath::Expression = "foo.bar".parse().unwrap();
+/// assert_eq!("foo.bar", expr.as_str());
+/// ```
+impl FromStr for Expression<'static> {
+    type Err = JmespathError;
+
+    fn from_str(expression: &str) -> Result<Expression<'static>, JmespathError> {
+        compile(expression)
+    }
+}
+
+/// Compiles an expression with the default Runtime.
+impl<'s> TryFrom<&'s str> for Expression<'static> {
+    type Error = JmespathError;
+
+    fn try_from(expression: &'s str) -> Result<Expression<'static>, JmespathError> {
+        compile(expression)
+    }
+}
+
+/// Options of a single evaluation, passed to `Expression::search_with_options`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EvalOptions {
+    context: Option<Rcvar>,
+    params: BTreeMap<String, Rcvar>,
+    page: Option<Page>,
+    max_output_elements: Option<usize>,
+    non_finite: NonFinite,
+    redact: bool,
+}
+
+impl EvalOptions {
+    /// Creates options that evaluate like `Expression::search`.
+    pub fn new() -> EvalOptions {
+        EvalOptions::default()
+    }
+
+    /// Sets a value that functions can read from `Context::context`, like
+    /// the `ctx()` extension function, so per-request constants don't have
+    /// to be spliced into the expression.
+    pub fn context(mut self, context: Rcvar) -> EvalOptions {
+        self.context = Some(context);
+        self
+    }
+
+    /// Binds a value to the parameter `:name` of the expression.
+    pub fn param<S: Into<String>>(mut self, name: S, value: Rcvar) -> EvalOptions {
+        self.params.insert(name.into(), value);
+        self
+    }
+
+    /// Keeps at most `limit` results of the outermost projection.
+    ///
+    /// The outermost projection is the one that produces the result, like
+    /// `people[?age > `40`].name`, also after a pipe as in
+    /// `people | [?age > `40`].name | @`. The evaluation stops once the
+    /// page is full instead of projecting every element of a huge array.
+    /// If no projection produces the result, the page is taken from the
+    /// result when it is an array.
+    ///
+    /// ```
+    /// use jmespath::{EvalOptions, Variable};
+    ///
+    /// let expr = jmespath::compile("[?@ > `1`]").unwrap();
+    /// let data = Variable::from_json("[1, 2, 3, 4, 5]").unwrap();
+    /// let page = EvalOptions::new().offset(1).limit(2);
+    /// assert_eq!("[3,4]", expr.search_with_options(data, &page).unwrap().to_string());
+    /// ```
+    pub fn limit(mut self, limit: usize) -> EvalOptions {
+        self.page.get_or_insert_with(Page::default).limit = Some(limit);
+        self
+    }
+
+    /// Skips the first `offset` results of the outermost projection, see
+    /// `EvalOptions::limit`.
+    pub fn offset(mut self, offset: usize) -> EvalOptions {
+        self.page.get_or_insert_with(Page::default).offset = offset;
+        self
+    }
+
+    /// Fails the evaluation with a `RuntimeError::LimitExceeded` error once
+    /// the arrays and objects it builds have more than `limit` elements in
+    /// total.
+    ///
+    /// Projections, flattens, slices, multi-selects and the functions
+    /// that return arrays or objects count the elements of the values
+    /// they build, including intermediate values, so an expression that
+    /// amplifies its input, like repeated `[@, @, @, @][]`, stops before
+    /// it exhausts memory. Only the elements of a built value are counted,
+    /// not the elements nested in them, which it shares with its input.
+    /// The limit is on elements rather than bytes: the size in bytes of a
+    /// value would take a walk of the elements it shares, on every step.
+    ///
+    /// ```
+    /// use jmespath::{EvalOptions, ErrorKind, Variable};
+    ///
+    /// let expr = jmespath::compile("[@, @][] | [@, @][] | [@, @][]").unwrap();
+    /// let data = Variable::from_json("[1, 2, 3]").unwrap();
+    /// let options = EvalOptions::new().max_output_elements(20);
+    /// let err = expr.search_with_options(data, &options).unwrap_err();
+    /// assert_eq!(ErrorKind::LimitExceeded, err.kind);
+    /// ```
+    pub fn max_output_elements(mut self, limit: usize) -> EvalOptions {
+        self.max_output_elements = Some(limit);
+        self
+    }
+
+    /// Sets what becomes of the NaN and infinite numbers that functions
+    /// compute, like the average of an empty array or a sum that
+    /// overflows, which JSON can't represent.
+    ///
+    /// ```
+    /// use jmespath::{EvalOptions, NonFinite, Variable};
+    ///
+    /// let expr = jmespath::compile("avg(@)").unwrap();
+    /// let data = Variable::from_json("[]").unwrap();
+    /// assert!(expr.search(&data).is_err());
+    /// let options = EvalOptions::new().non_finite(NonFinite::String);
+    /// let result = expr.search_with_options(&data, &options).unwrap();
+    /// assert_eq!(r#""NaN""#, result.to_string());
+    /// ```
+    pub fn non_finite(mut self, policy: NonFinite) -> EvalOptions {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Creates the errors of the evaluation with
+    /// `JmespathError::new_redacted`, which masks the literals of the
+    /// expression, so they can be logged safely.
+    ///
+    /// ```
+    /// use jmespath::{EvalOptions, Variable};
+    ///
+    /// let expr = jmespath::compile("abs('secret')").unwrap();
+    /// let options = EvalOptions::new().redact(true);
+    /// let err = expr.search_with_options(Variable::Null, &options).unwrap_err();
+    /// assert_eq!("abs('******')", err.expression);
+    /// ```
+    pub fn redact(mut self, redact: bool) -> EvalOptions {
+        self.redact = redact;
+        self
+    }
+}
+
+/// What becomes of a NaN or infinite number computed by a function, see
+/// `EvalOptions::non_finite`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    /// Fail the evaluation with a `RuntimeError::NotFinite` error.
+    #[default]
+    Error,
+    /// Return null instead.
+    Null,
+    /// Return the string `NaN`, `Infinity` or `-Infinity` instead.
+    String,
+}
+
 /// Context object used for error reporting.
 ///
 /// The Context struct is mostly used when interacting between the
@@ -445,6 +1318,22 @@ pub struct Context<'a> {
     pub runtime: &'a Runtime,
     /// Ast offset that is currently being evaluated.
     pub offset: usize,
+    /// Value set with `EvalOptions::context` for the evaluation.
+    pub context: Option<&'a Rcvar>,
+    /// Values bound to the parameters of the expression.
+    pub params: Option<&'a BTreeMap<String, Rcvar>>,
+    /// Step-through evaluation started by `Expression::debug`.
+    #[cfg(feature = "debugger")]
+    pub(crate) debug: Option<Session<'a>>,
+    /// Page of the outermost projection, taken by the first projection
+    /// that is evaluated.
+    pub(crate) page: Option<Page>,
+    /// Limit on the elements of the values built by the evaluation.
+    pub(crate) output: Option<OutputBudget>,
+    /// Policy for the NaN and infinite numbers computed by functions.
+    pub(crate) non_finite: NonFinite,
+    /// Whether errors are created with `JmespathError::new_redacted`.
+    pub(crate) redact: bool,
 }
 
 impl<'a> Context<'a> {
@@ -455,6 +1344,69 @@ impl<'a> Context<'a> {
             expression,
             runtime,
             offset: 0,
+            context: None,
+            params: None,
+            #[cfg(feature = "debugger")]
+            debug: None,
+            page: None,
+            output: None,
+            non_finite: NonFinite::default(),
+            redact: false,
+        }
+    }
+
+    /// Counts elements of a value built by a function against the
+    /// `EvalOptions::max_output_elements` budget of the evaluation, and
+    /// fails at the current offset once the budget is exceeded.
+    ///
+    /// Functions that build large values, like the reduction of a long
+    /// array, can call it as they go so that they stop early.
+    pub fn charge_output(&mut self, elements: usize) -> Result<(), JmespathError> {
+        if let Some(ref mut budget) = self.output {
+            budget.used = budget.used.saturating_add(elements);
+            if budget.used > budget.limit {
+                let reason = ErrorReason::Runtime(RuntimeError::LimitExceeded {
+                    resource: "output size".to_owned(),
+                    limit: budget.limit,
+                });
+                return Err(JmespathError::from_ctx(self, reason));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an array or object built by a function, after counting its
+    /// elements with `charge_output`.
+    pub fn output(&mut self, value: Variable) -> SearchResult {
+        self.charge_output(match value {
+            Variable::Array(ref array) => array.len(),
+            Variable::Object(ref object) => object.len(),
+            _ => 0,
+        })?;
+        Ok(Rcvar::new(value))
+    }
+
+    /// Returns a number computed by a function, applying the
+    /// `EvalOptions::non_finite` policy of the evaluation if it is NaN or
+    /// infinite.
+    pub fn number(&self, value: f64) -> SearchResult {
+        if let Some(n) = serde_json::Number::from_f64(value) {
+            return Ok(Rcvar::new(Variable::Number(n)));
+        }
+        let name = if value.is_nan() {
+            "NaN"
+        } else if value > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        };
+        match self.non_finite {
+            NonFinite::Error => {
+                let reason = ErrorReason::Runtime(RuntimeError::NotFinite(name.to_owned()));
+                Err(JmespathError::from_ctx(self, reason))
+            }
+            NonFinite::Null => Ok(Rcvar::new(Variable::Null)),
+            NonFinite::String => Ok(Rcvar::new(Variable::String(name.to_owned()))),
         }
     }
 }
@@ -470,6 +1422,48 @@ mod test {
         assert_eq!("foo | baz/foo | baz", format!("{}/{:?}", expr, expr));
     }
 
+    #[test]
+    fn checks_equivalence_of_normalized_expressions() {
+        let equivalent = [
+            ("a.b", "a | b"),
+            ("a.b.c", "(a.b) | c"),
+            ("a | (b | c)", "(a | b) | c"),
+            ("@.a | @", "a"),
+            ("a > b", "b < a"),
+            ("a >= `1`", "`1` <= a"),
+            ("a == 'x'", "'x' == a"),
+            ("a || (b || c)", "(a || b) || c"),
+            ("[?a || b]", "[?b || a]"),
+            ("[?a && (b || c) && a]", "[?(c || b) && a]"),
+            ("[?!!a]", "[?a]"),
+            ("!(a || b)", "!(b || a)"),
+        ];
+        for (a, b) in equivalent.iter() {
+            assert!(
+                compile(a).unwrap().equivalent_to(&compile(b).unwrap()),
+                "{} {}",
+                a,
+                b
+            );
+        }
+        // These produce different results for some inputs.
+        let different = [
+            ("a || b", "b || a"),
+            ("a && b", "b && a"),
+            ("!!a", "a"),
+            ("a[*].b", "a[*] | b"),
+            ("a < b", "b < a"),
+        ];
+        for (a, b) in different.iter() {
+            assert!(
+                !compile(a).unwrap().equivalent_to(&compile(b).unwrap()),
+                "{} {}",
+                a,
+                b
+            );
+        }
+    }
+
     #[test]
     fn implements_partial_eq() {
         let a = compile("@").unwrap();
@@ -490,7 +1484,7 @@ mod test {
         assert_eq!(
             &Ast::Field {
                 offset: 0,
-                name: "foo".to_string(),
+                name: "foo".into(),
             },
             expr.as_ast()
         );
@@ -509,8 +1503,409 @@ mod test {
         let _ = expr.clone();
     }
 
+    #[test]
+    fn reuses_arrays_that_are_not_transformed() {
+        let data = Rcvar::new(Variable::from_json(r#"{"a": [1, [2]], "b": [1, 2]}"#).unwrap());
+        // Without the `specialized` feature, `search` copies its input, so
+        // the data is interpreted directly.
+        let same = |expr: &str, field: &str| {
+            let mut ctx = Context::new(expr, &DEFAULT_RUNTIME);
+            let result = interpret(&data, &parse(expr).unwrap(), &mut ctx).unwrap();
+            Rcvar::ptr_eq(&result, &data.get_field(field))
+        };
+        assert!(same("a[:]", "a"));
+        assert!(same("a[0:10:1]", "a"));
+        assert!(same("a[*]", "a"));
+        assert!(same("b[]", "b"));
+        assert!(!same("a[1:]", "a"));
+        assert!(!same("a[]", "a"));
+        assert_eq!(
+            "[[2]]",
+            compile("a[?@ != `1`]")
+                .unwrap()
+                .search(&data)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "[1,2]",
+            compile("`[1, null, 2]`[*]")
+                .unwrap()
+                .search(())
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn drops_nulls_before_the_first_changed_element() {
+        let expr = compile("[?@ != `1`]").unwrap();
+        let search = |json: &str| {
+            let data = Variable::from_json(json).unwrap();
+            let count = expr.count(&data).unwrap();
+            (expr.search(&data).unwrap().to_string(), count)
+        };
+        assert_eq!(("[2]".to_owned(), 1), search("[null, 1, 2]"));
+        assert_eq!(("[]".to_owned(), 0), search("[null, 1]"));
+    }
+
+    #[test]
+    fn shares_copied_subtrees() {
+        let data = Rcvar::new(
+            Variable::from_json(r#"{"user": {"name": "a"}, "users": [{"name": "b"}]}"#).unwrap(),
+        );
+        let expr = compile("[{a: user, b: user}, [user, users[0]], users[*]]").unwrap();
+        let result = expr.search_shared(&data).unwrap();
+        let user = data.get_field("user");
+        let hash = result.get_index(0);
+        assert!(Rcvar::ptr_eq(&user, &hash.get_field("a")));
+        assert!(Rcvar::ptr_eq(&user, &hash.get_field("b")));
+        assert!(Rcvar::ptr_eq(&user, &result.get_index(1).get_index(0)));
+        let first = data.get_field("users").get_index(0);
+        assert!(Rcvar::ptr_eq(&first, &result.get_index(1).get_index(1)));
+        assert!(Rcvar::ptr_eq(&first, &result.get_index(2).get_index(0)));
+
+        let copy = result.deep_clone();
+        assert_eq!(*result, copy);
+        let hash = copy.get_index(0);
+        let (a, b) = (hash.get_field("a"), hash.get_field("b"));
+        assert!(!Rcvar::ptr_eq(&a, &b));
+        assert!(!Rcvar::ptr_eq(&user, &a));
+    }
+
+    #[test]
+    fn expression_clones_share_ast() {
+        let expr = compile("foo").unwrap();
+        let clone = expr.clone();
+        assert!(std::ptr::eq(expr.as_ast(), clone.as_ast()));
+        assert_eq!(expr, clone);
+    }
+
     #[test]
     fn test_invalid_number() {
         let _ = compile("6455555524");
     }
+
+    #[test]
+    fn safe_search_converts_panics_to_errors() {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        runtime.register_function(
+            "boom",
+            std::sync::Arc::new(|_: &[Rcvar], _: &mut Context<'_>| panic!("boom at {}", 1)),
+        );
+        let err = safe_search_with_runtime(&runtime, "boom()", ()).unwrap_err();
+        assert_eq!(ErrorReason::Internal("boom at 1".to_owned()), err.reason);
+        assert_eq!(ErrorKind::Internal, err.kind);
+        let err = safe_search_with_runtime(&runtime, "length(@)", ()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidType, err.kind);
+        assert!(safe_search("foo[", ()).unwrap_err().kind.is_parse());
+    }
+
+    #[cfg(feature = "preserve-order")]
+    #[test]
+    fn objects_keep_key_order() {
+        let data = Variable::from_json(r#"{"c": 3, "b": 2, "a": 1}"#).unwrap();
+        let result = compile("{z: a, a: b, m: c}")
+            .unwrap()
+            .search(&data)
+            .unwrap();
+        assert_eq!(r#"{"z":1,"a":2,"m":3}"#, result.to_string());
+        let result = compile("@").unwrap().search(&data).unwrap();
+        assert_eq!(r#"{"c":3,"b":2,"a":1}"#, result.to_string());
+    }
+
+    #[test]
+    fn parses_expressions_from_strings() {
+        let expr: Expression<'_> = "foo | bar".parse().unwrap();
+        assert_eq!(compile("foo | bar").unwrap(), expr);
+        assert_eq!(expr, Expression::try_from("foo | bar").unwrap());
+        let err = "foo[".parse::<Expression<'_>>().unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEnd, err.kind);
+        assert!(Expression::try_from("foo[").is_err());
+    }
+
+    #[test]
+    fn hashes_expressions_by_text() {
+        let mut counts = std::collections::HashMap::new();
+        for text in &["a.b", "a | b", "a.b"] {
+            *counts.entry(compile(text).unwrap()).or_insert(0) += 1;
+        }
+        assert_eq!(Some(&2), counts.get(&compile("a.b").unwrap()));
+        assert_eq!(2, counts.len());
+        assert_eq!("a | b", compile("a | b").unwrap().as_ref());
+    }
+
+    #[test]
+    fn shares_one_default_runtime() {
+        assert!(std::ptr::eq(default_runtime(), &*DEFAULT_RUNTIME));
+        assert_eq!(26, default_runtime().function_names().count());
+    }
+
+    #[test]
+    fn searches_with_the_default_runtime() {
+        let result: Result<Rcvar> = search("sort(@)", vec![2, 1]);
+        assert_eq!("[1,2]", result.unwrap().to_string());
+        assert!(search("sort(", ()).is_err());
+    }
+
+    #[test]
+    fn binds_parameters_at_evaluation() {
+        let expr = compile("people[?age > :min_age].name").unwrap();
+        assert_eq!("people[?age > :min_age].name", expr.as_ast().to_string());
+        let data = Variable::from_json(
+            r#"{"people": [{"name": "a", "age": 50}, {"name": "b", "age": 30}]}"#,
+        )
+        .unwrap();
+        let options = EvalOptions::new().param("min_age", Rcvar::new(Variable::from(20)));
+        assert_eq!(
+            r#"["a","b"]"#,
+            expr.search_with_options(&data, &options)
+                .unwrap()
+                .to_string()
+        );
+        // A parameter that is never evaluated doesn't need a value.
+        let empty = Variable::from_json(r#"{"people": []}"#).unwrap();
+        assert_eq!("[]", expr.search(empty).unwrap().to_string());
+        let err = expr.search(&data).unwrap_err();
+        assert_eq!(ErrorKind::UnboundParameter, err.kind);
+        assert_eq!(14, err.offset);
+        assert_eq!(
+            "Runtime error: No value is bound to parameter :min_age",
+            err.reason.to_string()
+        );
+        for invalid in &[":", ": a", ":'a'", "a:b", ":1"] {
+            assert!(compile(invalid).is_err(), "{}", invalid);
+        }
+        assert!(compile("a[:1]").is_ok());
+    }
+
+    #[test]
+    fn paginates_the_outermost_projection() {
+        let search = |expr: &str, json: &str, options: &EvalOptions| {
+            let data = Variable::from_json(json).unwrap();
+            compile(expr)
+                .unwrap()
+                .search_with_options(data, options)
+                .map(|result| result.to_string())
+        };
+        let page = EvalOptions::new().offset(1).limit(2);
+        let people = r#"[{"a": 1}, {"b": 2}, {"a": 3}, {"a": 4}, {"a": 5}]"#;
+        assert_eq!(Ok("[3,4]".to_owned()), search("[*].a", people, &page));
+        assert_eq!(
+            Ok("[3,4,5]".to_owned()),
+            search("[*].a", people, &EvalOptions::new().offset(1))
+        );
+        assert_eq!(
+            Ok("[1]".to_owned()),
+            search("[*].a", people, &EvalOptions::new().limit(1))
+        );
+        // Elements after a full page are not evaluated.
+        let strings = r#"["a", "bc", 1]"#;
+        let first_page = EvalOptions::new().limit(2);
+        assert_eq!(
+            Ok("[1,2]".to_owned()),
+            search("[*].length(@)", strings, &first_page)
+        );
+        assert!(search("[*].length(@)", strings, &page).is_err());
+        // Only the outermost projection is paginated, also after a pipe.
+        let nested = "[[1, 2, 3], [4, 5, 6]]";
+        assert_eq!(Ok("[[4,5,6]]".to_owned()), search("[*][*]", nested, &page));
+        assert_eq!(Ok("[[4,5,6]]".to_owned()), search("@ | [*]", nested, &page));
+        assert_eq!(Ok("[3,4]".to_owned()), search("[*].a | @", people, &page));
+        let wrapped = r#"{"x": {"items": [1, 2, 3, 4]}}"#;
+        assert_eq!(
+            Ok("[2,3]".to_owned()),
+            search("x | items[*]", wrapped, &page)
+        );
+        // Other arrays are paged once they are evaluated.
+        assert_eq!(Ok("[2,3]".to_owned()), search("[0]", nested, &page));
+        assert_eq!(Ok("null".to_owned()), search("x | [*]", "{}", &page));
+    }
+
+    #[test]
+    fn matches_without_evaluating_the_whole_result() {
+        let matches = |expr: &str, json: &str| {
+            let data = Variable::from_json(json).unwrap();
+            compile(expr).unwrap().matches(data)
+        };
+        // The first element matches, so the string is never passed to abs.
+        assert_eq!(Ok(true), matches("[?abs(@) > `1`]", r#"[-2, "x"]"#));
+        assert!(matches("[?abs(@) > `1`]", r#"[1, "x"]"#).is_err());
+        assert_eq!(Ok(false), matches("[*].a", r#"[{"b": 1}, {"b": 2}]"#));
+        assert_eq!(Ok(true), matches("!a || b | [*]", r#"{"a": 1, "b": [0]}"#));
+        assert_eq!(Ok(false), matches("a && b[*].c", r#"{"a": 1, "b": [{}]}"#));
+        assert_eq!(Ok(false), matches("a.b | [*]", "{}"));
+        assert_eq!(Ok(true), matches("a.b", r#"{"a": {"b": "x"}}"#));
+        assert_eq!(Ok(false), matches("`[]`", "null"));
+        // Parameters and limits apply as they do to a search.
+        let data = Variable::from_json(r#"[{"a": 1}, {"a": 2}]"#).unwrap();
+        let expr = compile("[?a == :a]").unwrap();
+        let options = EvalOptions::new().param("a", Rcvar::new(Variable::from(2)));
+        assert_eq!(Ok(true), expr.matches_with_options(&data, &options));
+        let expr = compile("[[@, @], [@, @]][] | [0]").unwrap();
+        assert_eq!(Ok(true), expr.matches(&data));
+        let options = EvalOptions::new().max_output_elements(5);
+        assert!(expr.matches_with_options(&data, &options).is_err());
+    }
+
+    #[test]
+    fn searches_for_the_first_result() {
+        let first = |expr: &str, json: &str| {
+            let data = Variable::from_json(json).unwrap();
+            compile(expr).unwrap().search_first(data)
+        };
+        let found = |expr: &str, json: &str| first(expr, json).unwrap().to_string();
+        // The second element matches, so the string is never passed to abs.
+        assert_eq!("-2", found("[?abs(@) > `1`]", r#"[1, -2, "x"]"#));
+        assert!(first("[?abs(@) > `2`]", r#"[1, -2, "x"]"#).is_err());
+        assert_eq!("2", found("[*].b", r#"[{"a": 1}, {"b": 2}]"#));
+        assert_eq!("null", found("[*].c", r#"[{"a": 1}, {"b": 2}]"#));
+        assert_eq!("1", found("a", r#"{"a": [1, 2]}"#));
+        assert_eq!("null", found("a", r#"{"a": {"b": 1}}"#));
+    }
+
+    #[test]
+    fn counts_results() {
+        let count = |expr: &str, json: &str| {
+            let data = Variable::from_json(json).unwrap();
+            compile(expr).unwrap().count(data).unwrap()
+        };
+        let data = r#"{"a": [{"b": 1}, {"c": 2}, {"b": [3]}], "d": "x"}"#;
+        assert_eq!(2, count("a[*].b", data));
+        assert_eq!(1, count("a[?c]", data));
+        assert_eq!(3, count("a", data));
+        assert_eq!(0, count("d", data));
+        assert_eq!(0, count("d[*]", data));
+        assert_eq!(1, count("a[*].b | [1]", data));
+        let expr = compile("[?length(@) > `0`]").unwrap();
+        assert!(expr.count(Variable::from_json("[1]").unwrap()).is_err());
+        // Parameters and limits apply as they do to a search.
+        let data = Variable::from_json(data).unwrap();
+        let expr = compile("a[?b == :b] | [0]").unwrap();
+        let options = EvalOptions::new().param("b", Rcvar::new(Variable::from(1)));
+        assert_eq!(Ok(0), expr.count_with_options(&data, &options));
+        let expr = compile("a[*].to_array(@)").unwrap();
+        assert_eq!(Ok(3), expr.count(&data));
+        let options = EvalOptions::new().max_output_elements(1);
+        assert!(expr.count_with_options(&data, &options).is_err());
+        // Projections piped or chained into are counted the same way.
+        let nested = r#"{"x": {"a": [{"b": 1}, {"c": 2}, {"c": 3}]}}"#;
+        assert_eq!(2, count("x | a[?c]", nested));
+        assert_eq!(3, count("x.a[*]", nested));
+        assert_eq!(1, count("x.a[*].b | @", nested));
+        assert_eq!(0, count("y.a[*]", nested));
+        let nested = Variable::from_json(nested).unwrap();
+        let expr = compile("x | a[?c]").unwrap();
+        assert!(expr.search_with_options(&nested, &options).is_err());
+        assert_eq!(Ok(2), expr.count_with_options(&nested, &options));
+    }
+
+    #[test]
+    fn limits_the_size_of_built_values() {
+        let search = |expr: &str, limit: usize| {
+            let data = Variable::from_json(r#"{"a": [1, 2, 3], "b": {"c": 1}}"#).unwrap();
+            let options = EvalOptions::new().max_output_elements(limit);
+            compile(expr).unwrap().search_with_options(data, &options)
+        };
+        assert!(search("a[*]", 0).is_ok());
+        assert!(search("a[::-1]", 3).is_ok());
+        assert!(search("a[::-1]", 2).is_err());
+        // The intermediate results count as well as the result.
+        assert!(search("[a, a][] | length(@)", 7).is_err());
+        assert!(search("[a, a][] | length(@)", 8).is_ok());
+        // So do the values returned by functions.
+        assert!(search("sort(a)", 2).is_err());
+        assert!(search("map(&[@], a)", 5).is_err());
+        assert!(search("map(&[@], a)", 6).is_ok());
+        assert!(search("merge(b, b)", 0).is_err());
+        assert!(search("{x: b.*}", 2).is_ok());
+        let err = search("{x: b.*}", 1).unwrap_err();
+        assert_eq!(ErrorKind::LimitExceeded, err.kind);
+        assert_eq!(0, err.offset);
+        assert_eq!(
+            "Runtime error: Exceeded the output size limit of 1",
+            err.reason.to_string()
+        );
+    }
+
+    #[test]
+    fn applies_the_non_finite_policy() {
+        let search = |expr: &str, policy: NonFinite| {
+            let data = Variable::from_json(r#"{"empty": [], "big": [1e308, 1e308]}"#).unwrap();
+            let options = EvalOptions::new().non_finite(policy);
+            compile(expr).unwrap().search_with_options(data, &options)
+        };
+        let err = search("{a: avg(empty)}", NonFinite::Error).unwrap_err();
+        assert_eq!(ErrorKind::NotFinite, err.kind);
+        assert_eq!(7, err.offset);
+        assert_eq!(
+            "Runtime error: Computed NaN, which is not a JSON number",
+            err.reason.to_string()
+        );
+        let result = search("avg(empty)", NonFinite::Null).unwrap();
+        assert_eq!(Variable::Null, *result);
+        let result = search("avg(empty)", NonFinite::String).unwrap();
+        assert_eq!(r#""NaN""#, result.to_string());
+        // With `bignum`, the sum is exact.
+        #[cfg(not(feature = "bignum"))]
+        {
+            let result = search("sum(big)", NonFinite::String).unwrap();
+            assert_eq!(r#""Infinity""#, result.to_string());
+            let result = search("avg(big)", NonFinite::Null).unwrap();
+            assert_eq!(Variable::Null, *result);
+        }
+    }
+
+    #[test]
+    fn combines_compiled_expressions() {
+        let adults = compile("people[?age >= `18`]").unwrap();
+        let names = compile("[*].name").unwrap();
+        let first = compile("[0]").unwrap();
+        let data = Variable::from_json(
+            r#"{"people": [{"age": 10, "name": "a"}, {"age": 20, "name": "b"}]}"#,
+        )
+        .unwrap();
+        let adult_names = adults.then(&names);
+        assert_eq!(r#"["b"]"#, adult_names.search(&data).unwrap().to_string());
+        let fallback = compile("nobody").unwrap().or(&adult_names.then(&first));
+        assert_eq!(r#""b""#, fallback.search(&data).unwrap().to_string());
+        let selected = adults.select(&[&names, &first]);
+        assert_eq!(
+            r#"[["b"],{"age":20,"name":"b"}]"#,
+            selected.search(&data).unwrap().to_string()
+        );
+        // The text of a combined expression parses to the same expression.
+        for expr in &[&adult_names, &fallback, &selected] {
+            assert!(expr.equivalent_to(&compile(expr.as_str()).unwrap()));
+        }
+        // Errors point into the text of the combined expression.
+        let absolute = compile("people").unwrap().then(&compile("abs(@)").unwrap());
+        let error = absolute.search(&data).unwrap_err();
+        assert_eq!("people.abs(@)", error.expression);
+        assert_eq!(10, error.offset);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn searches_batches_in_parallel_and_in_order() {
+        let documents: Vec<serde_json::Value> = (0..1000)
+            .map(|i| match i % 7 {
+                0 => serde_json::json!({ "a": "x" }),
+                _ => serde_json::json!({ "a": -i }),
+            })
+            .collect();
+        let results = compile("abs(a)").unwrap().search_batch_par(&documents);
+        assert_eq!(documents.len(), results.len());
+        for (i, result) in results.iter().enumerate() {
+            match i % 7 {
+                0 => assert!(result.is_err()),
+                _ => assert_eq!(Some(i as f64), result.as_ref().unwrap().as_number()),
+            }
+        }
+        assert!(compile("a")
+            .unwrap()
+            .search_batch_par::<serde_json::Value>(&[])
+            .is_empty());
+    }
 }
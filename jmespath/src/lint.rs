@@ -0,0 +1,280 @@
+//! Static analysis of JMESPath expressions.
+//!
+//! The linter flags constructs that are valid JMESPath but almost certainly
+//! do not do what the author intended, usually because they evaluate to the
+//! same value regardless of the input.
+//!
+//! ```
+//! use jmespath::lint::{lint, LintKind};
+//!
+//! let ast = jmespath::parse("foo || `\"default\"` || bar").unwrap();
+//! let lints = lint(&ast);
+//! assert_eq!(1, lints.len());
+//! assert_eq!(LintKind::UnreachableBranch, lints[0].kind);
+//! ```
+
+use std::fmt;
+
+use crate::ast::{Ast, Comparator};
+use crate::variable::JmespathType;
+use crate::visitor::AstVisitor;
+use crate::Rcvar;
+
+/// The kind of suspicious construct that was found.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LintKind {
+    /// A comparison that can only ever produce one value, such as comparing
+    /// literals of different types or ordering a non-numeric literal.
+    IncompatibleComparison,
+    /// The right hand side of `||` or `&&` that is never evaluated because
+    /// the left hand side is a literal.
+    UnreachableBranch,
+    /// A projection, flatten, or object values expression applied to a
+    /// literal of the wrong type, which always evaluates to null.
+    ProjectionOfLiteral,
+    /// An index or slice of a multi-select hash, which always evaluates to
+    /// null.
+    IndexOfMultiHash,
+    /// A field of a multi-select list, which always evaluates to null.
+    FieldOfMultiList,
+}
+
+/// A warning about a suspicious construct in an expression.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Lint {
+    /// What kind of construct was found.
+    pub kind: LintKind,
+    /// Approximate absolute position of the construct in the expression.
+    pub offset: usize,
+    /// Human readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(fmt, "{} (at offset {})", self.message, self.offset)
+    }
+}
+
+/// Returns the suspicious constructs found in the AST, in the order they
+/// were encountered.
+pub fn lint(ast: &Ast) -> Vec<Lint> {
+    let mut linter = Linter { lints: vec![] };
+    linter.visit(ast);
+    linter.lints
+}
+
+struct Linter {
+    lints: Vec<Lint>,
+}
+
+impl Linter {
+    fn warn(&mut self, kind: LintKind, offset: usize, message: String) {
+        self.lints.push(Lint {
+            kind,
+            offset,
+            message,
+        });
+    }
+
+    /// Warns if the node is a literal that isn't of the expected type.
+    fn check_literal(&mut self, node: &Ast, expected: JmespathType, what: &str) {
+        if let Ast::Literal { offset, ref value } = *node {
+            if value.get_type() != expected {
+                let message = format!("{} of a {} literal is always null", what, value.get_type());
+                self.warn(LintKind::ProjectionOfLiteral, offset, message);
+            }
+        }
+    }
+}
+
+fn literal(node: &Ast) -> Option<&Rcvar> {
+    match *node {
+        Ast::Literal { ref value, .. } => Some(value),
+        _ => None,
+    }
+}
+
+/// Returns true if the node evaluates to a truthy value for any input.
+fn always_truthy(node: &Ast) -> bool {
+    match *node {
+        Ast::Literal { ref value, .. } => value.is_truthy(),
+        Ast::Or {
+            ref lhs, ref rhs, ..
+        } => always_truthy(lhs) || always_truthy(rhs),
+        _ => false,
+    }
+}
+
+/// Returns true if the node evaluates to a falsy value for any input.
+fn always_falsy(node: &Ast) -> bool {
+    match *node {
+        Ast::Literal { ref value, .. } => !value.is_truthy(),
+        Ast::And {
+            ref lhs, ref rhs, ..
+        } => always_falsy(lhs) || always_falsy(rhs),
+        _ => false,
+    }
+}
+
+fn is_index(node: &Ast) -> bool {
+    match *node {
+        Ast::Index { .. } => true,
+        Ast::Projection { ref lhs, .. } => matches!(**lhs, Ast::Slice { .. }),
+        _ => false,
+    }
+}
+
+impl AstVisitor for Linter {
+    fn visit_comparison(&mut self, offset: usize, comparator: &Comparator, lhs: &Ast, rhs: &Ast) {
+        match *comparator {
+            Comparator::Equal | Comparator::NotEqual => {
+                if let (Some(l), Some(r)) = (literal(lhs), literal(rhs)) {
+                    if l.get_type() != r.get_type() {
+                        let message = format!(
+                            "comparing a {} literal to a {} literal is always {}",
+                            l.get_type(),
+                            r.get_type(),
+                            *comparator == Comparator::NotEqual
+                        );
+                        self.warn(LintKind::IncompatibleComparison, offset, message);
+                    }
+                }
+            }
+            _ => {
+                let non_number = [lhs, rhs]
+                    .iter()
+                    .filter_map(|node| literal(node))
+                    .find(|value| !value.is_number());
+                if let Some(value) = non_number {
+                    let message = format!(
+                        "ordering comparisons with a {} literal are always null",
+                        value.get_type()
+                    );
+                    self.warn(LintKind::IncompatibleComparison, offset, message);
+                }
+            }
+        }
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_or(&mut self, offset: usize, lhs: &Ast, rhs: &Ast) {
+        if always_truthy(lhs) {
+            let message = "the right side of `||` is unreachable because the left side \
+                           is always truthy"
+                .to_owned();
+            self.warn(LintKind::UnreachableBranch, offset, message);
+        }
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_and(&mut self, offset: usize, lhs: &Ast, rhs: &Ast) {
+        if always_falsy(lhs) {
+            let message = "the right side of `&&` is unreachable because the left side \
+                           is always falsy"
+                .to_owned();
+            self.warn(LintKind::UnreachableBranch, offset, message);
+        }
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_projection(&mut self, _offset: usize, lhs: &Ast, rhs: &Ast) {
+        self.check_literal(lhs, JmespathType::Array, "a projection");
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_flatten(&mut self, _offset: usize, node: &Ast) {
+        self.check_literal(node, JmespathType::Array, "flattening");
+        self.visit(node);
+    }
+
+    fn visit_object_values(&mut self, _offset: usize, node: &Ast) {
+        self.check_literal(node, JmespathType::Object, "the values");
+        self.visit(node);
+    }
+
+    fn visit_subexpr(&mut self, offset: usize, lhs: &Ast, rhs: &Ast) {
+        match (lhs, rhs) {
+            (Ast::MultiHash { .. }, rhs) if is_index(rhs) => {
+                let message = "indexing a multi-select hash is always null".to_owned();
+                self.warn(LintKind::IndexOfMultiHash, offset, message);
+            }
+            (Ast::MultiList { .. }, Ast::Field { name, .. }) => {
+                let message = format!("the field {:?} of a multi-select list is always null", name);
+                self.warn(LintKind::FieldOfMultiList, offset, message);
+            }
+            _ => (),
+        }
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(expr: &str) -> Vec<LintKind> {
+        lint(&crate::parse(expr).unwrap())
+            .into_iter()
+            .map(|lint| lint.kind)
+            .collect()
+    }
+
+    #[test]
+    fn flags_suspicious_constructs() {
+        use self::LintKind::*;
+        let cases = vec![
+            ("`1` == 'a'", vec![IncompatibleComparison]),
+            ("foo > 'a'", vec![IncompatibleComparison]),
+            ("'a' || foo", vec![UnreachableBranch]),
+            ("`false` && foo", vec![UnreachableBranch]),
+            ("'abc'[*].foo", vec![ProjectionOfLiteral]),
+            ("`{}`[]", vec![ProjectionOfLiteral]),
+            ("`[1]`.*", vec![ProjectionOfLiteral]),
+            ("{a: a}[0]", vec![IndexOfMultiHash]),
+            ("{a: a}[:1]", vec![IndexOfMultiHash]),
+            ("[a, b].c", vec![FieldOfMultiList]),
+            ("foo[?`1` > 'a'].{x: '' || y}", vec![IncompatibleComparison]),
+            ("sort_by(@, &(`true` || a))", vec![UnreachableBranch]),
+            ("a || 'b' || c", vec![UnreachableBranch]),
+            ("a && `null` && c", vec![UnreachableBranch]),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(expected, kinds(expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn does_not_flag_reasonable_expressions() {
+        let cases = [
+            "foo == 'a'",
+            "`1` == `2`",
+            "foo > `1`",
+            "foo || 'default'",
+            "`[1, 2]`[*]",
+            "`{\"a\": 1}`.*",
+            "{a: a}.a",
+            "[a, b][0]",
+            "'' || foo",
+            "`true` && foo",
+        ];
+        for expr in cases.iter() {
+            assert!(kinds(expr).is_empty(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn reports_offsets_and_messages() {
+        let lints = lint(&crate::parse("a || `\"x\"` || b").unwrap());
+        assert_eq!(
+            "the right side of `||` is unreachable because the left side is always truthy \
+             (at offset 11)",
+            lints[0].to_string()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! An object map that remembers the order keys were inserted in.
+//!
+//! With the `preserve-order` feature, `jmespath::Map`, the map backing
+//! `Variable::Object`, is an `OrderedMap` instead of a `BTreeMap`. Objects
+//! then keep the order of their keys: multi-hash expressions produce keys
+//! in the order they are written, and objects parsed from JSON keep the
+//! order of the document.
+//!
+//! ```
+//! use jmespath::map::OrderedMap;
+//! use jmespath::{Rcvar, Variable};
+//!
+//! let mut map = OrderedMap::new();
+//! map.insert("b".to_owned(), Rcvar::new(Variable::Null));
+//! map.insert("a".to_owned(), Rcvar::new(Variable::Bool(true)));
+//! assert_eq!(vec!["b", "a"], map.keys().collect::<Vec<_>>());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::slice;
+use std::vec;
+
+use serde::ser;
+
+use crate::Rcvar;
+
+/// Iterator over the entries of an `OrderedMap`, in insertion order.
+pub type Iter<'a> = std::iter::Map<
+    slice::Iter<'a, (String, Rcvar)>,
+    fn(&'a (String, Rcvar)) -> (&'a String, &'a Rcvar),
+>;
+
+/// Owning iterator over the entries of an `OrderedMap`, in insertion order.
+pub type IntoIter = vec::IntoIter<(String, Rcvar)>;
+
+/// A map of strings to variables that iterates in insertion order.
+///
+/// Inserting a key that is already present replaces its value and keeps
+/// its position. Two maps are equal if they have the same entries,
+/// regardless of their order, like JSON objects.
+#[derive(Clone, Default)]
+pub struct OrderedMap {
+    entries: Vec<(String, Rcvar)>,
+    /// Position of each key in `entries`.
+    positions: HashMap<String, usize>,
+}
+
+impl OrderedMap {
+    /// Creates an empty map.
+    pub fn new() -> OrderedMap {
+        OrderedMap::default()
+    }
+
+    /// Creates an empty map with room for `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> OrderedMap {
+        OrderedMap {
+            entries: Vec::with_capacity(capacity),
+            positions: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value of a key.
+    pub fn get(&self, key: &str) -> Option<&Rcvar> {
+        self.positions.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Returns a mutable reference to the value of a key.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Rcvar> {
+        match self.positions.get(key) {
+            Some(&i) => Some(&mut self.entries[i].1),
+            None => None,
+        }
+    }
+
+    /// Returns true if the map has a value for the key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// Inserts a value, returning the previous value of the key.
+    ///
+    /// New keys are added at the end; existing keys keep their position.
+    pub fn insert(&mut self, key: String, value: Rcvar) -> Option<Rcvar> {
+        match self.positions.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Removes a key, returning its value. The keys after it move up,
+    /// so the order of the remaining keys is unchanged.
+    pub fn remove(&mut self, key: &str) -> Option<Rcvar> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            *self.positions.get_mut(key.as_str()).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.positions.clear();
+    }
+
+    /// Returns an iterator over the entries in insertion order.
+    pub fn iter(&self) -> Iter<'_> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Returns an iterator over the keys in insertion order.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &String> + ExactSizeIterator {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over the values in insertion order.
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &Rcvar> + ExactSizeIterator {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl PartialEq for OrderedMap {
+    fn eq(&self, other: &OrderedMap) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Eq for OrderedMap {}
+
+impl fmt::Debug for OrderedMap {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> Index<&'a str> for OrderedMap {
+    type Output = Rcvar;
+
+    fn index(&self, key: &'a str) -> &Rcvar {
+        self.get(key).expect("key not found in map")
+    }
+}
+
+impl FromIterator<(String, Rcvar)> for OrderedMap {
+    fn from_iter<I: IntoIterator<Item = (String, Rcvar)>>(iter: I) -> OrderedMap {
+        let mut map = OrderedMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl Extend<(String, Rcvar)> for OrderedMap {
+    fn extend<I: IntoIterator<Item = (String, Rcvar)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for OrderedMap {
+    type Item = (String, Rcvar);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a OrderedMap {
+    type Item = (&'a String, &'a Rcvar);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Serializes the entries in insertion order.
+impl ser::Serialize for OrderedMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Variable;
+    use serde_json::Number;
+
+    fn num(n: u64) -> Rcvar {
+        Rcvar::new(Variable::Number(Number::from(n)))
+    }
+
+    fn map(keys: &[&str]) -> OrderedMap {
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| (key.to_string(), num(i as u64)))
+            .collect()
+    }
+
+    #[test]
+    fn iterates_in_insertion_order() {
+        let mut map = map(&["c", "a", "b"]);
+        assert_eq!(vec!["c", "a", "b"], map.keys().collect::<Vec<_>>());
+        // Replacing a value keeps the position of its key.
+        map.insert("c".to_owned(), Rcvar::new(Variable::Null));
+        assert_eq!(Some(&Rcvar::new(Variable::Null)), map.get("c"));
+        assert_eq!(vec!["c", "a", "b"], map.keys().collect::<Vec<_>>());
+        assert_eq!(
+            r#"{"c":null,"a":1,"b":2}"#,
+            serde_json::to_string(&map).unwrap()
+        );
+    }
+
+    #[test]
+    fn removes_keys() {
+        let mut map = map(&["a", "b", "c"]);
+        assert_eq!(Some(num(1)), map.remove("b"));
+        assert_eq!(None, map.remove("b"));
+        assert_eq!(vec!["a", "c"], map.keys().collect::<Vec<_>>());
+        assert_eq!(Some(&num(2)), map.get("c"));
+        assert!(!map.contains_key("b"));
+    }
+
+    #[test]
+    fn compares_without_order() {
+        let mut reversed = OrderedMap::new();
+        reversed.insert("b".to_owned(), num(1));
+        reversed.insert("a".to_owned(), num(0));
+        assert_eq!(map(&["a", "b"]), reversed);
+        assert_ne!(map(&["a", "b"]), map(&["a"]));
+        assert_ne!(map(&["a", "b"]), map(&["b", "a"]));
+    }
+}
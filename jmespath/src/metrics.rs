@@ -0,0 +1,145 @@
+//! Hooks for measuring how a Runtime compiles and evaluates expressions.
+//!
+//! A `Metrics` implementation registered with `RuntimeBuilder::metrics` is
+//! told about every compilation and evaluation of the Runtime, and about
+//! every lookup of an `ExpressionCache` that uses it. The crate doesn't
+//! depend on a metrics library: the implementation forwards the
+//! measurements to whatever the application uses, such as Prometheus
+//! counters and histograms or StatsD timers.
+//!
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use jmespath::metrics::Metrics;
+//! use jmespath::{JmespathError, Rcvar, Runtime};
+//!
+//! #[derive(Default)]
+//! struct Failures(AtomicUsize);
+//!
+//! impl Metrics for Failures {
+//!     fn on_eval(&self, _: &str, _: Duration, result: Result<&Rcvar, &JmespathError>) {
+//!         if result.is_err() {
+//!             self.0.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let failures = Arc::new(Failures::default());
+//! let runtime = Runtime::builder().metrics(failures.clone()).build();
+//! let expr = runtime.compile("abs(foo)").unwrap();
+//! assert!(expr.search(jmespath::Variable::Null).is_err());
+//! assert_eq!(1, failures.0.load(Ordering::Relaxed));
+//! ```
+
+use std::time::Duration;
+
+use crate::{JmespathError, Rcvar};
+
+/// Receives measurements from a Runtime.
+///
+/// Every method does nothing by default, so implementations only override
+/// the ones they record. The methods are called on the thread that
+/// compiles or evaluates the expression, and should be cheap.
+pub trait Metrics: Send + Sync {
+    /// Called after an expression is compiled, with the time it took and
+    /// the error if it failed to compile.
+    fn on_compile(&self, expression: &str, duration: Duration, result: Result<(), &JmespathError>) {
+        let _ = (expression, duration, result);
+    }
+
+    /// Called after an expression is evaluated, with the time it took and
+    /// its result.
+    fn on_eval(
+        &self,
+        expression: &str,
+        duration: Duration,
+        result: Result<&Rcvar, &JmespathError>,
+    ) {
+        let _ = (expression, duration, result);
+    }
+
+    /// Called when an `ExpressionCache` finds a compiled expression.
+    fn on_cache_hit(&self, expression: &str) {
+        let _ = expression;
+    }
+
+    /// Called when an `ExpressionCache` has to compile an expression.
+    fn on_cache_miss(&self, expression: &str) {
+        let _ = expression;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Runtime;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl Metrics for Recorder {
+        fn on_compile(&self, expression: &str, _: Duration, result: Result<(), &JmespathError>) {
+            self.push(format!("compile {} {}", expression, result.is_ok()));
+        }
+
+        fn on_eval(&self, expression: &str, _: Duration, result: Result<&Rcvar, &JmespathError>) {
+            self.push(format!("eval {} {}", expression, result.is_ok()));
+        }
+
+        fn on_cache_hit(&self, expression: &str) {
+            self.push(format!("hit {}", expression));
+        }
+
+        fn on_cache_miss(&self, expression: &str) {
+            self.push(format!("miss {}", expression));
+        }
+    }
+
+    #[test]
+    fn records_compilations_and_evaluations() {
+        let recorder = Arc::new(Recorder::default());
+        let runtime = Runtime::builder().metrics(recorder.clone()).build();
+        assert!(runtime.compile("a[").is_err());
+        runtime.search("a.b", crate::Variable::Null).unwrap();
+        assert!(runtime.search("abs(@)", "x").is_err());
+        let expr = runtime.compile("[*]").unwrap();
+        assert_eq!(Ok(0), expr.count(crate::Variable::Null));
+        assert_eq!(Ok(false), expr.matches(crate::Variable::Null));
+        assert_eq!(
+            vec![
+                "compile a[ false",
+                "compile a.b true",
+                "eval a.b true",
+                "compile abs(@) true",
+                "eval abs(@) false",
+                "compile [*] true",
+                "eval [*] true",
+                "eval [*] true",
+            ],
+            *recorder.0.lock().unwrap()
+        );
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn records_cache_lookups() {
+        let recorder = Arc::new(Recorder::default());
+        let runtime = Runtime::builder().metrics(recorder.clone()).build();
+        let mut cache = crate::cache::ExpressionCache::with_runtime(&runtime, 2);
+        cache.compile("a").unwrap();
+        cache.compile("a").unwrap();
+        assert_eq!(
+            vec!["miss a", "compile a true", "hit a"],
+            *recorder.0.lock().unwrap()
+        );
+    }
+}
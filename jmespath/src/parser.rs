@@ -5,40 +5,279 @@
 //! <https://tdop.github.io/>
 
 use std::collections::VecDeque;
+use std::fmt;
 
-use crate::ast::{Ast, Comparator, KeyValuePair};
-use crate::lexer::{tokenize, Token, TokenTuple};
-use crate::{ErrorReason, JmespathError};
+use crate::ast::{render_name, Ast, Comparator, KeyValuePair};
+use crate::lexer::{tokenize_spans, Token, TokenTuple, TOKEN_KINDS};
+use crate::{ErrorKind, ErrorReason, JmespathError, ParserOptions};
 
 /// Result of parsing an expression.
 pub type ParseResult = Result<Ast, JmespathError>;
 
 /// Parses a JMESPath expression into an AST.
 pub fn parse(expr: &str) -> ParseResult {
-    let tokens = tokenize(expr)?;
-    Parser::new(tokens, expr).parse()
+    parse_with_warnings(expr).map(|(ast, _)| ast)
+}
+
+/// Parses a JMESPath expression into an AST, also returning warnings about
+/// valid but discouraged syntax in the order it was encountered.
+///
+/// ```
+/// use jmespath::WarningKind;
+///
+/// let (_, warnings) = jmespath::parse_with_warnings("@.foo | @").unwrap();
+/// assert_eq!(
+///     vec![WarningKind::RedundantCurrentNode, WarningKind::IdentityPipe],
+///     warnings.iter().map(|w| w.kind).collect::<Vec<_>>()
+/// );
+/// ```
+pub fn parse_with_warnings(expr: &str) -> Result<(Ast, Vec<ParseWarning>), JmespathError> {
+    parse_with_options(expr, &ParserOptions::default())
+}
+
+/// Parses a JMESPath expression into an AST with the syntax extensions
+/// enabled in `options`, also returning its warnings.
+///
+/// `deny_warnings` is not applied here, since the warnings are returned.
+///
+/// ```
+/// use jmespath::ParserOptions;
+///
+/// let options = ParserOptions {
+///     recursive_descent: true,
+///     ..ParserOptions::default()
+/// };
+/// let (ast, _) = jmespath::parse_with_options("a..b", &options).unwrap();
+/// assert_eq!("a..b", ast.to_string());
+/// assert!(jmespath::parse("a..b").is_err());
+/// ```
+pub fn parse_with_options(
+    expr: &str,
+    options: &ParserOptions,
+) -> Result<(Ast, Vec<ParseWarning>), JmespathError> {
+    let parse = || {
+        let (tokens, ends) = tokenize_spans(expr)?
+            .into_iter()
+            .map(|(span, token)| ((span.start, token), span.end))
+            .unzip();
+        let mut parser = Parser::new(tokens, ends, expr);
+        parser.recursive_descent = options.recursive_descent;
+        let ast = parser.parse()?;
+        Ok((ast, parser.warnings))
+    };
+    parse().map_err(|e: JmespathError| e.redact_if(options.redact))
+}
+
+/// The kind of discouraged syntax that a parse warning is about.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WarningKind {
+    /// A string written as a JSON literal (`` `"foo"` ``) rather than as a
+    /// raw string literal (`'foo'`).
+    JsonStringLiteral,
+    /// `@.` before an expression, which has no effect.
+    RedundantCurrentNode,
+    /// A pipe to `@`, which has no effect.
+    IdentityPipe,
+    /// A key that appears more than once in a multi-select hash, like
+    /// `{a: x, a: y}`. The value of the last occurrence is used; with the
+    /// `preserve-order` feature, the key keeps the position of the first.
+    DuplicateKey,
+}
+
+/// A warning about valid but discouraged syntax in an expression.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseWarning {
+    /// What kind of syntax was found.
+    pub kind: WarningKind,
+    /// Absolute byte offset of the syntax in the expression.
+    pub offset: usize,
+    /// Human readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(fmt, "{} (at offset {})", self.message, self.offset)
+    }
+}
+
+/// A set of the kinds of tokens that the parser expected, used to list
+/// them in error messages and kept by the error for
+/// `JmespathError::expected`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct Expected(u16);
+
+impl Expected {
+    pub(crate) const NONE: Expected = Expected(0);
+    const NUMBER: Expected = Expected(1);
+    const IDENTIFIER: Expected = Expected(1 << 1);
+    const DOT: Expected = Expected(1 << 2);
+    const COLON: Expected = Expected(1 << 3);
+    const STAR: Expected = Expected(1 << 4);
+    const LBRACE: Expected = Expected(1 << 5);
+    const RBRACE: Expected = Expected(1 << 6);
+    const LBRACKET: Expected = Expected(1 << 7);
+    const RBRACKET: Expected = Expected(1 << 8);
+    const AMPERSAND: Expected = Expected(1 << 9);
+    const FILTER: Expected = Expected(1 << 10);
+    const COMMA: Expected = Expected(1 << 11);
+    const RPAREN: Expected = Expected(1 << 12);
+
+    /// Names of each kind in the order of their bits.
+    const NAMES: [&'static str; 13] = [
+        "number",
+        "identifier",
+        "'.'",
+        "':'",
+        "'*'",
+        "'{'",
+        "'}'",
+        "'['",
+        "']'",
+        "'&'",
+        "'[?'",
+        "','",
+        "')'",
+    ];
+
+    /// Returns the names of the kinds in the set.
+    pub(crate) fn names(self) -> impl Iterator<Item = &'static str> {
+        Expected::NAMES
+            .iter()
+            .enumerate()
+            .filter(move |&(bit, _)| self.0 & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+    }
+}
+
+impl std::ops::BitOr for Expected {
+    type Output = Expected;
+
+    fn bitor(self, other: Expected) -> Expected {
+        Expected(self.0 | other.0)
+    }
+}
+
+/// Writes the kinds as "a", "a or b", or "a, b, or c".
+impl fmt::Display for Expected {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let names: Vec<_> = self.names().collect();
+        match names.split_last() {
+            Some((last, [])) => write!(fmt, "{}", last),
+            Some((last, [first])) => write!(fmt, "{} or {}", first, last),
+            Some((last, rest)) => write!(fmt, "{}, or {}", rest.join(", "), last),
+            None => Ok(()),
+        }
+    }
 }
 
 /// The maximum binding power for a token that can stop a projection.
-const PROJECTION_STOP: usize = 10;
+pub(crate) const PROJECTION_STOP: usize = 10;
+
+/// The maximum depth of nested expressions accepted by the parser.
+///
+/// Both the parser and the interpreter are recursive, so the depth is
+/// bounded to keep hostile expressions from overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 128;
+
+/// The maximum height of the AST accepted by the parser.
+///
+/// Chains of operators like `a.b.c` or `a || b || c` don't nest, but build
+/// left-deep trees that the interpreter, `Drop`, and the visitors recurse
+/// into once per operand, so their height is bounded as well.
+const MAX_AST_HEIGHT: usize = 256;
+
+/// Returns the number of levels between a node built by a led and its left
+/// hand side at `target`, which is at most two levels down.
+fn levels_to(node: &Ast, target: *const Ast) -> Option<usize> {
+    let mut level = vec![node];
+    for levels in 0..3 {
+        if level.iter().any(|&node| std::ptr::eq(node, target)) {
+            return Some(levels);
+        }
+        level = level.iter().flat_map(|node| node.children()).collect();
+    }
+    None
+}
+
+/// Parses a token in prefix position, given its offset.
+type NudFn<'a> = fn(&mut Parser<'a>, usize, Token) -> ParseResult;
+
+/// Parses a token in infix or postfix position, given its offset and the
+/// left hand side.
+type LedFn<'a> = fn(&mut Parser<'a>, usize, Token, Box<Ast>) -> ParseResult;
+
+/// How a kind of token is parsed. A token without a nud can't start an
+/// expression, and a token without a led can't follow one.
+struct Rule<'a> {
+    nud: Option<NudFn<'a>>,
+    led: Option<LedFn<'a>>,
+}
+
+impl<'a> Rule<'a> {
+    const NONE: Rule<'a> = Rule {
+        nud: None,
+        led: None,
+    };
+
+    const fn nud(nud: NudFn<'a>) -> Rule<'a> {
+        Rule {
+            nud: Some(nud),
+            led: None,
+        }
+    }
+
+    const fn led(led: LedFn<'a>) -> Rule<'a> {
+        Rule {
+            nud: None,
+            led: Some(led),
+        }
+    }
+
+    const fn both(nud: NudFn<'a>, led: LedFn<'a>) -> Rule<'a> {
+        Rule {
+            nud: Some(nud),
+            led: Some(led),
+        }
+    }
+}
 
 struct Parser<'a> {
     /// Parsed tokens
     token_queue: VecDeque<TokenTuple>,
+    /// End offset of each token in the queue
+    token_ends: VecDeque<usize>,
     /// Shared EOF token
     eof_token: Token,
     /// Expression being parsed
     expr: &'a str,
-    /// The current character offset in the expression
+    /// The current byte offset in the expression
     offset: usize,
+    /// The end offset of the current token
+    end: usize,
+    /// The current nesting depth of the expression being parsed
+    depth: usize,
+    /// The largest height of the expressions parsed by nested calls to
+    /// `expr` since the last nud or led started
+    height: usize,
+    /// Warnings found so far
+    warnings: Vec<ParseWarning>,
+    /// Whether `..` parses as a recursive descent
+    recursive_descent: bool,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: VecDeque<TokenTuple>, expr: &'a str) -> Parser<'a> {
+    fn new(tokens: VecDeque<TokenTuple>, ends: VecDeque<usize>, expr: &'a str) -> Parser<'a> {
         Parser {
             token_queue: tokens,
+            token_ends: ends,
             eof_token: Token::Eof,
             offset: 0,
+            end: 0,
+            depth: 0,
+            height: 0,
+            warnings: vec![],
+            recursive_descent: false,
             expr,
         }
     }
@@ -64,6 +303,7 @@ impl<'a> Parser<'a> {
         match self.token_queue.pop_front() {
             Some((pos, tok)) => {
                 self.offset = pos;
+                self.end = self.token_ends.pop_front().unwrap_or(pos);
                 (pos, tok)
             }
             None => (self.offset, Token::Eof),
@@ -73,7 +313,7 @@ impl<'a> Parser<'a> {
     #[inline]
     fn peek(&self, lookahead: usize) -> &Token {
         match self.token_queue.get(lookahead) {
-            Some(&(_, ref t)) => t,
+            Some((_, t)) => t,
             None => &self.eof_token,
         }
     }
@@ -81,179 +321,386 @@ impl<'a> Parser<'a> {
     /// Returns a formatted error with the given message.
     fn err(&self, current_token: &Token, error_msg: &str, is_peek: bool) -> JmespathError {
         let mut actual_pos = self.offset;
+        let mut end = self.end;
         let mut buff = error_msg.to_string();
         buff.push_str(&format!(" -- found {:?}", current_token));
         if is_peek {
-            if let Some(&(p, _)) = self.token_queue.get(0) {
+            if let (Some(&(p, _)), Some(&e)) = (self.token_queue.front(), self.token_ends.front()) {
                 actual_pos = p;
+                end = e;
             }
         }
+        let kind = match *current_token {
+            Token::Eof => ErrorKind::UnexpectedEnd,
+            _ => ErrorKind::UnexpectedToken,
+        };
         JmespathError::new(self.expr, actual_pos, ErrorReason::Parse(buff))
+            .with_kind(kind)
+            .with_length(end - actual_pos)
+    }
+
+    /// Returns an error listing the kinds of tokens that were expected.
+    fn err_expected(&self, token: &Token, expected: Expected, is_peek: bool) -> JmespathError {
+        self.err(token, &format!("Expected {}", expected), is_peek)
+            .with_expected(expected)
+    }
+
+    fn warn(&mut self, kind: WarningKind, offset: usize, message: &str) {
+        self.warnings.push(ParseWarning {
+            kind,
+            offset,
+            message: message.to_owned(),
+        });
     }
 
     /// Main parse function of the Pratt parser that parses while RBP < LBP
     fn expr(&mut self, rbp: usize) -> ParseResult {
-        let mut left = self.nud();
-        while rbp < self.peek(0).lbp() {
-            left = self.led(Box::new(left?));
+        // The depth is not restored on error because parse errors are
+        // never recovered from.
+        // Only nested calls count: the leds of one level, like the
+        // segments of `a.b.c`, don't make the parser recurse any deeper.
+        let (depth, outer) = (self.depth, self.height);
+        self.descend()?;
+        // The height of the result is tracked as it grows: a led puts its
+        // node, and sometimes a second one like the `Flatten` of `a[]`,
+        // above the left hand side and the expressions it parses.
+        self.height = 0;
+        let mut left = self.nud()?;
+        let mut height = self.height + 1;
+        while rbp < self.peek(0).lbp() && height <= MAX_AST_HEIGHT {
+            self.height = 0;
+            let previous = Box::new(left);
+            let target: *const Ast = &*previous;
+            left = self.led(previous)?;
+            height = levels_to(&left, target).unwrap_or(1) + height.max(self.height);
+        }
+        if height > MAX_AST_HEIGHT {
+            return Err(self
+                .err(self.peek(0), "Exceeded maximum expression height", true)
+                .with_kind(ErrorKind::NestingTooDeep));
+        }
+        self.depth = depth;
+        self.height = outer.max(height);
+        Ok(left)
+    }
+
+    /// Increments the nesting depth, failing if it exceeds the maximum.
+    #[inline]
+    fn descend(&mut self) -> Result<(), JmespathError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            Err(self
+                .err(self.peek(0), "Exceeded maximum nesting depth", true)
+                .with_kind(ErrorKind::NestingTooDeep))
+        } else {
+            Ok(())
         }
-        left
     }
 
     fn nud(&mut self) -> ParseResult {
         let (offset, token) = self.advance_with_pos();
-        match token {
-            Token::At => Ok(Ast::Identity { offset }),
-            Token::Identifier(value) => Ok(Ast::Field {
-                name: value,
-                offset,
-            }),
-            Token::QuotedIdentifier(value) => match self.peek(0) {
-                Token::Lparen => {
-                    let message = "Quoted strings can't be a function name";
-                    Err(self.err(&Token::Lparen, message, true))
-                }
-                _ => Ok(Ast::Field {
-                    name: value,
-                    offset,
-                }),
-            },
-            Token::Star => self.parse_wildcard_values(Box::new(Ast::Identity { offset })),
-            Token::Literal(value) => Ok(Ast::Literal { value, offset }),
-            Token::Lbracket => match self.peek(0) {
-                &Token::Number(_) | &Token::Colon => self.parse_index(),
-                &Token::Star if self.peek(1) == &Token::Rbracket => {
-                    self.advance();
-                    self.parse_wildcard_index(Box::new(Ast::Identity { offset }))
-                }
-                _ => self.parse_multi_list(),
-            },
-            Token::Flatten => self.parse_flatten(Box::new(Ast::Identity { offset })),
-            Token::Lbrace => {
-                let mut pairs = vec![];
-                loop {
-                    // Requires at least on key value pair.
-                    pairs.push(self.parse_kvp()?);
-                    match self.advance() {
-                        // Terminal condition is the Rbrace token
-                        Token::Rbrace => break,
-                        // Skip commas as they are used to delineate kvps
-                        Token::Comma => continue,
-                        ref t => return Err(self.err(t, "Expected '}' or ','", false)),
-                    }
-                }
-                Ok(Ast::MultiHash {
-                    elements: pairs,
-                    offset,
-                })
-            }
-            t @ Token::Ampersand => {
-                let rhs = self.expr(t.lbp())?;
-                Ok(Ast::Expref {
-                    ast: Box::new(rhs),
-                    offset,
-                })
-            }
-            t @ Token::Not => Ok(Ast::Not {
-                node: Box::new(self.expr(t.lbp())?),
-                offset,
-            }),
-            Token::Filter => self.parse_filter(Box::new(Ast::Identity { offset })),
-            Token::Lparen => {
-                let result = self.expr(0)?;
-                match self.advance() {
-                    Token::Rparen => Ok(result),
-                    ref t => Err(self.err(t, "Expected ')' to close '('", false)),
-                }
-            }
-            ref t => Err(self.err(t, "Unexpected nud token", false)),
+        match Self::RULES[token.kind()].nud {
+            Some(nud) => nud(self, offset, token),
+            None => Err(self.err(&token, "Unexpected nud token", false)),
         }
     }
 
     fn led(&mut self, left: Box<Ast>) -> ParseResult {
         let (offset, token) = self.advance_with_pos();
+        match Self::RULES[token.kind()].led {
+            Some(led) => led(self, offset, token, left),
+            None => Err(self.err(&token, "Unexpected led token", false)),
+        }
+    }
+
+    /// Parse rules of each kind of token, indexed by `Token::kind`.
+    const RULES: [Rule<'a>; TOKEN_KINDS] = [
+        Rule::nud(Self::nud_identifier),                    // Identifier
+        Rule::nud(Self::nud_quoted_identifier),             // QuotedIdentifier
+        Rule::NONE,                                         // Number
+        Rule::nud(Self::nud_literal),                       // Literal
+        Rule::both(Self::nud_dot, Self::led_dot),           // Dot
+        Rule::nud(Self::nud_star),                          // Star
+        Rule::both(Self::nud_flatten, Self::led_flatten),   // Flatten
+        Rule::led(Self::led_and),                           // And
+        Rule::led(Self::led_or),                            // Or
+        Rule::led(Self::led_pipe),                          // Pipe
+        Rule::both(Self::nud_filter, Self::led_filter),     // Filter
+        Rule::both(Self::nud_lbracket, Self::led_lbracket), // Lbracket
+        Rule::NONE,                                         // Rbracket
+        Rule::NONE,                                         // Comma
+        Rule::nud(Self::nud_colon),                         // Colon
+        Rule::nud(Self::nud_not),                           // Not
+        Rule::led(Self::led_ne),                            // Ne
+        Rule::led(Self::led_eq),                            // Eq
+        Rule::led(Self::led_gt),                            // Gt
+        Rule::led(Self::led_gte),                           // Gte
+        Rule::led(Self::led_lt),                            // Lt
+        Rule::led(Self::led_lte),                           // Lte
+        Rule::nud(Self::nud_at),                            // At
+        Rule::nud(Self::nud_ampersand),                     // Ampersand
+        Rule::both(Self::nud_lparen, Self::led_lparen),     // Lparen
+        Rule::NONE,                                         // Rparen
+        Rule::nud(Self::nud_lbrace),                        // Lbrace
+        Rule::NONE,                                         // Rbrace
+        Rule::NONE,                                         // Eof
+    ];
+
+    fn nud_dot(&mut self, offset: usize, token: Token) -> ParseResult {
+        if self.at_descent(offset) {
+            self.advance();
+            self.parse_recursive_descent(offset, Box::new(Ast::Identity { offset }))
+        } else {
+            Err(self.err(&token, "Unexpected nud token", false))
+        }
+    }
+
+    /// Parses a parameter, a colon directly followed by an identifier.
+    fn nud_colon(&mut self, offset: usize, token: Token) -> ParseResult {
+        match self.token_queue.front() {
+            Some(&(pos, Token::Identifier(_))) if pos == offset + 1 => match self.advance() {
+                Token::Identifier(name) => Ok(Ast::Parameter { offset, name }),
+                _ => unreachable!("peeked an identifier"),
+            },
+            _ => Err(self.err(&token, "Unexpected nud token", false)),
+        }
+    }
+
+    fn nud_at(&mut self, offset: usize, _: Token) -> ParseResult {
+        Ok(Ast::Identity { offset })
+    }
+
+    fn nud_identifier(&mut self, offset: usize, token: Token) -> ParseResult {
         match token {
-            t @ Token::Dot => {
-                if self.peek(0) == &Token::Star {
-                    // Skip the star and parse the rhs
-                    self.advance();
-                    self.parse_wildcard_values(left)
-                } else {
-                    let offset = offset;
-                    let rhs = self.parse_dot(t.lbp())?;
-                    Ok(Ast::Subexpr {
-                        offset,
-                        lhs: left,
-                        rhs: Box::new(rhs),
-                    })
-                }
+            Token::Identifier(name) => Ok(Ast::Field { name, offset }),
+            _ => unreachable!("dispatched by token kind"),
+        }
+    }
+
+    fn nud_quoted_identifier(&mut self, offset: usize, token: Token) -> ParseResult {
+        match (token, self.peek(0)) {
+            (_, Token::Lparen) => {
+                let message = "Quoted strings can't be a function name";
+                Err(self.err(&Token::Lparen, message, true))
             }
-            Token::Lbracket => {
-                if match self.peek(0) {
-                    &Token::Number(_) | &Token::Colon => true,
-                    &Token::Star => false,
-                    t => return Err(self.err(t, "Expected number, ':', or '*'", true)),
-                } {
-                    Ok(Ast::Subexpr {
-                        offset,
-                        lhs: left,
-                        rhs: Box::new(self.parse_index()?),
-                    })
-                } else {
-                    self.advance();
-                    self.parse_wildcard_index(left)
-                }
+            (Token::QuotedIdentifier(name), _) => Ok(Ast::Field { name, offset }),
+            _ => unreachable!("dispatched by token kind"),
+        }
+    }
+
+    fn nud_star(&mut self, offset: usize, _: Token) -> ParseResult {
+        self.parse_wildcard_values(Box::new(Ast::Identity { offset }))
+    }
+
+    fn nud_literal(&mut self, offset: usize, token: Token) -> ParseResult {
+        let value = match token {
+            Token::Literal(value) => value,
+            _ => unreachable!("dispatched by token kind"),
+        };
+        if value.is_string() && self.expr[offset..].starts_with('`') {
+            let message = "string literals should be written as raw strings ('...')";
+            self.warn(WarningKind::JsonStringLiteral, offset, message);
+        }
+        Ok(Ast::Literal { value, offset })
+    }
+
+    fn nud_lbracket(&mut self, offset: usize, _: Token) -> ParseResult {
+        match self.peek(0) {
+            &Token::Number(_) | &Token::Colon => self.parse_index(),
+            &Token::Star if self.peek(1) == &Token::Rbracket => {
+                self.advance();
+                self.parse_wildcard_index(Box::new(Ast::Identity { offset }))
             }
-            t @ Token::Or => {
-                let offset = offset;
-                let rhs = self.expr(t.lbp())?;
-                Ok(Ast::Or {
-                    offset,
-                    lhs: left,
-                    rhs: Box::new(rhs),
-                })
+            _ => self.parse_multi_list(),
+        }
+    }
+
+    fn nud_flatten(&mut self, offset: usize, _: Token) -> ParseResult {
+        self.parse_flatten(Box::new(Ast::Identity { offset }))
+    }
+
+    fn nud_lbrace(&mut self, offset: usize, _: Token) -> ParseResult {
+        let mut pairs = vec![];
+        loop {
+            // Requires at least on key value pair.
+            let pair = self.parse_kvp(&pairs)?;
+            pairs.push(pair);
+            match self.advance() {
+                // Terminal condition is the Rbrace token
+                Token::Rbrace => break,
+                // Skip commas as they are used to delineate kvps
+                Token::Comma => continue,
+                ref t => {
+                    let expected = Expected::RBRACE | Expected::COMMA;
+                    return Err(self.err_expected(t, expected, false));
+                }
             }
-            t @ Token::And => {
-                let offset = offset;
-                let rhs = self.expr(t.lbp())?;
-                Ok(Ast::And {
-                    offset,
-                    lhs: left,
-                    rhs: Box::new(rhs),
-                })
+        }
+        Ok(Ast::MultiHash {
+            elements: pairs,
+            offset,
+        })
+    }
+
+    fn nud_ampersand(&mut self, offset: usize, token: Token) -> ParseResult {
+        let rhs = self.expr(token.lbp())?;
+        Ok(Ast::Expref {
+            ast: Box::new(rhs),
+            offset,
+        })
+    }
+
+    fn nud_not(&mut self, offset: usize, token: Token) -> ParseResult {
+        Ok(Ast::Not {
+            node: Box::new(self.expr(token.lbp())?),
+            offset,
+        })
+    }
+
+    fn nud_filter(&mut self, offset: usize, _: Token) -> ParseResult {
+        self.parse_filter(Box::new(Ast::Identity { offset }))
+    }
+
+    fn nud_lparen(&mut self, _: usize, _: Token) -> ParseResult {
+        let result = self.expr(0)?;
+        match self.advance() {
+            Token::Rparen => Ok(result),
+            ref t => Err(self
+                .err(t, "Expected ')' to close '('", false)
+                .with_expected(Expected::RPAREN)),
+        }
+    }
+
+    fn led_dot(&mut self, offset: usize, token: Token, left: Box<Ast>) -> ParseResult {
+        if self.at_descent(offset) {
+            self.advance();
+            self.parse_recursive_descent(offset, left)
+        } else if self.peek(0) == &Token::Star {
+            // Skip the star and parse the rhs
+            self.advance();
+            self.parse_wildcard_values(left)
+        } else {
+            if let Ast::Identity { offset } = *left {
+                let message = "`@.` has no effect and can be removed";
+                self.warn(WarningKind::RedundantCurrentNode, offset, message);
             }
-            t @ Token::Pipe => {
-                let offset = offset;
-                let rhs = self.expr(t.lbp())?;
-                Ok(Ast::Subexpr {
-                    offset,
-                    lhs: left,
-                    rhs: Box::new(rhs),
-                })
+            let rhs = self.parse_dot(token.lbp())?;
+            Ok(Ast::Subexpr {
+                offset,
+                lhs: left,
+                rhs: Box::new(rhs),
+            })
+        }
+    }
+
+    fn led_lbracket(&mut self, offset: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        if match self.peek(0) {
+            &Token::Number(_) | &Token::Colon => true,
+            &Token::Star => false,
+            t => {
+                let expected = Expected::NUMBER | Expected::COLON | Expected::STAR;
+                return Err(self.err_expected(t, expected, true));
             }
-            Token::Lparen => match *left {
-                Ast::Field { name: v, .. } => Ok(Ast::Function {
-                    offset,
-                    name: v,
-                    args: self.parse_list(Token::Rparen)?,
-                }),
-                _ => Err(self.err(self.peek(0), "Invalid function name", true)),
-            },
-            Token::Flatten => self.parse_flatten(left),
-            Token::Filter => self.parse_filter(left),
-            Token::Eq => self.parse_comparator(Comparator::Equal, left),
-            Token::Ne => self.parse_comparator(Comparator::NotEqual, left),
-            Token::Gt => self.parse_comparator(Comparator::GreaterThan, left),
-            Token::Gte => self.parse_comparator(Comparator::GreaterThanEqual, left),
-            Token::Lt => self.parse_comparator(Comparator::LessThan, left),
-            Token::Lte => self.parse_comparator(Comparator::LessThanEqual, left),
-            ref t => Err(self.err(t, "Unexpected led token", false)),
+        } {
+            Ok(Ast::Subexpr {
+                offset,
+                lhs: left,
+                rhs: Box::new(self.parse_index()?),
+            })
+        } else {
+            self.advance();
+            self.parse_wildcard_index(left)
         }
     }
 
-    fn parse_kvp(&mut self) -> Result<KeyValuePair, JmespathError> {
-        match self.advance() {
-            Token::Identifier(value) | Token::QuotedIdentifier(value) => {
+    fn led_or(&mut self, offset: usize, token: Token, left: Box<Ast>) -> ParseResult {
+        let rhs = self.expr(token.lbp())?;
+        Ok(Ast::Or {
+            offset,
+            lhs: left,
+            rhs: Box::new(rhs),
+        })
+    }
+
+    fn led_and(&mut self, offset: usize, token: Token, left: Box<Ast>) -> ParseResult {
+        let rhs = self.expr(token.lbp())?;
+        Ok(Ast::And {
+            offset,
+            lhs: left,
+            rhs: Box::new(rhs),
+        })
+    }
+
+    fn led_pipe(&mut self, offset: usize, token: Token, left: Box<Ast>) -> ParseResult {
+        let rhs = self.expr(token.lbp())?;
+        if let Ast::Identity { .. } = rhs {
+            let message = "piping to `@` has no effect and can be removed";
+            self.warn(WarningKind::IdentityPipe, offset, message);
+        }
+        Ok(Ast::Subexpr {
+            offset,
+            lhs: left,
+            rhs: Box::new(rhs),
+        })
+    }
+
+    // The signature is fixed by `LedFn`.
+    #[allow(clippy::boxed_local)]
+    fn led_lparen(&mut self, offset: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        match *left {
+            Ast::Field { name: v, .. } => Ok(Ast::Function {
+                offset,
+                name: v,
+                args: self.parse_list(Token::Rparen)?,
+            }),
+            _ => Err(self.err(self.peek(0), "Invalid function name", true)),
+        }
+    }
+
+    fn led_flatten(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_flatten(left)
+    }
+
+    fn led_filter(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_filter(left)
+    }
+
+    fn led_eq(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::Equal, left)
+    }
+
+    fn led_ne(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::NotEqual, left)
+    }
+
+    fn led_gt(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::GreaterThan, left)
+    }
+
+    fn led_gte(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::GreaterThanEqual, left)
+    }
+
+    fn led_lt(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::LessThan, left)
+    }
+
+    fn led_lte(&mut self, _: usize, _: Token, left: Box<Ast>) -> ParseResult {
+        self.parse_comparator(Comparator::LessThanEqual, left)
+    }
+
+    /// Parses a key value pair of a multi-select hash, warning if the key
+    /// is one of the `previous` keys.
+    fn parse_kvp(&mut self, previous: &[KeyValuePair]) -> Result<KeyValuePair, JmespathError> {
+        match self.advance_with_pos() {
+            (offset, Token::Identifier(value)) | (offset, Token::QuotedIdentifier(value)) => {
+                if previous.iter().any(|kvp| kvp.key == value) {
+                    let message = format!(
+                        "key {} is repeated and only its last value is used",
+                        render_name(&value)
+                    );
+                    self.warn(WarningKind::DuplicateKey, offset, &message);
+                }
                 if self.peek(0) == &Token::Colon {
                     self.advance();
                     Ok(KeyValuePair {
@@ -261,10 +708,13 @@ impl<'a> Parser<'a> {
                         value: self.expr(0)?,
                     })
                 } else {
-                    Err(self.err(self.peek(0), "Expected ':' to follow key", true))
+                    let error = self.err(self.peek(0), "Expected ':' to follow key", true);
+                    Err(error.with_expected(Expected::COLON))
                 }
             }
-            ref t => Err(self.err(t, "Expected Field to start key value pair", false)),
+            (_, ref t) => Err(self
+                .err(t, "Expected Field to start key value pair", false)
+                .with_expected(Expected::IDENTIFIER)),
         }
     }
 
@@ -288,7 +738,7 @@ impl<'a> Parser<'a> {
                     }),
                 })
             }
-            ref t => Err(self.err(t, "Expected ']'", false)),
+            ref t => Err(self.err_expected(t, Expected::RBRACKET, false)),
         }
     }
 
@@ -315,8 +765,38 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Returns true if the dot at `offset` is directly followed by another
+    /// dot that starts a recursive descent.
+    fn at_descent(&self, offset: usize) -> bool {
+        self.recursive_descent
+            && matches!(self.token_queue.front(), Some(&(pos, Token::Dot)) if pos == offset + 1)
+    }
+
+    /// Creates a projection for "..name", given the offset of the first dot.
+    fn parse_recursive_descent(&mut self, offset: usize, lhs: Box<Ast>) -> ParseResult {
+        let name = match self.advance() {
+            Token::Identifier(name) | Token::QuotedIdentifier(name) => name,
+            ref t => return Err(self.err_expected(t, Expected::IDENTIFIER, false)),
+        };
+        let rhs = Box::new(self.projection_rhs(Token::Star.lbp())?);
+        Ok(Ast::Projection {
+            offset,
+            lhs: Box::new(Ast::RecursiveDescent {
+                offset,
+                node: lhs,
+                name,
+            }),
+            rhs,
+        })
+    }
+
     /// Parses the right hand side of a dot expression.
     fn parse_dot(&mut self, lbp: usize) -> ParseResult {
+        if self.at_descent(self.offset) {
+            let offset = self.offset;
+            self.advance();
+            return self.parse_recursive_descent(offset, Box::new(Ast::Identity { offset }));
+        }
         if match self.peek(0) {
             &Token::Lbracket => true,
             &Token::Identifier(_)
@@ -324,7 +804,15 @@ impl<'a> Parser<'a> {
             | &Token::Star
             | &Token::Lbrace
             | &Token::Ampersand => false,
-            t => return Err(self.err(t, "Expected identifier, '*', '{', '[', '&', or '[?'", true)),
+            t => {
+                let expected = Expected::IDENTIFIER
+                    | Expected::STAR
+                    | Expected::LBRACE
+                    | Expected::LBRACKET
+                    | Expected::AMPERSAND
+                    | Expected::FILTER;
+                return Err(self.err_expected(t, expected, true));
+            }
         } {
             self.advance();
             self.parse_multi_list()
@@ -345,7 +833,8 @@ impl<'a> Parser<'a> {
                 });
             }
             t => {
-                return Err(self.err(t, "Expected '.', '[', or '[?'", true));
+                let expected = Expected::DOT | Expected::LBRACKET | Expected::FILTER;
+                return Err(self.err_expected(t, expected, true));
             }
         } {
             self.advance();
@@ -366,7 +855,9 @@ impl<'a> Parser<'a> {
                     rhs,
                 })
             }
-            ref t => Err(self.err(t, "Expected ']' for wildcard index", false)),
+            ref t => Err(self
+                .err(t, "Expected ']' for wildcard index", false)
+                .with_expected(Expected::RBRACKET)),
         }
     }
 
@@ -393,7 +884,10 @@ impl<'a> Parser<'a> {
                     parts[pos] = Some(value);
                     match self.peek(0) {
                         &Token::Colon | &Token::Rbracket => (),
-                        t => return Err(self.err(t, "Expected ':', or ']'", true)),
+                        t => {
+                            let expected = Expected::COLON | Expected::RBRACKET;
+                            return Err(self.err_expected(t, expected, true));
+                        }
                     };
                 }
                 Token::Rbracket => break,
@@ -404,10 +898,16 @@ impl<'a> Parser<'a> {
                     pos += 1;
                     match self.peek(0) {
                         &Token::Number(_) | &Token::Colon | &Token::Rbracket => continue,
-                        t => return Err(self.err(t, "Expected number, ':', or ']'", true)),
+                        t => {
+                            let expected = Expected::NUMBER | Expected::COLON | Expected::RBRACKET;
+                            return Err(self.err_expected(t, expected, true));
+                        }
                     };
                 }
-                ref t => return Err(self.err(t, "Expected number, ':', or ']'", false)),
+                ref t => {
+                    let expected = Expected::NUMBER | Expected::COLON | Expected::RBRACKET;
+                    return Err(self.err_expected(t, expected, false));
+                }
             }
         }
 
@@ -471,3 +971,150 @@ impl<'a> Parser<'a> {
         Ok(nodes)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejects_expressions_nested_too_deeply() {
+        let nested = format!("{}a{}", "(".repeat(300), ")".repeat(300));
+        let err = parse(&nested).unwrap_err();
+        assert!(err.to_string().contains("Exceeded maximum nesting depth"));
+        let nested = format!("{}a{}", "[".repeat(100), "]".repeat(100));
+        assert!(parse(&nested).is_ok());
+    }
+
+    #[test]
+    fn parses_long_flat_chains() {
+        let chain = |separator: &str, operands: usize| vec!["a"; operands].join(separator);
+        let data = crate::Variable::from_json(r#"{"a": {"a": 1}}"#).unwrap();
+        for separator in &[".", " || ", " | ", " && "] {
+            let expr = crate::compile(&chain(separator, 250)).unwrap();
+            assert!(expr.search(&data).is_ok(), "{}", separator);
+            // Longer chains are rejected instead of overflowing the stack
+            // when they are evaluated or dropped.
+            for &operands in &[10_000, 100_000] {
+                let expression = chain(separator, operands);
+                let error = crate::compile(&expression).unwrap_err();
+                assert_eq!(ErrorKind::NestingTooDeep, error.kind, "{}", separator);
+                assert!(crate::Runtime::new().search(&expression, &data).is_err());
+            }
+        }
+        let nested = format!("{}{}{}", "[".repeat(100), chain(".", 200), "]".repeat(100));
+        assert!(parse(&nested).is_err());
+    }
+
+    #[test]
+    fn parses_recursive_descent_when_enabled() {
+        let options = ParserOptions {
+            recursive_descent: true,
+            ..ParserOptions::default()
+        };
+        let parse = |expr: &str| parse_with_options(expr, &options).map(|(ast, _)| ast);
+        for expr in &["..a", "a..b", "a..b.c[0]", "a[*]..b", "a..\"b c\" | [0]"] {
+            assert_eq!(*expr, parse(expr).unwrap().to_string());
+        }
+        assert_eq!("..a", parse("@..a").unwrap().to_string());
+        for expr in &["a..", "a. .b", "a..*", "a...b"] {
+            assert!(parse(expr).is_err(), "{}", expr);
+        }
+        let error = crate::parse("a..b").unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedToken, error.kind);
+        assert!(crate::parse("..a").is_err());
+    }
+
+    #[test]
+    fn does_not_panic_on_token_soup() {
+        // Deterministically generate odd combinations of tokens and make
+        // sure that parsing (and evaluating anything that parses) never
+        // panics.
+        let atoms = [
+            "a",
+            "\"b\"",
+            "'c'",
+            "`1`",
+            "`[1]`",
+            "0",
+            "-1",
+            "2147483647",
+            ".",
+            "*",
+            "[]",
+            "[?",
+            "[",
+            "]",
+            "{",
+            "}",
+            "(",
+            ")",
+            ",",
+            ":",
+            "|",
+            "||",
+            "&&",
+            "&",
+            "!",
+            "==",
+            "<",
+            ">=",
+            "@",
+            "length",
+            "\u{e9}",
+            " ",
+        ];
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..5000 {
+            let mut expr = String::new();
+            for _ in 0..(seed % 12) {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                expr.push_str(atoms[(seed % atoms.len() as u64) as usize]);
+            }
+            if let Ok(ast) = parse(&expr) {
+                let mut ctx = crate::Context::new(&expr, &crate::DEFAULT_RUNTIME);
+                let data =
+                    crate::Rcvar::new(crate::Variable::from_json("{\"a\": [1, [2]]}").unwrap());
+                let _ = crate::interpreter::interpret(&data, &ast, &mut ctx);
+            }
+        }
+    }
+
+    #[test]
+    fn collects_warnings() {
+        use self::WarningKind::*;
+        let cases = vec![
+            ("`\"a\"` == 'a'", vec![(JsonStringLiteral, 0)]),
+            ("foo[?@.bar > `1`]", vec![(RedundantCurrentNode, 5)]),
+            ("foo | @", vec![(IdentityPipe, 4)]),
+            (
+                "a | (b | @) | @.c",
+                vec![(IdentityPipe, 7), (RedundantCurrentNode, 14)],
+            ),
+            ("@ | `[\"a\"]`.foo", vec![]),
+            (
+                "{a: b, \"a\": c, b: d, a: e}",
+                vec![(DuplicateKey, 7), (DuplicateKey, 21)],
+            ),
+        ];
+        for (expr, expected) in cases {
+            let (_, warnings) = parse_with_warnings(expr).unwrap();
+            let actual: Vec<_> = warnings.iter().map(|w| (w.kind, w.offset)).collect();
+            assert_eq!(expected, actual, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn lists_expected_token_kinds() {
+        assert_eq!("']'", Expected::RBRACKET.to_string());
+        assert_eq!(
+            "':' or ']'",
+            (Expected::RBRACKET | Expected::COLON).to_string()
+        );
+        assert_eq!(
+            "number, ':', or '*'",
+            (Expected::STAR | Expected::NUMBER | Expected::COLON).to_string()
+        );
+    }
+}
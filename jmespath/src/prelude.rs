@@ -0,0 +1,21 @@
+//! The types and functions needed by most users of the crate.
+//!
+//! ```
+//! use jmespath::prelude::*;
+//!
+//! fn names(data: Variable) -> Result<Rcvar> {
+//!     let expr: Expression = "people[*].name".parse()?;
+//!     expr.search(data)
+//! }
+//!
+//! let data = Variable::from_json(r#"{"people": [{"name": "a"}]}"#).unwrap();
+//! assert_eq!(r#"["a"]"#, names(data).unwrap().to_string());
+//! ```
+
+pub use crate::functions::{ArgumentType, CustomFunction, Function, Signature};
+#[cfg(feature = "iter")]
+pub use crate::iter::JmespathIteratorExt;
+pub use crate::{
+    compile, default_runtime, search, Context, EvalOptions, Expression, JmespathError, Rcvar,
+    Result, Runtime, RuntimeBuilder, ToJmespath, Variable, DEFAULT_RUNTIME,
+};
@@ -0,0 +1,179 @@
+//! Per-node profiling of expression evaluation.
+//!
+//! A `Profiler` evaluates expressions node by node and accumulates how
+//! often each AST node was evaluated and how long it took, across every
+//! search made through it. Its report lists the nodes by the time spent in
+//! them, so the filter or function call that dominates the cost of a slow
+//! expression is at the top.
+//!
+//! ```
+//! use jmespath::profile::Profiler;
+//!
+//! let expr = jmespath::compile("people[?age > `40`].name").unwrap();
+//! let data = jmespath::Variable::from_json(
+//!     r#"{"people": [{"name": "a", "age": 50}, {"name": "b", "age": 30}]}"#,
+//! )
+//! .unwrap();
+//! let mut profiler = Profiler::new();
+//! for _ in 0..10 {
+//!     profiler.search(&expr, &data).unwrap();
+//! }
+//! let report = profiler.report();
+//! let comparison = report.iter().find(|entry| entry.node == "age > `40`").unwrap();
+//! assert_eq!(20, comparison.calls);
+//! println!("{}", profiler);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::ast::{Ast, NodeKind};
+use crate::debugger::{DebugAction, Debugger, Frame};
+use crate::interpreter::SearchResult;
+use crate::{Expression, ToJmespath};
+
+/// The time spent evaluating one AST node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// Offset of the node in the expression.
+    pub offset: usize,
+    /// The node, formatted as an expression.
+    pub node: String,
+    /// Number of times the node was evaluated.
+    pub calls: u64,
+    /// Time spent evaluating the node, including its children.
+    pub total: Duration,
+    /// Time spent evaluating the node, excluding its children.
+    pub self_time: Duration,
+}
+
+/// Accumulates the evaluation counts and times of AST nodes.
+///
+/// Nodes are identified by their offset and kind, so the nodes of an
+/// expression searched several times, and the nodes of an expression
+/// reference evaluated by a function, add up to one entry each.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    entries: HashMap<(usize, NodeKind), ProfileEntry>,
+    /// Start time and time spent in children of the nodes being evaluated.
+    stack: Vec<(Instant, Duration)>,
+}
+
+impl Profiler {
+    /// Creates a profiler with no measurements.
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Searches data with an expression, measuring each node.
+    pub fn search<T: ToJmespath>(&mut self, expression: &Expression<'_>, data: T) -> SearchResult {
+        self.stack.clear();
+        expression.debug(data, self)
+    }
+
+    /// Returns the measured nodes, the ones with the most total time first.
+    pub fn report(&self) -> Vec<ProfileEntry> {
+        let mut entries: Vec<_> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| b.total.cmp(&a.total).then(a.offset.cmp(&b.offset)));
+        entries
+    }
+
+    /// Removes every measurement.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stack.clear();
+    }
+}
+
+impl Debugger for Profiler {
+    fn on_node(&mut self, _: &Frame<'_>) -> DebugAction {
+        self.stack.push((Instant::now(), Duration::ZERO));
+        DebugAction::Step
+    }
+
+    fn on_node_end(&mut self, node: &Ast, _: &SearchResult) {
+        let (start, children) = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let elapsed = start.elapsed();
+        if let Some(parent) = self.stack.last_mut() {
+            parent.1 += elapsed;
+        }
+        let entry = self
+            .entries
+            .entry((node.offset(), node.node_kind()))
+            .or_insert_with(|| ProfileEntry {
+                offset: node.offset(),
+                node: node.to_string(),
+                calls: 0,
+                total: Duration::ZERO,
+                self_time: Duration::ZERO,
+            });
+        entry.calls += 1;
+        entry.total += elapsed;
+        entry.self_time += elapsed.saturating_sub(children);
+    }
+}
+
+/// Writes the report as a table, one node per line.
+impl fmt::Display for Profiler {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            fmt,
+            "{:>8} {:>12} {:>12} {:>6}  node",
+            "calls", "total", "self", "offset"
+        )?;
+        for entry in self.report() {
+            writeln!(
+                fmt,
+                "{:>8} {:>12} {:>12} {:>6}  {}",
+                entry.calls,
+                format!("{:?}", entry.total),
+                format!("{:?}", entry.self_time),
+                entry.offset,
+                entry.node
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Variable;
+
+    #[test]
+    fn counts_node_evaluations() {
+        let expr = crate::compile("sort_by(@, &a)[0].a").unwrap();
+        let data = Variable::from_json(r#"[{"a": 3}, {"a": 1}, {"a": 2}]"#).unwrap();
+        let mut profiler = Profiler::new();
+        profiler.search(&expr, &data).unwrap();
+        profiler.search(&expr, &data).unwrap();
+        let report = profiler.report();
+        let calls = |node: &str, offset: usize| {
+            report
+                .iter()
+                .find(|entry| entry.node == node && entry.offset == offset)
+                .map(|entry| entry.calls)
+        };
+        assert_eq!(Some(2), calls("sort_by(@, &a)", 7));
+        // The expression reference is evaluated once per element.
+        assert_eq!(Some(6), calls("a", 12));
+        assert_eq!(Some(2), calls("a", 18));
+        // The root node includes the time of every other node.
+        let root = report
+            .iter()
+            .find(|entry| entry.node == "sort_by(@, &a)[0].a")
+            .unwrap();
+        for entry in &report {
+            assert!(entry.self_time <= entry.total);
+            assert!(entry.total <= root.total);
+        }
+        assert_eq!(report.len() + 1, profiler.to_string().lines().count());
+        profiler.clear();
+        assert!(profiler.report().is_empty());
+    }
+}
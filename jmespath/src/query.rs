@@ -0,0 +1,318 @@
+//! Programmatic construction of JMESPath expressions.
+//!
+//! `Query` builds an `Ast` directly rather than concatenating strings, so
+//! values taken from user input (field names, literals, etc.) can never
+//! change the structure of the expression.
+//!
+//! ```
+//! use jmespath::query::Query;
+//!
+//! // foo[0].bar[*].baz
+//! let query = Query::field("foo")
+//!     .index(0)
+//!     .get("bar")
+//!     .project(Query::field("baz"));
+//! assert_eq!("foo[0].bar[*].baz", query.to_expression_string());
+//!
+//! // Names are quoted as needed.
+//! let query = Query::field("a.b").get("\"c\"");
+//! assert_eq!(r#""a.b"."\"c\"""#, query.to_expression_string());
+//! ```
+//!
+//! Methods that are chained onto a query, such as `get` and `index`, apply
+//! to the result of the entire query, like the right hand side of a pipe.
+//! To keep applying an expression to each element of a projection, pass it
+//! as the argument of `project`, `filter`, `values`, or `flatten`.
+
+use crate::ast::{Ast, Comparator, KeyValuePair, SmallString};
+use crate::{Expression, Rcvar, Runtime, Variable, DEFAULT_RUNTIME};
+
+/// Builds a JMESPath AST.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Query {
+    ast: Ast,
+}
+
+impl Query {
+    /// Creates a query that returns the current node (`@`).
+    pub fn identity() -> Query {
+        Query::from(Ast::Identity { offset: 0 })
+    }
+
+    /// Creates a query that returns a field of the current node.
+    pub fn field<S: Into<String>>(name: S) -> Query {
+        Query::from(Ast::Field {
+            offset: 0,
+            name: SmallString::from(name.into()),
+        })
+    }
+
+    /// Creates a query that returns a literal value.
+    pub fn literal(value: Variable) -> Query {
+        Query::from(Ast::Literal {
+            offset: 0,
+            value: Rcvar::new(value),
+        })
+    }
+
+    /// Creates a query that calls a function.
+    pub fn function<S: Into<String>>(name: S, args: Vec<Query>) -> Query {
+        Query::from(Ast::Function {
+            offset: 0,
+            name: SmallString::from(name.into()),
+            args: args.into_iter().map(Query::into_ast).collect(),
+        })
+    }
+
+    /// Creates a multi-select list (`[a, b]`) of the given queries.
+    pub fn multi_list(elements: Vec<Query>) -> Query {
+        Query::from(Ast::MultiList {
+            offset: 0,
+            elements: elements.into_iter().map(Query::into_ast).collect(),
+        })
+    }
+
+    /// Creates a multi-select hash (`{a: a, b: b}`) of the given keys and
+    /// queries.
+    pub fn multi_hash<S: Into<String>>(elements: Vec<(S, Query)>) -> Query {
+        Query::from(Ast::MultiHash {
+            offset: 0,
+            elements: elements
+                .into_iter()
+                .map(|(key, value)| KeyValuePair {
+                    key: SmallString::from(key.into()),
+                    value: value.into_ast(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Evaluates `rhs` against the result of this query (`a | b`).
+    pub fn then(self, rhs: Query) -> Query {
+        Query::from(Ast::Subexpr {
+            offset: 0,
+            lhs: Box::new(self.ast),
+            rhs: Box::new(rhs.ast),
+        })
+    }
+
+    /// Returns a field of the result (`a.b`).
+    pub fn get<S: Into<String>>(self, name: S) -> Query {
+        self.then(Query::field(name))
+    }
+
+    /// Returns an element of the result (`a[0]`).
+    pub fn index(self, idx: i32) -> Query {
+        self.then(Query::from(Ast::Index { offset: 0, idx }))
+    }
+
+    /// Returns a slice of the result (`a[start:stop:step]`).
+    pub fn slice(self, start: Option<i32>, stop: Option<i32>, step: i32) -> Query {
+        self.then(Query::from(Ast::Projection {
+            offset: 0,
+            lhs: Box::new(Ast::Slice {
+                offset: 0,
+                start,
+                stop,
+                step,
+            }),
+            rhs: Box::new(Ast::Identity { offset: 0 }),
+        }))
+    }
+
+    /// Projects `rhs` onto each element of the result (`a[*].b`).
+    pub fn project(self, rhs: Query) -> Query {
+        Query::from(Ast::Projection {
+            offset: 0,
+            lhs: Box::new(self.ast),
+            rhs: Box::new(rhs.ast),
+        })
+    }
+
+    /// Projects `rhs` onto each value of the resulting object (`a.*.b`).
+    pub fn values(self, rhs: Query) -> Query {
+        Query::from(Ast::ObjectValues {
+            offset: 0,
+            node: Box::new(self.ast),
+        })
+        .project(rhs)
+    }
+
+    /// Flattens the result and projects `rhs` onto each element (`a[].b`).
+    pub fn flatten(self, rhs: Query) -> Query {
+        Query::from(Ast::Flatten {
+            offset: 0,
+            node: Box::new(self.ast),
+        })
+        .project(rhs)
+    }
+
+    /// Projects `rhs` onto each element of the result for which
+    /// `predicate` is truthy (`a[?predicate].b`).
+    pub fn filter(self, predicate: Query, rhs: Query) -> Query {
+        self.project(Query::from(Ast::Condition {
+            offset: 0,
+            predicate: Box::new(predicate.ast),
+            then: Box::new(rhs.ast),
+        }))
+    }
+
+    /// Returns this result if it is truthy, otherwise `rhs` (`a || b`).
+    pub fn or(self, rhs: Query) -> Query {
+        Query::from(Ast::Or {
+            offset: 0,
+            lhs: Box::new(self.ast),
+            rhs: Box::new(rhs.ast),
+        })
+    }
+
+    /// Returns `rhs` if this result is truthy, otherwise this result
+    /// (`a && b`).
+    pub fn and(self, rhs: Query) -> Query {
+        Query::from(Ast::And {
+            offset: 0,
+            lhs: Box::new(self.ast),
+            rhs: Box::new(rhs.ast),
+        })
+    }
+
+    /// Negates the truthiness of the result (`!a`).
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Query {
+        Query::from(Ast::Not {
+            offset: 0,
+            node: Box::new(self.ast),
+        })
+    }
+
+    /// Compares the result to `rhs` (`a == b`, `a < b`, etc).
+    pub fn compare(self, comparator: Comparator, rhs: Query) -> Query {
+        Query::from(Ast::Comparison {
+            offset: 0,
+            comparator,
+            lhs: Box::new(self.ast),
+            rhs: Box::new(rhs.ast),
+        })
+    }
+
+    /// Creates an expression reference to this query (`&a`).
+    pub fn expref(self) -> Query {
+        Query::from(Ast::Expref {
+            offset: 0,
+            ast: Box::new(self.ast),
+        })
+    }
+
+    /// Returns the built AST.
+    pub fn as_ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    /// Consumes the query, returning the built AST.
+    pub fn into_ast(self) -> Ast {
+        self.ast
+    }
+
+    /// Returns the query as JMESPath expression text.
+    pub fn to_expression_string(&self) -> String {
+        self.ast.to_expression_string()
+    }
+
+    /// Creates an `Expression` that uses the default runtime.
+    pub fn compile(self) -> Expression<'static> {
+        self.compile_with(&DEFAULT_RUNTIME)
+    }
+
+    /// Creates an `Expression` that uses the given runtime.
+    pub fn compile_with(self, runtime: &Runtime) -> Expression<'_> {
+        Expression::new(self.to_expression_string(), self.ast, runtime)
+    }
+}
+
+impl From<Ast> for Query {
+    fn from(ast: Ast) -> Query {
+        Query { ast }
+    }
+}
+
+impl From<Query> for Ast {
+    fn from(query: Query) -> Ast {
+        query.ast
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn search(query: Query, data: &str) -> Rcvar {
+        query
+            .compile()
+            .search(Variable::from_json(data).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn builds_expressions_that_reparse() {
+        let queries = vec![
+            (
+                Query::field("foo").index(0).project(Query::field("bar")),
+                "foo[0][*].bar",
+            ),
+            (
+                Query::field("people").filter(
+                    Query::field("age").compare(
+                        Comparator::GreaterThan,
+                        Query::literal(Variable::from_json("20").unwrap()),
+                    ),
+                    Query::multi_hash(vec![("name", Query::field("name"))]),
+                ),
+                "people[?age > `20`].{name: name}",
+            ),
+            (
+                Query::function("length", vec![Query::identity()])
+                    .or(Query::field("a").and(Query::field("b").not())),
+                "length(@) || a && !b",
+            ),
+            (
+                Query::field("a")
+                    .values(Query::identity())
+                    .slice(None, None, -1),
+                "a.* | [::-1]",
+            ),
+            (
+                Query::function(
+                    "sort_by",
+                    vec![Query::field("a"), Query::field("b").expref()],
+                )
+                .flatten(Query::multi_list(vec![Query::field("c")])),
+                "sort_by(a, &b)[][c]",
+            ),
+        ];
+        for (query, expected) in queries {
+            assert_eq!(expected, query.to_expression_string());
+            assert!(crate::parse(expected).is_ok());
+        }
+    }
+
+    #[test]
+    fn names_from_input_cannot_inject_syntax() {
+        let query = Query::field("a || b").get("c[0]");
+        assert_eq!(
+            *search(query, r#"{"a || b": {"c[0]": 1}, "a": 2}"#),
+            Variable::from_json("1").unwrap()
+        );
+    }
+
+    #[test]
+    fn compiles_query_with_expression_text() {
+        let expr = Query::field("foo").get("bar").compile();
+        assert_eq!("foo.bar", expr.as_str());
+        assert_eq!(
+            *expr
+                .search(Variable::from_json(r#"{"foo": {"bar": true}}"#).unwrap())
+                .unwrap(),
+            Variable::Bool(true)
+        );
+    }
+}
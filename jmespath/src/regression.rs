@@ -0,0 +1,287 @@
+//! Regression tests of expressions against JSON fixtures.
+//!
+//! A fixture is a JSON object with an `expression`, the `input` document
+//! it is evaluated against, and either the `expected` result or the
+//! `error` code (see `ErrorKind::code`) it is expected to fail with. A
+//! fixture file holds one fixture or an array of them; fixtures can have a
+//! `name`, and are otherwise named after their file and position.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use jmespath::regression::run_directory;
+//!
+//! let report = run_directory(Path::new("tests/rules"), jmespath::default_runtime()).unwrap();
+//! println!("{}", report.to_json());
+//! assert!(report.is_success());
+//! ```
+//!
+//! The `jp test <directory>` command of the CLI runs a directory of
+//! fixtures the same way.
+
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::{Rcvar, Runtime, Variable};
+
+/// An expression with an input document and the expected outcome.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixture {
+    /// Name of the fixture in reports.
+    pub name: String,
+    /// The expression to evaluate.
+    pub expression: String,
+    /// The document the expression is evaluated against.
+    pub input: Rcvar,
+    /// The expected result, or the code of the expected error.
+    pub expected: Result<Rcvar, String>,
+}
+
+impl Fixture {
+    /// Parses a fixture object, naming it `name` unless it has a name.
+    pub fn from_json(name: &str, value: &Value) -> Result<Fixture, String> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| format!("{}: fixture is not an object", name))?;
+        let name = match object.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => return Err(format!("{}: name is not a string", name)),
+            None => name.to_owned(),
+        };
+        let expression = object
+            .get("expression")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{}: expression is missing or not a string", name))?
+            .to_owned();
+        let variable = |value: &Value| {
+            Variable::try_from(value)
+                .map(Rcvar::new)
+                .map_err(|e| e.to_string())
+        };
+        let input = variable(object.get("input").unwrap_or(&Value::Null))?;
+        let expected = match (object.get("expected"), object.get("error")) {
+            (Some(expected), None) => Ok(variable(expected)?),
+            (None, Some(Value::String(code))) => Err(code.clone()),
+            (None, Some(_)) => return Err(format!("{}: error is not a string", name)),
+            _ => return Err(format!("{}: fixture needs either expected or error", name)),
+        };
+        Ok(Fixture {
+            name,
+            expression,
+            input,
+            expected,
+        })
+    }
+
+    /// Evaluates the fixture with a runtime.
+    pub fn run(&self, runtime: &Runtime) -> FixtureResult {
+        let actual = runtime
+            .search(&self.expression, &self.input)
+            .map_err(|e| (e.kind.code().to_owned(), e.to_string()));
+        let outcome = match (&self.expected, actual) {
+            (Ok(expected), Ok(actual)) if *expected == actual => Outcome::Passed,
+            (Err(expected), Err((code, _))) if *expected == code => Outcome::Passed,
+            (_, Ok(actual)) => Outcome::Failed(Ok(actual)),
+            (_, Err((code, message))) => Outcome::Failed(Err((code, message))),
+        };
+        FixtureResult {
+            fixture: self.clone(),
+            outcome,
+        }
+    }
+}
+
+/// Whether a fixture had its expected outcome.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// The expression had the expected result or error.
+    Passed,
+    /// The expression had another result, or failed with the given error
+    /// code and message.
+    Failed(Result<Rcvar, (String, String)>),
+}
+
+/// The outcome of one fixture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixtureResult {
+    /// The fixture that was run.
+    pub fixture: Fixture,
+    /// Whether it passed.
+    pub outcome: Outcome,
+}
+
+impl FixtureResult {
+    /// Returns true if the fixture had its expected outcome.
+    pub fn is_success(&self) -> bool {
+        self.outcome == Outcome::Passed
+    }
+
+    /// Returns the result as a JSON object with `name`, `expression`, and
+    /// `status` keys, and the expected and actual outcomes of a failure.
+    pub fn to_json(&self) -> Value {
+        let fixture = &self.fixture;
+        let mut value = json!({
+            "name": fixture.name,
+            "expression": fixture.expression,
+            "status": if self.is_success() { "passed" } else { "failed" },
+        });
+        if let Outcome::Failed(ref actual) = self.outcome {
+            let object = value.as_object_mut().unwrap();
+            match fixture.expected {
+                Ok(ref expected) => object.insert("expected".to_owned(), json!(expected)),
+                Err(ref code) => object.insert("expected_error".to_owned(), json!(code)),
+            };
+            match *actual {
+                Ok(ref actual) => object.insert("actual".to_owned(), json!(actual)),
+                Err((ref code, ref message)) => {
+                    object.insert("actual_error".to_owned(), json!(code));
+                    object.insert("message".to_owned(), json!(message))
+                }
+            };
+        }
+        value
+    }
+}
+
+/// Results of running fixtures, in the order they were run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegressionReport {
+    /// The result of each fixture.
+    pub results: Vec<FixtureResult>,
+}
+
+impl RegressionReport {
+    /// Returns the number of fixtures that passed.
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.is_success()).count()
+    }
+
+    /// Returns the number of fixtures that failed.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Returns true if no fixture failed.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Returns the report as a JSON object with `passed` and `failed`
+    /// counts and the `results` of the fixtures.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "passed": self.passed(),
+            "failed": self.failed(),
+            "results": self.results.iter().map(FixtureResult::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for RegressionReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        for result in self.results.iter().filter(|r| !r.is_success()) {
+            let fixture = &result.fixture;
+            write!(
+                fmt,
+                "FAILED {}: {}\n  expected: ",
+                fixture.name, fixture.expression
+            )?;
+            match fixture.expected {
+                Ok(ref expected) => write!(fmt, "{}", expected)?,
+                Err(ref code) => write!(fmt, "error {}", code)?,
+            }
+            match result.outcome {
+                Outcome::Failed(Ok(ref actual)) => writeln!(fmt, "\n  actual:   {}", actual)?,
+                Outcome::Failed(Err((ref code, _))) => {
+                    writeln!(fmt, "\n  actual:   error {}", code)?
+                }
+                Outcome::Passed => writeln!(fmt)?,
+            }
+        }
+        write!(fmt, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+/// Reads the fixtures of a file, which holds a fixture or an array of
+/// fixtures.
+pub fn load_file(path: &Path) -> Result<Vec<Fixture>, String> {
+    let filename = path.to_string_lossy().into_owned();
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", filename, e))?;
+    let json =
+        serde_json::from_str::<Value>(&contents).map_err(|e| format!("{}: {}", filename, e))?;
+    match json {
+        Value::Array(fixtures) => fixtures
+            .iter()
+            .enumerate()
+            .map(|(i, fixture)| Fixture::from_json(&format!("{}#{}", filename, i), fixture))
+            .collect(),
+        fixture => Ok(vec![Fixture::from_json(&filename, &fixture)?]),
+    }
+}
+
+/// Runs every `.json` fixture file found in a directory with a runtime.
+pub fn run_directory(dir: &Path, runtime: &Runtime) -> Result<RegressionReport, String> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut report = RegressionReport::default();
+    for path in paths {
+        for fixture in load_file(&path)? {
+            report.results.push(fixture.run(runtime));
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(fixture: Value) -> FixtureResult {
+        Fixture::from_json("test", &fixture)
+            .unwrap()
+            .run(crate::default_runtime())
+    }
+
+    #[test]
+    fn runs_fixtures() {
+        let passed = run(json!({"expression": "a", "input": {"a": 1}, "expected": 1.0}));
+        assert!(passed.is_success());
+        let failed = run(json!({"name": "b", "expression": "a", "input": {"a": 1}, "expected": 2}));
+        assert_eq!(
+            json!({"name": "b", "expression": "a", "status": "failed", "expected": 2, "actual": 1}),
+            failed.to_json()
+        );
+        let error = run(json!({"expression": "abs(a)", "error": "invalid_type"}));
+        assert!(error.is_success());
+        let error = run(json!({"expression": "abs(a)", "expected": null}));
+        assert_eq!("invalid_type", error.to_json()["actual_error"]);
+        let report = RegressionReport {
+            results: vec![passed, failed],
+        };
+        assert_eq!(
+            "FAILED b: a\n  expected: 2\n  actual:   1\n1 passed, 1 failed",
+            report.to_string()
+        );
+        assert_eq!(json!(1), report.to_json()["failed"]);
+    }
+
+    #[test]
+    fn rejects_invalid_fixtures() {
+        let err = |fixture: Value| Fixture::from_json("test", &fixture).unwrap_err();
+        assert_eq!("test: fixture is not an object", err(json!([])));
+        assert_eq!(
+            "test: expression is missing or not a string",
+            err(json!({"expected": 1}))
+        );
+        assert_eq!(
+            "test: fixture needs either expected or error",
+            err(json!({"expression": "a", "expected": 1, "error": "parse"}))
+        );
+    }
+}
@@ -1,26 +1,382 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
+use crate::ast::Ast;
 use crate::functions::*;
-use crate::parse;
-use crate::Expression;
-use crate::JmespathError;
+use crate::interpreter::SearchResult;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::parse_with_options;
+use crate::stats::stats;
+use crate::visitor::AstVisitor;
+use crate::{ErrorReason, Expression, JmespathError, Map, Rcvar, RuntimeError, ToJmespath};
 
 /// Compiles JMESPath expressions.
 ///
 /// Most use cases don't need to worry about how Runtime works.
 /// You really only need to create your own Runtimes if you are
-/// utilizing custom functions in your expressions.
+/// utilizing custom functions in your expressions, or limiting the
+/// expressions it accepts. A `RuntimeBuilder` configures all of that at once:
+///
+/// ```
+/// use jmespath::{Limits, Runtime};
+///
+/// let runtime = Runtime::builder()
+///     .limits(Limits {
+///         max_expression_length: Some(16),
+///         ..Limits::default()
+///     })
+///     .build();
+/// assert!(runtime.compile("length(foo)").is_ok());
+/// assert!(runtime.compile("length(foo.bar.baz)").is_err());
+/// ```
+#[derive(Clone)]
 pub struct Runtime {
     // functions: HashMap<String, Box<dyn Function>>,
     functions: HashMap<String, Arc<dyn Function>>,
+    options: ParserOptions,
+    limits: Limits,
+    key_order: KeyOrder,
+    profile: Option<Profile>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn Metrics>>,
 }
 
 impl Default for Runtime {
     fn default() -> Self {
         Runtime {
             functions: HashMap::with_capacity(26),
+            options: ParserOptions::default(),
+            limits: Limits::default(),
+            key_order: KeyOrder::default(),
+            profile: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+}
+
+/// Options that change how a Runtime parses expressions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ParserOptions {
+    /// Fails to compile expressions that have parse warnings, instead of
+    /// attaching the warnings to the compiled expression.
+    pub deny_warnings: bool,
+    /// Parses `a..name` as a recursive descent, which projects the values
+    /// of the key `name` found at any depth in `a`, like the `..` operator
+    /// of JSONPath. `..name` searches the current node. Disabled by
+    /// default, since `..` is not valid JMESPath.
+    pub recursive_descent: bool,
+    /// Masks the literals of the expression and the literal values quoted
+    /// by the message in the errors of parsing and compiling it, so they
+    /// can be logged safely. See `JmespathError::new_redacted`.
+    pub redact: bool,
+}
+
+/// Limits on the size and complexity of the expressions a Runtime compiles.
+///
+/// Expressions that exceed a limit fail to compile with a
+/// `RuntimeError::LimitExceeded` error. No limits are set by default.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Limits {
+    /// Maximum length of an expression in bytes.
+    pub max_expression_length: Option<usize>,
+    /// Maximum number of nodes in the AST of an expression.
+    pub max_nodes: Option<usize>,
+    /// Maximum depth of the AST of an expression, as reported by
+    /// `ExpressionStats::max_depth`.
+    pub max_depth: Option<usize>,
+}
+
+/// Order of the keys of the objects a Runtime visits and builds.
+///
+/// The key order applies to every step of an evaluation: object
+/// projections like `*.name` visit values in that order, `keys()` and
+/// `values()` return them in that order, and the objects built by
+/// multi-hashes like `{b: b, a: a}`, `merge()` and `deep_merge()` have
+/// their keys in that order. Objects of the searched document are returned
+/// as they are.
+///
+/// Every order is deterministic, so an expression searching the same
+/// document always produces the same result.
+///
+/// ```
+/// use jmespath::{KeyOrder, Runtime, Variable};
+///
+/// let runtime = Runtime::builder().key_order(KeyOrder::Lexicographic).build();
+/// let data = Variable::from_json(r#"{"b": 1, "a": 2}"#).unwrap();
+/// let result = runtime.search("[keys(@), merge(@, {c: `3`})]", data).unwrap();
+/// assert_eq!(r#"[["a","b"],{"a":2,"b":1,"c":3}]"#, result.to_string());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum KeyOrder {
+    /// The iteration order of `jmespath::Map`: sorted by key, or the order
+    /// of insertion with the `preserve-order` feature.
+    #[default]
+    Native,
+    /// Sorted by key, comparing the bytes of the keys.
+    Lexicographic,
+    /// The order in which the keys were inserted, which is the order of the
+    /// JSON document for parsed objects.
+    #[cfg(feature = "preserve-order")]
+    Insertion,
+}
+
+impl KeyOrder {
+    /// Returns true if objects have to be sorted to be in this order.
+    /// Without `preserve-order`, `jmespath::Map` is always sorted by key.
+    fn sorts(self) -> bool {
+        self == KeyOrder::Lexicographic && cfg!(feature = "preserve-order")
+    }
+
+    /// Returns the entries of an object in this order.
+    pub(crate) fn entries(self, map: &Map) -> Vec<(&String, &Rcvar)> {
+        let mut entries: Vec<_> = map.iter().collect();
+        if self.sorts() {
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+        }
+        entries
+    }
+
+    /// Returns an object built by an evaluation with its keys in this
+    /// order.
+    pub(crate) fn arrange(self, map: Map) -> Map {
+        if !self.sorts() {
+            return map;
+        }
+        let mut entries: Vec<_> = map.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_iter().collect()
+    }
+}
+
+/// A named set of the capabilities the functions of a Runtime may have.
+///
+/// A Runtime built with a profile only registers the functions whose
+/// capabilities are all in the profile, so expressions of untrusted users
+/// can't call the others: they are unknown functions. Parameters read the
+/// input of the evaluation too, so a profile without `Capability::Context`
+/// also fails to compile the expressions that use `:param`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use jmespath::{Context, Profile, Rcvar, Runtime};
+///
+/// let echo = |args: &[Rcvar], _: &mut Context<'_>| Ok(args[0].clone());
+/// let runtime = Runtime::builder()
+///     .profile(Profile::pure())
+///     .function("echo", Arc::new(echo))
+///     .build();
+/// assert!(runtime.get_function("length").is_some());
+/// assert!(runtime.get_function("echo").is_none());
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Profile {
+    name: String,
+    capabilities: BTreeSet<Capability>,
+}
+
+impl Profile {
+    /// Creates a profile that allows the given capabilities.
+    pub fn new<I>(name: &str, capabilities: I) -> Profile
+    where
+        I: IntoIterator<Item = Capability>,
+    {
+        Profile {
+            name: name.to_owned(),
+            capabilities: capabilities.into_iter().collect(),
+        }
+    }
+
+    /// The `pure` profile, which only allows functions that compute their
+    /// result from their arguments.
+    pub fn pure() -> Profile {
+        Profile::new("pure", vec![])
+    }
+
+    /// The `no-regex` profile, which allows every capability except
+    /// `Capability::Pattern`.
+    pub fn no_regex() -> Profile {
+        Profile::new("no-regex", vec![Capability::Context, Capability::Custom])
+    }
+
+    /// The `full` profile, which allows every capability.
+    pub fn full() -> Profile {
+        Profile::new(
+            "full",
+            vec![Capability::Pattern, Capability::Context, Capability::Custom],
+        )
+    }
+
+    /// Returns the profile named `pure`, `no-regex` or `full`.
+    pub fn named(name: &str) -> Option<Profile> {
+        match name {
+            "pure" => Some(Profile::pure()),
+            "no-regex" => Some(Profile::no_regex()),
+            "full" => Some(Profile::full()),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if the profile allows a capability.
+    pub fn allows_capability(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    /// Returns true if the profile allows every capability of a function.
+    pub fn allows(&self, function: &dyn Function) -> bool {
+        function
+            .capabilities()
+            .iter()
+            .all(|capability| self.allows_capability(*capability))
+    }
+}
+
+/// Builds a `Runtime`.
+///
+/// The built-in functions are registered unless `builtins(false)` is
+/// called. Functions added with `function` replace built-in functions of
+/// the same name.
+#[derive(Clone)]
+pub struct RuntimeBuilder {
+    builtins: bool,
+    #[cfg(feature = "extensions")]
+    extensions: bool,
+    #[cfg(feature = "datetime")]
+    datetime: bool,
+    functions: Vec<(String, Arc<dyn Function>)>,
+    options: ParserOptions,
+    limits: Limits,
+    key_order: KeyOrder,
+    profile: Option<Profile>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        RuntimeBuilder {
+            builtins: true,
+            #[cfg(feature = "extensions")]
+            extensions: false,
+            #[cfg(feature = "datetime")]
+            datetime: false,
+            functions: vec![],
+            options: ParserOptions::default(),
+            limits: Limits::default(),
+            key_order: KeyOrder::default(),
+            profile: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+}
+
+impl RuntimeBuilder {
+    /// Creates a builder with the built-in functions and no limits.
+    pub fn new() -> RuntimeBuilder {
+        RuntimeBuilder::default()
+    }
+
+    /// Sets whether the built-in functions are registered.
+    pub fn builtins(mut self, builtins: bool) -> RuntimeBuilder {
+        self.builtins = builtins;
+        self
+    }
+
+    /// Sets whether the functions of `jmespath::functions::extensions` are
+    /// registered. They are not registered by default.
+    #[cfg(feature = "extensions")]
+    pub fn extensions(mut self, extensions: bool) -> RuntimeBuilder {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Sets whether `to_timestamp` is registered. It is not a function of
+    /// the JMESPath specification, so it is not registered by default.
+    #[cfg(feature = "datetime")]
+    pub fn datetime(mut self, datetime: bool) -> RuntimeBuilder {
+        self.datetime = datetime;
+        self
+    }
+
+    /// Adds a function.
+    pub fn function(mut self, name: &str, f: Arc<dyn Function>) -> RuntimeBuilder {
+        self.functions.push((name.to_owned(), f));
+        self
+    }
+
+    /// Sets the parser options.
+    pub fn parser_options(mut self, options: ParserOptions) -> RuntimeBuilder {
+        self.options = options;
+        self
+    }
+
+    /// Sets the limits on compiled expressions.
+    pub fn limits(mut self, limits: Limits) -> RuntimeBuilder {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the order in which object projections visit the values of an
+    /// object.
+    pub fn key_order(mut self, key_order: KeyOrder) -> RuntimeBuilder {
+        self.key_order = key_order;
+        self
+    }
+
+    /// Locks the Runtime to a profile. Functions outside the profile,
+    /// built-in or not, are not registered.
+    pub fn profile(mut self, profile: Profile) -> RuntimeBuilder {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Sets the hooks that are told about the compilations and evaluations
+    /// of the Runtime.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> RuntimeBuilder {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Creates the Runtime.
+    pub fn build(self) -> Runtime {
+        let mut runtime = Runtime::new();
+        runtime.profile = self.profile;
+        if self.builtins {
+            runtime.register_builtin_functions();
+        }
+        #[cfg(feature = "extensions")]
+        {
+            if self.extensions {
+                crate::functions::extensions::register_extensions(&mut runtime);
+            }
+        }
+        #[cfg(feature = "datetime")]
+        {
+            if self.datetime {
+                runtime.register_datetime_functions();
+            }
+        }
+        for (name, f) in self.functions {
+            runtime.register_function(&name, f);
         }
+        runtime.options = self.options;
+        runtime.limits = self.limits;
+        runtime.key_order = self.key_order;
+        #[cfg(feature = "metrics")]
+        {
+            runtime.metrics = self.metrics;
+        }
+        runtime
     }
 }
 
@@ -30,19 +386,113 @@ impl Runtime {
         Default::default()
     }
 
+    /// Creates a builder for a Runtime.
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::new()
+    }
+
+    /// Returns the parser options of the runtime.
+    pub fn parser_options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// Returns the limits of the runtime.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Returns the order in which object projections visit the values of
+    /// an object.
+    pub fn key_order(&self) -> KeyOrder {
+        self.key_order
+    }
+
+    /// Returns the profile the runtime is locked to, if any.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Returns the metrics hooks of the runtime, if any.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Option<&dyn Metrics> {
+        self.metrics.as_deref()
+    }
+
     /// Creates a new JMESPath expression from an expression string.
     ///
     /// The provided expression is expected to adhere to the JMESPath
     /// grammar: <https://jmespath.org/specification.html>
     #[inline]
     pub fn compile<'a>(&'a self, expression: &str) -> Result<Expression<'a>, JmespathError> {
-        parse(expression).map(|ast| Expression::new(expression, ast, self))
+        let compile = || {
+            self.compile_unmeasured(expression)
+                .map_err(|e| e.redact_if(self.options.redact))
+        };
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(ref metrics) = self.metrics {
+                let start = Instant::now();
+                let result = compile();
+                metrics.on_compile(expression, start.elapsed(), result.as_ref().map(|_| ()));
+                return result;
+            }
+        }
+        compile()
+    }
+
+    fn compile_unmeasured<'a>(&'a self, expression: &str) -> Result<Expression<'a>, JmespathError> {
+        check_limit(
+            expression,
+            "expression length",
+            expression.len(),
+            self.limits.max_expression_length,
+        )?;
+        let (ast, warnings) = parse_with_options(expression, &self.options)?;
+        if let Some(ref profile) = self.profile {
+            if !profile.allows_capability(Capability::Context) {
+                check_parameters(expression, &ast, profile)?;
+            }
+        }
+        if self.options.deny_warnings {
+            if let Some(warning) = warnings.first() {
+                return Err(JmespathError::new(
+                    expression,
+                    warning.offset,
+                    ErrorReason::Parse(warning.message.clone()),
+                ));
+            }
+        }
+        if self.limits.max_nodes.is_some() || self.limits.max_depth.is_some() {
+            let stats = stats(&ast);
+            check_limit(
+                expression,
+                "node count",
+                stats.node_count,
+                self.limits.max_nodes,
+            )?;
+            check_limit(expression, "depth", stats.max_depth, self.limits.max_depth)?;
+        }
+        Ok(Expression::new(expression, ast, self).with_warnings(warnings))
+    }
+
+    /// Compiles an expression and searches data with it.
+    pub fn search<T: ToJmespath>(&self, expression: &str, data: T) -> SearchResult {
+        self.compile(expression)?.search(data)
     }
 
     /// Adds a new function to the runtime.
+    ///
+    /// Returns false, without adding the function, if the runtime is
+    /// locked to a profile that doesn't allow it.
     #[inline]
-    pub fn register_function(&mut self, name: &str, f: Arc<dyn Function>) {
-        self.functions.insert(name.to_owned(), f.into());
+    pub fn register_function(&mut self, name: &str, f: Arc<dyn Function>) -> bool {
+        if let Some(ref profile) = self.profile {
+            if !profile.allows(f.as_ref()) {
+                return false;
+            }
+        }
+        self.functions.insert(name.to_owned(), f);
+        true
     }
 
     /// Removes a function from the runtime.
@@ -58,6 +508,12 @@ impl Runtime {
         self.functions.get(name).map(AsRef::as_ref)
     }
 
+    /// Returns the names of the functions registered with the runtime, in
+    /// no particular order.
+    pub fn function_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.functions.keys().map(String::as_str)
+    }
+
     /// Registers all of the builtin JMESPath functions with the runtime.
     pub fn register_builtin_functions(&mut self) {
         self.register_function("abs", Arc::new(AbsFn::new()));
@@ -87,4 +543,214 @@ impl Runtime {
         self.register_function("type", Arc::new(TypeFn::new()));
         self.register_function("values", Arc::new(ValuesFn::new()));
     }
+
+    /// Registers `to_timestamp`, which converts RFC 3339 strings and Unix
+    /// times to timestamps.
+    #[cfg(feature = "datetime")]
+    pub fn register_datetime_functions(&mut self) {
+        self.register_function("to_timestamp", Arc::new(ToTimestampFn::new()));
+    }
+}
+
+fn check_limit(
+    expression: &str,
+    resource: &str,
+    value: usize,
+    limit: Option<usize>,
+) -> Result<(), JmespathError> {
+    match limit {
+        Some(limit) if value > limit => Err(JmespathError::new(
+            expression,
+            0,
+            ErrorReason::Runtime(RuntimeError::LimitExceeded {
+                resource: resource.to_owned(),
+                limit,
+            }),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fails on the first parameter of an expression, which the profile forbids.
+fn check_parameters(expression: &str, ast: &Ast, profile: &Profile) -> Result<(), JmespathError> {
+    struct Parameters(Option<usize>);
+
+    impl AstVisitor for Parameters {
+        fn visit_parameter(&mut self, offset: usize, _name: &str) {
+            self.0.get_or_insert(offset);
+        }
+    }
+
+    let mut parameters = Parameters(None);
+    parameters.visit(ast);
+    match parameters.0 {
+        Some(offset) => Err(JmespathError::new(
+            expression,
+            offset,
+            ErrorReason::Parse(format!(
+                "Parameters are not allowed by the {} profile",
+                profile.name()
+            )),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn builds_runtime_with_functions() {
+        let runtime = Runtime::builder().build();
+        assert!(runtime.get_function("length").is_some());
+        let runtime = Runtime::builder()
+            .builtins(false)
+            .function("length", Arc::new(TypeFn::new()))
+            .build();
+        assert_eq!(vec!["length"], runtime.function_names().collect::<Vec<_>>());
+        let result = runtime.search("length(@)", true).unwrap();
+        assert_eq!(Some("boolean"), result.as_str());
+    }
+
+    #[test]
+    fn applies_limits_when_compiling() {
+        let runtime = Runtime::builder()
+            .limits(Limits {
+                max_nodes: Some(3),
+                max_depth: Some(2),
+                ..Limits::default()
+            })
+            .build();
+        assert!(runtime.compile("a.b").is_ok());
+        let error = runtime.compile("a.b.c").unwrap_err();
+        assert_eq!(ErrorKind::LimitExceeded, error.kind);
+        assert_eq!(
+            "Runtime error: Exceeded the node count limit of 3",
+            error.reason.to_string()
+        );
+        assert!(runtime.compile("[[[a]]]").is_err());
+    }
+
+    #[test]
+    fn denies_warnings() {
+        let runtime = Runtime::builder()
+            .parser_options(ParserOptions {
+                deny_warnings: true,
+                ..ParserOptions::default()
+            })
+            .build();
+        assert!(runtime.compile("a | b").is_ok());
+        let error = runtime.compile("a | @").unwrap_err();
+        assert_eq!(ErrorKind::Parse, error.kind);
+        assert!(Runtime::builder().build().compile("a | @").is_ok());
+    }
+
+    #[test]
+    fn locks_functions_to_a_profile() {
+        let echo = |args: &[crate::Rcvar], _: &mut crate::Context<'_>| Ok(args[0].clone());
+        let mut runtime = Runtime::builder()
+            .profile(Profile::named("no-regex").unwrap())
+            .function("echo", Arc::new(echo))
+            .build();
+        assert_eq!("no-regex", runtime.profile().unwrap().name());
+        assert!(runtime.get_function("echo").is_some());
+        assert!(runtime.get_function("sort").is_some());
+        let custom = CustomFunction::new(Signature::new(vec![], None), Box::new(echo));
+        assert!(Profile::full().allows(&custom));
+        assert!(!Profile::pure().allows(&custom));
+        assert!(runtime.register_function("custom", Arc::new(custom)));
+
+        let mut runtime = Runtime::builder().profile(Profile::pure()).build();
+        assert!(!runtime.register_function("echo", Arc::new(echo)));
+        let error = runtime.search("echo(@)", 1).unwrap_err();
+        assert_eq!(ErrorKind::UnknownFunction, error.kind);
+        assert!(Profile::named("none").is_none());
+        assert!(Runtime::new().profile().is_none());
+    }
+
+    #[test]
+    fn rejects_undeclared_capabilities() {
+        struct Echo;
+
+        impl Function for Echo {
+            fn evaluate(&self, args: &[crate::Rcvar], _: &mut crate::Context<'_>) -> SearchResult {
+                Ok(args[0].clone())
+            }
+        }
+
+        assert!(!Profile::pure().allows(&Echo));
+        assert!(Profile::pure().allows(&LengthFn::new()));
+        let runtime = Runtime::builder()
+            .profile(Profile::pure())
+            .function("echo", Arc::new(Echo))
+            .build();
+        assert!(runtime.get_function("echo").is_none());
+    }
+
+    #[test]
+    fn rejects_parameters_outside_the_profile() {
+        let pure = Runtime::builder().profile(Profile::pure()).build();
+        let error = pure.compile("a[?b == :limit]").unwrap_err();
+        assert_eq!((ErrorKind::Parse, 8), (error.kind, error.offset));
+        assert!(pure.compile("a[?b == `1`]").is_ok());
+        let no_regex = Runtime::builder().profile(Profile::no_regex()).build();
+        assert!(no_regex.compile("a[?b == :limit]").is_ok());
+    }
+
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn profiles_restrict_extension_functions() {
+        let runtime =
+            |profile: Profile| Runtime::builder().extensions(true).profile(profile).build();
+        let pure = runtime(Profile::pure());
+        assert!(pure.get_function("deep_merge").is_some());
+        assert!(pure.get_function("ctx").is_none());
+        assert!(pure.get_function("wildcard_keys").is_none());
+        let no_regex = runtime(Profile::no_regex());
+        assert!(no_regex.get_function("ctx").is_some());
+        assert!(no_regex.get_function("wildcard_keys").is_none());
+        let full = runtime(Profile::full());
+        assert!(full.get_function("wildcard_keys").is_some());
+    }
+
+    #[test]
+    fn projects_object_values_in_key_order() {
+        let data =
+            crate::Variable::from_json(r#"{"b": {"x": 1}, "c": {"x": 2}, "a": {"x": 3}}"#).unwrap();
+        let search = |key_order: KeyOrder| {
+            let runtime = Runtime::builder().key_order(key_order).build();
+            runtime.search("*.x", &data).unwrap().to_string()
+        };
+        assert_eq!("[3,1,2]", search(KeyOrder::Lexicographic));
+        #[cfg(not(feature = "preserve-order"))]
+        assert_eq!("[3,1,2]", search(KeyOrder::Native));
+        #[cfg(feature = "preserve-order")]
+        {
+            assert_eq!("[1,2,3]", search(KeyOrder::Native));
+            assert_eq!("[1,2,3]", search(KeyOrder::Insertion));
+        }
+        assert_eq!(KeyOrder::Native, Runtime::new().key_order());
+    }
+
+    #[test]
+    fn builds_objects_in_key_order() {
+        let data = crate::Variable::from_json(r#"{"b": {"y": 1}, "a": {"x": 2}}"#).unwrap();
+        let search = |key_order: KeyOrder| {
+            let runtime = Runtime::builder().key_order(key_order).build();
+            let expression = "[keys(@), values(@), merge(b, a), {z: b, y: a}]";
+            runtime.search(expression, &data).unwrap().to_string()
+        };
+        let sorted = r#"[["a","b"],[{"x":2},{"y":1}],{"x":2,"y":1},{"y":{"x":2},"z":{"y":1}}]"#;
+        assert_eq!(sorted, search(KeyOrder::Lexicographic));
+        #[cfg(not(feature = "preserve-order"))]
+        assert_eq!(sorted, search(KeyOrder::Native));
+        #[cfg(feature = "preserve-order")]
+        {
+            let kept = r#"[["b","a"],[{"y":1},{"x":2}],{"y":1,"x":2},{"z":{"y":1},"y":{"x":2}}]"#;
+            assert_eq!(kept, search(KeyOrder::Native));
+            assert_eq!(kept, search(KeyOrder::Insertion));
+        }
+    }
 }
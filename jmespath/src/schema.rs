@@ -0,0 +1,801 @@
+//! Static inference of the shape of the data an expression works with.
+//!
+//! `input_shape` derives the shape that an input must have for every part
+//! of an expression to select something, which can be used to check that
+//! the data produced by one system matches the queries run by another.
+//! `result_shape` derives the shape of the values an expression can return,
+//! which can be written as JSON Schema for consumers of the results.
+//!
+//! ```
+//! use jmespath::schema::{input_shape, result_shape, Shape};
+//!
+//! let ast = jmespath::parse("foo[?bar > `10`].baz").unwrap();
+//! let input = input_shape(&ast);
+//! assert_eq!("{foo: [{bar: number, baz: any}]}", input.to_string());
+//! assert_eq!("[any]", result_shape(&ast, &input).to_string());
+//! assert_eq!("[any] | null", result_shape(&ast, &Shape::Any).to_string());
+//!
+//! let ast = jmespath::parse("{name: to_string(name), count: length(@)}").unwrap();
+//! let schema = result_shape(&ast, &input).to_json_schema();
+//! let expected = r#"{
+//!     "type": "object",
+//!     "properties": {"count": {"type": "number"}, "name": {"type": "string"}},
+//!     "required": ["count", "name"]
+//! }"#;
+//! assert_eq!(jmespath::Variable::from_json(expected).unwrap(), schema);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ast::{render_name, Ast, Comparator};
+use crate::functions::ArgumentType;
+use crate::{Map, Rcvar, Runtime, Variable, DEFAULT_RUNTIME};
+
+/// Describes the shape of a JSON value.
+///
+/// Objects only describe the keys that are used, so other keys may be
+/// present.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    /// Any value.
+    Any,
+    /// Null.
+    Null,
+    /// A boolean.
+    Bool,
+    /// A number.
+    Number,
+    /// A string.
+    String,
+    /// An array with elements of the given shape.
+    Array(Box<Shape>),
+    /// An object with the given keys.
+    Object(BTreeMap<String, Shape>),
+    /// An object with values of the given shape, regardless of the key.
+    Map(Box<Shape>),
+    /// One of the given shapes.
+    Union(Vec<Shape>),
+}
+
+impl Shape {
+    /// Returns the shape of values that have both this shape and `other`.
+    ///
+    /// When the shapes are of different types, the value can't have both,
+    /// so the result is the union of the two.
+    pub fn merge(self, other: Shape) -> Shape {
+        match (self, other) {
+            (Shape::Any, shape) | (shape, Shape::Any) => shape,
+            (Shape::Array(a), Shape::Array(b)) => Shape::Array(Box::new(a.merge(*b))),
+            (Shape::Map(a), Shape::Map(b)) => Shape::Map(Box::new(a.merge(*b))),
+            (Shape::Object(mut a), Shape::Object(b)) => {
+                for (key, shape) in b {
+                    let merged = match a.remove(&key) {
+                        Some(existing) => existing.merge(shape),
+                        None => shape,
+                    };
+                    a.insert(key, merged);
+                }
+                Shape::Object(a)
+            }
+            (Shape::Object(a), Shape::Map(b)) | (Shape::Map(b), Shape::Object(a)) => Shape::Object(
+                a.into_iter()
+                    .map(|(key, shape)| (key, shape.merge((*b).clone())))
+                    .collect(),
+            ),
+            (Shape::Union(a), Shape::Union(b)) => b.into_iter().fold(Shape::Union(a), Shape::merge),
+            (Shape::Union(mut members), shape) | (shape, Shape::Union(mut members)) => {
+                match members.iter().position(|m| m.same_type(&shape)) {
+                    Some(i) => {
+                        let member = members.remove(i);
+                        members.insert(i, member.merge(shape));
+                    }
+                    None => members.push(shape),
+                }
+                Shape::Union(members)
+            }
+            (a, b) => {
+                if a.same_type(&b) {
+                    a
+                } else {
+                    Shape::Union(vec![a, b])
+                }
+            }
+        }
+    }
+
+    /// Returns the shape as a JSON Schema.
+    ///
+    /// The keys of objects are listed as required properties.
+    pub fn to_json_schema(&self) -> Variable {
+        let mut schema = Map::new();
+        match *self {
+            Shape::Any => (),
+            Shape::Array(ref element) => {
+                schema.insert("type".to_owned(), string("array"));
+                if **element != Shape::Any {
+                    schema.insert("items".to_owned(), Rcvar::new(element.to_json_schema()));
+                }
+            }
+            Shape::Object(ref keys) => {
+                schema.insert("type".to_owned(), string("object"));
+                if !keys.is_empty() {
+                    let properties = keys
+                        .iter()
+                        .map(|(key, shape)| (key.clone(), Rcvar::new(shape.to_json_schema())))
+                        .collect();
+                    let required = keys.keys().map(|key| string(key)).collect();
+                    schema.insert(
+                        "properties".to_owned(),
+                        Rcvar::new(Variable::Object(properties)),
+                    );
+                    schema.insert("required".to_owned(), Rcvar::new(Variable::Array(required)));
+                }
+            }
+            Shape::Map(ref value) => {
+                schema.insert("type".to_owned(), string("object"));
+                if **value != Shape::Any {
+                    schema.insert(
+                        "additionalProperties".to_owned(),
+                        Rcvar::new(value.to_json_schema()),
+                    );
+                }
+            }
+            Shape::Union(ref members) => {
+                let schemas: Vec<_> = members.iter().map(Shape::to_json_schema).collect();
+                // Unions of plain types are written as a list of types.
+                let types: Option<Vec<_>> = schemas
+                    .iter()
+                    .map(|schema| match schema.as_object() {
+                        Some(schema) if schema.len() == 1 => schema.get("type").cloned(),
+                        _ => None,
+                    })
+                    .collect();
+                match types {
+                    Some(types) => {
+                        schema.insert("type".to_owned(), Rcvar::new(Variable::Array(types)))
+                    }
+                    None => schema.insert(
+                        "anyOf".to_owned(),
+                        Rcvar::new(Variable::Array(
+                            schemas.into_iter().map(Rcvar::new).collect(),
+                        )),
+                    ),
+                };
+            }
+            Shape::Null => {
+                schema.insert("type".to_owned(), string("null"));
+            }
+            Shape::Bool => {
+                schema.insert("type".to_owned(), string("boolean"));
+            }
+            Shape::Number => {
+                schema.insert("type".to_owned(), string("number"));
+            }
+            Shape::String => {
+                schema.insert("type".to_owned(), string("string"));
+            }
+        }
+        Variable::Object(schema)
+    }
+
+    /// Returns the shape of a value.
+    fn of(value: &Variable) -> Shape {
+        match *value {
+            Variable::Null => Shape::Null,
+            Variable::Bool(_) => Shape::Bool,
+            Variable::Number(_) => Shape::Number,
+            Variable::String(_) => Shape::String,
+            Variable::Array(ref elements) => Shape::Array(Box::new(union(
+                elements.iter().map(|element| Shape::of(element)).collect(),
+            ))),
+            Variable::Object(ref keys) => Shape::Object(
+                keys.iter()
+                    .map(|(key, value)| (key.clone(), Shape::of(value)))
+                    .collect(),
+            ),
+            Variable::Expref(_) => Shape::Any,
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(_) => Shape::Any,
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(_) => Shape::String,
+        }
+    }
+
+    fn same_type(&self, other: &Shape) -> bool {
+        match (self, other) {
+            (Shape::Object(_), Shape::Map(_)) | (Shape::Map(_), Shape::Object(_)) => true,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+
+    fn from_argument_type(argument: &ArgumentType) -> Shape {
+        match *argument {
+            ArgumentType::String => Shape::String,
+            ArgumentType::Number => Shape::Number,
+            ArgumentType::Bool => Shape::Bool,
+            ArgumentType::Object => Shape::Object(BTreeMap::new()),
+            ArgumentType::Array => Shape::Array(Box::new(Shape::Any)),
+            ArgumentType::TypedArray(ref t) => Shape::Array(Box::new(Shape::from_argument_type(t))),
+            ArgumentType::Union(ref types) => {
+                // JSON documents have no binary data, so a union accepts
+                // the shapes of its other types.
+                #[cfg(feature = "bytes")]
+                let types: Vec<_> = types
+                    .iter()
+                    .filter(|t| **t != ArgumentType::Bytes)
+                    .cloned()
+                    .collect();
+                let members: Vec<_> = types.iter().map(Shape::from_argument_type).collect();
+                if members.contains(&Shape::Any) {
+                    Shape::Any
+                } else {
+                    Shape::Union(members)
+                }
+            }
+            ArgumentType::Any | ArgumentType::Null | ArgumentType::Expref => Shape::Any,
+            #[cfg(feature = "bytes")]
+            ArgumentType::Bytes => Shape::Any,
+            #[cfg(feature = "datetime")]
+            ArgumentType::Timestamp => Shape::Any,
+        }
+    }
+}
+
+/// Writes the shape with JSON-like syntax, e.g. `{foo: [number]}`.
+impl fmt::Display for Shape {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            Shape::Any => write!(fmt, "any"),
+            Shape::Null => write!(fmt, "null"),
+            Shape::Bool => write!(fmt, "boolean"),
+            Shape::Number => write!(fmt, "number"),
+            Shape::String => write!(fmt, "string"),
+            Shape::Array(ref element) => write!(fmt, "[{}]", element),
+            Shape::Object(ref keys) => {
+                write!(fmt, "{{")?;
+                for (i, (key, shape)) in keys.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{}: {}", render_name(key), shape)?;
+                }
+                write!(fmt, "}}")
+            }
+            Shape::Map(ref value) => write!(fmt, "{{*: {}}}", value),
+            Shape::Union(ref members) => {
+                for (i, member) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " | ")?;
+                    }
+                    write!(fmt, "{}", member)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the shape an input needs for every part of the expression to
+/// select something, using the functions of the default runtime.
+///
+/// Values that are used without any type requirement have the shape
+/// `Shape::Any`, and values that aren't used at all are left out.
+pub fn input_shape(ast: &Ast) -> Shape {
+    input_shape_with_runtime(&DEFAULT_RUNTIME, ast)
+}
+
+/// Returns the shape an input needs for every part of the expression to
+/// select something, using the function signatures of the given runtime.
+pub fn input_shape_with_runtime(runtime: &Runtime, ast: &Ast) -> Shape {
+    required(runtime, ast, Shape::Any)
+}
+
+/// Returns the element shape of an array shape.
+fn element(shape: Shape) -> Shape {
+    match shape {
+        Shape::Array(element) => *element,
+        _ => Shape::Any,
+    }
+}
+
+/// Returns the shape the input of `ast` needs for its result to have the
+/// `expected` shape.
+fn required(runtime: &Runtime, ast: &Ast, expected: Shape) -> Shape {
+    match *ast {
+        Ast::Identity { .. } => expected,
+        Ast::Field { ref name, .. } => {
+            let mut keys = BTreeMap::new();
+            keys.insert(name.to_string(), expected);
+            Shape::Object(keys)
+        }
+        Ast::Index { .. } => Shape::Array(Box::new(expected)),
+        Ast::Slice { .. } => Shape::Array(Box::new(element(expected))),
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => {
+            let rhs = required(runtime, rhs, expected);
+            required(runtime, lhs, rhs)
+        }
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => {
+            let rhs = required(runtime, rhs, element(expected));
+            required(runtime, lhs, Shape::Array(Box::new(rhs)))
+        }
+        Ast::Flatten { ref node, .. } => {
+            // Each element is either a value or an array of values.
+            let element = match element(expected) {
+                Shape::Any => Shape::Any,
+                shape => Shape::Union(vec![shape.clone(), Shape::Array(Box::new(shape))]),
+            };
+            required(runtime, node, Shape::Array(Box::new(element)))
+        }
+        Ast::ObjectValues { ref node, .. } => {
+            required(runtime, node, Shape::Map(Box::new(element(expected))))
+        }
+        // The key can be at any depth, so nothing more is known.
+        Ast::RecursiveDescent { ref node, .. } => required(runtime, node, Shape::Any),
+        Ast::Condition {
+            ref predicate,
+            ref then,
+            ..
+        } => required(runtime, predicate, Shape::Any).merge(required(runtime, then, expected)),
+        Ast::Comparison {
+            ref comparator,
+            ref lhs,
+            ref rhs,
+            ..
+        } => {
+            // Ordering comparisons are null unless both sides are numbers.
+            let operand = match *comparator {
+                Comparator::Equal | Comparator::NotEqual => Shape::Any,
+                _ => Shape::Number,
+            };
+            required(runtime, lhs, operand.clone()).merge(required(runtime, rhs, operand))
+        }
+        Ast::Or {
+            ref lhs, ref rhs, ..
+        } => required(runtime, lhs, expected.clone()).merge(required(runtime, rhs, expected)),
+        Ast::And {
+            ref lhs, ref rhs, ..
+        } => required(runtime, lhs, Shape::Any).merge(required(runtime, rhs, expected)),
+        Ast::Not { ref node, .. } => required(runtime, node, Shape::Any),
+        Ast::MultiList { ref elements, .. } => {
+            let expected = element(expected);
+            elements.iter().fold(Shape::Any, |shape, node| {
+                shape.merge(required(runtime, node, expected.clone()))
+            })
+        }
+        Ast::MultiHash { ref elements, .. } => {
+            let mut expected = match expected {
+                Shape::Object(keys) => keys,
+                _ => BTreeMap::new(),
+            };
+            elements.iter().fold(Shape::Any, |shape, kvp| {
+                let value = expected.remove(kvp.key.as_str()).unwrap_or(Shape::Any);
+                shape.merge(required(runtime, &kvp.value, value))
+            })
+        }
+        Ast::Function {
+            ref name, ref args, ..
+        } => function_required(runtime, name, args),
+        Ast::Literal { .. } | Ast::Parameter { .. } | Ast::Expref { .. } => Shape::Any,
+    }
+}
+
+/// Returns the shape the input of a function call needs for its arguments
+/// to match the signature of the function.
+fn function_required(runtime: &Runtime, name: &str, args: &[Ast]) -> Shape {
+    let signature = runtime.get_function(name).and_then(|f| f.signature());
+    // Builtin functions that take an expression reference apply it to each
+    // element of their array argument.
+    let expref_input = args.iter().fold(Shape::Any, |shape, arg| match *arg {
+        Ast::Expref { ref ast, .. } => shape.merge(required(runtime, ast, Shape::Any)),
+        _ => shape,
+    });
+    args.iter()
+        .enumerate()
+        .fold(Shape::Any, |shape, (position, arg)| {
+            let argument = signature.and_then(|signature| {
+                signature
+                    .inputs
+                    .get(position)
+                    .or(signature.variadic.as_ref())
+            });
+            let mut expected = argument.map_or(Shape::Any, Shape::from_argument_type);
+            if let Shape::Array(_) = expected {
+                expected = expected.merge(Shape::Array(Box::new(expref_input.clone())));
+            }
+            shape.merge(required(runtime, arg, expected))
+        })
+}
+
+/// Every type of value, used when nothing is known about a value.
+fn any_members() -> Vec<Shape> {
+    vec![
+        Shape::Null,
+        Shape::Bool,
+        Shape::Number,
+        Shape::String,
+        Shape::Array(Box::new(Shape::Any)),
+        Shape::Map(Box::new(Shape::Any)),
+    ]
+}
+
+fn string(s: &str) -> Rcvar {
+    Rcvar::new(Variable::String(s.to_owned()))
+}
+
+/// Returns the shape of values that have any of the given shapes.
+fn union(shapes: Vec<Shape>) -> Shape {
+    let mut members = vec![];
+    for shape in shapes {
+        match shape {
+            Shape::Any => return Shape::Any,
+            Shape::Union(nested) => {
+                for shape in nested {
+                    if !members.contains(&shape) {
+                        members.push(shape);
+                    }
+                }
+            }
+            shape => {
+                if !members.contains(&shape) {
+                    members.push(shape);
+                }
+            }
+        }
+    }
+    // Null is written last, e.g. `string | null`.
+    if let Some(i) = members.iter().position(|m| *m == Shape::Null) {
+        let null = members.remove(i);
+        members.push(null);
+    }
+    if members.len() == 1 {
+        members.remove(0)
+    } else if members.is_empty() || any_members().iter().all(|m| members.contains(m)) {
+        Shape::Any
+    } else {
+        Shape::Union(members)
+    }
+}
+
+/// Applies `f` to each type of value the shape allows, returning the union
+/// of the results.
+fn each<F: FnMut(&Shape) -> Shape>(shape: &Shape, mut f: F) -> Shape {
+    match *shape {
+        Shape::Any => union(any_members().iter().map(f).collect()),
+        Shape::Union(ref members) => union(members.iter().map(f).collect()),
+        ref shape => f(shape),
+    }
+}
+
+/// Removes null from the shape, returning None if it only allows null.
+fn non_null(shape: Shape) -> Option<Shape> {
+    match shape {
+        Shape::Null => None,
+        Shape::Union(members) => Some(union(
+            members.into_iter().filter(|m| *m != Shape::Null).collect(),
+        )),
+        shape => Some(shape),
+    }
+}
+
+/// Returns the shape of a multi-select, which is null when its input is.
+fn multi_select(input: &Shape, shape: Shape) -> Shape {
+    match *input {
+        Shape::Null => Shape::Null,
+        Shape::Any => union(vec![shape, Shape::Null]),
+        Shape::Union(ref members) if members.contains(&Shape::Null) => {
+            union(vec![shape, Shape::Null])
+        }
+        _ => shape,
+    }
+}
+
+/// Returns the shape of the values the expression can return when it is
+/// evaluated against an input of the given shape.
+///
+/// Pass `Shape::Any` when nothing is known about the input. Builtin
+/// functions are assumed to return their standard types, and other
+/// functions return any value.
+pub fn result_shape(ast: &Ast, input: &Shape) -> Shape {
+    match *ast {
+        Ast::Identity { .. } => input.clone(),
+        Ast::Literal { ref value, .. } => Shape::of(value),
+        Ast::Parameter { .. } => Shape::Any,
+        Ast::Field { ref name, .. } => each(input, |shape| match *shape {
+            Shape::Object(ref keys) => keys.get(name.as_str()).cloned().unwrap_or(Shape::Any),
+            Shape::Map(ref value) => union(vec![(**value).clone(), Shape::Null]),
+            _ => Shape::Null,
+        }),
+        Ast::Index { .. } => each(input, |shape| match *shape {
+            Shape::Array(ref element) => union(vec![(**element).clone(), Shape::Null]),
+            _ => Shape::Null,
+        }),
+        Ast::Slice { .. } => each(input, |shape| match *shape {
+            Shape::Array(_) => shape.clone(),
+            _ => Shape::Null,
+        }),
+        Ast::Subexpr {
+            ref lhs, ref rhs, ..
+        } => result_shape(rhs, &result_shape(lhs, input)),
+        Ast::Projection {
+            ref lhs, ref rhs, ..
+        } => each(&result_shape(lhs, input), |shape| match *shape {
+            Shape::Array(ref element) => {
+                // Projections never contain nulls, but an array of no values
+                // can only be described as an array of anything.
+                let element = non_null(result_shape(rhs, element)).unwrap_or(Shape::Any);
+                Shape::Array(Box::new(element))
+            }
+            _ => Shape::Null,
+        }),
+        Ast::Flatten { ref node, .. } => each(&result_shape(node, input), |shape| match *shape {
+            Shape::Array(ref element) => {
+                Shape::Array(Box::new(each(element, |shape| match *shape {
+                    Shape::Array(ref nested) => (**nested).clone(),
+                    ref shape => shape.clone(),
+                })))
+            }
+            _ => Shape::Null,
+        }),
+        Ast::ObjectValues { ref node, .. } => {
+            each(&result_shape(node, input), |shape| match *shape {
+                // Other keys can be present, so their values are unknown.
+                Shape::Object(_) => Shape::Array(Box::new(Shape::Any)),
+                Shape::Map(ref value) => Shape::Array(value.clone()),
+                _ => Shape::Null,
+            })
+        }
+        Ast::RecursiveDescent { ref node, .. } => {
+            each(&result_shape(node, input), |shape| match *shape {
+                Shape::Object(_) | Shape::Map(_) | Shape::Array(_) => {
+                    Shape::Array(Box::new(Shape::Any))
+                }
+                _ => Shape::Null,
+            })
+        }
+        Ast::Condition { ref then, .. } => union(vec![result_shape(then, input), Shape::Null]),
+        Ast::Comparison {
+            ref comparator,
+            ref lhs,
+            ref rhs,
+            ..
+        } => match *comparator {
+            Comparator::Equal | Comparator::NotEqual => Shape::Bool,
+            _ if result_shape(lhs, input) == Shape::Number
+                && result_shape(rhs, input) == Shape::Number =>
+            {
+                Shape::Bool
+            }
+            _ => union(vec![Shape::Bool, Shape::Null]),
+        },
+        Ast::Or {
+            ref lhs, ref rhs, ..
+        } => union(
+            non_null(result_shape(lhs, input))
+                .into_iter()
+                .chain(Some(result_shape(rhs, input)))
+                .collect(),
+        ),
+        Ast::And {
+            ref lhs, ref rhs, ..
+        } => union(vec![result_shape(lhs, input), result_shape(rhs, input)]),
+        Ast::Not { .. } => Shape::Bool,
+        Ast::MultiList { ref elements, .. } => multi_select(
+            input,
+            Shape::Array(Box::new(union(
+                elements
+                    .iter()
+                    .map(|node| result_shape(node, input))
+                    .collect(),
+            ))),
+        ),
+        Ast::MultiHash { ref elements, .. } => multi_select(
+            input,
+            Shape::Object(
+                elements
+                    .iter()
+                    .map(|kvp| (kvp.key.to_string(), result_shape(&kvp.value, input)))
+                    .collect(),
+            ),
+        ),
+        Ast::Function {
+            ref name, ref args, ..
+        } => function_result(name, args, input),
+        Ast::Expref { .. } => Shape::Any,
+    }
+}
+
+/// Returns the shape of the value returned by a builtin function.
+fn function_result(name: &str, args: &[Ast], input: &Shape) -> Shape {
+    let arg = |i: usize| {
+        args.get(i)
+            .map_or(Shape::Any, |arg| result_shape(arg, input))
+    };
+    let element = |shape: Shape| {
+        each(&shape, |shape| match *shape {
+            Shape::Array(ref element) => (**element).clone(),
+            _ => Shape::Any,
+        })
+    };
+    match name {
+        "abs" | "ceil" | "floor" | "length" | "sum" => Shape::Number,
+        "avg" | "to_number" => union(vec![Shape::Number, Shape::Null]),
+        "contains" | "ends_with" | "starts_with" => Shape::Bool,
+        "join" | "to_string" | "type" => Shape::String,
+        "keys" => Shape::Array(Box::new(Shape::String)),
+        "values" => Shape::Array(Box::new(each(&arg(0), |shape| match *shape {
+            Shape::Map(ref value) => (**value).clone(),
+            _ => Shape::Any,
+        }))),
+        "merge" => Shape::Map(Box::new(Shape::Any)),
+        "max" | "min" | "max_by" | "min_by" => union(vec![element(arg(0)), Shape::Null]),
+        "reverse" | "sort" | "sort_by" => arg(0),
+        "map" => match args.first() {
+            Some(Ast::Expref { ast, .. }) => {
+                Shape::Array(Box::new(result_shape(ast, &element(arg(1)))))
+            }
+            _ => Shape::Array(Box::new(Shape::Any)),
+        },
+        "not_null" => union(
+            (0..args.len())
+                .filter_map(|i| non_null(arg(i)))
+                .chain(Some(Shape::Null))
+                .collect(),
+        ),
+        "to_array" => each(&arg(0), |shape| match *shape {
+            Shape::Array(_) => shape.clone(),
+            ref shape => Shape::Array(Box::new(shape.clone())),
+        }),
+        _ => Shape::Any,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shape(expr: &str) -> String {
+        input_shape(&crate::parse(expr).unwrap()).to_string()
+    }
+
+    #[test]
+    fn infers_shape_of_paths() {
+        let cases = [
+            ("@", "any"),
+            ("'foo'", "any"),
+            ("foo", "{foo: any}"),
+            ("foo.bar[0]", "{foo: {bar: [any]}}"),
+            ("foo[*].bar", "{foo: [{bar: any}]}"),
+            ("foo[1:].bar", "{foo: [{bar: any}]}"),
+            ("foo.*.bar", "{foo: {*: {bar: any}}}"),
+            ("foo[].bar", "{foo: [{bar: any} | [{bar: any}]]}"),
+            ("foo | bar", "{foo: {bar: any}}"),
+            ("a.b && a.c || d", "{a: {b: any, c: any}, d: any}"),
+            ("[a, b.c]", "{a: any, b: {c: any}}"),
+            ("{x: a.b, y: a.c}", "{a: {b: any, c: any}}"),
+            ("\"a b\"", "{\"a b\": any}"),
+        ];
+        for (expr, expected) in cases.iter() {
+            assert_eq!(*expected, shape(expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn infers_types_of_values() {
+        let cases = [
+            ("foo[?bar > `10`].baz", "{foo: [{bar: number, baz: any}]}"),
+            ("a == 'x' && b <= c", "{a: any, b: number, c: number}"),
+            ("length(foo)", "{foo: [any] | {} | string}"),
+            ("sum(foo[*].bar)", "{foo: [{bar: number}]}"),
+            ("sort_by(people, &age)[0].name", "{people: [{age: any}]}"),
+            ("foo[0] || foo.bar", "{foo: [any] | {bar: any}}"),
+            ("abs(a) || starts_with(a, 'x')", "{a: number | string}"),
+            ("merge(a, b)", "{a: {}, b: {}}"),
+        ];
+        for (expr, expected) in cases.iter() {
+            assert_eq!(*expected, shape(expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn uses_signatures_of_runtime() {
+        let mut runtime = Runtime::new();
+        runtime.register_builtin_functions();
+        runtime.register_function(
+            "length",
+            std::sync::Arc::new(|_: &[crate::Rcvar], _: &mut crate::Context<'_>| {
+                Ok(crate::Rcvar::new(crate::Variable::Null))
+            }),
+        );
+        let ast = crate::parse("length(foo) || floor(bar)").unwrap();
+        assert_eq!(
+            "{bar: number, foo: any}",
+            input_shape_with_runtime(&runtime, &ast).to_string()
+        );
+    }
+
+    fn result(expr: &str, input: &str) -> String {
+        let input = if input.is_empty() {
+            Shape::Any
+        } else {
+            Shape::of(&Variable::from_json(input).unwrap())
+        };
+        result_shape(&crate::parse(expr).unwrap(), &input).to_string()
+    }
+
+    #[test]
+    fn infers_result_shapes() {
+        let cases = [
+            ("foo", "", "any"),
+            ("foo", r#"{"foo": "a"}"#, "string"),
+            ("foo.bar", r#"{"foo": "a"}"#, "null"),
+            ("foo[0]", r#"{"foo": [1, 2]}"#, "number | null"),
+            (
+                "foo[*].bar",
+                r#"{"foo": [{"bar": "a"}, {"bar": null}]}"#,
+                "[string]",
+            ),
+            ("foo[*]", "", "[any] | null"),
+            (
+                "foo[].a",
+                r#"{"foo": [[{"a": 1}], {"a": true}]}"#,
+                "[number | boolean]",
+            ),
+            ("*", "", "[any] | null"),
+            ("a == b", "", "boolean"),
+            ("a < b", "", "boolean | null"),
+            ("a < b", r#"{"a": 1, "b": 2}"#, "boolean"),
+            ("a || 'x'", r#"{"a": null}"#, "string"),
+            ("!a", "", "boolean"),
+            ("[a, b]", r#"{"a": 1, "b": "x"}"#, "[number | string]"),
+            ("{x: a, y: `[true]`}", "", "{x: any, y: [boolean]} | null"),
+            ("length(@)", "", "number"),
+            ("max(a)", r#"{"a": [1]}"#, "number | null"),
+            ("map(&a, @)", r#"[{"a": "x"}]"#, "[string]"),
+            ("sort_by(@, &a)", r#"[{"a": "x"}]"#, "[{a: string}]"),
+            ("not_null(a, `1`)", "", "any"),
+            ("to_array(a)", r#"{"a": 1}"#, "[number]"),
+            ("custom(a)", "", "any"),
+        ];
+        for (expr, input, expected) in cases.iter() {
+            assert_eq!(*expected, result(expr, input), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn converts_shapes_to_json_schema() {
+        let cases = vec![
+            (Shape::Any, "{}"),
+            (
+                Shape::Array(Box::new(Shape::Number)),
+                r#"{"items":{"type":"number"},"type":"array"}"#,
+            ),
+            (
+                Shape::Union(vec![Shape::String, Shape::Null]),
+                r#"{"type":["string","null"]}"#,
+            ),
+            (
+                Shape::Union(vec![Shape::Number, Shape::Array(Box::new(Shape::Number))]),
+                r#"{"anyOf":[{"type":"number"},{"items":{"type":"number"},"type":"array"}]}"#,
+            ),
+            (
+                Shape::Map(Box::new(Shape::Bool)),
+                r#"{"additionalProperties":{"type":"boolean"},"type":"object"}"#,
+            ),
+            (Shape::Object(BTreeMap::new()), r#"{"type":"object"}"#),
+        ];
+        for (shape, expected) in cases {
+            // Compare values, since key order depends on the `preserve-order` feature.
+            assert_eq!(
+                Variable::from_json(expected).unwrap(),
+                shape.to_json_schema(),
+                "{}",
+                shape
+            );
+        }
+    }
+}
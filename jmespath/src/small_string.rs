@@ -0,0 +1,248 @@
+//! A string that stores short values without allocating.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// Number of bytes that are stored inline.
+const INLINE_CAPACITY: usize = 22;
+
+/// An immutable string that stores up to 22 bytes inline and longer
+/// values in a `String`.
+///
+/// Identifiers in tokens and AST nodes are stored in a `SmallString`,
+/// since most JSON keys and function names are short. It dereferences to
+/// `str` and compares, hashes, and formats like one.
+///
+/// ```
+/// use jmespath::ast::SmallString;
+///
+/// let name = SmallString::from("foo");
+/// assert_eq!("foo", name);
+/// assert!(name.is_inline());
+/// assert!(!SmallString::from("a".repeat(23)).is_inline());
+/// ```
+#[derive(Clone)]
+pub struct SmallString(Repr);
+
+/// The bytes of `Inline` are only written by `From<&str>`, which copies
+/// a whole `str` and its length, so `bytes[..len]` is always valid UTF-8.
+#[derive(Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl SmallString {
+    /// Creates an empty string.
+    pub fn new() -> SmallString {
+        SmallString::from("")
+    }
+
+    /// Returns the string as a `str`.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            // SAFETY: inline bytes are copied from a `str`, see `Repr`.
+            Repr::Inline { len, ref bytes } => unsafe {
+                std::str::from_utf8_unchecked(&bytes[..len as usize])
+            },
+            Repr::Heap(ref string) => string,
+        }
+    }
+
+    /// Returns true if the string is stored inline.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+
+    /// Converts the string into a `String`.
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Heap(string) => string,
+            Repr::Inline { .. } => self.as_str().to_owned(),
+        }
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> SmallString {
+        SmallString::new()
+    }
+}
+
+/// The only constructor of inline strings, which keeps the invariant of
+/// `Repr::Inline`.
+impl<'a> From<&'a str> for SmallString {
+    fn from(value: &'a str) -> SmallString {
+        if value.len() <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+            bytes[..value.len()].copy_from_slice(value.as_bytes());
+            SmallString(Repr::Inline {
+                len: value.len() as u8,
+                bytes,
+            })
+        } else {
+            SmallString(Repr::Heap(value.to_owned()))
+        }
+    }
+}
+
+impl<'a> From<&'a String> for SmallString {
+    fn from(value: &'a String) -> SmallString {
+        SmallString::from(value.as_str())
+    }
+}
+
+/// Short strings are copied inline, so the `String` is dropped.
+impl From<String> for SmallString {
+    fn from(value: String) -> SmallString {
+        if value.len() <= INLINE_CAPACITY {
+            SmallString::from(value.as_str())
+        } else {
+            SmallString(Repr::Heap(value))
+        }
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(value: SmallString) -> String {
+        value.into_string()
+    }
+}
+
+impl Deref for SmallString {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SmallString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for SmallString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &SmallString) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+impl PartialEq<str> for SmallString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for SmallString {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for SmallString {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<SmallString> for str {
+    fn eq(&self, other: &SmallString) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<SmallString> for &str {
+    fn eq(&self, other: &SmallString) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<SmallString> for String {
+    fn eq(&self, other: &SmallString) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialOrd for SmallString {
+    fn partial_cmp(&self, other: &SmallString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallString {
+    fn cmp(&self, other: &SmallString) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for SmallString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Display for SmallString {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt::Display::fmt(self.as_str(), fmt)
+    }
+}
+
+impl fmt::Debug for SmallString {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt::Debug::fmt(self.as_str(), fmt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stores_short_strings_inline() {
+        for len in &[0, 1, INLINE_CAPACITY, INLINE_CAPACITY + 1, 100] {
+            let value = "é".repeat(*len / 2) + &"a".repeat(*len % 2);
+            let small = SmallString::from(value.clone());
+            assert_eq!(value, small);
+            assert_eq!(value.len() <= INLINE_CAPACITY, small.is_inline());
+            assert_eq!(format!("{:?}", value), format!("{:?}", small));
+            assert_eq!(value, small.into_string());
+        }
+        assert!(std::mem::size_of::<SmallString>() <= 32);
+    }
+
+    #[test]
+    fn compares_like_str() {
+        let a = SmallString::from("a");
+        let long = SmallString::from("b".repeat(30));
+        assert!(a < long);
+        assert_eq!(SmallString::from("a".to_owned()), a);
+        assert_eq!("a", a);
+        assert_eq!(a, "a");
+        assert_eq!(
+            Some(&1),
+            [(a.clone(), 1)]
+                .iter()
+                .cloned()
+                .collect::<std::collections::HashMap<_, _>>()
+                .get("a")
+        );
+    }
+}
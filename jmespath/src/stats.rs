@@ -0,0 +1,111 @@
+//! Size and complexity measurements of JMESPath expressions.
+//!
+//! Services that evaluate untrusted expressions can use the statistics of
+//! an expression to reject ones that are too complex, or to route simple
+//! field extractions to a cheaper code path.
+//!
+#![cfg_attr(feature = "stats", doc = "```")]
+#![cfg_attr(not(feature = "stats"), doc = "```ignore")]
+//! let expr = jmespath::compile("people[?age > `20`].name | sort(@)").unwrap();
+//! let stats = expr.stats();
+//! assert_eq!(1, stats.projections);
+//! assert_eq!(1, stats.filters);
+//! assert_eq!(1, stats.function_calls);
+//! assert!(!stats.simple_path);
+//! assert!(jmespath::compile("a.b[0].c").unwrap().stats().simple_path);
+//! ```
+
+use crate::ast::Ast;
+use crate::interpreter::path_steps;
+use crate::visitor::{walk, AstVisitor};
+
+/// Statistics about the AST of an expression.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ExpressionStats {
+    /// Number of nodes in the AST.
+    pub node_count: usize,
+    /// Number of nodes on the longest path from the root to a leaf, which
+    /// is 1 for an expression with a single node.
+    pub max_depth: usize,
+    /// Number of projections, including the projections of filters.
+    pub projections: usize,
+    /// Number of filter conditions, like `[?foo]`.
+    pub filters: usize,
+    /// Number of function calls.
+    pub function_calls: usize,
+    /// True if the expression is only a chain of fields and indices, like
+    /// `a.b[0].c`, that selects at most one value of the input.
+    pub simple_path: bool,
+}
+
+/// Returns the statistics of an AST.
+pub fn stats(ast: &Ast) -> ExpressionStats {
+    let mut counter = Counter {
+        depth: 0,
+        stats: ExpressionStats {
+            simple_path: path_steps(ast).is_some(),
+            ..ExpressionStats::default()
+        },
+    };
+    counter.visit(ast);
+    counter.stats
+}
+
+struct Counter {
+    depth: usize,
+    stats: ExpressionStats,
+}
+
+impl AstVisitor for Counter {
+    fn visit(&mut self, node: &Ast) {
+        self.depth += 1;
+        self.stats.node_count += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        match *node {
+            Ast::Projection { .. } => self.stats.projections += 1,
+            Ast::Condition { .. } => self.stats.filters += 1,
+            Ast::Function { .. } => self.stats.function_calls += 1,
+            _ => (),
+        }
+        walk(self, node);
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stats_of(expr: &str) -> ExpressionStats {
+        stats(&crate::parse(expr).unwrap())
+    }
+
+    #[test]
+    fn counts_nodes() {
+        assert_eq!(
+            ExpressionStats {
+                node_count: 1,
+                max_depth: 1,
+                projections: 0,
+                filters: 0,
+                function_calls: 0,
+                simple_path: true,
+            },
+            stats_of("foo")
+        );
+        let stats = stats_of("a[*].b[?c].length(@)");
+        assert_eq!(2, stats.projections);
+        assert_eq!(1, stats.filters);
+        assert_eq!(1, stats.function_calls);
+        assert_eq!(4, stats_of("a.b.c.d").max_depth);
+    }
+
+    #[test]
+    fn detects_simple_paths() {
+        assert!(stats_of("a.b[0].\"c\" | d[-1]").simple_path);
+        assert!(stats_of("@").simple_path);
+        assert!(!stats_of("a[*].b").simple_path);
+        assert!(!stats_of("a[0:1]").simple_path);
+        assert!(!stats_of("{a: a}").simple_path);
+    }
+}
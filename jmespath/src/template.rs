@@ -0,0 +1,185 @@
+//! Templates that render text with embedded expressions.
+//!
+//! A `Template` is text with `{{ expression }}` placeholders, like the
+//! messages generated from JSON events. Every expression is compiled once
+//! when the template is compiled, and a placeholder renders the result of
+//! its expression: strings are written as they are, null as nothing, and
+//! other values as JSON.
+//!
+//! ```
+//! use jmespath::template::Template;
+//! use jmespath::Variable;
+//!
+//! let template = Template::new(
+//!     "{{ user.name }} has {{ length(items) }} items: {{ items[*].id }}",
+//! )
+//! .unwrap();
+//! let data = Variable::from_json(
+//!     r#"{"user": {"name": "Ada"}, "items": [{"id": 1}, {"id": 2}]}"#,
+//! )
+//! .unwrap();
+//! assert_eq!("Ada has 2 items: [1,2]", template.render(data).unwrap());
+//! ```
+
+use crate::{ErrorReason, Expression, JmespathError, Runtime, ToJmespath, Variable};
+
+/// Text with compiled `{{ expression }}` placeholders.
+#[derive(Clone)]
+pub struct Template<'a> {
+    parts: Vec<Part<'a>>,
+}
+
+#[derive(Clone)]
+enum Part<'a> {
+    Text(String),
+    Expression(Expression<'a>),
+}
+
+impl Template<'static> {
+    /// Compiles a template with the default runtime.
+    pub fn new(template: &str) -> Result<Template<'static>, JmespathError> {
+        Template::compile(template, crate::default_runtime())
+    }
+}
+
+impl<'a> Template<'a> {
+    /// Compiles the expressions of a template with the runtime.
+    ///
+    /// A placeholder ends at the first `}}` outside of the braces, quotes
+    /// and literals of its expression, so `{{ {a: b} }}` renders a
+    /// multi-hash. Fails if a placeholder is not closed or if its
+    /// expression doesn't compile.
+    pub fn compile(template: &str, runtime: &'a Runtime) -> Result<Template<'a>, JmespathError> {
+        let mut parts = vec![];
+        let mut rest = 0;
+        while let Some(open) = template[rest..].find("{{").map(|i| rest + i) {
+            if open > rest {
+                parts.push(Part::Text(template[rest..open].to_owned()));
+            }
+            let start = open + 2;
+            let end = closing(&template[start..])
+                .map(|i| start + i)
+                .ok_or_else(|| {
+                    JmespathError::new(
+                        template,
+                        open,
+                        ErrorReason::Parse("Unclosed placeholder".to_owned()),
+                    )
+                    .with_length(2)
+                    .redact_if(runtime.parser_options().redact)
+                })?;
+            let source = template[start..end].trim_end();
+            let offset = start + source.len() - source.trim_start().len();
+            // Errors point into the template rather than the placeholder.
+            let expression = runtime.compile(source.trim_start()).map_err(|error| {
+                error
+                    .relocate(template, offset)
+                    .redact_if(runtime.parser_options().redact)
+            })?;
+            parts.push(Part::Expression(expression));
+            rest = end + 2;
+        }
+        if rest < template.len() {
+            parts.push(Part::Text(template[rest..].to_owned()));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Returns the expressions of the placeholders, in order.
+    pub fn expressions(&self) -> impl Iterator<Item = &Expression<'a>> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Expression(expression) => Some(expression),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Renders the template against a document.
+    ///
+    /// Fails with the error of the first expression that fails.
+    pub fn render<T: ToJmespath>(&self, data: T) -> Result<String, JmespathError> {
+        let data = data.to_jmespath()?;
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Expression(expression) => match *expression.search_shared(&data)? {
+                    Variable::String(ref s) => rendered.push_str(s),
+                    Variable::Null => {}
+                    ref value => rendered.push_str(&value.to_string()),
+                },
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+/// Returns the offset of the `}}` that closes a placeholder.
+fn closing(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(_), b'\\') => i += 1,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, c @ (b'\'' | b'"' | b'`')) => quote = Some(c),
+            (None, b'{') => depth += 1,
+            (None, b'}') if depth > 0 => depth -= 1,
+            (None, b'}') if bytes.get(i + 1) == Some(&b'}') => return Some(i),
+            (None, _) => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let template = Template::new("{{a}}-{{ {x: b} }}-{{ 'x}}y' }}{{missing}}!").unwrap();
+        assert_eq!(4, template.expressions().count());
+        let data = Variable::from_json(r#"{"a": "text", "b": [1, true]}"#).unwrap();
+        assert_eq!(
+            r#"text-{"x":[1,true]}-x}}y!"#,
+            template.render(data).unwrap()
+        );
+        assert_eq!(
+            "no placeholders",
+            Template::new("no placeholders")
+                .unwrap()
+                .render(())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_placeholders() {
+        let error = Template::new("a {{ b").err().unwrap();
+        assert_eq!(("a {{ b", 2), (error.expression.as_str(), error.offset));
+        assert_eq!(
+            "Parse error: Unclosed placeholder",
+            error.reason.to_string()
+        );
+        let error = Template::new("x\n{{ a }} {{ b[ }}").err().unwrap();
+        assert_eq!("x\n{{ a }} {{ b[ }}", error.expression);
+        assert_eq!((15, 2, 14), (error.offset, error.line, error.column));
+        assert_eq!(vec!["number", ":", "*"], error.expected());
+        let runtime = Runtime::builder()
+            .parser_options(crate::ParserOptions {
+                redact: true,
+                ..crate::ParserOptions::default()
+            })
+            .build();
+        let error = Template::compile("a {{ b == 'c' 'd' }}", &runtime)
+            .err()
+            .unwrap();
+        assert_eq!("a {{ b == '*' '*' }}", error.expression);
+        let template = Template::new("{{ abs(a) }}").unwrap();
+        assert!(template.render(Variable::Null).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! Helpers for fuzzing and property testing JMESPath integrations.
+//!
+//! This module is only available when the `testing` feature is enabled.
+//! It provides an `arbitrary::Arbitrary` implementation for `Ast` (for use
+//! with cargo-fuzz or similar tools), `proptest` strategies that generate
+//! ASTs, and small functions that can serve as the body of a fuzz target.
+//!
+//! ```
+//! use jmespath::testing::arb_ast;
+//! use proptest::strategy::{Strategy, ValueTree};
+//! use proptest::test_runner::TestRunner;
+//!
+//! let mut runner = TestRunner::default();
+//! let ast = arb_ast().new_tree(&mut runner).unwrap().current();
+//! let expr = jmespath::Expression::new("", ast, &jmespath::DEFAULT_RUNTIME);
+//! let _ = expr.search(());
+//! ```
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+
+use crate::ast::{Ast, Comparator, KeyValuePair, SmallString};
+use crate::variable::Variable;
+use crate::{parse, Expression, Rcvar, DEFAULT_RUNTIME};
+
+/// Maximum depth of ASTs created through `Arbitrary`.
+const MAX_ARBITRARY_DEPTH: usize = 8;
+
+/// Function names that generated `Function` nodes choose from. Unknown
+/// names are included so that error paths are exercised too.
+const FUNCTION_NAMES: &[&str] = &[
+    "abs",
+    "avg",
+    "contains",
+    "join",
+    "keys",
+    "length",
+    "map",
+    "max_by",
+    "merge",
+    "not_null",
+    "reverse",
+    "sort",
+    "sort_by",
+    "to_array",
+    "to_number",
+    "to_string",
+    "type",
+    "unknown",
+];
+
+const COMPARATORS: &[Comparator] = &[
+    Comparator::Equal,
+    Comparator::NotEqual,
+    Comparator::LessThan,
+    Comparator::LessThanEqual,
+    Comparator::GreaterThan,
+    Comparator::GreaterThanEqual,
+];
+
+/// Parses arbitrary bytes as an expression. Intended as the body of a
+/// fuzz target: it must never panic, whatever the input.
+pub fn fuzz_parse(data: &[u8]) {
+    if let Ok(expression) = std::str::from_utf8(data) {
+        let _ = parse(expression);
+    }
+}
+
+/// Parses arbitrary bytes as an expression and, if parsing succeeds,
+/// evaluates the expression against `input`.
+pub fn fuzz_search(data: &[u8], input: &Rcvar) {
+    if let Ok(expression) = std::str::from_utf8(data) {
+        if let Ok(expr) = DEFAULT_RUNTIME.compile(expression) {
+            let _ = expr.search(input);
+        }
+    }
+}
+
+/// Evaluates an arbitrary AST against `input` using the default runtime.
+pub fn fuzz_interpret(ast: Ast, input: &Rcvar) {
+    let _ = Expression::new("", ast, &DEFAULT_RUNTIME).search(input);
+}
+
+impl<'a> Arbitrary<'a> for Comparator {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.choose(COMPARATORS).cloned()
+    }
+}
+
+impl<'a> Arbitrary<'a> for KeyValuePair {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_kvp(u, MAX_ARBITRARY_DEPTH)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Ast {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_ast(u, MAX_ARBITRARY_DEPTH)
+    }
+}
+
+fn arbitrary_literal(u: &mut Unstructured<'_>) -> arbitrary::Result<Rcvar> {
+    let var = match u.int_in_range(0..=4)? {
+        0 => Variable::Null,
+        1 => Variable::Bool(u.arbitrary()?),
+        2 => Variable::Number(i64::arbitrary(u)?.into()),
+        3 => Variable::String(u.arbitrary()?),
+        _ => Variable::Array(vec![Rcvar::new(Variable::Number(u8::arbitrary(u)?.into()))]),
+    };
+    Ok(Rcvar::new(var))
+}
+
+fn arbitrary_name(u: &mut Unstructured<'_>) -> arbitrary::Result<SmallString> {
+    // Favor a handful of short names so generated fields hit real keys.
+    if u.arbitrary()? {
+        Ok(SmallString::from(*u.choose(&["a", "b", "foo", "bar"])?))
+    } else {
+        u.arbitrary::<String>().map(SmallString::from)
+    }
+}
+
+fn arbitrary_kvp(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<KeyValuePair> {
+    Ok(KeyValuePair {
+        key: arbitrary_name(u)?,
+        value: arbitrary_ast(u, depth)?,
+    })
+}
+
+fn arbitrary_children(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<Vec<Ast>> {
+    let len = u.int_in_range(0..=3)?;
+    (0..len).map(|_| arbitrary_ast(u, depth)).collect()
+}
+
+fn arbitrary_box(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<Box<Ast>> {
+    arbitrary_ast(u, depth).map(Box::new)
+}
+
+fn arbitrary_ast(u: &mut Unstructured<'_>, depth: usize) -> arbitrary::Result<Ast> {
+    let offset = 0;
+    let max_choice = if depth == 0 || u.is_empty() { 4 } else { 17 };
+    let depth = depth.saturating_sub(1);
+    Ok(match u.int_in_range(0..=max_choice)? {
+        0 => Ast::Identity { offset },
+        1 => Ast::Field {
+            offset,
+            name: arbitrary_name(u)?,
+        },
+        2 => Ast::Index {
+            offset,
+            idx: u.arbitrary()?,
+        },
+        3 => Ast::Literal {
+            offset,
+            value: arbitrary_literal(u)?,
+        },
+        4 => Ast::Slice {
+            offset,
+            start: u.arbitrary()?,
+            stop: u.arbitrary()?,
+            step: u.arbitrary()?,
+        },
+        5 => Ast::Comparison {
+            offset,
+            comparator: u.arbitrary()?,
+            lhs: arbitrary_box(u, depth)?,
+            rhs: arbitrary_box(u, depth)?,
+        },
+        6 => Ast::Condition {
+            offset,
+            predicate: arbitrary_box(u, depth)?,
+            then: arbitrary_box(u, depth)?,
+        },
+        7 => Ast::Expref {
+            offset,
+            ast: arbitrary_box(u, depth)?,
+        },
+        8 => Ast::Flatten {
+            offset,
+            node: arbitrary_box(u, depth)?,
+        },
+        9 => Ast::Function {
+            offset,
+            name: SmallString::from(*u.choose(FUNCTION_NAMES)?),
+            args: arbitrary_children(u, depth)?,
+        },
+        10 => Ast::MultiList {
+            offset,
+            elements: arbitrary_children(u, depth)?,
+        },
+        11 => Ast::MultiHash {
+            offset,
+            elements: (0..u.int_in_range(1..=3)?)
+                .map(|_| arbitrary_kvp(u, depth))
+                .collect::<arbitrary::Result<_>>()?,
+        },
+        12 => Ast::Not {
+            offset,
+            node: arbitrary_box(u, depth)?,
+        },
+        13 => Ast::Projection {
+            offset,
+            lhs: arbitrary_box(u, depth)?,
+            rhs: arbitrary_box(u, depth)?,
+        },
+        14 => Ast::ObjectValues {
+            offset,
+            node: arbitrary_box(u, depth)?,
+        },
+        15 => Ast::And {
+            offset,
+            lhs: arbitrary_box(u, depth)?,
+            rhs: arbitrary_box(u, depth)?,
+        },
+        16 => Ast::Or {
+            offset,
+            lhs: arbitrary_box(u, depth)?,
+            rhs: arbitrary_box(u, depth)?,
+        },
+        _ => Ast::Subexpr {
+            offset,
+            lhs: arbitrary_box(u, depth)?,
+            rhs: arbitrary_box(u, depth)?,
+        },
+    })
+}
+
+/// Strategy that generates field names, favoring a few short names.
+pub fn arb_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        3 => prop::sample::select(vec!["a", "b", "foo", "bar"]).prop_map(String::from),
+        1 => "[a-zA-Z_][a-zA-Z0-9_]{0,8}",
+        1 => any::<String>(),
+    ]
+}
+
+/// Strategy that generates scalar literal values.
+pub fn arb_literal() -> impl Strategy<Value = Rcvar> {
+    prop_oneof![
+        Just(Variable::Null),
+        any::<bool>().prop_map(Variable::Bool),
+        any::<i64>().prop_map(|n| Variable::Number(n.into())),
+        "[a-z]{0,8}".prop_map(Variable::String),
+    ]
+    .prop_map(Rcvar::new)
+}
+
+/// Strategy that generates comparators.
+pub fn arb_comparator() -> impl Strategy<Value = Comparator> {
+    prop::sample::select(COMPARATORS.to_vec())
+}
+
+/// Strategy that generates arbitrary (not necessarily parseable) ASTs.
+pub fn arb_ast() -> impl Strategy<Value = Ast> {
+    let leaf = prop_oneof![
+        Just(Ast::Identity { offset: 0 }),
+        arb_name().prop_map(|name| Ast::Field {
+            offset: 0,
+            name: name.into(),
+        }),
+        any::<i32>().prop_map(|idx| Ast::Index { offset: 0, idx }),
+        arb_literal().prop_map(|value| Ast::Literal { offset: 0, value }),
+        (any::<Option<i32>>(), any::<Option<i32>>(), any::<i32>()).prop_map(
+            |(start, stop, step)| Ast::Slice {
+                offset: 0,
+                start,
+                stop,
+                step,
+            }
+        ),
+    ];
+    leaf.prop_recursive(8, 64, 3, |inner| {
+        let pair = (inner.clone(), inner.clone()).prop_map(|(l, r)| (Box::new(l), Box::new(r)));
+        prop_oneof![
+            (arb_comparator(), pair.clone()).prop_map(|(comparator, (lhs, rhs))| {
+                Ast::Comparison {
+                    offset: 0,
+                    comparator,
+                    lhs,
+                    rhs,
+                }
+            }),
+            pair.clone().prop_map(|(predicate, then)| Ast::Condition {
+                offset: 0,
+                predicate,
+                then,
+            }),
+            inner.clone().prop_map(|ast| Ast::Expref {
+                offset: 0,
+                ast: Box::new(ast),
+            }),
+            inner.clone().prop_map(|node| Ast::Flatten {
+                offset: 0,
+                node: Box::new(node),
+            }),
+            (
+                prop::sample::select(FUNCTION_NAMES),
+                prop::collection::vec(inner.clone(), 0..3)
+            )
+                .prop_map(|(name, args)| Ast::Function {
+                    offset: 0,
+                    name: SmallString::from(name),
+                    args,
+                }),
+            prop::collection::vec(inner.clone(), 0..3).prop_map(|elements| Ast::MultiList {
+                offset: 0,
+                elements,
+            }),
+            prop::collection::vec((arb_name(), inner.clone()), 1..3).prop_map(|pairs| {
+                Ast::MultiHash {
+                    offset: 0,
+                    elements: pairs
+                        .into_iter()
+                        .map(|(key, value)| KeyValuePair {
+                            key: key.into(),
+                            value,
+                        })
+                        .collect(),
+                }
+            }),
+            inner.clone().prop_map(|node| Ast::Not {
+                offset: 0,
+                node: Box::new(node),
+            }),
+            pair.clone().prop_map(|(lhs, rhs)| Ast::Projection {
+                offset: 0,
+                lhs,
+                rhs,
+            }),
+            inner.prop_map(|node| Ast::ObjectValues {
+                offset: 0,
+                node: Box::new(node),
+            }),
+            pair.clone().prop_map(|(lhs, rhs)| Ast::And {
+                offset: 0,
+                lhs,
+                rhs
+            }),
+            pair.clone().prop_map(|(lhs, rhs)| Ast::Or {
+                offset: 0,
+                lhs,
+                rhs
+            }),
+            pair.prop_map(|(lhs, rhs)| Ast::Subexpr {
+                offset: 0,
+                lhs,
+                rhs
+            }),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn input() -> Rcvar {
+        Rcvar::new(Variable::from_json(r#"{"a": [1, {"b": 2}], "foo": {"bar": "x"}}"#).unwrap())
+    }
+
+    #[test]
+    fn builds_arbitrary_asts_from_bytes() {
+        let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&bytes);
+        while !u.is_empty() {
+            fuzz_interpret(Ast::arbitrary(&mut u).unwrap(), &input());
+        }
+    }
+
+    #[test]
+    fn fuzz_helpers_accept_invalid_utf8() {
+        fuzz_parse(&[0xff, 0xfe, b'(']);
+        fuzz_search(b"a[0]", &input());
+    }
+
+    proptest! {
+        #[test]
+        fn interpreting_arbitrary_asts_never_panics(ast in arb_ast()) {
+            fuzz_interpret(ast, &input());
+        }
+
+        #[test]
+        fn parsing_arbitrary_strings_never_panics(expr in "[a-z@*.\\[\\]{}()|&!=<>,:'`\" 0-9?-]{0,24}") {
+            fuzz_search(expr.as_bytes(), &input());
+        }
+    }
+}
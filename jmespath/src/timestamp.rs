@@ -0,0 +1,374 @@
+//! Timestamps, the values of `Variable::Timestamp`.
+//!
+//! With the `datetime` feature, runtimes built with
+//! `RuntimeBuilder::datetime(true)` have a `to_timestamp()` function, which
+//! parses RFC 3339 strings like `2024-05-01T12:30:00+02:00`, or dates like
+//! `2024-05-01`, into timestamps. Timestamps compare by the instant they denote whatever their
+//! offset, so filters like `[?to_timestamp(created) > to_timestamp('2024-01-01')]`
+//! compare dates instead of failing on strings. They are serialized as
+//! RFC 3339 strings with the offset they were parsed with.
+//!
+//! ```
+//! use jmespath::timestamp::Timestamp;
+//!
+//! let a = Timestamp::parse("2024-05-01T12:30:00+02:00").unwrap();
+//! let b = Timestamp::parse("2024-05-01T10:30:00Z").unwrap();
+//! assert_eq!(a, b);
+//! assert_eq!(1714559400, a.unix_seconds());
+//! assert_eq!("2024-05-01T12:30:00+02:00", a.to_string());
+//! ```
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// An instant, with the UTC offset it is written with.
+#[derive(Clone, Copy, Debug)]
+pub struct Timestamp {
+    /// Seconds since 1970-01-01T00:00:00Z.
+    seconds: i64,
+    nanos: u32,
+    /// Offset from UTC, in minutes.
+    offset: i16,
+}
+
+impl Timestamp {
+    /// Parses an RFC 3339 timestamp, like `2024-05-01T12:30:00.5Z`, or a
+    /// date like `2024-05-01`, which is midnight UTC.
+    ///
+    /// Returns None if the text is not a valid timestamp of the years 0000
+    /// to 9999. Digits of the fraction of a second after the ninth are
+    /// ignored.
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        let mut cursor = Cursor(text.as_bytes());
+        let year = cursor.digits(4)?;
+        cursor.expect(b'-')?;
+        let month = cursor.digits(2)?;
+        cursor.expect(b'-')?;
+        let day = cursor.digits(2)?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day);
+        if cursor.0.is_empty() {
+            return Some(Timestamp {
+                seconds: days * SECONDS_PER_DAY,
+                nanos: 0,
+                offset: 0,
+            });
+        }
+        match cursor.next()? {
+            b'T' | b't' | b' ' => {}
+            _ => return None,
+        }
+        let hour = cursor.digits(2)?;
+        cursor.expect(b':')?;
+        let minute = cursor.digits(2)?;
+        cursor.expect(b':')?;
+        let second = cursor.digits(2)?;
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        let mut nanos = 0;
+        if cursor.0.first() == Some(&b'.') {
+            cursor.next();
+            let fraction = cursor.take_while(|c| c.is_ascii_digit());
+            if fraction.is_empty() {
+                return None;
+            }
+            for i in 0..9 {
+                nanos = nanos * 10 + fraction.get(i).map_or(0, |d| u32::from(d - b'0'));
+            }
+        }
+        let offset = match cursor.next()? {
+            b'Z' | b'z' => 0,
+            sign @ (b'+' | b'-') => {
+                let hours = cursor.digits(2)?;
+                cursor.expect(b':')?;
+                let minutes = cursor.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                let offset = (hours * 60 + minutes) as i16;
+                if sign == b'-' {
+                    -offset
+                } else {
+                    offset
+                }
+            }
+            _ => return None,
+        };
+        if !cursor.0.is_empty() {
+            return None;
+        }
+        let local = days * SECONDS_PER_DAY + i64::from(hour * 3600 + minute * 60 + second);
+        Some(Timestamp {
+            seconds: local - i64::from(offset) * 60,
+            nanos,
+            offset,
+        })
+    }
+
+    /// Returns the UTC timestamp of a number of seconds since
+    /// 1970-01-01T00:00:00Z, or None if it is not finite or not in the
+    /// years 0000 to 9999.
+    pub fn from_unix(seconds: f64) -> Option<Timestamp> {
+        let whole = seconds.floor();
+        let min = days_from_civil(0, 1, 1) * SECONDS_PER_DAY;
+        let max = days_from_civil(10_000, 1, 1) * SECONDS_PER_DAY;
+        if !(min as f64..max as f64).contains(&whole) {
+            return None;
+        }
+        let nanos = ((seconds - whole) * 1e9) as u32;
+        Some(Timestamp {
+            seconds: whole as i64,
+            nanos: nanos.min(999_999_999),
+            offset: 0,
+        })
+    }
+
+    /// Returns the number of whole seconds since 1970-01-01T00:00:00Z.
+    pub fn unix_seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// Returns the nanoseconds past the whole seconds.
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Returns the offset from UTC the timestamp is written with, in
+    /// minutes.
+    pub fn offset_minutes(&self) -> i16 {
+        self.offset
+    }
+}
+
+/// Timestamps are equal if they denote the same instant, even if they are
+/// written with different offsets.
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Timestamp) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Timestamp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Timestamp) -> Ordering {
+        (self.seconds, self.nanos).cmp(&(other.seconds, other.nanos))
+    }
+}
+
+/// Writes the timestamp in RFC 3339 format, with its offset and without
+/// trailing zeros in the fraction of a second.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let local = self.seconds + i64::from(self.offset) * 60;
+        let (year, month, day) = civil_from_days(local.div_euclid(SECONDS_PER_DAY));
+        let time = local.rem_euclid(SECONDS_PER_DAY);
+        write!(
+            fmt,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        if self.nanos > 0 {
+            let fraction = format!("{:09}", self.nanos);
+            write!(fmt, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        match self.offset {
+            0 => write!(fmt, "Z"),
+            offset => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let minutes = offset.unsigned_abs();
+                write!(fmt, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+        }
+    }
+}
+
+/// The unparsed rest of a timestamp.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn next(&mut self) -> Option<u8> {
+        let (&first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(first)
+    }
+
+    fn expect(&mut self, expected: u8) -> Option<()> {
+        self.next().filter(|&c| c == expected).map(|_| ())
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(u8) -> bool) -> &'a [u8] {
+        let len = self.0.iter().take_while(|&&c| predicate(c)).count();
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        taken
+    }
+
+    /// Reads a number of exactly `count` digits.
+    fn digits(&mut self, count: usize) -> Option<u32> {
+        let digits = self.0.get(..count)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        self.0 = &self.0[count..];
+        Some(digits.iter().fold(0, |n, d| n * 10 + u32::from(d - b'0')))
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to a date of the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    // Years start in March, so leap days end them.
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = i64::from((month + 9) % 12);
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the year, month and day of a number of days from 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = u32::try_from(day_of_year - (153 * month_from_march + 2) / 5 + 1).unwrap_or(1);
+    let month = u32::try_from((month_from_march + 2) % 12 + 1).unwrap_or(1);
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str) -> Timestamp {
+        Timestamp::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_rfc_3339() {
+        assert_eq!(0, parse("1970-01-01T00:00:00Z").unix_seconds());
+        assert_eq!(951_782_400, parse("2000-02-29").unix_seconds());
+        assert_eq!(-62_167_219_200, parse("0000-01-01").unix_seconds());
+        let ts = parse("2024-05-01t12:30:00.123456789999-07:30");
+        assert_eq!(1_714_593_600, ts.unix_seconds());
+        assert_eq!(123_456_789, ts.subsec_nanos());
+        assert_eq!(-450, ts.offset_minutes());
+        assert_eq!(
+            parse("2024-05-01 20:00:00.1Z"),
+            parse("2024-05-01T21:00:00.100+01:00")
+        );
+        for invalid in &[
+            "",
+            "2024",
+            "2024-13-01",
+            "2023-02-29",
+            "2024-05-01T",
+            "2024-05-01T24:00:00Z",
+            "2024-05-01T12:30:00",
+            "2024-05-01T12:30:00.Z",
+            "2024-05-01T12:30:00+0200",
+            "2024-05-01T12:30:00Zjunk",
+            "+2024-05-01",
+        ] {
+            assert_eq!(None, Timestamp::parse(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn formats_rfc_3339() {
+        for text in &[
+            "1970-01-01T00:00:00Z",
+            "1969-12-31T23:59:59.5Z",
+            "0000-03-01T00:00:00Z",
+            "9999-12-31T23:59:59.999999999-23:59",
+            "2024-02-29T01:00:00+14:00",
+        ] {
+            assert_eq!(*text, parse(text).to_string());
+        }
+        assert_eq!("2024-05-01T00:00:00Z", parse("2024-05-01").to_string());
+        let ts = Timestamp::from_unix(-0.25).unwrap();
+        assert_eq!("1969-12-31T23:59:59.75Z", ts.to_string());
+        assert_eq!(None, Timestamp::from_unix(f64::NAN));
+        assert_eq!(None, Timestamp::from_unix(1e300));
+    }
+
+    #[test]
+    fn orders_by_instant() {
+        assert!(parse("2024-05-01T00:00:00+02:00") < parse("2024-04-30T23:00:00Z"));
+        assert!(parse("2024-05-01T00:00:00.1Z") > parse("2024-05-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn compares_timestamps_in_expressions() {
+        use crate::Variable;
+
+        let data = Variable::from_json(
+            r#"[
+                {"id": 1, "at": "2024-05-01T09:00:00+02:00"},
+                {"id": 2, "at": "2024-05-01T08:00:00Z"},
+                {"id": 3, "at": "not a date"}
+            ]"#,
+        )
+        .unwrap();
+        let runtime = crate::Runtime::builder().datetime(true).build();
+        let search = |expr: &str| runtime.search(expr, &data).unwrap().to_string();
+        assert_eq!(
+            "[2]",
+            search("[?to_timestamp(at) > to_timestamp('2024-05-01T07:30:00Z')].id")
+        );
+        assert_eq!(
+            "[1]",
+            search("[?to_timestamp(at) == to_timestamp('2024-05-01T07:00:00Z')].id")
+        );
+        // Strings can't be ordered, so the filter drops every element.
+        assert_eq!("[]", search("[?at > '2024-05-01T07:30:00Z'].id"));
+        assert_eq!(
+            r#"["timestamp","2024-05-01T09:00:00+02:00",null]"#,
+            search("[type(to_timestamp([0].at)), to_string(to_timestamp([0].at)), to_timestamp([2].at)]")
+        );
+        assert_eq!(
+            r#""1970-01-01T00:01:30.5Z""#,
+            search("to_timestamp(`90.5`)")
+        );
+        assert_eq!(
+            r#"["2024-05-01T08:00:00Z"]"#,
+            search("[1].at | [to_timestamp(@)]")
+        );
+        // `to_timestamp` is not a function of the specification.
+        assert!(crate::search("to_timestamp(`0`)", &data).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Recording and replaying evaluation traces.
+//!
+//! A `Trace` records the value each AST node of an expression is evaluated
+//! against, in evaluation order, along with the result. Traces serialize
+//! to JSON, so a trace of a failing evaluation can be attached to a bug
+//! report and replayed in a test, where replaying it evaluates the
+//! expression again on the recorded input and records a new trace to
+//! compare.
+//!
+//! ```
+//! use jmespath::trace::Trace;
+//!
+//! let expr = jmespath::compile("a[*].b").unwrap();
+//! let data = jmespath::Variable::from_json(r#"{"a": [{"b": 1}, {"b": 2}]}"#).unwrap();
+//! let trace = Trace::record(&expr, data).unwrap();
+//! assert_eq!("[1,2]", trace.result.as_ref().unwrap().to_string());
+//!
+//! let json = serde_json::to_string(&trace).unwrap();
+//! let restored: Trace = serde_json::from_str(&json).unwrap();
+//! let replayed = restored.replay(jmespath::default_runtime()).unwrap();
+//! assert_eq!(None, restored.first_difference(&replayed));
+//! ```
+
+use serde::{de, ser};
+
+use crate::debugger::{DebugAction, Frame};
+use crate::{Expression, JmespathError, Rcvar, Runtime, ToJmespath, Variable};
+
+/// The evaluation of one AST node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceStep {
+    /// Offset of the node in the expression.
+    pub offset: usize,
+    /// The node, formatted as an expression.
+    pub node: String,
+    /// Value the node was evaluated against.
+    pub input: Rcvar,
+}
+
+/// The nodes an expression evaluated and its result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    /// The expression that was evaluated.
+    pub expression: String,
+    /// The data the expression was evaluated against.
+    pub input: Rcvar,
+    /// The evaluated nodes, in evaluation order.
+    pub steps: Vec<TraceStep>,
+    /// The result, or the message of the error the evaluation failed with.
+    pub result: Result<Rcvar, String>,
+}
+
+impl Trace {
+    /// Evaluates an expression and records its trace.
+    ///
+    /// Fails only if the data can't be converted to a `Variable`; errors of
+    /// the evaluation are recorded in the trace.
+    pub fn record<T: ToJmespath>(
+        expression: &Expression<'_>,
+        data: T,
+    ) -> Result<Trace, JmespathError> {
+        let input = data.to_jmespath()?;
+        let mut steps = vec![];
+        let result = expression.debug(&input, &mut |frame: &Frame<'_>| {
+            steps.push(TraceStep {
+                offset: frame.node.offset(),
+                node: frame.node.to_string(),
+                input: frame.value.clone(),
+            });
+            DebugAction::Step
+        });
+        Ok(Trace {
+            expression: expression.as_str().to_owned(),
+            input,
+            steps,
+            result: result.map_err(|e| e.to_string()),
+        })
+    }
+
+    /// Compiles the expression of the trace with the runtime and records a
+    /// new trace of it on the same input.
+    pub fn replay(&self, runtime: &Runtime) -> Result<Trace, JmespathError> {
+        Trace::record(&runtime.compile(&self.expression)?, &self.input)
+    }
+
+    /// Returns the position of the first step that differs from another
+    /// trace, or the number of steps if only the results differ.
+    pub fn first_difference(&self, other: &Trace) -> Option<usize> {
+        let common = self.steps.len().min(other.steps.len());
+        match (0..common).find(|&i| self.steps[i] != other.steps[i]) {
+            Some(i) => Some(i),
+            None if self.steps.len() != other.steps.len() => Some(common),
+            None if self.result != other.result => Some(common),
+            None => None,
+        }
+    }
+}
+
+/// Serializes a step as an object with `offset`, `node`, and `input` keys.
+impl ser::Serialize for TraceStep {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("TraceStep", 3)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field("node", &self.node)?;
+        state.serialize_field("input", &self.input)?;
+        state.end()
+    }
+}
+
+/// Serializes a trace as an object with `expression`, `input`, and `steps`
+/// keys, and a `result` or an `error` key.
+impl ser::Serialize for Trace {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Trace", 4)?;
+        state.serialize_field("expression", &self.expression)?;
+        state.serialize_field("input", &self.input)?;
+        state.serialize_field("steps", &self.steps)?;
+        match self.result {
+            Ok(ref result) => state.serialize_field("result", result)?,
+            Err(ref error) => state.serialize_field("error", error)?,
+        }
+        state.end()
+    }
+}
+
+/// Deserializes a trace from the form it is serialized in.
+impl<'de> de::Deserialize<'de> for Trace {
+    fn deserialize<D>(deserializer: D) -> Result<Trace, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value = Variable::deserialize(deserializer)?;
+        from_variable(&value).map_err(de::Error::custom)
+    }
+}
+
+fn from_variable(value: &Variable) -> Result<Trace, String> {
+    let field = |value: &Variable, key: &str| {
+        value
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("trace is missing `{}`", key))
+    };
+    let string = |value: &Variable, key: &str| {
+        field(value, key)?
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| format!("`{}` of a trace is not a string", key))
+    };
+    let steps = field(value, "steps")?;
+    let steps = steps
+        .as_array()
+        .ok_or("`steps` of a trace is not an array")?
+        .iter()
+        .map(|step| {
+            Ok(TraceStep {
+                offset: field(step, "offset")?
+                    .as_number()
+                    .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+                    .ok_or("`offset` of a trace step is not an offset")?
+                    as usize,
+                node: string(step, "node")?,
+                input: field(step, "input")?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    let result = match value.get("error") {
+        Some(_) => Err(string(value, "error")?),
+        None => Ok(field(value, "result")?),
+    };
+    Ok(Trace {
+        expression: string(value, "expression")?,
+        input: field(value, "input")?,
+        steps,
+        result,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(expr: &str, data: &str) -> Trace {
+        let expr = crate::compile(expr).unwrap();
+        Trace::record(&expr, Variable::from_json(data).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn records_node_inputs_in_order() {
+        let trace = record("a.b", r#"{"a": {"b": 1}}"#);
+        let steps: Vec<_> = trace
+            .steps
+            .iter()
+            .map(|step| format!("{}@{} {}", step.node, step.offset, step.input))
+            .collect();
+        assert_eq!(
+            vec![
+                r#"a.b@1 {"a":{"b":1}}"#,
+                r#"a@0 {"a":{"b":1}}"#,
+                r#"b@2 {"b":1}"#,
+            ],
+            steps
+        );
+        let trace = record("abs(a)", r#"{"a": "x"}"#);
+        assert!(trace.result.unwrap_err().contains("expects type number"));
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        for trace in &[
+            record("sort_by(@, &a)[0]", r#"[{"a": 2}, {"a": 1}]"#),
+            record("abs(@)", r#""x""#),
+        ] {
+            let json = serde_json::to_string(trace).unwrap();
+            assert_eq!(*trace, serde_json::from_str::<Trace>(&json).unwrap());
+        }
+        assert!(serde_json::from_str::<Trace>(r#"{"expression": "a"}"#).is_err());
+    }
+
+    #[test]
+    fn finds_differences_between_traces() {
+        let trace = record("[a, b]", r#"{"a": 1, "b": 2}"#);
+        assert_eq!(
+            None,
+            trace.first_difference(&trace.replay(crate::default_runtime()).unwrap())
+        );
+        let mut other = trace.clone();
+        other.steps[2].input = Rcvar::new(Variable::Null);
+        assert_eq!(Some(2), trace.first_difference(&other));
+        other.steps.truncate(2);
+        assert_eq!(Some(2), trace.first_difference(&other));
+        let mut other = trace.clone();
+        other.result = Err("error".to_owned());
+        assert_eq!(Some(3), trace.first_difference(&other));
+    }
+}
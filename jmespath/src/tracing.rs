@@ -0,0 +1,239 @@
+//! Log-style events for the compilations and evaluations of a Runtime.
+//!
+//! A `Tracer` is a `Metrics` implementation that describes every
+//! compilation and evaluation with an `Event`, and hands it to a function
+//! that writes it wherever the application logs, so services can see which
+//! expressions are slow without wrapping every call. The crate doesn't
+//! depend on a logging library: an `Event` is displayed as a line of
+//! `key=value` fields, and its fields are public for structured loggers.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use jmespath::tracing::Tracer;
+//! use jmespath::Runtime;
+//!
+//! let lines = Arc::new(Mutex::new(vec![]));
+//! let sink = lines.clone();
+//! let tracer = Tracer::new(move |event| sink.lock().unwrap().push(event.to_string()));
+//! let runtime = Runtime::builder().metrics(Arc::new(tracer)).build();
+//! runtime.search("foo[*].bar", jmespath::Variable::Null).unwrap();
+//! let lines = lines.lock().unwrap();
+//! assert!(lines[0].starts_with("jmespath compile hash="));
+//! assert!(lines[0].contains(" nodes=3 "));
+//! assert!(lines[1].ends_with(" result=null"));
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use crate::metrics::Metrics;
+use crate::variable::JmespathType;
+use crate::{JmespathError, Rcvar};
+
+/// What an `Event` describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// An expression was compiled.
+    Compile,
+    /// An expression was evaluated.
+    Eval,
+}
+
+/// A compilation or an evaluation of an expression.
+#[derive(Clone, Copy, Debug)]
+pub struct Event<'a> {
+    /// Whether the expression was compiled or evaluated.
+    pub phase: Phase,
+    /// The text of the expression.
+    pub expression: &'a str,
+    /// A hash of the expression text, which identifies the expression in
+    /// logs without writing it. The hash is the same in every process of
+    /// the same build.
+    pub hash: u64,
+    /// The number of nodes of the AST, for the compilations that succeed.
+    pub nodes: Option<usize>,
+    /// The time the compilation or the evaluation took.
+    pub duration: Duration,
+    /// The type of the result of an evaluation, None for a compilation, or
+    /// the error if it failed.
+    pub result: Result<Option<JmespathType>, &'a JmespathError>,
+}
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self.phase {
+            Phase::Compile => "compile",
+            Phase::Eval => "eval",
+        };
+        write!(fmt, "jmespath {} hash={:016x}", phase, self.hash)?;
+        if let Some(nodes) = self.nodes {
+            write!(fmt, " nodes={}", nodes)?;
+        }
+        write!(fmt, " duration={:?}", self.duration)?;
+        match self.result {
+            Ok(Some(result)) => write!(fmt, " result={}", result),
+            Ok(None) => Ok(()),
+            Err(error) => write!(fmt, " error={:?}", error.message()),
+        }
+    }
+}
+
+/// Hands an `Event` to a function for every compilation and evaluation.
+pub struct Tracer<F> {
+    sink: F,
+    threshold: Duration,
+}
+
+impl<F> Tracer<F>
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    /// Creates a tracer that hands every event to `sink`.
+    pub fn new(sink: F) -> Tracer<F> {
+        Tracer {
+            sink,
+            threshold: Duration::from_secs(0),
+        }
+    }
+
+    /// Only hands over the events that took at least `threshold`, to log
+    /// the slow expressions of a busy service.
+    pub fn slower_than(mut self, threshold: Duration) -> Tracer<F> {
+        self.threshold = threshold;
+        self
+    }
+}
+
+impl<F> Metrics for Tracer<F>
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    fn on_compile(&self, expression: &str, duration: Duration, result: Result<(), &JmespathError>) {
+        if duration < self.threshold {
+            return;
+        }
+        // The AST is not handed to metrics, so the expression is parsed
+        // again to count its nodes.
+        let nodes = match result {
+            Ok(()) => crate::parse(expression)
+                .ok()
+                .map(|ast| crate::stats::stats(&ast).node_count),
+            Err(_) => None,
+        };
+        (self.sink)(&Event {
+            phase: Phase::Compile,
+            expression,
+            hash: hash(expression),
+            nodes,
+            duration,
+            result: result.map(|()| None),
+        });
+    }
+
+    fn on_eval(
+        &self,
+        expression: &str,
+        duration: Duration,
+        result: Result<&Rcvar, &JmespathError>,
+    ) {
+        if duration < self.threshold {
+            return;
+        }
+        (self.sink)(&Event {
+            phase: Phase::Eval,
+            expression,
+            hash: hash(expression),
+            nodes: None,
+            duration,
+            result: result.map(|value| Some(value.get_type())),
+        });
+    }
+}
+
+fn hash(expression: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    expression.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Runtime;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn describes_compilations_and_evaluations() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let tracer = Tracer::new(move |event: &Event<'_>| {
+            let result = event.result.map_err(|e| e.message());
+            sink.lock()
+                .unwrap()
+                .push((event.phase, event.hash, event.nodes, result));
+        });
+        let runtime = Runtime::builder().metrics(Arc::new(tracer)).build();
+        assert!(runtime.compile("a[").is_err());
+        runtime.search("@ | length(@)", "ab").unwrap();
+        assert!(runtime.search("abs(@)", "x").is_err());
+        let events = events.lock().unwrap();
+        assert_eq!(5, events.len());
+        assert_eq!((Phase::Compile, None), (events[0].0, events[0].2));
+        assert!(events[0].3.is_err());
+        assert_eq!(
+            (Phase::Compile, Some(4), Ok(None)),
+            (events[1].0, events[1].2, events[1].3.clone())
+        );
+        assert_eq!(
+            (Phase::Eval, events[1].1, Ok(Some(JmespathType::Number))),
+            (events[2].0, events[2].1, events[2].3.clone())
+        );
+        assert_ne!(events[1].1, events[3].1);
+        assert_eq!(Phase::Eval, events[4].0);
+        assert!(events[4].3.is_err());
+    }
+
+    #[test]
+    fn skips_fast_events() {
+        let count = Arc::new(Mutex::new(0));
+        let sink = count.clone();
+        let tracer = Tracer::new(move |_: &Event<'_>| *sink.lock().unwrap() += 1)
+            .slower_than(Duration::from_secs(60));
+        let runtime = Runtime::builder().metrics(Arc::new(tracer)).build();
+        runtime.search("a", crate::Variable::Null).unwrap();
+        assert_eq!(0, *count.lock().unwrap());
+    }
+
+    #[test]
+    fn displays_events() {
+        let error = crate::compile("a[").err().unwrap();
+        let event = Event {
+            phase: Phase::Compile,
+            expression: "a[",
+            hash: 255,
+            nodes: None,
+            duration: Duration::from_millis(2),
+            result: Err(&error),
+        };
+        assert_eq!(
+            format!(
+                "jmespath compile hash=00000000000000ff duration=2ms error={:?}",
+                error.message()
+            ),
+            event.to_string()
+        );
+        let event = Event {
+            phase: Phase::Eval,
+            nodes: Some(3),
+            result: Ok(Some(JmespathType::Array)),
+            ..event
+        };
+        assert_eq!(
+            "jmespath eval hash=00000000000000ff nodes=3 duration=2ms result=array",
+            event.to_string()
+        );
+    }
+}
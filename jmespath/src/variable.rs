@@ -5,20 +5,22 @@ use serde::*;
 use serde_json::error::Error;
 use serde_json::value::Value;
 use std::cmp::{max, Ordering};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::iter::Iterator;
 use std::string::ToString;
 use std::vec;
 
 use crate::ast::{Ast, Comparator};
+#[cfg(feature = "datetime")]
+use crate::timestamp::Timestamp;
 use crate::ToJmespath;
-use crate::{JmespathError, Rcvar};
+use crate::{JmespathError, Map, Rcvar};
 use serde_json::Number;
 use std::convert::TryFrom;
 
 /// JMESPath types.
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum JmespathType {
     Null,
     String,
@@ -27,6 +29,10 @@ pub enum JmespathType {
     Array,
     Object,
     Expref,
+    #[cfg(feature = "bytes")]
+    Bytes,
+    #[cfg(feature = "datetime")]
+    Timestamp,
 }
 
 impl fmt::Display for JmespathType {
@@ -42,6 +48,10 @@ impl fmt::Display for JmespathType {
                 JmespathType::Array => "array",
                 JmespathType::Object => "object",
                 JmespathType::Expref => "expref",
+                #[cfg(feature = "bytes")]
+                JmespathType::Bytes => "bytes",
+                #[cfg(feature = "datetime")]
+                JmespathType::Timestamp => "timestamp",
             }
         )
     }
@@ -55,8 +65,17 @@ pub enum Variable {
     Bool(bool),
     Number(Number),
     Array(Vec<Rcvar>),
-    Object(BTreeMap<String, Rcvar>),
+    Object(Map),
     Expref(Ast),
+    /// Binary data, with the `bytes` feature. Serialized with
+    /// `serialize_bytes`, which JSON serializers write as an array of
+    /// numbers.
+    #[cfg(feature = "bytes")]
+    Bytes(Vec<u8>),
+    /// An instant, with the `datetime` feature. Serialized as an RFC 3339
+    /// string.
+    #[cfg(feature = "datetime")]
+    Timestamp(Timestamp),
 }
 
 impl Eq for Variable {}
@@ -84,27 +103,120 @@ fn float_eq(a: f64, b: f64) -> bool {
     }
 }
 
-/// Implement PartialEq for looser floating point comparisons.
+/// An array or an object being copied by `Variable::deep_clone`.
+struct CloneFrame<'a> {
+    /// Keys of an object, or None for an array.
+    keys: Option<Vec<String>>,
+    /// Elements left to copy, the last one first.
+    pending: Vec<&'a Variable>,
+    /// Copies of the elements copied so far.
+    copied: Vec<Rcvar>,
+}
+
+impl<'a> CloneFrame<'a> {
+    /// Starts copying an array or an object, or returns the copy of any
+    /// other value.
+    fn open(value: &'a Variable) -> Result<CloneFrame<'a>, Variable> {
+        let (keys, pending): (_, Vec<&Variable>) = match value {
+            Variable::Array(array) => (None, array.iter().map(|v| &**v).rev().collect()),
+            Variable::Object(map) => (
+                Some(map.keys().cloned().collect()),
+                map.values().map(|v| &**v).rev().collect(),
+            ),
+            other => return Err(other.clone()),
+        };
+        Ok(CloneFrame {
+            keys,
+            copied: Vec::with_capacity(pending.len()),
+            pending,
+        })
+    }
+
+    /// Returns the copy once every element is copied.
+    fn close(self) -> Variable {
+        match self.keys {
+            Some(keys) => Variable::Object(keys.into_iter().zip(self.copied).collect()),
+            None => Variable::Array(self.copied),
+        }
+    }
+}
+
+/// Compares two numbers, regardless of whether they were written as
+/// integers or floats.
+///
+/// Two integers are compared exactly, so integers above 2^53 that convert
+/// to the same float are still ordered. Otherwise the numbers are compared
+/// as floats, which are equal if `float_eq` considers them equal: `1` equals
+/// `1.0`, and `0.71` equals `0.7100000000000002`.
+///
+/// With the `bignum` feature, numbers are compared exactly as the decimals
+/// they are written as, so `0.71` is less than `0.7100000000000002`.
+fn compare_numbers(a: &Number, b: &Number) -> Ordering {
+    #[cfg(feature = "bignum")]
+    {
+        if let Some(ordering) = crate::decimal::compare(a, b) {
+            return ordering;
+        }
+    }
+    if let (Some(a), Some(b)) = (integer(a), integer(b)) {
+        return a.cmp(&b);
+    }
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) if float_eq(a, b) => Ordering::Equal,
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Less),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Returns the value of a number stored as an i64 or a u64, or None if it
+/// is stored as a float.
+pub(crate) fn integer(n: &Number) -> Option<i128> {
+    n.as_i64()
+        .map(i128::from)
+        .or_else(|| n.as_u64().map(i128::from))
+}
+
+/// Returns an integer as a number stored as an i64 or a u64, or None if it
+/// fits in neither.
+pub(crate) fn integer_number(n: i128) -> Option<Number> {
+    u64::try_from(n)
+        .map(Number::from)
+        .or_else(|_| i64::try_from(n).map(Number::from))
+        .ok()
+}
+
+/// Equality as defined by the JMESPath specification, which `==`, `!=`,
+/// `contains`, and the other functions that compare values use.
+///
+/// Values of different types are never equal. Numbers are equal if they
+/// have the same value, whether they are written as integers or floats.
+/// Arrays are equal if they have the same length and their elements are
+/// equal in order. Objects are equal if they have the same keys and the
+/// values of each key are equal, in any order of the keys.
 impl PartialEq for Variable {
     fn eq(&self, other: &Variable) -> bool {
-        if self.get_type() != other.get_type() {
-            false
-        } else {
-            match self {
-                Variable::Number(a) => {
-                    if let (Some(a), Some(b)) = (a.as_f64(), other.as_number()) {
-                        float_eq(a, b)
-                    } else {
-                        false
-                    }
-                }
-                Variable::String(ref s) => Some(s) == other.as_string(),
-                Variable::Bool(b) => Some(*b) == other.as_boolean(),
-                Variable::Array(ref a) => Some(a) == other.as_array(),
-                Variable::Object(ref o) => Some(o) == other.as_object(),
-                Variable::Expref(ref e) => Some(e) == other.as_expref(),
-                Variable::Null => true,
+        fn same(a: &Rcvar, b: &Rcvar) -> bool {
+            Rcvar::ptr_eq(a, b) || a == b
+        }
+        match (self, other) {
+            (Variable::Number(a), Variable::Number(b)) => compare_numbers(a, b) == Ordering::Equal,
+            (Variable::String(a), Variable::String(b)) => a == b,
+            (Variable::Bool(a), Variable::Bool(b)) => a == b,
+            (Variable::Array(a), Variable::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
             }
+            (Variable::Object(a), Variable::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, value)| b.get(key).is_some_and(|other| same(value, other)))
+            }
+            (Variable::Expref(a), Variable::Expref(b)) => a == b,
+            #[cfg(feature = "bytes")]
+            (Variable::Bytes(a), Variable::Bytes(b)) => a == b,
+            #[cfg(feature = "datetime")]
+            (Variable::Timestamp(a), Variable::Timestamp(b)) => a == b,
+            (Variable::Null, Variable::Null) => true,
+            _ => false,
         }
     }
 }
@@ -149,13 +261,15 @@ impl Ord for Variable {
                         Ordering::Equal
                     }
                 }
-                JmespathType::Number => {
-                    if let (Some(a), Some(b)) = (self.as_number(), other.as_number()) {
-                        a.partial_cmp(&b).unwrap_or(Ordering::Less)
-                    } else {
-                        Ordering::Equal
-                    }
-                }
+                JmespathType::Number => match (self, other) {
+                    (Variable::Number(a), Variable::Number(b)) => compare_numbers(a, b),
+                    _ => Ordering::Equal,
+                },
+                #[cfg(feature = "datetime")]
+                JmespathType::Timestamp => match (self, other) {
+                    (Variable::Timestamp(a), Variable::Timestamp(b)) => a.cmp(b),
+                    _ => Ordering::Equal,
+                },
                 _ => Ordering::Equal,
             }
         }
@@ -180,7 +294,7 @@ fn convert_map<'a, T>(value: T) -> Result<Variable, JmespathError>
 where
     T: Iterator<Item = (&'a String, &'a Value)>,
 {
-    let mut map: BTreeMap<String, Rcvar> = BTreeMap::new();
+    let mut map: Map = Map::new();
     for kvp in value {
         map.insert(kvp.0.to_owned(), kvp.1.to_jmespath()?);
     }
@@ -231,6 +345,86 @@ impl TryFrom<Value> for Variable {
     }
 }
 
+impl<'a> From<&'a str> for Variable {
+    fn from(value: &'a str) -> Variable {
+        Variable::String(value.to_owned())
+    }
+}
+
+impl From<String> for Variable {
+    fn from(value: String) -> Variable {
+        Variable::String(value)
+    }
+}
+
+impl From<bool> for Variable {
+    fn from(value: bool) -> Variable {
+        Variable::Bool(value)
+    }
+}
+
+impl From<i64> for Variable {
+    fn from(value: i64) -> Variable {
+        Variable::Number(Number::from(value))
+    }
+}
+
+/// Converts NaN and infinite numbers, which JSON can't represent, to null.
+impl From<f64> for Variable {
+    fn from(value: f64) -> Variable {
+        Number::from_f64(value).map_or(Variable::Null, Variable::Number)
+    }
+}
+
+impl<T: Into<Variable>> From<Vec<T>> for Variable {
+    fn from(values: Vec<T>) -> Variable {
+        Variable::Array(
+            values
+                .into_iter()
+                .map(|value| Rcvar::new(value.into()))
+                .collect(),
+        )
+    }
+}
+
+/// Converts a map to an object. Unless objects are sorted by key, the keys
+/// are in the order of the `HashMap`, which is unspecified.
+impl<T: Into<Variable>> From<HashMap<String, T>> for Variable {
+    fn from(values: HashMap<String, T>) -> Variable {
+        Variable::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, Rcvar::new(value.into())))
+                .collect(),
+        )
+    }
+}
+
+impl<T: Into<Variable>> From<BTreeMap<String, T>> for Variable {
+    fn from(values: BTreeMap<String, T>) -> Variable {
+        Variable::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, Rcvar::new(value.into())))
+                .collect(),
+        )
+    }
+}
+
+/// Converts any serializable value into a `Variable`.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::new();
+/// map.insert("a", vec![1, 2]);
+/// let value = jmespath::to_value(&map).unwrap();
+/// assert_eq!(r#"{"a":[1,2]}"#, value.to_string());
+/// ```
+pub fn to_value<T: ser::Serialize>(value: T) -> Result<Variable, JmespathError> {
+    Variable::from_serializable(value)
+}
+
 impl Variable {
     /// Shortcut function to encode a `T` into a JMESPath `Variable`
     pub fn from_serializable<T>(value: T) -> Result<Variable, JmespathError>
@@ -264,9 +458,9 @@ impl Variable {
         self.as_object().is_some()
     }
 
-    /// If the value is an Object, returns the associated BTreeMap.
+    /// If the value is an Object, returns the associated Map.
     /// Returns None otherwise.
-    pub fn as_object(&self) -> Option<&BTreeMap<String, Rcvar>> {
+    pub fn as_object(&self) -> Option<&Map> {
         match self {
             Variable::Object(map) => Some(map),
             _ => None,
@@ -287,6 +481,12 @@ impl Variable {
         }
     }
 
+    /// If the value is a String, returns it as a str.
+    /// Returns None otherwise.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_string().map(String::as_str)
+    }
+
     /// Returns true if the value is a Number. Returns false otherwise.
     pub fn is_number(&self) -> bool {
         matches!(self, Variable::Number(_))
@@ -294,6 +494,9 @@ impl Variable {
 
     /// If the value is a number, return or cast it to a f64.
     /// Returns None otherwise.
+    ///
+    /// Integers beyond 2^53 are rounded to the nearest f64; use `as_i64` or
+    /// `as_u64` to read them exactly.
     pub fn as_number(&self) -> Option<f64> {
         match self {
             Variable::Number(f) => f.as_f64(),
@@ -301,6 +504,24 @@ impl Variable {
         }
     }
 
+    /// If the value is an integer that fits in an i64, returns it.
+    /// Returns None otherwise, including for floats like `1.0`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Variable::Number(n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// If the value is a non-negative integer that fits in a u64, returns
+    /// it. Returns None otherwise, including for floats like `1.0`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Variable::Number(n) => n.as_u64(),
+            _ => None,
+        }
+    }
+
     /// Returns true if the value is a Boolean. Returns false otherwise.
     pub fn is_boolean(&self) -> bool {
         self.as_boolean().is_some()
@@ -315,6 +536,11 @@ impl Variable {
         }
     }
 
+    /// Shorter alias of `as_boolean`.
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_boolean()
+    }
+
     /// Returns true if the value is a Null. Returns false otherwise.
     pub fn is_null(&self) -> bool {
         self.as_null().is_some()
@@ -344,6 +570,39 @@ impl Variable {
         }
     }
 
+    /// Returns a copy of the value that shares none of its nested values.
+    ///
+    /// `clone()` copies arrays and objects but shares their elements, like
+    /// search results share the subtrees they copy from their input. The
+    /// copy owns every nested value instead, so `Rcvar::get_mut` succeeds on
+    /// each of them and changing one never changes another part of a
+    /// result.
+    ///
+    /// The copy is made with an explicit stack rather than recursion, so
+    /// deeply nested values can't overflow the stack.
+    pub fn deep_clone(&self) -> Variable {
+        let mut stack = match CloneFrame::open(self) {
+            Ok(frame) => vec![frame],
+            Err(copy) => return copy,
+        };
+        loop {
+            let top = stack.last_mut().expect("the stack is not empty");
+            match top.pending.pop() {
+                Some(child) => match CloneFrame::open(child) {
+                    Ok(frame) => stack.push(frame),
+                    Err(copy) => top.copied.push(Rcvar::new(copy)),
+                },
+                None => {
+                    let copy = stack.pop().expect("the stack is not empty").close();
+                    match stack.last_mut() {
+                        Some(parent) => parent.copied.push(Rcvar::new(copy)),
+                        None => return copy,
+                    }
+                }
+            }
+        }
+    }
+
     /// If the value is an object, returns the value associated with the provided key.
     /// Otherwise, returns Null.
     #[inline]
@@ -356,6 +615,29 @@ impl Variable {
         Rcvar::new(Variable::Null)
     }
 
+    /// If the value is an object, returns the value of the key.
+    /// Returns None if the value isn't an object or doesn't have the key.
+    ///
+    /// ```
+    /// use jmespath::Variable;
+    ///
+    /// let value = Variable::from_json(r#"{"a": [{"b": "c"}]}"#).unwrap();
+    /// let c = value.get("a").and_then(|a| a.get_element(0)).and_then(|b| b.get("b"));
+    /// assert_eq!(Some("c"), c.and_then(|c| c.as_str()));
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&Rcvar> {
+        self.as_object().and_then(|map| map.get(key))
+    }
+
+    /// If the value is an array, returns the element at the index.
+    /// Returns None if the value isn't an array or the index is out of bounds.
+    ///
+    /// Unlike `get_index`, which returns Null, this distinguishes a missing
+    /// element from a null one.
+    pub fn get_element(&self, index: usize) -> Option<&Rcvar> {
+        self.as_array().and_then(|array| array.get(index))
+    }
+
     /// If the value is an array, then gets an array value by index. Otherwise returns Null.
     #[inline]
     pub fn get_index(&self, index: usize) -> Rcvar {
@@ -390,6 +672,10 @@ impl Variable {
             Variable::Array(ref a) => !a.is_empty(),
             Variable::Object(ref o) => !o.is_empty(),
             Variable::Number(_) => true,
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(_) => true,
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(ref b) => !b.is_empty(),
             _ => false,
         }
     }
@@ -404,16 +690,21 @@ impl Variable {
             Variable::Object(_) => JmespathType::Object,
             Variable::Null => JmespathType::Null,
             Variable::Expref(_) => JmespathType::Expref,
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(_) => JmespathType::Bytes,
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(_) => JmespathType::Timestamp,
         }
     }
 
     /// Compares two Variable values using a comparator.
     pub fn compare(&self, cmp: &Comparator, value: &Variable) -> Option<bool> {
         // Ordering requires numeric values.
-        if !(self.is_number() && value.is_number()
-            || *cmp == Comparator::NotEqual
-            || *cmp == Comparator::Equal)
-        {
+        let ordered = self.is_number() && value.is_number();
+        // With `datetime`, timestamps are ordered too.
+        #[cfg(feature = "datetime")]
+        let ordered = ordered || self.is_timestamp() && value.is_timestamp();
+        if !(ordered || *cmp == Comparator::NotEqual || *cmp == Comparator::Equal) {
             return None;
         }
         match *cmp {
@@ -430,6 +721,43 @@ impl Variable {
     pub fn slice(&self, start: Option<i32>, stop: Option<i32>, step: i32) -> Option<Vec<Rcvar>> {
         self.as_array().map(|a| slice(a, start, stop, step))
     }
+
+    /// Returns true if the value is a timestamp.
+    #[cfg(feature = "datetime")]
+    pub fn is_timestamp(&self) -> bool {
+        self.as_timestamp().is_some()
+    }
+
+    /// If the value is a timestamp, returns it. Returns None otherwise.
+    #[cfg(feature = "datetime")]
+    pub fn as_timestamp(&self) -> Option<&Timestamp> {
+        match *self {
+            Variable::Timestamp(ref timestamp) => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the value is binary data.
+    #[cfg(feature = "bytes")]
+    pub fn is_bytes(&self) -> bool {
+        self.as_bytes().is_some()
+    }
+
+    /// If the value is binary data, returns its bytes. Returns None
+    /// otherwise.
+    #[cfg(feature = "bytes")]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Variable::Bytes(ref bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice of the variable if the variable is binary data.
+    #[cfg(feature = "bytes")]
+    pub fn slice_bytes(&self, start: Option<i32>, stop: Option<i32>, step: i32) -> Option<Vec<u8>> {
+        self.as_bytes().map(|b| slice(b, start, stop, step))
+    }
 }
 
 impl Variable {
@@ -442,6 +770,10 @@ impl Variable {
             Variable::Array(_) => de::Unexpected::Seq,
             Variable::Object(_) => de::Unexpected::Map,
             Variable::Expref(_) => de::Unexpected::Other("expression"),
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(b) => de::Unexpected::Bytes(b),
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(_) => de::Unexpected::Other("timestamp"),
         }
     }
 }
@@ -450,12 +782,43 @@ impl Variable {
 // Variable slicing implementation
 // ------------------------------------------
 
-fn slice(array: &[Rcvar], start: Option<i32>, stop: Option<i32>, step: i32) -> Vec<Rcvar> {
+fn slice<T: Clone>(array: &[T], start: Option<i32>, stop: Option<i32>, step: i32) -> Vec<T> {
     let mut result = vec![];
     let len = array.len() as i32;
     if len == 0 {
         return result;
     }
+    let (a, b) = slice_endpoints(len, start, stop, step);
+    // Stop when stepping past the end would overflow, e.g. [1::2147483647].
+    let mut i = Some(a);
+    if step > 0 {
+        while let Some(index) = i.filter(|index| *index < b) {
+            result.push(array[index as usize].clone());
+            i = index.checked_add(step);
+        }
+    } else {
+        while let Some(index) = i.filter(|index| *index > b) {
+            result.push(array[index as usize].clone());
+            i = index.checked_add(step);
+        }
+    }
+    result
+}
+
+/// Returns true if slicing an array of the given length returns every
+/// element in order, so the array itself can be used as the result.
+pub(crate) fn is_identity_slice(
+    len: usize,
+    start: Option<i32>,
+    stop: Option<i32>,
+    step: i32,
+) -> bool {
+    len > 0 && step == 1 && slice_endpoints(len as i32, start, stop, step) == (0, len as i32)
+}
+
+/// Returns the index of the first element of a slice and the index that
+/// ends it, which is exclusive, for a non-empty array.
+fn slice_endpoints(len: i32, start: Option<i32>, stop: Option<i32>, step: i32) -> (i32, i32) {
     let a: i32 = match start {
         Some(starting_index) => adjust_slice_endpoint(len, starting_index, step),
         _ if step < 0 => len - 1,
@@ -466,19 +829,7 @@ fn slice(array: &[Rcvar], start: Option<i32>, stop: Option<i32>, step: i32) -> V
         _ if step < 0 => -1,
         _ => len,
     };
-    let mut i = a;
-    if step > 0 {
-        while i < b {
-            result.push(array[i as usize].clone());
-            i += step;
-        }
-    } else {
-        while i > b {
-            result.push(array[i as usize].clone());
-            i += step;
-        }
-    }
-    result
+    (a, b)
 }
 
 #[inline]
@@ -567,6 +918,18 @@ impl<'de> de::Deserialize<'de> for Variable {
                 Ok(Variable::String(value))
             }
 
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn visit_bytes<E>(self, value: &[u8]) -> Result<Variable, E> {
+                Ok(Variable::Bytes(value.to_vec()))
+            }
+
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Variable, E> {
+                Ok(Variable::Bytes(value))
+            }
+
             #[inline]
             fn visit_none<E>(self) -> Result<Variable, E> {
                 Ok(Variable::Null)
@@ -604,7 +967,26 @@ impl<'de> de::Deserialize<'de> for Variable {
             where
                 V: de::MapAccess<'de>,
             {
-                let mut values = BTreeMap::new();
+                let mut values = Map::new();
+
+                // With `bignum`, serde_json deserializes numbers as a map
+                // from a private key to their digits.
+                #[cfg(feature = "bignum")]
+                {
+                    match visitor.next_key::<String>()? {
+                        Some(key) if key == crate::decimal::NUMBER_TOKEN => {
+                            let digits = visitor.next_value::<String>()?;
+                            return digits
+                                .parse()
+                                .map(Variable::Number)
+                                .map_err(de::Error::custom);
+                        }
+                        Some(key) => {
+                            values.insert(key, visitor.next_value()?);
+                        }
+                        None => return Ok(Variable::Object(values)),
+                    }
+                }
 
                 while let Some((key, value)) = visitor.next_entry()? {
                     values.insert(key, value);
@@ -643,6 +1025,10 @@ impl<'de> de::Deserializer<'de> for Variable {
                 value: None,
             }),
             Variable::Expref(v) => visitor.visit_string(format!("<expression: {:?}>", v)),
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(v) => visitor.visit_byte_buf(v),
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(v) => visitor.visit_string(v.to_string()),
         }
     }
 
@@ -868,7 +1254,7 @@ impl<'de> de::SeqAccess<'de> for SeqDeserializer {
 }
 
 struct MapDeserializer {
-    iter: <BTreeMap<String, Rcvar> as IntoIterator>::IntoIter,
+    iter: <Map as IntoIterator>::IntoIter,
     value: Option<Variable>,
 }
 
@@ -924,6 +1310,264 @@ impl<'de> de::Deserializer<'de> for MapDeserializer {
     }
 }
 
+/// Deserializes a value without taking ownership of it.
+///
+/// Strings and object keys are lent to the deserialized value, so a search
+/// result can be deserialized into a type that borrows from it, such as one
+/// with `&str` fields, without copying:
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(serde_derive::Deserialize)]
+/// struct Person<'a> {
+///     name: &'a str,
+///     age: u32,
+/// }
+///
+/// let data = jmespath::Variable::from_json(r#"{"people": [{"name": "a", "age": 30}]}"#).unwrap();
+/// let result = jmespath::compile("people[0]").unwrap().search(data).unwrap();
+/// let person = Person::deserialize(&*result).unwrap();
+/// assert_eq!(("a", 30), (person.name, person.age));
+/// ```
+impl<'de> de::Deserializer<'de> for &'de Variable {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match *self {
+            Variable::Null => visitor.visit_unit(),
+            Variable::Bool(v) => visitor.visit_bool(v),
+            Variable::Number(ref v) => v.deserialize_any(visitor),
+            Variable::String(ref v) => visitor.visit_borrowed_str(v),
+            Variable::Array(ref v) => visitor.visit_seq(BorrowedSeqDeserializer { iter: v.iter() }),
+            Variable::Object(ref v) => visitor.visit_map(BorrowedMapDeserializer {
+                iter: v.iter(),
+                value: None,
+            }),
+            Variable::Expref(ref v) => visitor.visit_string(format!("<expression: {:?}>", v)),
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(ref v) => visitor.visit_borrowed_bytes(v),
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(ref v) => visitor.visit_string(v.to_string()),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match *self {
+            Variable::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (variant, value) = match *self {
+            // enums are encoded in json as maps with a single key:value pair
+            Variable::Object(ref value) if value.len() == 1 => {
+                let (variant, value) = value.iter().next().unwrap();
+                (variant.as_str(), Some(&**value))
+            }
+            Variable::Object(_) => {
+                return Err(de::Error::invalid_value(
+                    de::Unexpected::Map,
+                    &"map with a single key",
+                ));
+            }
+            Variable::String(ref variant) => (variant.as_str(), None),
+            ref other => {
+                return Err(de::Error::invalid_type(
+                    other.unexpected(),
+                    &"string or map",
+                ));
+            }
+        };
+        visitor.visit_enum(BorrowedEnumDeserializer { variant, value })
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string
+        unit seq bytes byte_buf map unit_struct tuple_struct struct
+        identifier tuple ignored_any
+    }
+}
+
+struct BorrowedEnumDeserializer<'de> {
+    variant: &'de str,
+    value: Option<&'de Variable>,
+}
+
+impl<'de> de::EnumAccess<'de> for BorrowedEnumDeserializer<'de> {
+    type Error = Error;
+    type Variant = BorrowedVariantDeserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = de::value::BorrowedStrDeserializer::new(self.variant);
+        let visitor = BorrowedVariantDeserializer { value: self.value };
+        seed.deserialize(variant).map(|v| (v, visitor))
+    }
+}
+
+/// The value of an enum variant, which is `None` for a unit variant
+/// encoded as a string.
+struct BorrowedVariantDeserializer<'de> {
+    value: Option<&'de Variable>,
+}
+
+impl<'de> de::VariantAccess<'de> for BorrowedVariantDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.value {
+            Some(value) => de::Deserialize::deserialize(value),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Some(value @ Variable::Array(_)) => de::Deserializer::deserialize_any(value, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"tuple variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Some(value @ Variable::Object(_)) => de::Deserializer::deserialize_any(value, visitor),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"struct variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+struct BorrowedSeqDeserializer<'de> {
+    iter: std::slice::Iter<'de, Rcvar>,
+}
+
+impl<'de> de::SeqAccess<'de> for BorrowedSeqDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(&**value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct BorrowedMapDeserializer<'de> {
+    iter: <&'de Map as IntoIterator>::IntoIter,
+    value: Option<&'de Variable>,
+}
+
+impl<'de> de::MapAccess<'de> for BorrowedMapDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(de::value::BorrowedStrDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
+
 // Serde Variable serialization
 impl ser::Serialize for Variable {
     #[inline]
@@ -934,13 +1578,57 @@ impl ser::Serialize for Variable {
         match self {
             Variable::Null => serializer.serialize_unit(),
             Variable::Bool(v) => serializer.serialize_bool(*v),
-            Variable::Number(v) => v.serialize(serializer),
+            Variable::Number(v) => serialize_number(v, serializer),
             Variable::String(ref v) => serializer.serialize_str(v),
             Variable::Array(ref v) => v.serialize(serializer),
             Variable::Object(ref v) => v.serialize(serializer),
             Variable::Expref(ref e) => serializer.serialize_str(&format!("<expression: {:?}>", e)),
+            #[cfg(feature = "bytes")]
+            Variable::Bytes(ref v) => serializer.serialize_bytes(v),
+            #[cfg(feature = "datetime")]
+            Variable::Timestamp(ref v) => serializer.collect_str(v),
+        }
+    }
+}
+
+/// Serializes a number the same way on every platform.
+///
+/// Integers, and floats without a fractional part that fit in a 64-bit
+/// integer, from -2^63 up to 2^64, are serialized as integers, so `1.0` is
+/// written `1` and `1e18` is written `1000000000000000000`. Other floats
+/// are serialized as floats, which JSON serializers write as the shortest
+/// digits that parse back to the same float. These are written with an
+/// exponent when they are below 1e-5 in magnitude, like `1.5e-6`, and
+/// when they are integral but outside of the 64-bit range, so `2^64` is
+/// written `1.8446744073709552e+19` and `-1e19` is written `-1e+19`.
+/// Others are written without one, like `0.00001` or `0.1`.
+///
+/// With the `bignum` feature, numbers are serialized with the digits of
+/// the decimal they are written as, without leading or trailing zeros, and
+/// without an exponent unless that takes more than 20 zeros: `1.50` is
+/// written `1.5`, and `1.0e3` is written `1000`.
+fn serialize_number<S>(number: &Number, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    #[cfg(feature = "bignum")]
+    {
+        if let Some(decimal) = crate::decimal::Decimal::from_number(number) {
+            return decimal.to_number().serialize(serializer);
         }
     }
+    match number.as_f64() {
+        Some(f) if number.is_f64() && f.fract() == 0.0 => {
+            if (0.0..18_446_744_073_709_551_616.0).contains(&f) {
+                serializer.serialize_u64(f as u64)
+            } else if (-9_223_372_036_854_775_808.0..0.0).contains(&f) {
+                serializer.serialize_i64(f as i64)
+            } else {
+                serializer.serialize_f64(f)
+            }
+        }
+        _ => number.serialize(serializer),
+    }
 }
 
 /// Create a `serde::Serializer` that serializes a `Serialize`e into a `Variable`.
@@ -959,12 +1647,12 @@ pub struct TupleVariantState {
 #[doc(hidden)]
 pub struct StructVariantState {
     name: String,
-    map: BTreeMap<String, Rcvar>,
+    map: Map,
 }
 
 #[doc(hidden)]
 pub struct MapState {
-    map: BTreeMap<String, Rcvar>,
+    map: Map,
     next_key: Option<String>,
 }
 
@@ -1046,6 +1734,12 @@ impl ser::Serializer for Serializer {
         Ok(Variable::String(String::from(value)))
     }
 
+    #[cfg(feature = "bytes")]
+    fn serialize_bytes(self, value: &[u8]) -> Result<Variable, Error> {
+        Ok(Variable::Bytes(value.to_vec()))
+    }
+
+    #[cfg(not(feature = "bytes"))]
     fn serialize_bytes(self, value: &[u8]) -> Result<Variable, Error> {
         let vec = value
             .iter()
@@ -1075,18 +1769,14 @@ impl ser::Serializer for Serializer {
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<Variable, Error>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Variable, Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -1094,10 +1784,10 @@ impl ser::Serializer for Serializer {
         value: &T,
     ) -> Result<Variable, Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        let mut values = BTreeMap::new();
-        values.insert(String::from(variant), Rcvar::new(to_variable(&value)?));
+        let mut values = Map::new();
+        values.insert(String::from(variant), Rcvar::new(to_variable(value)?));
         Ok(Variable::Object(values))
     }
 
@@ -1107,9 +1797,9 @@ impl ser::Serializer for Serializer {
     }
 
     #[inline]
-    fn serialize_some<V: ?Sized>(self, value: &V) -> Result<Variable, Error>
+    fn serialize_some<V>(self, value: &V) -> Result<Variable, Error>
     where
-        V: ser::Serialize,
+        V: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
@@ -1141,7 +1831,7 @@ impl ser::Serializer for Serializer {
 
     fn serialize_map(self, _len: Option<usize>) -> Result<MapState, Error> {
         Ok(MapState {
-            map: BTreeMap::new(),
+            map: Map::new(),
             next_key: None,
         })
     }
@@ -1159,7 +1849,7 @@ impl ser::Serializer for Serializer {
     ) -> Result<StructVariantState, Error> {
         Ok(StructVariantState {
             name: String::from(variant),
-            map: BTreeMap::new(),
+            map: Map::new(),
         })
     }
 }
@@ -1168,9 +1858,9 @@ impl ser::SerializeSeq for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         self.0.push(Rcvar::new(to_variable(value)?));
         Ok(())
@@ -1185,9 +1875,9 @@ impl ser::SerializeTuple for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -1201,9 +1891,9 @@ impl ser::SerializeTupleStruct for SeqState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeSeq::serialize_element(self, value)
     }
@@ -1217,16 +1907,16 @@ impl ser::SerializeTupleVariant for TupleVariantState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        self.vec.push(Rcvar::new(to_variable(&value)?));
+        self.vec.push(Rcvar::new(to_variable(value)?));
         Ok(())
     }
 
     fn end(self) -> Result<Variable, Error> {
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert(self.name, Rcvar::new(Variable::Array(self.vec)));
         Ok(Variable::Object(object))
     }
@@ -1236,9 +1926,9 @@ impl ser::SerializeMap for MapState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         match to_variable(key)? {
             Variable::String(s) => self.next_key = Some(s),
@@ -1247,9 +1937,9 @@ impl ser::SerializeMap for MapState {
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         let key = self
             .next_key
@@ -1268,15 +1958,31 @@ impl ser::SerializeStruct for MapState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         ser::SerializeMap::serialize_key(self, key)?;
         ser::SerializeMap::serialize_value(self, value)
     }
 
     fn end(self) -> Result<Variable, Error> {
+        // With `bignum`, serde_json serializes numbers as a struct with a
+        // private field holding their digits.
+        #[cfg(feature = "bignum")]
+        {
+            if let (1, Some(digits)) = (
+                self.map.len(),
+                self.map
+                    .get(crate::decimal::NUMBER_TOKEN)
+                    .and_then(|digits| digits.as_string()),
+            ) {
+                return digits
+                    .parse()
+                    .map(Variable::Number)
+                    .map_err(de::Error::custom);
+            }
+        }
         ser::SerializeMap::end(self)
     }
 }
@@ -1285,9 +1991,9 @@ impl ser::SerializeStructVariant for StructVariantState {
     type Ok = Variable;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         self.map
             .insert(String::from(key), Rcvar::new(to_variable(value)?));
@@ -1295,7 +2001,7 @@ impl ser::SerializeStructVariant for StructVariantState {
     }
 
     fn end(self) -> Result<Variable, Error> {
-        let mut object = BTreeMap::new();
+        let mut object = Map::new();
         object.insert(self.name, Rcvar::new(Variable::Object(self.map)));
         Ok(Variable::Object(object))
     }
@@ -1305,9 +2011,9 @@ impl ser::SerializeStructVariant for StructVariantState {
 mod tests {
     use super::*;
     use crate::ast::{Ast, Comparator};
+    use crate::Map;
     use crate::Rcvar;
     use serde_json::{self, Number, Value};
-    use std::collections::BTreeMap;
 
     #[test]
     fn creates_variable_from_str() {
@@ -1322,13 +2028,13 @@ mod tests {
     fn test_determines_types() {
         assert_eq!(
             JmespathType::Object,
-            Variable::from_json(&"{\"foo\": \"bar\"}")
+            Variable::from_json("{\"foo\": \"bar\"}")
                 .unwrap()
                 .get_type()
         );
         assert_eq!(
             JmespathType::Array,
-            Variable::from_json(&"[\"foo\"]").unwrap().get_type()
+            Variable::from_json("[\"foo\"]").unwrap().get_type()
         );
         assert_eq!(JmespathType::Null, Variable::Null.get_type());
         assert_eq!(JmespathType::Boolean, Variable::Bool(true).get_type());
@@ -1344,28 +2050,19 @@ mod tests {
 
     #[test]
     fn test_is_truthy() {
-        assert_eq!(
-            true,
-            Variable::from_json(&"{\"foo\": \"bar\"}")
-                .unwrap()
-                .is_truthy()
-        );
-        assert_eq!(false, Variable::from_json(&"{}").unwrap().is_truthy());
-        assert_eq!(true, Variable::from_json(&"[\"foo\"]").unwrap().is_truthy());
-        assert_eq!(false, Variable::from_json(&"[]").unwrap().is_truthy());
-        assert_eq!(false, Variable::Null.is_truthy());
-        assert_eq!(true, Variable::Bool(true).is_truthy());
-        assert_eq!(false, Variable::Bool(false).is_truthy());
-        assert_eq!(true, Variable::String("foo".to_string()).is_truthy());
-        assert_eq!(false, Variable::String("".to_string()).is_truthy());
-        assert_eq!(
-            true,
-            Variable::Number(Number::from_f64(10.0).unwrap()).is_truthy()
-        );
-        assert_eq!(
-            true,
-            Variable::Number(Number::from_f64(0.0).unwrap()).is_truthy()
-        );
+        assert!(Variable::from_json("{\"foo\": \"bar\"}")
+            .unwrap()
+            .is_truthy());
+        assert!(!Variable::from_json("{}").unwrap().is_truthy());
+        assert!(Variable::from_json("[\"foo\"]").unwrap().is_truthy());
+        assert!(!Variable::from_json("[]").unwrap().is_truthy());
+        assert!(!Variable::Null.is_truthy());
+        assert!(Variable::Bool(true).is_truthy());
+        assert!(!Variable::Bool(false).is_truthy());
+        assert!(Variable::String("foo".to_string()).is_truthy());
+        assert!(!Variable::String("".to_string()).is_truthy());
+        assert!(Variable::Number(Number::from_f64(10.0).unwrap()).is_truthy());
+        assert!(Variable::Number(Number::from_f64(0.0).unwrap()).is_truthy());
     }
 
     #[test]
@@ -1411,8 +2108,8 @@ mod tests {
 
     #[test]
     fn determines_if_null() {
-        assert_eq!(false, Variable::Bool(true).is_null());
-        assert_eq!(true, Variable::Null.is_null());
+        assert!(!Variable::Bool(true).is_null());
+        assert!(Variable::Null.is_null());
     }
 
     #[test]
@@ -1423,8 +2120,8 @@ mod tests {
 
     #[test]
     fn determines_if_boolean() {
-        assert_eq!(true, Variable::Bool(true).is_boolean());
-        assert_eq!(false, Variable::Null.is_boolean());
+        assert!(Variable::Bool(true).is_boolean());
+        assert!(!Variable::Null.is_boolean());
     }
 
     #[test]
@@ -1435,8 +2132,8 @@ mod tests {
 
     #[test]
     fn determines_if_string() {
-        assert_eq!(false, Variable::Bool(true).is_string());
-        assert_eq!(true, Variable::String("foo".to_string()).is_string());
+        assert!(!Variable::Bool(true).is_string());
+        assert!(Variable::String("foo".to_string()).is_string());
     }
 
     #[test]
@@ -1488,10 +2185,7 @@ mod tests {
 
     #[test]
     fn test_is_expref() {
-        assert_eq!(
-            true,
-            Variable::Expref(Ast::Identity { offset: 0 }).is_expref()
-        );
+        assert!(Variable::Expref(Ast::Identity { offset: 0 }).is_expref());
         assert_eq!(
             &Ast::Identity { offset: 0 },
             Variable::Expref(Ast::Identity { offset: 0 })
@@ -1533,8 +2227,8 @@ mod tests {
     #[test]
     fn test_parses_json_object() {
         let var = Variable::from_json("{\"a\": 1, \"b\": {\"c\": true}}").unwrap();
-        let mut expected = BTreeMap::new();
-        let mut sub_obj = BTreeMap::new();
+        let mut expected = Map::new();
+        let mut sub_obj = Map::new();
         expected.insert(
             "a".to_string(),
             Rcvar::new(Variable::Number(Number::from_f64(1.0).unwrap())),
@@ -1593,6 +2287,8 @@ mod tests {
             Variable::Number(Number::from_f64(1.000000000001).unwrap()),
             Variable::Number(Number::from_f64(1.000000000001).unwrap())
         );
+        // With `bignum`, numbers are compared exactly.
+        #[cfg(not(feature = "bignum"))]
         assert_eq!(
             Variable::Number(Number::from_f64(0.7100000000000002).unwrap()),
             Variable::Number(Number::from_f64(0.71).unwrap())
@@ -1649,6 +2345,312 @@ mod tests {
         let var = Variable::from_serializable(&map).unwrap();
         let json_string = serde_json::to_string(&var).unwrap();
 
-        assert_eq!(r#"{"num":41.0}"#, json_string);
+        assert_eq!(r#"{"num":41}"#, json_string);
+    }
+
+    #[test]
+    #[cfg(not(feature = "bignum"))]
+    fn serializes_numbers_deterministically() {
+        let json = |f: f64| Variable::from(f).to_string();
+        assert_eq!("1", json(1.0));
+        assert_eq!("-3", json(-3.0));
+        assert_eq!("0", json(-0.0));
+        assert_eq!("1000000000000000000", json(1e18));
+        assert_eq!("18446744073709549568", json(18446744073709549568.0));
+        assert_eq!("-9223372036854775808", json(-9223372036854775808.0));
+        assert_eq!("1.8446744073709552e+19", json(18446744073709551616.0));
+        assert_eq!("-1e+19", json(-1e19));
+        assert_eq!("0.00001", json(0.00001));
+        assert_eq!("1.5e-6", json(0.0000015));
+        assert_eq!("0.1", json(0.1));
+        assert_eq!("1.5e-7", json(0.00000015));
+        assert_eq!("0.30000000000000004", json(0.1 + 0.2));
+        assert_eq!("12", Variable::from(12).to_string());
+        let parsed = Variable::from_json("[1.0, 2.5]").unwrap();
+        assert_eq!("[1,2.5]", parsed.to_string());
+    }
+
+    #[test]
+    fn keeps_integers_exact() {
+        let data = Variable::from_json(
+            r#"{"id": 18446744073709551615, "min": -9223372036854775807, "ids": [9007199254740993, 1]}"#,
+        )
+        .unwrap();
+        let search = |expr: &str| crate::search(expr, &data).unwrap().to_string();
+        assert_eq!("18446744073709551615", search("id"));
+        assert_eq!("[9007199254740993,1]", search("ids"));
+        assert_eq!("9007199254740993", search("max(ids)"));
+        assert_eq!("9223372036854775807", search("abs(min)"));
+        assert_eq!("9007199254740993", search("ceil(ids[0])"));
+        assert_eq!("9007199254740993", search("floor(ids[0])"));
+        assert_eq!("9007199254740994", search("sum(ids)"));
+        assert_eq!("\"9007199254740993\"", search("to_string(ids[0])"));
+        assert_eq!("false", search("ids[0] == `9007199254740992`"));
+        assert_eq!(Some(u64::MAX), search_var(&data, "id").as_u64());
+        assert_eq!(None, search_var(&data, "id").as_i64());
+        assert_eq!(
+            Some(-9223372036854775807),
+            search_var(&data, "min").as_i64()
+        );
+        assert_eq!(None, Variable::from(1.0).as_i64());
+        assert_eq!(Variable::Number(Number::from(1u64)), Variable::from(1));
+        #[cfg(not(feature = "bignum"))]
+        {
+            assert_eq!("1.8446744073709552e+19", search("sum([id, `1`])"));
+            assert_eq!("1.5", search("sum([ids[1], `0.5`])"));
+        }
+    }
+
+    fn search_var(data: &Variable, expr: &str) -> Variable {
+        crate::search(expr, data).unwrap().as_ref().clone()
+    }
+
+    #[test]
+    #[cfg(feature = "bignum")]
+    fn keeps_the_digits_of_big_numbers() {
+        let amount = "170141183460469231731687303715884105727";
+        let data = Variable::from_json(&format!(
+            r#"{{"a": {0}, "b": 170141183460469231731687303715884105726, "c": [{0}, 0.1, 0.2]}}"#,
+            amount
+        ))
+        .unwrap();
+        let search = |expr: &str| crate::search(expr, &data).unwrap().to_string();
+        assert_eq!(amount, search("a"));
+        assert_eq!("true", search("a > b"));
+        assert_eq!("false", search("a == b"));
+        assert_eq!("true", search(&format!("a == `{}`", amount)));
+        assert_eq!(
+            "170141183460469231731687303715884105727.3",
+            search("sum(c)")
+        );
+        assert_eq!("0.3", search("sum(c[1:])"));
+        assert_eq!("0.15", search("avg(c[1:])"));
+        assert_eq!(amount, search("max(c)"));
+        assert_eq!(
+            amount,
+            search("floor(`170141183460469231731687303715884105727.5`)")
+        );
+        assert_eq!("-1", search("ceil(`-1.5`)"));
+        assert_eq!("1.5", search("abs(`-1.50`)"));
+        let round_trip = Variable::from_json(&data.to_string()).unwrap();
+        assert_eq!(data, round_trip);
+        let value = serde_json::to_value(&data).unwrap();
+        assert_eq!(amount, value["a"].to_string());
+        assert_eq!(data, Variable::from_serializable(&value).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn holds_binary_data() {
+        use serde::de::value::{BytesDeserializer, Error};
+
+        struct Payload;
+        impl Serialize for Payload {
+            fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&[1, 2, 3, 4])
+            }
+        }
+
+        let bytes = Variable::from_serializable(Payload).unwrap();
+        assert_eq!(Some(&[1, 2, 3, 4][..]), bytes.as_bytes());
+        assert_eq!(JmespathType::Bytes, bytes.get_type());
+        assert_eq!("[1,2,3,4]", bytes.to_string());
+        let search = |expr: &str| crate::search(expr, &bytes).unwrap();
+        assert_eq!(Variable::from(4), *search("length(@)"));
+        assert_eq!(Variable::Bytes(vec![4, 2]), *search("[::-2]"));
+        assert_eq!(Variable::Bytes(vec![2, 3]), *search("@[1:3]"));
+        assert_eq!(Variable::Bytes(vec![]), *search("[9:]"));
+        assert_eq!(Variable::Null, *search("[1:3].a"));
+        assert_eq!(Variable::Null, *search("[*]"));
+        assert_eq!(Variable::Null, *search("{b: @}.b[*]"));
+        assert!(crate::search("abs(@)", &bytes).is_err());
+
+        let deserializer = BytesDeserializer::<Error>::new(&[5, 6]);
+        let deserialized = Variable::deserialize(deserializer).unwrap();
+        assert_eq!(Variable::Bytes(vec![5, 6]), deserialized);
+        assert!(deserialized.is_truthy());
+        assert!(!Variable::Bytes(vec![]).is_truthy());
+    }
+
+    #[test]
+    fn converts_from_rust_values() {
+        assert_eq!(Variable::String("a".to_owned()), Variable::from("a"));
+        assert_eq!(Variable::Bool(true), Variable::from(true));
+        assert_eq!(Some(-3.0), Variable::from(-3).as_number());
+        assert_eq!(Some(1.5), Variable::from(1.5).as_number());
+        assert_eq!(Variable::Null, Variable::from(f64::NAN));
+        assert_eq!("[1,2]", Variable::from(vec![1, 2]).to_string());
+        let mut map = HashMap::new();
+        map.insert("a".to_owned(), vec!["b"]);
+        assert_eq!(r#"{"a":["b"]}"#, Variable::from(map).to_string());
+        assert_eq!(Variable::from(vec![true]), to_value(vec![true]).unwrap());
+    }
+
+    #[test]
+    fn accessors_return_options() {
+        let value = Variable::from_json(r#"{"a": [true, null], "b": "c"}"#).unwrap();
+        assert_eq!(Some("c"), value.get("b").and_then(|b| b.as_str()));
+        assert_eq!(None, value.get("x"));
+        let a = value.get("a").unwrap();
+        assert_eq!(Some(true), a.get_element(0).and_then(|v| v.as_bool()));
+        assert_eq!(Some(&Rcvar::new(Variable::Null)), a.get_element(1));
+        assert_eq!(None, a.get_element(2));
+        assert_eq!(None, a.get("b"));
+        assert_eq!(None, value.get_element(0));
+        assert_eq!(None, Variable::Bool(true).as_str());
+    }
+
+    #[test]
+    fn compares_integers_and_floats() {
+        let num = |json: &str| Variable::from_json(json).unwrap();
+        assert_eq!(num("1"), num("1.0"));
+        assert_eq!(num("-0.0"), num("0"));
+        assert!(num("1") < num("1.5"));
+        assert!(num("-1") < num("18446744073709551615"));
+        assert!(num("9007199254740992") < num("9007199254740993"));
+        assert_ne!(num("9007199254740992"), num("9007199254740993"));
+        assert!(num("2.5e1") > num("24"));
+        let cmp = |a: &str, c: Comparator, b: &str| num(a).compare(&c, &num(b));
+        assert_eq!(Some(true), cmp("1.0", Comparator::LessThanEqual, "1"));
+        assert_eq!(Some(true), cmp("1.0", Comparator::GreaterThanEqual, "1"));
+        assert_eq!(Some(false), cmp("1", Comparator::NotEqual, "1e0"));
+        assert_eq!(Some(false), cmp("1e0", Comparator::LessThan, "1"));
+    }
+
+    #[test]
+    fn compares_arrays_and_objects_deeply() {
+        let var = |json: &str| Variable::from_json(json).unwrap();
+        assert_eq!(
+            var(r#"{"a": [1, {"b": 2}], "c": null}"#),
+            var(r#"{"c": null, "a": [1.0, {"b": 2}]}"#)
+        );
+        assert_ne!(var(r#"{"a": 1}"#), var(r#"{"a": 1, "b": 1}"#));
+        assert_ne!(var(r#"{"a": 1, "b": 1}"#), var(r#"{"a": 1, "c": 1}"#));
+        assert_ne!(var("[1, 2]"), var("[2, 1]"));
+        assert_ne!(var("[1]"), var("[1, 1]"));
+        assert_ne!(var("[]"), var("{}"));
+        assert_ne!(var("0"), var("false"));
+        assert_ne!(var("\"1\""), var("1"));
+        let result = crate::search("contains(@, `{\"a\": [1.0]}`)", var(r#"[{"a": [1]}]"#));
+        assert_eq!(Some(true), result.unwrap().as_bool());
+    }
+
+    /// Slices like Python's `slice.indices`, which the specification
+    /// refers to.
+    fn python_slice(len: i64, start: Option<i64>, stop: Option<i64>, step: i64) -> Vec<i64> {
+        let (lower, upper) = if step > 0 { (0, len) } else { (-1, len - 1) };
+        let clamp = |bound: Option<i64>, default: i64| match bound {
+            None => default,
+            Some(b) if b < 0 => (b + len).max(lower),
+            Some(b) => b.min(upper),
+        };
+        let start = clamp(start, if step > 0 { lower } else { upper });
+        let stop = clamp(stop, if step > 0 { upper } else { lower });
+        let mut indices = vec![];
+        let mut i = start;
+        while (step > 0 && i < stop) || (step < 0 && i > stop) {
+            indices.push(i);
+            i += step;
+        }
+        indices
+    }
+
+    #[test]
+    fn slices_like_python() {
+        let extremes = vec![Some(i32::MIN), Some(i32::MAX), None];
+        let bounds: Vec<Option<i32>> = (-7..=7).map(Some).chain(extremes).collect();
+        for len in 0..6 {
+            let array: Vec<Rcvar> = (0..len).map(|i| Rcvar::new(Variable::from(i))).collect();
+            for start in &bounds {
+                for stop in &bounds {
+                    for step in [i32::MIN, -3, -2, -1, 1, 2, 3, i32::MAX] {
+                        let expected = python_slice(
+                            len,
+                            start.map(i64::from),
+                            stop.map(i64::from),
+                            i64::from(step),
+                        );
+                        let actual: Vec<i64> = slice(&array, *start, *stop, step)
+                            .iter()
+                            .map(|v| v.as_number().unwrap() as i64)
+                            .collect();
+                        assert_eq!(expected, actual, "{} {:?}:{:?}:{}", len, start, stop, step);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deserializes_borrowed_variables() {
+        use serde::Deserialize;
+        use std::collections::BTreeMap;
+
+        #[derive(serde_derive::Deserialize, PartialEq, Debug)]
+        enum Shape {
+            Point,
+            Circle(f64),
+            Rect { w: u32, h: u32 },
+        }
+
+        #[derive(serde_derive::Deserialize, PartialEq, Debug)]
+        struct Item<'a> {
+            name: &'a str,
+            tags: Vec<&'a str>,
+            shapes: Vec<Shape>,
+            note: Option<String>,
+            counts: BTreeMap<&'a str, i64>,
+        }
+
+        let var = Variable::from_json(
+            r#"{"name": "box", "tags": ["a", "b"], "note": null, "counts": {"x": -1},
+                "shapes": ["Point", {"Circle": 1.5}, {"Rect": {"w": 2, "h": 3}}]}"#,
+        )
+        .unwrap();
+        let item = Item::deserialize(&var).unwrap();
+        assert_eq!(
+            Item {
+                name: "box",
+                tags: vec!["a", "b"],
+                shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+                note: None,
+                counts: vec![("x", -1)].into_iter().collect(),
+            },
+            item
+        );
+        // The borrowed strings point into the variable.
+        assert_eq!(
+            var.get("name").unwrap().as_str().unwrap().as_ptr(),
+            item.name.as_ptr()
+        );
+        assert_eq!(var, Variable::deserialize(&var).unwrap());
+        assert!(u32::deserialize(&Variable::from("1")).is_err());
+        assert!(Shape::deserialize(&Variable::from_json(r#"{"a": 1, "b": 2}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn deep_clones_deeply_nested_values() {
+        let mut value = Variable::from_json(r#"{"a": [1, {"b": null}], "c": "d"}"#).unwrap();
+        let copy = value.deep_clone();
+        assert_eq!(value, copy);
+        assert!(!Rcvar::ptr_eq(&value.get_field("a"), &copy.get_field("a")));
+        for _ in 0..100_000 {
+            value = Variable::Array(vec![Rcvar::new(value)]);
+        }
+        // Both values are taken apart level by level, since dropping or
+        // comparing them recurses too.
+        let unnest = |value: Variable| match value {
+            Variable::Array(mut array) => {
+                let inner = array.pop().unwrap();
+                Rcvar::try_unwrap(inner).unwrap_or_else(|inner| (*inner).clone())
+            }
+            other => other,
+        };
+        let mut copy = value.deep_clone();
+        for _ in 0..100_000 {
+            value = unnest(value);
+            copy = unnest(copy);
+        }
+        assert_eq!(value, copy);
     }
 }
@@ -0,0 +1,462 @@
+//! Traversal and rewriting of JMESPath ASTs.
+//!
+//! Implement `AstVisitor` and override the methods for the nodes you are
+//! interested in. Every method has a default implementation that visits the
+//! children of the node, so only the interesting variants need to be handled.
+//!
+#![cfg_attr(feature = "visitor", doc = "```")]
+#![cfg_attr(not(feature = "visitor"), doc = "```ignore")]
+//! use jmespath::ast::Ast;
+//! use jmespath::visitor::AstVisitor;
+//!
+//! /// Collects the name of every function called by an expression.
+//! struct Functions(Vec<String>);
+//!
+//! impl AstVisitor for Functions {
+//!     fn visit_function(&mut self, _offset: usize, name: &str, args: &[Ast]) {
+//!         self.0.push(name.to_owned());
+//!         for arg in args {
+//!             self.visit(arg);
+//!         }
+//!     }
+//! }
+//!
+//! let ast = jmespath::parse("sort(keys(@)) | length(@)").unwrap();
+//! let mut functions = Functions(vec![]);
+//! functions.visit(&ast);
+//! assert_eq!(vec!["sort", "keys", "length"], functions.0);
+//! ```
+
+use crate::ast::{Ast, Comparator, KeyValuePair};
+use crate::Rcvar;
+
+/// Visits the nodes of an AST.
+///
+/// `visit` is the entry point and dispatches each node to the method for its
+/// variant using `walk`. Overridden methods that want to keep descending into
+/// the tree call `visit` on the children they are given.
+pub trait AstVisitor {
+    /// Visits a node, dispatching to the method for its variant.
+    fn visit(&mut self, node: &Ast) {
+        walk(self, node);
+    }
+
+    fn visit_comparison(&mut self, _offset: usize, _comparator: &Comparator, lhs: &Ast, rhs: &Ast) {
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_condition(&mut self, _offset: usize, predicate: &Ast, then: &Ast) {
+        self.visit(predicate);
+        self.visit(then);
+    }
+
+    fn visit_identity(&mut self, _offset: usize) {}
+
+    fn visit_expref(&mut self, _offset: usize, ast: &Ast) {
+        self.visit(ast);
+    }
+
+    fn visit_flatten(&mut self, _offset: usize, node: &Ast) {
+        self.visit(node);
+    }
+
+    fn visit_function(&mut self, _offset: usize, _name: &str, args: &[Ast]) {
+        for arg in args {
+            self.visit(arg);
+        }
+    }
+
+    fn visit_field(&mut self, _offset: usize, _name: &str) {}
+
+    fn visit_index(&mut self, _offset: usize, _idx: i32) {}
+
+    fn visit_literal(&mut self, _offset: usize, _value: &Rcvar) {}
+
+    fn visit_parameter(&mut self, _offset: usize, _name: &str) {}
+
+    fn visit_multi_list(&mut self, _offset: usize, elements: &[Ast]) {
+        for element in elements {
+            self.visit(element);
+        }
+    }
+
+    fn visit_multi_hash(&mut self, _offset: usize, elements: &[KeyValuePair]) {
+        for kvp in elements {
+            self.visit(&kvp.value);
+        }
+    }
+
+    fn visit_not(&mut self, _offset: usize, node: &Ast) {
+        self.visit(node);
+    }
+
+    fn visit_projection(&mut self, _offset: usize, lhs: &Ast, rhs: &Ast) {
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_object_values(&mut self, _offset: usize, node: &Ast) {
+        self.visit(node);
+    }
+
+    fn visit_recursive_descent(&mut self, _offset: usize, node: &Ast, _name: &str) {
+        self.visit(node);
+    }
+
+    fn visit_and(&mut self, _offset: usize, lhs: &Ast, rhs: &Ast) {
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_or(&mut self, _offset: usize, lhs: &Ast, rhs: &Ast) {
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+
+    fn visit_slice(&mut self, _offset: usize, _start: Option<i32>, _stop: Option<i32>, _step: i32) {
+    }
+
+    fn visit_subexpr(&mut self, _offset: usize, lhs: &Ast, rhs: &Ast) {
+        self.visit(lhs);
+        self.visit(rhs);
+    }
+}
+
+/// Calls the `visitor` method that corresponds to the variant of `node`.
+pub fn walk<V: AstVisitor + ?Sized>(visitor: &mut V, node: &Ast) {
+    match *node {
+        Ast::Comparison {
+            offset,
+            ref comparator,
+            ref lhs,
+            ref rhs,
+        } => visitor.visit_comparison(offset, comparator, lhs, rhs),
+        Ast::Condition {
+            offset,
+            ref predicate,
+            ref then,
+        } => visitor.visit_condition(offset, predicate, then),
+        Ast::Identity { offset } => visitor.visit_identity(offset),
+        Ast::Expref { offset, ref ast } => visitor.visit_expref(offset, ast),
+        Ast::Flatten { offset, ref node } => visitor.visit_flatten(offset, node),
+        Ast::Function {
+            offset,
+            ref name,
+            ref args,
+        } => visitor.visit_function(offset, name, args),
+        Ast::Field { offset, ref name } => visitor.visit_field(offset, name),
+        Ast::Index { offset, idx } => visitor.visit_index(offset, idx),
+        Ast::Literal { offset, ref value } => visitor.visit_literal(offset, value),
+        Ast::Parameter { offset, ref name } => visitor.visit_parameter(offset, name),
+        Ast::MultiList {
+            offset,
+            ref elements,
+        } => visitor.visit_multi_list(offset, elements),
+        Ast::MultiHash {
+            offset,
+            ref elements,
+        } => visitor.visit_multi_hash(offset, elements),
+        Ast::Not { offset, ref node } => visitor.visit_not(offset, node),
+        Ast::Projection {
+            offset,
+            ref lhs,
+            ref rhs,
+        } => visitor.visit_projection(offset, lhs, rhs),
+        Ast::ObjectValues { offset, ref node } => visitor.visit_object_values(offset, node),
+        Ast::RecursiveDescent {
+            offset,
+            ref node,
+            ref name,
+        } => visitor.visit_recursive_descent(offset, node, name),
+        Ast::And {
+            offset,
+            ref lhs,
+            ref rhs,
+        } => visitor.visit_and(offset, lhs, rhs),
+        Ast::Or {
+            offset,
+            ref lhs,
+            ref rhs,
+        } => visitor.visit_or(offset, lhs, rhs),
+        Ast::Slice {
+            offset,
+            start,
+            stop,
+            step,
+        } => visitor.visit_slice(offset, start, stop, step),
+        Ast::Subexpr {
+            offset,
+            ref lhs,
+            ref rhs,
+        } => visitor.visit_subexpr(offset, lhs, rhs),
+    }
+}
+
+/// Rebuilds an AST, allowing any node to be replaced.
+///
+/// `fold` takes ownership of each node and returns its replacement. The
+/// default implementation folds the children of the node using
+/// `fold_children`, so an implementation usually matches on the nodes it
+/// wants to rewrite and delegates everything else back to `fold_children`.
+///
+#[cfg_attr(feature = "visitor", doc = "```")]
+#[cfg_attr(not(feature = "visitor"), doc = "```ignore")]
+/// use jmespath::ast::Ast;
+/// use jmespath::visitor::{fold_children, AstFolder};
+///
+/// /// Renames every field named `old` to `new`.
+/// struct Rename;
+///
+/// impl AstFolder for Rename {
+///     fn fold(&mut self, node: Ast) -> Ast {
+///         match node {
+///             Ast::Field { offset, ref name } if name == "old" => Ast::Field {
+///                 offset,
+///                 name: "new".into(),
+///             },
+///             node => fold_children(self, node),
+///         }
+///     }
+/// }
+///
+/// let ast = Rename.fold(jmespath::parse("old[?old > `1`].name").unwrap());
+/// assert_eq!("new[?new > `1`].name", ast.to_expression_string());
+/// ```
+pub trait AstFolder {
+    /// Returns the replacement for a node.
+    fn fold(&mut self, node: Ast) -> Ast {
+        fold_children(self, node)
+    }
+}
+
+/// Rebuilds `node` with each of its children replaced by `folder.fold`.
+pub fn fold_children<F: AstFolder + ?Sized>(folder: &mut F, node: Ast) -> Ast {
+    let mut fold = |node: Box<Ast>| Box::new(folder.fold(*node));
+    match node {
+        Ast::Comparison {
+            offset,
+            comparator,
+            lhs,
+            rhs,
+        } => Ast::Comparison {
+            offset,
+            comparator,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Condition {
+            offset,
+            predicate,
+            then,
+        } => Ast::Condition {
+            offset,
+            predicate: fold(predicate),
+            then: fold(then),
+        },
+        Ast::Expref { offset, ast } => Ast::Expref {
+            offset,
+            ast: fold(ast),
+        },
+        Ast::Flatten { offset, node } => Ast::Flatten {
+            offset,
+            node: fold(node),
+        },
+        Ast::Function { offset, name, args } => Ast::Function {
+            offset,
+            name,
+            args: args.into_iter().map(|arg| folder.fold(arg)).collect(),
+        },
+        Ast::MultiList { offset, elements } => Ast::MultiList {
+            offset,
+            elements: elements.into_iter().map(|e| folder.fold(e)).collect(),
+        },
+        Ast::MultiHash { offset, elements } => Ast::MultiHash {
+            offset,
+            elements: elements
+                .into_iter()
+                .map(|kvp| KeyValuePair {
+                    key: kvp.key,
+                    value: folder.fold(kvp.value),
+                })
+                .collect(),
+        },
+        Ast::Not { offset, node } => Ast::Not {
+            offset,
+            node: fold(node),
+        },
+        Ast::Projection { offset, lhs, rhs } => Ast::Projection {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::ObjectValues { offset, node } => Ast::ObjectValues {
+            offset,
+            node: fold(node),
+        },
+        Ast::RecursiveDescent { offset, node, name } => Ast::RecursiveDescent {
+            offset,
+            node: fold(node),
+            name,
+        },
+        Ast::And { offset, lhs, rhs } => Ast::And {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Or { offset, lhs, rhs } => Ast::Or {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        Ast::Subexpr { offset, lhs, rhs } => Ast::Subexpr {
+            offset,
+            lhs: fold(lhs),
+            rhs: fold(rhs),
+        },
+        leaf @ Ast::Identity { .. }
+        | leaf @ Ast::Field { .. }
+        | leaf @ Ast::Index { .. }
+        | leaf @ Ast::Literal { .. }
+        | leaf @ Ast::Parameter { .. }
+        | leaf @ Ast::Slice { .. } => leaf,
+    }
+}
+
+/// Adapts a closure into a folder that rewrites the tree bottom up.
+struct MapAst<F>(F);
+
+impl<F: FnMut(Ast) -> Ast> AstFolder for MapAst<F> {
+    fn fold(&mut self, node: Ast) -> Ast {
+        let node = fold_children(self, node);
+        (self.0)(node)
+    }
+}
+
+/// Rebuilds an AST bottom up, replacing every node with the result of `f`.
+///
+/// `f` is called on each node after its children have been rewritten.
+pub fn map_ast<F: FnMut(Ast) -> Ast>(node: Ast, f: F) -> Ast {
+    MapAst(f).fold(node)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Records every node type and field name in visiting order.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl AstVisitor for Recorder {
+        fn visit(&mut self, node: &Ast) {
+            let name = format!("{:?}", node);
+            self.0.push(name[..name.find(' ').unwrap()].to_owned());
+            walk(self, node);
+        }
+
+        fn visit_field(&mut self, _offset: usize, name: &str) {
+            self.0.push(name.to_owned());
+        }
+    }
+
+    #[test]
+    fn visits_every_node_in_order() {
+        let ast = crate::parse("a[?b > `1`].{c: !c, d: [d, &e]} || f[0:1] | *.g[]").unwrap();
+        let mut recorder = Recorder::default();
+        recorder.visit(&ast);
+        assert_eq!(
+            vec![
+                "Subexpr",
+                "Or",
+                "Projection",
+                "Field",
+                "a",
+                "Condition",
+                "Comparison",
+                "Field",
+                "b",
+                "Literal",
+                "MultiHash",
+                "Not",
+                "Field",
+                "c",
+                "MultiList",
+                "Field",
+                "d",
+                "Expref",
+                "Field",
+                "e",
+                "Subexpr",
+                "Field",
+                "f",
+                "Projection",
+                "Slice",
+                "Identity",
+                "Projection",
+                "Flatten",
+                "Projection",
+                "ObjectValues",
+                "Identity",
+                "Field",
+                "g",
+                "Identity",
+            ],
+            recorder.0
+        );
+    }
+
+    #[test]
+    fn overridden_methods_can_skip_children() {
+        struct Fields(usize);
+        impl AstVisitor for Fields {
+            fn visit_field(&mut self, _offset: usize, _name: &str) {
+                self.0 += 1;
+            }
+            fn visit_expref(&mut self, _offset: usize, _ast: &Ast) {}
+        }
+        let ast = crate::parse("sort_by(a, &b.c)[*].d").unwrap();
+        let mut fields = Fields(0);
+        fields.visit(&ast);
+        assert_eq!(2, fields.0);
+    }
+
+    #[test]
+    fn maps_ast_bottom_up() {
+        let ast = crate::parse("a.b || length(c)").unwrap();
+        let mut order = vec![];
+        let ast = map_ast(ast, |node| {
+            order.push(format!("{:?}", node).split(' ').next().unwrap().to_owned());
+            match node {
+                Ast::Field { offset, name } => Ast::Field {
+                    offset,
+                    name: name.to_uppercase().into(),
+                },
+                node => node,
+            }
+        });
+        assert_eq!("A.B || length(C)", ast.to_expression_string());
+        assert_eq!(
+            vec!["Field", "Field", "Subexpr", "Field", "Function", "Or"],
+            order
+        );
+    }
+
+    #[test]
+    fn folder_can_replace_subtrees() {
+        /// Replaces `not_null` calls with their first argument.
+        struct Unwrap;
+        impl AstFolder for Unwrap {
+            fn fold(&mut self, node: Ast) -> Ast {
+                match node {
+                    Ast::Function { name, mut args, .. } if name == "not_null" => {
+                        self.fold(args.remove(0))
+                    }
+                    node => fold_children(self, node),
+                }
+            }
+        }
+        let ast = crate::parse("[not_null(not_null(a), b), `1`]").unwrap();
+        assert_eq!("[a, `1`]", Unwrap.fold(ast).to_expression_string());
+    }
+}